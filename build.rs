@@ -0,0 +1,19 @@
+// build.rs - 把当前 git commit 写入 GIT_HASH 环境变量，供 run_info 使用
+
+use std::process::Command;
+
+fn main() {
+    // 在没有 git 的环境（如 cross 容器）中由 GIT_HASH 环境变量直接给出
+    let hash = std::env::var("GIT_HASH").ok().filter(|h| !h.is_empty()).or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    }).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_HASH={}", hash);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+}
@@ -0,0 +1,26 @@
+#!/bin/sh
+# 容器入口：有命令行参数时直接传给程序；否则按环境变量拼出 split 命令，
+# 便于 Nextflow / Cromwell 这类只方便设置环境变量的调度器使用。
+#
+#   SCATAC_R1, SCATAC_R2     输入 FASTQ（必需）
+#   SCATAC_OUTPUT            输出前缀（必需）
+#   SCATAC_THREADS           处理线程数
+#   SCATAC_WHITELIST         条形码白名单
+#   SCATAC_COMPRESS          非空时压缩输出
+#   SCATAC_EXTRA_ARGS        其余参数，按空白分割后原样追加
+set -eu
+
+if [ "$#" -gt 0 ]; then
+    exec scatac-barcode-splitter "$@"
+fi
+
+: "${SCATAC_R1:?set SCATAC_R1 or pass arguments}"
+: "${SCATAC_R2:?set SCATAC_R2 or pass arguments}"
+: "${SCATAC_OUTPUT:?set SCATAC_OUTPUT or pass arguments}"
+
+set -- split -1 "$SCATAC_R1" -2 "$SCATAC_R2" -o "$SCATAC_OUTPUT"
+[ -n "${SCATAC_THREADS:-}" ] && set -- "$@" -t "$SCATAC_THREADS"
+[ -n "${SCATAC_WHITELIST:-}" ] && set -- "$@" -w "$SCATAC_WHITELIST"
+[ -n "${SCATAC_COMPRESS:-}" ] && set -- "$@" -c
+# shellcheck disable=SC2086
+exec scatac-barcode-splitter "$@" ${SCATAC_EXTRA_ARGS:-}
@@ -0,0 +1,9 @@
+#!/usr/bin/env bash
+set -euxo pipefail
+
+# 依赖的许可证随包分发（bioconda 对 Rust 包的要求）
+cargo-bundle-licenses --format yaml --output THIRDPARTY.yml
+
+# 源码包中没有 .git，提交号无法由 build.rs 取得
+export GIT_HASH=${GIT_HASH:-v${PKG_VERSION}}
+cargo install --no-track --locked --verbose --root "${PREFIX}" --path .
@@ -0,0 +1,34 @@
+#!/usr/bin/env bash
+# 比较系统分配器、jemalloc 与 mimalloc（`jemalloc`、`mimalloc` feature）下 split 的吞吐量
+#
+# 用法: scripts/bench-allocator.sh [PAIRS] [THREADS] [RUNS]
+# 用 gen_test_data 生成 PAIRS 对合成 read（默认 5000000），三种构建各跑 RUNS 次（默认 3）
+# 完整的 split（gzip 输出），报告每次的耗时与 pairs/s。
+set -euo pipefail
+
+pairs=${1:-5000000}
+threads=${2:-$(nproc)}
+runs=${3:-3}
+name=scatac-barcode-splitter
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+# 各构建放在不同的 target 目录，互不覆盖
+cargo build --release --quiet --bins --target-dir target/bench-system
+cargo build --release --quiet --bins --target-dir target/bench-jemalloc --features jemalloc
+cargo build --release --quiet --bins --target-dir target/bench-mimalloc --features mimalloc
+
+target/bench-system/release/gen_test_data -n "$pairs" --barcodes 5000 --barcode-error-rate 0.01 \
+    --write-barcodes "$work/whitelist.txt" -1 "$work/R1.fastq.gz" -2 "$work/R2.fastq.gz"
+
+for allocator in system jemalloc mimalloc; do
+    for run in $(seq "$runs"); do
+        start=$(date +%s.%N)
+        "target/bench-$allocator/release/$name" split -1 "$work/R1.fastq.gz" -2 "$work/R2.fastq.gz" \
+            -o "$work/out" --whitelist "$work/whitelist.txt" --compress --threads "$threads" --quiet
+        end=$(date +%s.%N)
+        awk -v a="$allocator" -v r="$run" -v p="$pairs" -v s="$start" -v e="$end" \
+            'BEGIN { printf "%-8s run %d: %.2fs (%.0f pairs/s)\n", a, r, e - s, p / (e - s) }'
+        rm -f "$work"/out_*
+    done
+done
@@ -0,0 +1,23 @@
+#!/usr/bin/env bash
+# 把 release 构建的可执行文件连同文档打包到 dist/
+#
+# 用法: scripts/package-release.sh TARGET VERSION
+set -euo pipefail
+
+target=$1
+version=$2
+name=scatac-barcode-splitter
+bin=$name
+[[ $target == *windows* ]] && bin=$name.exe
+
+staging=$name-$version-$target
+rm -rf "$staging"
+mkdir -p "$staging" dist
+cp "target/$target/release/$bin" README.md CHANGELOG.md "$staging/"
+
+if [[ $target == *windows* ]]; then
+    7z a "dist/$staging.zip" "$staging" > /dev/null
+else
+    tar -czf "dist/$staging.tar.gz" "$staging"
+fi
+rm -rf "$staging"
@@ -0,0 +1,95 @@
+// adaptive_batch.rs - 按处理耗时动态调整 batch 大小（--adaptive-batch）
+
+use crate::stats::AdaptiveBatchStats;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// 未给出上限时，batch 最多增长到初始大小的倍数
+pub const MAX_GROWTH: usize = 16;
+
+/// 处理一个 batch 的目标耗时范围
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchTarget {
+    pub min: Duration,
+    pub max: Duration,
+    /// batch 大小的上限（如按内存预算算出的值）；`None` 时为初始大小的 [`MAX_GROWTH`] 倍
+    pub max_batch_size: Option<usize>,
+}
+
+impl Default for BatchTarget {
+    fn default() -> Self {
+        BatchTarget { min: Duration::from_millis(100), max: Duration::from_millis(500), max_batch_size: None }
+    }
+}
+
+/// 读取线程与处理线程共享的 batch 大小
+///
+/// 处理线程每处理完一个 batch 调用 [`observe`](Self::observe)：按该 batch 的耗时折算出当前大小的
+/// batch 需要的时间，低于目标下限时把大小增加 10%，高于上限时减少 10%。
+#[derive(Debug)]
+pub struct AdaptiveBatchSize {
+    size: AtomicUsize,
+    initial: usize,
+    max_size: usize,
+    target: BatchTarget,
+    smallest: AtomicUsize,
+    largest: AtomicUsize,
+    adjustments: AtomicU64,
+}
+
+impl AdaptiveBatchSize {
+    pub fn new(initial: usize, target: BatchTarget) -> Self {
+        let initial = initial.max(1);
+        let max_size = target.max_batch_size.unwrap_or(initial.saturating_mul(MAX_GROWTH)).max(1);
+        let initial = initial.min(max_size);
+        AdaptiveBatchSize {
+            size: AtomicUsize::new(initial),
+            initial,
+            max_size,
+            target,
+            smallest: AtomicUsize::new(initial),
+            largest: AtomicUsize::new(initial),
+            adjustments: AtomicU64::new(0),
+        }
+    }
+
+    /// 当前的 batch 大小
+    pub fn get(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// 记录处理 `len` 对 read 所用的时间，必要时调整 batch 大小
+    pub fn observe(&self, len: usize, elapsed: Duration) {
+        if len == 0 {
+            return;
+        }
+        let size = self.get();
+        let projected = elapsed.as_nanos() * size as u128 / len as u128;
+        let step = (size / 10).max(1);
+        let next = if projected < self.target.min.as_nanos() {
+            (size + step).min(self.max_size)
+        } else if projected > self.target.max.as_nanos() {
+            size.saturating_sub(step).max(1)
+        } else {
+            return;
+        };
+        // 其他线程同时调整过时以它的结果为准
+        if next != size && self.size.compare_exchange(size, next, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.adjustments.fetch_add(1, Ordering::Relaxed);
+            self.smallest.fetch_min(next, Ordering::Relaxed);
+            self.largest.fetch_max(next, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> AdaptiveBatchStats {
+        AdaptiveBatchStats {
+            initial: self.initial as u64,
+            final_size: self.get() as u64,
+            smallest: self.smallest.load(Ordering::Relaxed) as u64,
+            largest: self.largest.load(Ordering::Relaxed) as u64,
+            adjustments: self.adjustments.load(Ordering::Relaxed),
+            target_min_ms: self.target.min.as_millis() as u64,
+            target_max_ms: self.target.max.as_millis() as u64,
+        }
+    }
+}
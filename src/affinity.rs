@@ -0,0 +1,42 @@
+// affinity.rs - 把线程绑定到指定的 CPU（--affinity、--numa-local）
+//
+// 只在 Linux 上生效（sched_setaffinity），其他系统上什么也不做。
+
+/// 解析 CPU 列表（如 `0-3,8,10-11`，与 sysfs 及 taskset 的格式相同）；格式不对时为 `None`
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (first.parse::<usize>().ok()?, last.parse::<usize>().ok()?);
+                if first > last {
+                    return None;
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// 把当前线程绑定到 `cpus`（之后由它启动的线程也继承）；失败时保持原样
+pub fn pin_current_thread(cpus: &[usize]) {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: cpu_set_t 是普通的位图，全零即空集；CPU_SET 只写入其中的位
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for &cpu in cpus.iter().filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize) {
+                libc::CPU_SET(cpu, &mut set);
+            }
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                log::debug!("Failed to pin thread to CPUs {:?}", cpus);
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = cpus;
+    }
+}
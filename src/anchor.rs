@@ -0,0 +1,57 @@
+// anchor.rs - 按 R2 中的固定序列（如 ME）定位基因组片段与条形码的分界（--anchor）
+
+use crate::readthrough::find_adapter;
+use anyhow::{bail, Result};
+
+/// 基因组片段与条形码之间的锚定序列
+///
+/// 锚定序列之前的碱基作为基因组片段，之后紧接着的碱基作为条形码。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anchor {
+    seq: Vec<u8>,
+    max_mismatches: usize,
+}
+
+/// 一条 R2 中锚定序列的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnchorHit {
+    /// 锚定序列第一个碱基的位置
+    pub start: usize,
+    /// 锚定序列之后第一个碱基的位置，即条形码的开头
+    pub end: usize,
+    /// 符合条件的命中数；多于一个时取最靠 3' 端的
+    pub hits: usize,
+}
+
+impl Anchor {
+    /// 锚定序列只能含 ACGT（不区分大小写），且错配数必须小于其长度
+    pub fn new(seq: &str, max_mismatches: usize) -> Result<Self> {
+        let seq = seq.as_bytes().to_ascii_uppercase();
+        if seq.is_empty() || !seq.iter().all(|b| b"ACGT".contains(b)) {
+            bail!("Anchor sequence must be a non-empty sequence of A, C, G and T");
+        }
+        if max_mismatches >= seq.len() {
+            bail!("Anchor mismatches ({}) must be fewer than the anchor length ({})", max_mismatches, seq.len());
+        }
+        Ok(Anchor { seq, max_mismatches })
+    }
+
+    pub fn sequence(&self) -> &[u8] {
+        &self.seq
+    }
+
+    pub fn max_mismatches(&self) -> usize {
+        self.max_mismatches
+    }
+
+    /// 在 `r2_seq` 中查找锚定序列：之前至少有 1 个碱基、之后至少有 `tail_len` 个碱基
+    pub fn locate(&self, r2_seq: &[u8], tail_len: usize) -> Option<AnchorHit> {
+        let searchable = r2_seq.get(1..r2_seq.len().checked_sub(tail_len)?)?;
+        let (mut last, mut hits) = (None, 0);
+        for i in find_adapter(searchable, &self.seq, self.max_mismatches) {
+            last = Some(i + 1);
+            hits += 1;
+        }
+        last.map(|start| AnchorHit { start, end: start + self.seq.len(), hits })
+    }
+}
@@ -0,0 +1,249 @@
+// bam.rs - 未比对 BAM（uBAM）输入
+//
+// 只解析还原 FASTQ 所需的字段（read 名、FLAG、序列、质量值），tag 与比对信息直接跳过。
+// 不依赖 noodles：BGZF 是多成员 gzip，直接复用 `open_compressed` 的（并行）解压。
+
+use crate::failure::{Classify, Failure};
+use crate::fastq_io::open_compressed;
+use crate::progress::PipelineCounters;
+use crate::reverse_complement;
+use anyhow::{Context, Result};
+use fastq::OwnedRecord;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+const FLAG_PAIRED: u16 = 0x1;
+const FLAG_REVERSE: u16 = 0x10;
+const FLAG_FIRST: u16 = 0x40;
+const FLAG_LAST: u16 = 0x80;
+const FLAG_SECONDARY: u16 = 0x100;
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+/// BAM 中 4-bit 编码的碱基
+const SEQ_CODES: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+/// 输入文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Fastq,
+    Ubam,
+    Cram,
+}
+
+/// 按文件头的 magic bytes 判断格式：BGZF/gzip 解压后以 `BAM\1` 开头为 BAM，
+/// 以 `CRAM` 开头为 CRAM，其余按 FASTQ 处理
+pub fn detect_input_format<P: AsRef<Path>>(path: P) -> Result<InputFormat> {
+    let path = path.as_ref();
+    let mut f = File::open(path).with_context(|| format!("Failed to open {}", path.display()))
+        .classify(Failure::InputUnreadable)?;
+    let mut magic = [0u8; 4];
+    let n = read_up_to(&mut f, &mut magic)?;
+    if n == 4 && &magic == b"CRAM" {
+        return Ok(InputFormat::Cram);
+    }
+    if n >= 2 && magic[0] == 31 && magic[1] == 139 {
+        let mut decoder = MultiGzDecoder::new(File::open(path)?);
+        let mut inner = [0u8; 4];
+        if read_up_to(&mut decoder, &mut inner).unwrap_or(0) == 4 && &inner == b"BAM\x01" {
+            return Ok(InputFormat::Ubam);
+        }
+    }
+    Ok(InputFormat::Fastq)
+}
+
+fn read_up_to<R: Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 一条 BAM 记录中还原 FASTQ 所需的部分
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BamRecord {
+    pub name: Vec<u8>,
+    pub flag: u16,
+    /// 测序方向的序列（REVERSE 标记的记录已还原）
+    pub seq: Vec<u8>,
+    /// Phred+33 质量值
+    pub qual: Vec<u8>,
+}
+
+/// 顺序读取 BAM 记录
+pub struct BamReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> BamReader<R> {
+    /// 读取并跳过 BAM 头（SAM 文本与参考序列列表）
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        inner.read_exact(&mut magic)?;
+        if &magic != b"BAM\x01" {
+            return Err(invalid("not a BAM file (missing BAM\\1 magic)"));
+        }
+        let l_text = read_u32(&mut inner)? as u64;
+        io::copy(&mut (&mut inner).take(l_text), &mut io::sink())?;
+        let n_ref = read_u32(&mut inner)?;
+        for _ in 0..n_ref {
+            let l_name = read_u32(&mut inner)? as u64;
+            // 名称 + l_ref
+            io::copy(&mut (&mut inner).take(l_name + 4), &mut io::sink())?;
+        }
+        Ok(BamReader { inner, buf: Vec::new() })
+    }
+
+    /// 读取下一条记录到 `record`，文件结束时返回 `false`
+    pub fn read_record(&mut self, record: &mut BamRecord) -> io::Result<bool> {
+        let mut size = [0u8; 4];
+        // 只有在记录边界读到 0 字节才是文件结束；解压器报告的截断照常返回错误
+        match read_up_to(&mut self.inner, &mut size)? {
+            0 => return Ok(false),
+            4 => {}
+            _ => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated BAM record")),
+        }
+        let block_size = u32::from_le_bytes(size) as usize;
+        if block_size < 32 {
+            return Err(invalid("BAM record shorter than its fixed fields"));
+        }
+        self.buf.resize(block_size, 0);
+        self.inner.read_exact(&mut self.buf)?;
+        parse_record(&self.buf, record)
+    }
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn parse_record(data: &[u8], record: &mut BamRecord) -> io::Result<bool> {
+    let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let l_read_name = data[8] as usize;
+    let n_cigar_op = u16_at(12) as usize;
+    let flag = u16_at(14);
+    let l_seq = u32::from_le_bytes([data[16], data[17], data[18], data[19]]) as usize;
+
+    let name_start = 32;
+    let seq_start = name_start + l_read_name + 4 * n_cigar_op;
+    let qual_start = seq_start + l_seq.div_ceil(2);
+    if l_read_name == 0 || qual_start + l_seq > data.len() {
+        return Err(invalid("truncated BAM record"));
+    }
+
+    record.name.clear();
+    // read 名以 NUL 结尾
+    record.name.extend_from_slice(&data[name_start..name_start + l_read_name - 1]);
+    record.flag = flag;
+
+    record.seq.clear();
+    record.seq.extend((0..l_seq).map(|i| {
+        let byte = data[seq_start + i / 2];
+        SEQ_CODES[if i % 2 == 0 { byte >> 4 } else { byte & 0x0f } as usize]
+    }));
+
+    record.qual.clear();
+    let qual = &data[qual_start..qual_start + l_seq];
+    if qual.first() == Some(&0xff) {
+        // 缺失的质量值记为 Phred 0
+        record.qual.resize(l_seq, b'!');
+    } else {
+        record.qual.extend(qual.iter().map(|&q| q.min(93) + 33));
+    }
+
+    if flag & FLAG_REVERSE != 0 {
+        record.seq = reverse_complement(&record.seq);
+        record.qual.reverse();
+    }
+    Ok(true)
+}
+
+/// 配对过程中跳过的记录数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PairingStats {
+    /// 找不到相邻 mate 的记录
+    pub missing_mate: u64,
+    /// 未标记为 paired、first/last 标记不唯一，或为 secondary/supplementary 的记录
+    pub inconsistent_flags: u64,
+}
+
+/// 把 uBAM 中相邻的 first/last mate 还原为 (R1, R2) 记录对
+pub struct UbamPairReader<R> {
+    reader: BamReader<R>,
+    pending: Option<BamRecord>,
+    record: BamRecord,
+    pub stats: PairingStats,
+}
+
+impl<R: Read> UbamPairReader<R> {
+    pub fn new(inner: R) -> io::Result<Self> {
+        Ok(UbamPairReader {
+            reader: BamReader::new(inner)?,
+            pending: None,
+            record: BamRecord::default(),
+            stats: PairingStats::default(),
+        })
+    }
+
+    /// 读取下一对 read，文件结束时返回 `None`
+    pub fn next_pair(&mut self) -> io::Result<Option<(OwnedRecord, OwnedRecord)>> {
+        loop {
+            if !self.reader.read_record(&mut self.record)? {
+                if self.pending.take().is_some() {
+                    self.stats.missing_mate += 1;
+                }
+                return Ok(None);
+            }
+            let flag = self.record.flag;
+            let first = flag & FLAG_FIRST != 0;
+            let last = flag & FLAG_LAST != 0;
+            if flag & FLAG_PAIRED == 0 || first == last || flag & (FLAG_SECONDARY | FLAG_SUPPLEMENTARY) != 0 {
+                self.stats.inconsistent_flags += 1;
+                continue;
+            }
+            if first {
+                if self.pending.replace(std::mem::take(&mut self.record)).is_some() {
+                    self.stats.missing_mate += 1;
+                }
+                continue;
+            }
+            match self.pending.take() {
+                Some(mate) if mate.name == self.record.name => {
+                    let r2 = std::mem::take(&mut self.record);
+                    return Ok(Some((to_fastq(mate), to_fastq(r2))));
+                }
+                // 上一条 first mate 与这条 last mate 都缺少对应的 mate
+                Some(_) => self.stats.missing_mate += 2,
+                None => self.stats.missing_mate += 1,
+            }
+        }
+    }
+}
+
+fn to_fastq(record: BamRecord) -> OwnedRecord {
+    OwnedRecord { head: record.name, seq: record.seq, sep: None, qual: record.qual }
+}
+
+/// 打开 uBAM 文件（BGZF 可按 block 并行解压）
+pub fn open_ubam<P: AsRef<Path>>(
+    path: P,
+    read_threads: usize,
+    counters: Option<Arc<PipelineCounters>>,
+) -> Result<UbamPairReader<BufReader<Box<dyn Read + Send>>>> {
+    let path = path.as_ref();
+    let reader = BufReader::with_capacity(1 << 20, open_compressed(path, read_threads, counters)?);
+    UbamPairReader::new(reader).with_context(|| format!("Failed to read BAM header of {}", path.display()))
+}
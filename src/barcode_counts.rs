@@ -0,0 +1,203 @@
+// barcode_counts.rs - 每个条形码的 read 计数（TSV / Parquet 输出）
+
+use crate::failure::{Classify, Failure};
+use anyhow::{Context, Result};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// 单个条形码的计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BarcodeCount {
+    pub reads: u64,
+    /// 观测序列本身就是该条形码的 read 数
+    pub raw_reads: u64,
+    /// 经白名单纠错后才归到该条形码的 read 数
+    pub corrected_reads: u64,
+}
+
+/// 按最终输出的条形码（纠错后）统计 read 数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BarcodeCounter {
+    counts: HashMap<Vec<u8>, BarcodeCount>,
+    /// 是否启用了白名单纠错；决定输出是否包含 raw/corrected 两列
+    pub correction: bool,
+}
+
+impl BarcodeCounter {
+    pub fn new(correction: bool) -> Self {
+        BarcodeCounter { counts: HashMap::new(), correction }
+    }
+
+    pub fn add(&mut self, barcode: &[u8], corrected: bool) {
+        let count = match self.counts.get_mut(barcode) {
+            Some(count) => count,
+            None => self.counts.entry(barcode.to_vec()).or_default(),
+        };
+        count.reads += 1;
+        if corrected {
+            count.corrected_reads += 1;
+        } else {
+            count.raw_reads += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &BarcodeCounter) {
+        for (barcode, c) in &other.counts {
+            let count = self.counts.entry(barcode.clone()).or_default();
+            count.reads += c.reads;
+            count.raw_reads += c.raw_reads;
+            count.corrected_reads += c.corrected_reads;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn get(&self, barcode: &[u8]) -> Option<BarcodeCount> {
+        self.counts.get(barcode).copied()
+    }
+
+    /// 按 read 数降序、条形码升序排列
+    pub fn sorted(&self) -> Vec<(&[u8], BarcodeCount)> {
+        let mut rows: Vec<(&[u8], BarcodeCount)> = self.counts.iter().map(|(k, &v)| (k.as_slice(), v)).collect();
+        rows.sort_by(|a, b| b.1.reads.cmp(&a.1.reads).then_with(|| a.0.cmp(b.0)));
+        rows
+    }
+
+    /// 写成 TSV：`barcode  reads [raw_reads  corrected_reads]`
+    pub fn write_tsv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+            .classify(Failure::OutputIo)?;
+        let mut w = BufWriter::new(file);
+        if self.correction {
+            writeln!(w, "barcode\treads\traw_reads\tcorrected_reads")?;
+        } else {
+            writeln!(w, "barcode\treads")?;
+        }
+        for (barcode, c) in self.sorted() {
+            let barcode = String::from_utf8_lossy(barcode);
+            if self.correction {
+                writeln!(w, "{}\t{}\t{}\t{}", barcode, c.reads, c.raw_reads, c.corrected_reads)?;
+            } else {
+                writeln!(w, "{}\t{}", barcode, c.reads)?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// 写成 rank 曲线数据 CSV：`rank,barcode,count,cumulative_fraction`
+    ///
+    /// 行顺序同 [`sorted`](Self::sorted)，read 数相同时按条形码排序，重复运行得到相同的文件。
+    /// 给出 `max_points` 且条形码更多时，只写出按 rank 对数均匀抽取的约 `max_points` 行
+    /// （总是包含第一名与最后一名）；累计比例仍按全部条形码计算。
+    pub fn write_rank_plot<P: AsRef<Path>>(&self, path: P, max_points: Option<usize>) -> Result<()> {
+        let path = path.as_ref();
+        let rows = self.sorted();
+        let total: u64 = rows.iter().map(|(_, c)| c.reads).sum();
+        let ranks = max_points.filter(|&n| n < rows.len()).map(|n| log_spaced_ranks(rows.len(), n));
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+            .classify(Failure::OutputIo)?;
+        let mut w = BufWriter::new(file);
+        writeln!(w, "rank,barcode,count,cumulative_fraction")?;
+        let mut cumulative = 0u64;
+        for (i, (barcode, c)) in rows.iter().enumerate() {
+            cumulative += c.reads;
+            let rank = i + 1;
+            if ranks.as_ref().is_none_or(|ranks| ranks.contains(&rank)) {
+                writeln!(w, "{},{},{},{:.6}", rank, String::from_utf8_lossy(barcode), c.reads,
+                         cumulative as f64 / total as f64)?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// 写成 Parquet，列与 TSV 相同（需要 `parquet` feature）
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        use arrow_array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+        use parquet::arrow::ArrowWriter;
+        use parquet::basic::Compression;
+        use parquet::file::properties::WriterProperties;
+        use std::sync::Arc;
+
+        let path = path.as_ref();
+        let rows = self.sorted();
+        let u64_column = |f: fn(&BarcodeCount) -> u64| -> ArrayRef {
+            Arc::new(rows.iter().map(|(_, c)| f(c)).collect::<UInt64Array>())
+        };
+        let mut columns: Vec<(&str, ArrayRef)> = vec![
+            ("barcode", Arc::new(rows.iter().map(|(b, _)| Some(String::from_utf8_lossy(b))).collect::<StringArray>())),
+            ("reads", u64_column(|c| c.reads)),
+        ];
+        if self.correction {
+            columns.push(("raw_reads", u64_column(|c| c.raw_reads)));
+            columns.push(("corrected_reads", u64_column(|c| c.corrected_reads)));
+        }
+        let batch = RecordBatch::try_from_iter(columns)?;
+
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+            .classify(Failure::OutputIo)?;
+        let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// 1..=`len` 中按对数均匀分布的至多 `points` 个 rank（`points` 至少为 2）
+fn log_spaced_ranks(len: usize, points: usize) -> BTreeSet<usize> {
+    let max = (len as f64).ln();
+    (0..points)
+        .map(|k| ((max * k as f64 / (points - 1) as f64).exp().round() as usize).clamp(1, len))
+        .collect()
+}
+
+/// 按总 read 数过滤条形码（`--min-reads-per-barcode`）
+///
+/// 计数来自预先读一遍输入得到的 [`BarcodeCounter`]，因此与拆分时一样是纠错后的条形码。
+#[derive(Debug, Clone, Default)]
+pub struct MinReadsFilter {
+    min_reads: u64,
+    /// read 数达到阈值的条形码
+    kept: HashSet<Vec<u8>>,
+    removed_barcodes: u64,
+}
+
+impl MinReadsFilter {
+    pub fn new(counter: &BarcodeCounter, min_reads: u64) -> Self {
+        let mut kept = HashSet::new();
+        let mut removed_barcodes = 0;
+        for (barcode, count) in &counter.counts {
+            if count.reads >= min_reads {
+                kept.insert(barcode.clone());
+            } else {
+                removed_barcodes += 1;
+            }
+        }
+        MinReadsFilter { min_reads, kept, removed_barcodes }
+    }
+
+    pub fn min_reads(&self) -> u64 {
+        self.min_reads
+    }
+
+    /// read 数低于阈值而被去掉的条形码数
+    pub fn removed_barcodes(&self) -> u64 {
+        self.removed_barcodes
+    }
+
+    pub fn passes(&self, barcode: &[u8]) -> bool {
+        self.kept.contains(barcode)
+    }
+}
@@ -0,0 +1,69 @@
+// barcode_select.rs - 按细胞条形码挑选 read（--select-barcodes）
+
+use crate::failure::{Classify, Failure};
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// 要挑选的条形码
+///
+/// 每行一个条形码（支持 .gz），与输出的 R2 序列（白名单纠错之后）比较。
+/// 行内第一个空白或 `-` 之后的部分被忽略，因此可以直接使用 `AAAC...-1` 形式的细胞名。
+#[derive(Debug, Clone, Default)]
+pub struct BarcodeSelection {
+    barcodes: HashSet<Vec<u8>>,
+}
+
+impl BarcodeSelection {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut barcodes = HashSet::new();
+        for line in reader.lines() {
+            let line = line?;
+            let bc = cell_barcode(&line);
+            if !bc.is_empty() {
+                barcodes.insert(bc);
+            }
+        }
+        if barcodes.is_empty() {
+            bail!("Barcode list is empty");
+        }
+        Ok(BarcodeSelection { barcodes })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open barcode list {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Box::new(MultiGzDecoder::new(f)),
+            _          => Box::new(f),
+        };
+        Self::from_reader(BufReader::new(reader))
+            .with_context(|| format!("Failed to load barcode list {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.barcodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.barcodes.is_empty()
+    }
+
+    pub fn contains(&self, barcode: &[u8]) -> bool {
+        self.barcodes.contains(barcode)
+    }
+
+    /// 列表中的条形码（已规范化），顺序不定
+    pub fn barcodes(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.barcodes.iter().map(|bc| bc.as_slice())
+    }
+}
+
+/// 细胞名中的条形码部分：第一个空白或 `-` 之前，转为大写
+pub(crate) fn cell_barcode(cell: &str) -> Vec<u8> {
+    cell.split(|c: char| c.is_whitespace() || c == '-').next().unwrap_or("").as_bytes().to_ascii_uppercase()
+}
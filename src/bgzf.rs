@@ -0,0 +1,185 @@
+// bgzf.rs - BGZF 多线程并行解压
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use flate2::read::DeflateDecoder;
+use flate2::Crc;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::thread;
+
+/// 每个解压任务包含的 BGZF block 数（单个 block 解压后最多 64 KiB）
+const BLOCKS_PER_CHUNK: usize = 64;
+
+type ChunkResult = io::Result<Vec<u8>>;
+/// 一个解压任务：若干原始 block 及其结果回传通道
+type ChunkJob = (Vec<Vec<u8>>, Sender<ChunkResult>);
+
+/// 判断文件是否为 BGZF 格式（gzip 头带 `BC` extra 子字段）
+pub fn is_bgzf<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut header = [0u8; 16];
+    let mut f = File::open(path)?;
+    let mut filled = 0;
+    while filled < header.len() {
+        let n = f.read(&mut header[filled..])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        filled += n;
+    }
+    Ok(header[0] == 31 && header[1] == 139 && header[2] == 8 && header[3] & 4 != 0
+        && header[12] == b'B' && header[13] == b'C')
+}
+
+/// 并行解压 BGZF 文件的 `Read` 实现
+///
+/// BGZF 的每个 block 都是独立的 gzip member：后台线程按 block 头中的 BSIZE
+/// 把文件切成若干 chunk，交给解压线程池并行 inflate，再按原顺序拼回字节流，
+/// 下游的 FASTQ parser 感知不到差别。
+pub struct BgzfParallelReader {
+    chunks: Receiver<Receiver<ChunkResult>>,
+    current: Vec<u8>,
+    pos: usize,
+}
+
+impl BgzfParallelReader {
+    pub fn new<R: Read + Send + 'static>(inner: R, threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (job_tx, job_rx): (Sender<ChunkJob>, Receiver<ChunkJob>) = bounded(threads * 2);
+        // 按提交顺序排队的结果通道，保证输出顺序与文件一致
+        let (order_tx, order_rx) = bounded(threads * 2);
+
+        for _ in 0..threads {
+            let job_rx = job_rx.clone();
+            thread::spawn(move || {
+                while let Ok((blocks, result_tx)) = job_rx.recv() {
+                    let _ = result_tx.send(inflate_blocks(&blocks));
+                }
+            });
+        }
+
+        thread::spawn(move || {
+            let mut inner = inner;
+            loop {
+                let mut blocks = Vec::with_capacity(BLOCKS_PER_CHUNK);
+                let mut error = None;
+                while blocks.len() < BLOCKS_PER_CHUNK {
+                    match read_block(&mut inner) {
+                        Ok(Some(block)) => blocks.push(block),
+                        Ok(None) => break,
+                        Err(e) => {
+                            error = Some(e);
+                            break;
+                        }
+                    }
+                }
+                let done = blocks.len() < BLOCKS_PER_CHUNK;
+
+                if !blocks.is_empty() {
+                    let (result_tx, result_rx) = bounded(1);
+                    if job_tx.send((blocks, result_tx)).is_err() || order_tx.send(result_rx).is_err() {
+                        return;
+                    }
+                }
+                // 已读到的 block 先交出去，错误排在它们之后报告
+                if let Some(e) = error {
+                    let (result_tx, result_rx) = bounded(1);
+                    let _ = result_tx.send(Err(e));
+                    let _ = order_tx.send(result_rx);
+                    return;
+                }
+                if done {
+                    return;
+                }
+            }
+        });
+
+        BgzfParallelReader { chunks: order_rx, current: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for BgzfParallelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.current.len() {
+            let Ok(result_rx) = self.chunks.recv() else { return Ok(0) };
+            let chunk = result_rx
+                .recv()
+                .map_err(|_| io::Error::other("BGZF decompression worker exited"))??;
+            self.current = chunk;
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.current.len() - self.pos);
+        buf[..n].copy_from_slice(&self.current[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 读取一个完整的 BGZF block（含头尾），文件结束时返回 `None`
+fn read_block<R: Read>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 12];
+    match r.read_exact(&mut header[..1]) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    r.read_exact(&mut header[1..])?;
+    if header[0] != 31 || header[1] != 139 || header[2] != 8 || header[3] & 4 == 0 {
+        return Err(invalid("not a BGZF block (missing gzip FEXTRA header)"));
+    }
+
+    let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+    let mut extra = vec![0u8; xlen];
+    r.read_exact(&mut extra)?;
+
+    let mut bsize = None;
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let slen = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+        if extra[i] == b'B' && extra[i + 1] == b'C' && slen == 2 && i + 6 <= extra.len() {
+            bsize = Some(u16::from_le_bytes([extra[i + 4], extra[i + 5]]) as usize + 1);
+        }
+        i += 4 + slen;
+    }
+    let bsize = bsize.ok_or_else(|| invalid("BGZF block is missing the BC subfield"))?;
+    let rest = bsize
+        .checked_sub(12 + xlen)
+        .ok_or_else(|| invalid("BGZF block size smaller than its header"))?;
+
+    let mut block = Vec::with_capacity(bsize);
+    block.extend_from_slice(&header);
+    block.extend_from_slice(&extra);
+    block.resize(bsize, 0);
+    r.read_exact(&mut block[12 + xlen..12 + xlen + rest])?;
+    Ok(Some(block))
+}
+
+/// 解压一组 block，并校验 CRC32 与 ISIZE
+fn inflate_blocks(blocks: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(blocks.len() << 16);
+    for block in blocks {
+        let xlen = u16::from_le_bytes([block[10], block[11]]) as usize;
+        if block.len() < 12 + xlen + 8 {
+            return Err(invalid("truncated BGZF block"));
+        }
+        let trailer = &block[block.len() - 8..];
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+
+        let start = out.len();
+        DeflateDecoder::new(&block[12 + xlen..block.len() - 8]).read_to_end(&mut out)?;
+        if out.len() - start != isize {
+            return Err(invalid("BGZF block size mismatch"));
+        }
+        let mut check = Crc::new();
+        check.update(&out[start..]);
+        if check.sum() != crc {
+            return Err(invalid("BGZF block CRC mismatch"));
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,92 @@
+// gen_test_data.rs - 生成合成的 MGI scATAC-seq R1/R2 FASTQ，用于测试与基准
+//
+// 生成逻辑在 `scatac_barcode_splitter::simulate`（也用于 `simulate` 子命令），这里只保留原有的命令行。
+
+use anyhow::Result;
+use clap::Parser;
+use scatac_barcode_splitter::pipeline::{BARCODE_LEN, R2_LEN};
+use scatac_barcode_splitter::simulate::{load_barcodes, write_barcodes, write_record, Output, SimulateConfig, Simulator};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "gen_test_data", version)]
+#[command(about = "Generate synthetic MGI scATAC-seq R1/R2 FASTQ pairs (R2 = genomic + reverse-complemented barcode)")]
+struct Args {
+    #[arg(short = 'n', long, value_name = "N", default_value = "1000", help = "Number of read pairs")]
+    records: u64,
+
+    #[arg(long, value_name = "LEN", default_value = "50", value_parser = clap::value_parser!(u16).range(1..),
+          help = "R1 length")]
+    r1_len: u16,
+
+    #[arg(long, value_name = "LEN", default_value_t = R2_LEN as u16,
+          value_parser = clap::value_parser!(u16).range(BARCODE_LEN as i64..),
+          help = "R2 length; the last 16 bp hold the reverse-complemented barcode")]
+    r2_len: u16,
+
+    #[arg(long, value_name = "FILE", conflicts_with = "barcodes",
+          help = "Draw barcodes from this whitelist (one per line, `-1` suffixes ignored, .gz supported)")]
+    whitelist: Option<PathBuf>,
+
+    #[arg(long, value_name = "N", default_value = "100", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Number of random barcodes to draw from when no --whitelist is given")]
+    barcodes: u64,
+
+    #[arg(long, value_name = "FILE", help = "Write the barcodes in use to FILE (one per line), e.g. for --whitelist")]
+    write_barcodes: Option<PathBuf>,
+
+    #[arg(long, value_name = "RATE", default_value = "0", value_parser = parse_rate,
+          help = "Per-base substitution rate in the barcode")]
+    barcode_error_rate: f64,
+
+    #[arg(long, value_name = "RATE", default_value = "0", value_parser = parse_rate,
+          help = "Per-base rate of N calls in the barcode (applied after substitutions)")]
+    barcode_n_rate: f64,
+
+    #[arg(long, default_value = "42", help = "Random seed; the same seed and options give the same reads")]
+    seed: u64,
+
+    #[arg(short = '1', long, value_name = "FILE", requires = "r2",
+          help = "R1 output (.gz compresses) [default: interleaved R1/R2 on stdout]")]
+    r1: Option<PathBuf>,
+
+    #[arg(short = '2', long, value_name = "FILE", requires = "r1", help = "R2 output (.gz compresses)")]
+    r2: Option<PathBuf>,
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(r) if (0.0..=1.0).contains(&r) => Ok(r),
+        Ok(_) => Err("rate must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let simulator = Simulator::new(SimulateConfig {
+        pairs: args.records,
+        r1_len: args.r1_len as usize,
+        r2_len: args.r2_len as usize,
+        barcodes: args.whitelist.as_deref().map(load_barcodes).transpose()?,
+        random_barcodes: args.barcodes,
+        barcode_error_rate: args.barcode_error_rate,
+        barcode_n_rate: args.barcode_n_rate,
+        seed: args.seed,
+        ..SimulateConfig::default()
+    })?;
+    if let Some(path) = &args.write_barcodes {
+        write_barcodes(path, simulator.barcodes())?;
+    }
+
+    let (mut r1_out, mut r2_out) = match (&args.r1, &args.r2) {
+        (Some(r1), Some(r2)) => (Output::create(r1)?, Some(Output::create(r2)?)),
+        _ => (Output::stdout(), None),
+    };
+    for pair in simulator {
+        write_record(&mut r1_out, &pair.r1)?;
+        write_record(r2_out.as_mut().unwrap_or(&mut r1_out), &pair.r2)?;
+    }
+    r1_out.finish()?;
+    r2_out.map_or(Ok(()), Output::finish)
+}
@@ -0,0 +1,78 @@
+// cleanup.rs - 运行失败时删除本次创建的部分输出（--cleanup-on-error）
+//
+// 创建输出前先 `register`；所有输出写完后 `outputs_complete` 清空记录，之后的失败不再删除。
+
+use crate::remote::is_remote;
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+
+/// 本次运行创建（或覆盖）的本地输出
+static CREATED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// 记录即将创建的输出；对象存储的输出出错时不会提交，不需要记录
+pub fn register(path: &Path) {
+    let mut created = CREATED.lock();
+    if !is_remote(path) && !created.iter().any(|p| p == path) {
+        created.push(path.to_path_buf());
+    }
+}
+
+/// 所有输出都已完整写出：之后出错也不再删除
+pub fn outputs_complete() {
+    CREATED.lock().clear();
+}
+
+/// 删除记录的输出及其临时名（原子写入的 `.tmp`、出错时改名的 `.incomplete`），返回实际删除的文件
+///
+/// 每个删除的文件与删除失败都打印到标准错误。
+pub fn remove_partial_outputs() -> Vec<PathBuf> {
+    let mut removed = Vec::new();
+    for path in std::mem::take(&mut *CREATED.lock()) {
+        for suffix in ["", ".tmp", ".incomplete"] {
+            let mut candidate = path.clone().into_os_string();
+            candidate.push(suffix);
+            let candidate = PathBuf::from(candidate);
+            if !candidate.is_file() {
+                continue;
+            }
+            match std::fs::remove_file(&candidate) {
+                Ok(()) => {
+                    eprintln!("Removed partial output {}", candidate.display());
+                    removed.push(candidate);
+                }
+                Err(e) => eprintln!("WARNING: Failed to remove partial output {}: {}", candidate.display(), e),
+            }
+        }
+    }
+    removed
+}
+
+/// 收到 SIGINT、SIGTERM、SIGHUP 时删除部分输出后以 128 + 信号值退出（仅 Unix；SIGKILL 无法处理）
+///
+/// 必须在启动其他线程之前调用：这些信号在所有线程中被屏蔽，由一个专门的线程用 `sigwait` 接收，
+/// 删除文件不在信号处理函数中进行。
+pub fn remove_on_signal() {
+    #[cfg(unix)]
+    {
+        // SAFETY: sigset_t 由 sigemptyset 初始化；pthread_sigmask 与 sigwait 只读写传入的集合
+        unsafe {
+            let mut signals: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut signals);
+            for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+                libc::sigaddset(&mut signals, signal);
+            }
+            if libc::pthread_sigmask(libc::SIG_BLOCK, &signals, std::ptr::null_mut()) != 0 {
+                log::debug!("Failed to block termination signals; partial outputs are not removed on signals");
+                return;
+            }
+            std::thread::spawn(move || {
+                let mut signal = 0;
+                if libc::sigwait(&signals, &mut signal) == 0 {
+                    eprintln!("Received signal {}, removing partial outputs", signal);
+                    remove_partial_outputs();
+                    std::process::exit(128 + signal);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// complexity.rs - 测序饱和度与文库复杂度估计
+
+/// 饱和度：重复观测所占的比例，即 1 − 不同片段数 / 总 read 数
+///
+/// `total` 为 0 时返回 0。
+pub fn saturation(total: u64, unique: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    1.0 - unique.min(total) as f64 / total as f64
+}
+
+/// 按 Lander-Waterman 模型估计文库中不同分子的个数 C
+///
+/// 解 `unique = C × (1 − exp(−total / C))`。没有重复（`unique >= total`）时文库大小无法估计，返回 `None`。
+pub fn estimate_library_size(total: u64, unique: u64) -> Option<f64> {
+    if unique == 0 || unique >= total {
+        return None;
+    }
+    let (n, u) = (total as f64, unique as f64);
+    // 在 C 上二分：f(C) = C(1 − e^(−n/C)) 随 C 单调递增，C = u 时小于 u
+    let expected = |c: f64| c * (1.0 - (-n / c).exp());
+    let mut low = u;
+    let mut high = u * 2.0;
+    while expected(high) < u {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if expected(mid) < u { low = mid } else { high = mid }
+    }
+    Some((low + high) / 2.0)
+}
+
+/// 测序深度为当前 `fold` 倍时预计的不同片段数
+///
+/// 没有重复时按不同片段数与深度同比增长计算。
+pub fn projected_unique(total: u64, unique: u64, fold: f64) -> f64 {
+    match estimate_library_size(total, unique) {
+        Some(c) => c * (1.0 - (-(total as f64) * fold / c).exp()),
+        None => unique as f64 * fold,
+    }
+}
@@ -0,0 +1,149 @@
+// contaminants.rs - 按共有 k-mer 筛查已知污染序列（--contaminants）
+
+use crate::failure::{Classify, Failure};
+use crate::reverse_complement;
+use crate::stats::{ContaminantHit, ContaminantStats};
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// k-mer 长度
+pub const KMER_LEN: usize = 21;
+
+/// 2-bit 编码的 k-mer；含 N 等其他字符的 k-mer 跳过
+fn kmers(seq: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    seq.windows(KMER_LEN).filter_map(|kmer| {
+        kmer.iter().try_fold(0u64, |code, &base| {
+            let value = match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+            Some(code << 2 | value)
+        })
+    })
+}
+
+/// 污染序列的 k-mer 索引（正反两条链）
+#[derive(Debug, Clone)]
+pub struct ContaminantIndex {
+    names: Vec<String>,
+    kmers: Vec<HashSet<u64>>,
+}
+
+impl ContaminantIndex {
+    /// FASTA 格式；序列名取 `>` 之后第一个空白之前的部分，同名的序列合并
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut records: Vec<(String, Vec<u8>)> = Vec::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(header) = line.strip_prefix('>') {
+                let name = header.split_whitespace().next().unwrap_or("");
+                if name.is_empty() {
+                    bail!("Line {}: FASTA record without a name", line_no + 1);
+                }
+                records.push((name.to_string(), Vec::new()));
+            } else if !line.is_empty() {
+                let Some((_, seq)) = records.last_mut() else {
+                    bail!("Line {}: sequence before the first FASTA header", line_no + 1);
+                };
+                seq.extend_from_slice(line.as_bytes());
+            }
+        }
+        let mut index = ContaminantIndex { names: Vec::new(), kmers: Vec::new() };
+        for (name, seq) in records {
+            let kmers: HashSet<u64> = kmers(&seq).chain(kmers(&reverse_complement(&seq))).collect();
+            if kmers.is_empty() {
+                bail!("Contaminant '{}' has no {}-mer without N", name, KMER_LEN);
+            }
+            match index.names.iter().position(|n| *n == name) {
+                Some(i) => index.kmers[i].extend(kmers),
+                None => {
+                    index.names.push(name);
+                    index.kmers.push(kmers);
+                }
+            }
+        }
+        if index.names.is_empty() {
+            bail!("Contaminant file has no sequences");
+        }
+        Ok(index)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open contaminant file {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Box::new(MultiGzDecoder::new(f)),
+            _          => Box::new(f),
+        };
+        Self::from_reader(BufReader::new(reader))
+            .with_context(|| format!("Failed to load contaminant file {}", path.display()))
+    }
+
+    /// 污染序列名，按文件中的顺序
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// `seq` 与每条污染序列共有的不同 k-mer 数
+    pub fn shared_kmers(&self, seq: &[u8]) -> Vec<usize> {
+        let read_kmers: HashSet<u64> = kmers(seq).collect();
+        self.kmers.iter().map(|set| read_kmers.iter().filter(|k| set.contains(k)).count()).collect()
+    }
+}
+
+/// 抽样筛查：每 `every` 条 read 抽一条，与某条污染序列共有至少 `min_kmers` 个 k-mer 时记为命中
+///
+/// 只是估计，不做比对；一条 read 可以同时命中多条污染序列。只在一个线程中使用。
+#[derive(Debug, Clone)]
+pub struct ContaminantScreen {
+    index: ContaminantIndex,
+    every: u64,
+    min_kmers: usize,
+    seen: u64,
+    sampled: u64,
+    hits: Vec<u64>,
+}
+
+impl ContaminantScreen {
+    pub fn new(index: ContaminantIndex, every: u64, min_kmers: usize) -> Self {
+        let hits = vec![0; index.names.len()];
+        ContaminantScreen { index, every: every.max(1), min_kmers: min_kmers.max(1), seen: 0, sampled: 0, hits }
+    }
+
+    /// 记录一条 read；只有被抽中的才检查
+    pub fn add(&mut self, seq: &[u8]) {
+        self.seen += 1;
+        if !self.seen.is_multiple_of(self.every) {
+            return;
+        }
+        self.sampled += 1;
+        for (hits, shared) in self.hits.iter_mut().zip(self.index.shared_kmers(seq)) {
+            if shared >= self.min_kmers {
+                *hits += 1;
+            }
+        }
+    }
+
+    pub fn to_stats(&self) -> ContaminantStats {
+        ContaminantStats {
+            sample_every: self.every,
+            sampled: self.sampled,
+            kmer_len: KMER_LEN,
+            min_kmers: self.min_kmers,
+            contaminants: self.index.names.iter().zip(&self.hits).map(|(name, &reads)| ContaminantHit {
+                name: name.clone(),
+                reads,
+                fraction: reads as f64 / self.sampled.max(1) as f64,
+            }).collect(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// duplicate_names.rs - 估计输入中重复出现的 read 名比例（--check-duplicate-names）
+
+use crate::stats::DuplicateNameStats;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher};
+
+/// 同时保留的抽样名字（64 位哈希）数上限；超过时抽样率减半
+pub const MAX_SAMPLED: usize = 1 << 16;
+
+/// 按名字哈希抽样的重复名估计器
+///
+/// 只保留哈希值最高 `level` 位全为 0 的名字（抽样率 2^-level），同一个名字的每次出现要么都被抽中、
+/// 要么都不被抽中，因此抽样中的重复比例就是整体重复比例的无偏估计。保留的名字超过上限时 `level`
+/// 加 1，丢掉不再满足条件的一半，内存固定。
+///
+/// 只比较 64 位哈希：不同名字哈希相同而被误判为重复的概率约为 抽样名字数² / 2^65，可以忽略；
+/// 不会漏判。每个处理线程各持有一个，结束时用 [`merge`](Self::merge) 合并，结果与线程数无关。
+#[derive(Debug, Clone)]
+pub struct DuplicateNames {
+    max_sampled: usize,
+    level: u32,
+    seen: u64,
+    counts: HashMap<u64, u32>,
+}
+
+impl Default for DuplicateNames {
+    fn default() -> Self {
+        Self::with_max_sampled(MAX_SAMPLED)
+    }
+}
+
+impl DuplicateNames {
+    pub fn new() -> Self {
+        DuplicateNames::default()
+    }
+
+    /// 指定保留的名字数上限
+    pub fn with_max_sampled(max_sampled: usize) -> Self {
+        DuplicateNames { max_sampled: max_sampled.max(1), level: 0, seen: 0, counts: HashMap::new() }
+    }
+
+    /// 记录一个名字（去掉 /1、/2 与注释之后的部分）
+    pub fn add(&mut self, name: &[u8]) {
+        self.seen += 1;
+        // SipHash 使用固定的 key，各线程、各次运行的哈希一致
+        let hash = BuildHasherDefault::<DefaultHasher>::default().hash_one(name);
+        if hash.leading_zeros() < self.level {
+            return;
+        }
+        *self.counts.entry(hash).or_insert(0) += 1;
+        self.shrink();
+    }
+
+    pub fn merge(&mut self, other: &DuplicateNames) {
+        self.seen += other.seen;
+        self.level = self.level.max(other.level);
+        for (&hash, &count) in &other.counts {
+            *self.counts.entry(hash).or_insert(0) += count;
+        }
+        let level = self.level;
+        self.counts.retain(|hash, _| hash.leading_zeros() >= level);
+        self.shrink();
+    }
+
+    fn shrink(&mut self) {
+        while self.counts.len() > self.max_sampled {
+            self.level += 1;
+            let level = self.level;
+            self.counts.retain(|hash, _| hash.leading_zeros() >= level);
+        }
+    }
+
+    pub fn to_stats(&self) -> DuplicateNameStats {
+        let sampled: u64 = self.counts.values().map(|&count| count as u64).sum();
+        let duplicates = sampled - self.counts.len() as u64;
+        let fraction = duplicates as f64 / sampled.max(1) as f64;
+        DuplicateNameStats {
+            names: self.seen,
+            sample_rate: 0.5f64.powi(self.level as i32),
+            sampled,
+            sampled_duplicates: duplicates,
+            fraction,
+            estimated_duplicates: (fraction * self.seen as f64).round() as u64,
+        }
+    }
+}
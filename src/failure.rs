@@ -0,0 +1,89 @@
+// failure.rs - 失败类别与对应的退出码，供工作流区分值得重试与不值得重试的失败
+
+use std::fmt;
+use std::io;
+
+/// 失败类别；命令行程序按它选择退出码（见 README 的“退出码”）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// 参数错误，或参数组合不能使用
+    Usage,
+    /// 输入文件不存在或无法打开
+    InputUnreadable,
+    /// 输入格式错误：FASTQ 记录不完整、gzip 或 BAM 损坏等
+    MalformedInput,
+    /// R1/R2 不成对、不同步，或来自不同的测序
+    Pairing,
+    /// 输出无法创建或写入
+    OutputIo,
+    /// 结果超出阈值（如 `--strict` 下的重复 read 名称）
+    Threshold,
+}
+
+impl Failure {
+    /// 未归类的错误
+    pub const OTHER_EXIT_CODE: u8 = 1;
+    /// 被 SIGINT 中断（128 + 信号编号）
+    pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Failure::Usage => 2,
+            Failure::InputUnreadable => 3,
+            Failure::MalformedInput => 4,
+            Failure::Pairing => 5,
+            Failure::OutputIo => 6,
+            Failure::Threshold => 7,
+        }
+    }
+
+    /// 给错误标上这个类别
+    pub fn error(self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        Classified { failure: self, error: error.into() }.into()
+    }
+
+    /// 错误链中最外层的类别；没有标注时，`io::ErrorKind::InvalidData` 等解析错误记为 [`Failure::MalformedInput`]
+    pub fn of(error: &anyhow::Error) -> Option<Failure> {
+        error.chain()
+            .find_map(|e| e.downcast_ref::<Classified>().map(|c| c.failure))
+            .or_else(|| error.chain().find_map(|e| match e.downcast_ref::<io::Error>()?.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Some(Failure::MalformedInput),
+                _ => None,
+            }))
+    }
+
+    /// 错误对应的退出码
+    pub fn exit_code_of(error: &anyhow::Error) -> u8 {
+        Failure::of(error).map_or(Failure::OTHER_EXIT_CODE, Failure::exit_code)
+    }
+}
+
+/// 标注了类别的错误；显示为原错误，原错误的来源照常出现在错误链中
+#[derive(Debug)]
+pub struct Classified {
+    failure: Failure,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for Classified {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// 给 `Result` 中的错误标上类别
+pub trait Classify<T> {
+    fn classify(self, failure: Failure) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> Classify<T> for Result<T, E> {
+    fn classify(self, failure: Failure) -> anyhow::Result<T> {
+        self.map_err(|e| failure.error(e))
+    }
+}
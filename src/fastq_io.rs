@@ -0,0 +1,122 @@
+// fastq_io.rs - FASTQ 文件的打开与逐条读取
+
+use crate::bgzf::{is_bgzf, BgzfParallelReader};
+use crate::failure::{Classify, Failure};
+use crate::progress::PipelineCounters;
+use crate::{CountingReader, PathContext};
+use anyhow::{Context, Result};
+use fastq::OwnedRecord;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// 打开 FASTQ 文件，gzip 或 plain 都能自动判断；bgzip 压缩的文件按 block 并行解压
+pub fn open_fastq<P: AsRef<Path>>(p: P, read_threads: usize) -> Result<Box<dyn Read + Send>> {
+    open_fastq_counted(p, read_threads, None)
+}
+
+/// 同 [`open_fastq`]，另把读取的文件字节数（解压前）累加到 `counters`；读取错误带上路径
+pub fn open_fastq_counted<P: AsRef<Path>>(
+    p: P,
+    read_threads: usize,
+    counters: Option<Arc<PipelineCounters>>,
+) -> Result<Box<dyn Read + Send>> {
+    let path = p.as_ref();
+    let reader = if path.extension().and_then(|s| s.to_str()) == Some("gz") {
+        open_compressed(path, read_threads, counters)?
+    } else {
+        open_raw(path, counters)?
+    };
+    Ok(Box::new(PathContext::new(reader, path.display().to_string())))
+}
+
+/// 打开 gzip / BGZF 压缩的文件（不看扩展名）；BGZF 在 `read_threads > 1` 时并行解压
+pub fn open_compressed<P: AsRef<Path>>(
+    p: P,
+    read_threads: usize,
+    counters: Option<Arc<PipelineCounters>>,
+) -> Result<Box<dyn Read + Send>> {
+    let path = p.as_ref();
+    let f = open_raw(path, counters)?;
+    Ok(if read_threads > 1 && is_bgzf(path).unwrap_or(false) {
+        Box::new(BgzfParallelReader::new(f, read_threads))
+    } else {
+        Box::new(MultiGzDecoder::new(f))
+    })
+}
+
+fn open_raw(path: &Path, counters: Option<Arc<PipelineCounters>>) -> Result<Box<dyn Read + Send>> {
+    let f = File::open(path).with_context(|| format!("Failed to open {}", path.display()))
+        .classify(Failure::InputUnreadable)?;
+    Ok(match counters {
+        Some(counters) => Box::new(CountingReader::new(BufReader::new(f), Arc::clone(&counters.input_bytes_read))),
+        None => Box::new(f),
+    })
+}
+
+/// 以 `BufRead` 形式打开 FASTQ，供逐条读取使用
+pub fn open_fastq_reader<P: AsRef<Path>>(p: P) -> Result<BufReader<Box<dyn Read + Send>>> {
+    Ok(BufReader::with_capacity(1 << 20, open_fastq(p, 1)?))
+}
+
+fn read_line<R: BufRead>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<usize> {
+    buf.clear();
+    let n = reader.read_until(b'\n', buf)?;
+    while matches!(buf.last(), Some(b'\n' | b'\r')) {
+        buf.pop();
+    }
+    Ok(n)
+}
+
+/// 读取一条 FASTQ 记录到 `record`（复用其内存），文件结束时返回 `false`
+///
+/// '+' 行后面若有内容，保存在 `record.sep` 中。
+pub fn read_fastq_record<R: BufRead>(reader: &mut R, record: &mut OwnedRecord) -> io::Result<bool> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    if read_line(reader, &mut record.head)? == 0 {
+        return Ok(false);
+    }
+    if record.head.first() != Some(&b'@') {
+        return Err(invalid("FASTQ header line does not start with '@'"));
+    }
+    record.head.remove(0);
+
+    if read_line(reader, &mut record.seq)? == 0 {
+        return Err(invalid("truncated FASTQ record"));
+    }
+
+    let mut plus = record.sep.take().unwrap_or_default();
+    if read_line(reader, &mut plus)? == 0 || plus.first() != Some(&b'+') {
+        return Err(invalid("FASTQ separator line does not start with '+'"));
+    }
+    plus.remove(0);
+    record.sep = if plus.is_empty() { None } else { Some(plus) };
+
+    if read_line(reader, &mut record.qual)? == 0 {
+        return Err(invalid("truncated FASTQ record"));
+    }
+    Ok(true)
+}
+
+/// 写出一条 FASTQ 记录；`record.sep` 有内容时写在 '+' 之后，否则为单独的 '+'
+pub fn write_fastq_record<W: Write + ?Sized>(writer: &mut W, record: &OwnedRecord) -> io::Result<()> {
+    writer.write_all(b"@")?;
+    writer.write_all(&record.head)?;
+    writer.write_all(b"\n")?;
+    writer.write_all(&record.seq)?;
+    writer.write_all(b"\n+")?;
+    if let Some(sep) = &record.sep {
+        writer.write_all(sep)?;
+    }
+    writer.write_all(b"\n")?;
+    writer.write_all(&record.qual)?;
+    writer.write_all(b"\n")
+}
+
+/// 空记录，作为 [`read_fastq_record`] 的初始缓冲
+pub fn empty_record() -> OwnedRecord {
+    OwnedRecord { head: Vec::new(), seq: Vec::new(), sep: None, qual: Vec::new() }
+}
@@ -0,0 +1,255 @@
+// groups.rs - 按条形码分组输出（--split-by）
+
+use crate::barcode_select::cell_barcode;
+use crate::failure::{Classify, Failure};
+use anyhow::{bail, Context, Result};
+use crate::fastq_io::write_fastq_record;
+use crate::FinishWrite;
+use fastq::OwnedRecord;
+use flate2::read::MultiGzDecoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// 不在分组表中的条形码所属的组
+pub const UNASSIGNED_GROUP: &str = "unassigned";
+
+/// `--shard-by-bc-prefix` 允许的最大前缀长度（4^4 + 1 个分片）
+pub const MAX_SHARD_PREFIX: usize = 4;
+
+/// 前缀中有 N（或其他非 ACGT 字符）的条形码所属分片的名字
+pub const N_SHARD: &str = "N";
+
+/// 按条形码前 `k` 个碱基分片：ACGT 组成的前缀按 2-bit 编码为 `0..4^k`，其余为 `4^k`
+pub fn prefix_shard(barcode: &[u8], k: usize) -> usize {
+    let n_shard = 1 << (2 * k);
+    if barcode.len() < k {
+        return n_shard;
+    }
+    barcode[..k].iter().try_fold(0, |shard, &base| {
+        let value = match base.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => return None,
+        };
+        Some(shard << 2 | value)
+    }).unwrap_or(n_shard)
+}
+
+/// [`prefix_shard`] 的全部分片名，按分片序号排列：前缀本身，最后是 [`N_SHARD`]
+pub fn prefix_shard_names(k: usize) -> Vec<String> {
+    let mut names: Vec<String> = (0..1usize << (2 * k))
+        .map(|shard| (0..k).rev().map(|i| b"ACGT"[(shard >> (2 * i)) & 3] as char).collect())
+        .collect();
+    names.push(N_SHARD.to_string());
+    names
+}
+
+/// 把组名转成可用于文件名的形式：字母、数字、`.`、`-`、`_` 以外的字符替换为 `_`
+pub fn sanitize_group_name(name: &str) -> String {
+    let sanitized: String = name.trim().chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+    // 避免 `.`、`..` 及隐藏文件
+    sanitized.trim_start_matches('.').to_string()
+}
+
+/// 条形码到组的映射
+///
+/// TSV 每行 `barcode<TAB>group`（支持 .gz），条形码按 `--select-barcodes` 的规则规范化，
+/// 组名经 [`sanitize_group_name`] 处理。第一行的第一列为 `barcode` 时视为表头。
+#[derive(Debug, Clone)]
+pub struct BarcodeGroups {
+    barcodes: HashMap<Vec<u8>, usize>,
+    /// 组名；最后一个总是 [`UNASSIGNED_GROUP`]（表中也可以直接使用这个组）
+    names: Vec<String>,
+}
+
+impl BarcodeGroups {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut barcodes = HashMap::new();
+        let mut names: Vec<String> = Vec::new();
+        // 清理后的组名 → (序号, 原始组名)
+        let mut index: HashMap<String, (usize, String)> = HashMap::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            let (bc, group) = (fields.next().unwrap_or(""), fields.next().unwrap_or("").trim());
+            if line_no == 0 && bc.eq_ignore_ascii_case("barcode") {
+                continue;
+            }
+            if group.is_empty() {
+                bail!("Line {}: expected `barcode<TAB>group`", line_no + 1);
+            }
+            let name = sanitize_group_name(group);
+            if name.is_empty() {
+                bail!("Line {}: group name '{}' is empty after sanitizing", line_no + 1, group);
+            }
+            let id = match index.get(&name) {
+                Some((_, original)) if original != group => {
+                    bail!("Groups '{}' and '{}' both map to file name '{}'", original, group, name);
+                }
+                Some(&(id, _)) => id,
+                None => {
+                    names.push(name.clone());
+                    index.insert(name, (names.len() - 1, group.to_string()));
+                    names.len() - 1
+                }
+            };
+            if let Some(previous) = barcodes.insert(cell_barcode(bc), id) {
+                if previous != id {
+                    bail!("Line {}: barcode {} is assigned to both '{}' and '{}'", line_no + 1, bc, names[previous], names[id]);
+                }
+            }
+        }
+        if barcodes.is_empty() {
+            bail!("Group table is empty");
+        }
+        if !index.contains_key(UNASSIGNED_GROUP) {
+            names.push(UNASSIGNED_GROUP.to_string());
+        }
+        Ok(BarcodeGroups { barcodes, names })
+    }
+
+    /// 每个条形码自成一组（`--split-per-barcode`），组名即条形码，按字母顺序编号
+    pub fn per_barcode<'a>(barcodes: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut sorted: Vec<&[u8]> = barcodes.into_iter().collect();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let mut names: Vec<String> = sorted.iter().map(|bc| sanitize_group_name(&String::from_utf8_lossy(bc))).collect();
+        let barcodes = sorted.into_iter().enumerate().map(|(id, bc)| (bc.to_vec(), id)).collect();
+        names.push(UNASSIGNED_GROUP.to_string());
+        BarcodeGroups { barcodes, names }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open group table {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Box::new(MultiGzDecoder::new(f)),
+            _          => Box::new(f),
+        };
+        Self::from_reader(BufReader::new(reader))
+            .with_context(|| format!("Failed to load group table {}", path.display()))
+    }
+
+    /// 组名（已清理），包括 [`UNASSIGNED_GROUP`]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// 条形码所属组的序号；不在表中时为 [`UNASSIGNED_GROUP`] 的序号
+    pub fn group_of(&self, barcode: &[u8]) -> usize {
+        self.barcodes.get(barcode).copied().unwrap_or_else(|| self.unassigned())
+    }
+
+    fn unassigned(&self) -> usize {
+        self.names.iter().position(|n| n == UNASSIGNED_GROUP).unwrap()
+    }
+}
+
+/// 按组名创建一组 R1、R2、R3 写入端；第二个参数为 true 时该组的输出之前被关闭过，应追加写入
+pub type GroupWriterFactory = Box<dyn FnMut(&str, bool) -> Result<[Box<dyn FinishWrite>; 3]> + Send>;
+
+/// 按需为每个组打开输出
+///
+/// 某个组第一次出现时才调用工厂函数，因此没有 read 的组不会产生文件。同时打开的组数超过
+/// `max_open` 时报错，避免耗尽文件描述符；[`with_lru_eviction`](Self::with_lru_eviction)
+/// 之后改为关闭最久未写入的组，需要时再以追加方式打开。
+pub struct GroupWriters {
+    factory: GroupWriterFactory,
+    max_open: usize,
+    evict: bool,
+    /// 组序号 → (写入端, 最近一次写入的序号)
+    open: HashMap<usize, ([Box<dyn FinishWrite>; 3], u64)>,
+    /// 打开过的组的组名与写出的 pair 数
+    groups: HashMap<usize, (String, u64)>,
+    writes: u64,
+    evictions: u64,
+}
+
+impl GroupWriters {
+    pub fn new(factory: GroupWriterFactory, max_open: usize) -> Self {
+        GroupWriters {
+            factory,
+            max_open: max_open.max(1),
+            evict: false,
+            open: HashMap::new(),
+            groups: HashMap::new(),
+            writes: 0,
+            evictions: 0,
+        }
+    }
+
+    /// 打开的组数达到上限时关闭最久未写入的组，而不是报错
+    pub fn with_lru_eviction(mut self) -> Self {
+        self.evict = true;
+        self
+    }
+
+    /// 把一个 pair 的三条记录写入 `group` 组；`name` 只在该组第一次出现时调用
+    pub fn write(&mut self, group: usize, name: impl FnOnce() -> String, records: [OwnedRecord; 3]) -> Result<()> {
+        if !self.open.contains_key(&group) {
+            let reopen = self.groups.contains_key(&group);
+            let name = match self.groups.get(&group) {
+                Some((name, _)) => name.clone(),
+                None => name(),
+            };
+            if self.open.len() >= self.max_open {
+                if !self.evict {
+                    bail!("More than {} groups have reads (next: '{}'); raise --max-open-groups", self.max_open, name);
+                }
+                self.close_least_recently_used()?;
+            }
+            let writers = (self.factory)(&name, reopen)
+                .with_context(|| format!("Failed to open outputs for group '{}'", name))?;
+            self.open.insert(group, (writers, 0));
+            self.groups.entry(group).or_insert((name, 0));
+        }
+        self.writes += 1;
+        let (writers, last_write) = self.open.get_mut(&group).unwrap();
+        *last_write = self.writes;
+        for (writer, record) in writers.iter_mut().zip(records) {
+            write_fastq_record(writer, &record)?;
+        }
+        self.groups.get_mut(&group).unwrap().1 += 1;
+        Ok(())
+    }
+
+    fn close_least_recently_used(&mut self) -> Result<()> {
+        let Some(&group) = self.open.iter().min_by_key(|(_, (_, last_write))| *last_write).map(|(group, _)| group) else {
+            return Ok(());
+        };
+        let (writers, _) = self.open.remove(&group).unwrap();
+        for mut writer in writers {
+            writer.finish()?;
+        }
+        self.evictions += 1;
+        Ok(())
+    }
+
+    /// 因打开的组数达到上限而关闭输出的次数
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// 收尾并关闭全部输出，返回每组写出的 pair 数
+    pub fn finish(self) -> Result<BTreeMap<String, u64>> {
+        for (_, (writers, _)) in self.open {
+            for mut writer in writers {
+                writer.finish()?;
+            }
+        }
+        if self.evictions > 0 {
+            log::debug!("Closed and later reopened group outputs {} times", self.evictions);
+        }
+        Ok(self.groups.into_values().collect())
+    }
+}
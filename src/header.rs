@@ -0,0 +1,54 @@
+// header.rs - Illumina (CASAVA 1.8+) read header 解析
+
+/// 解析后的 Illumina read name
+///
+/// 格式：`<instrument>:<run>:<flowcell>:<lane>:<tile>:<x>:<y>[ <comment>]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IlluminaHeader<'a> {
+    pub instrument: &'a [u8],
+    pub run_number: &'a [u8],
+    pub flowcell: &'a [u8],
+    pub lane: u32,
+    pub tile: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+fn parse_u32(field: &[u8]) -> Option<u32> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+/// 解析 FASTQ header（不含 '@'）；不是 Illumina 格式（如 SRA、MGI）时返回 `None`
+pub fn parse_illumina_header(head: &[u8]) -> Option<IlluminaHeader<'_>> {
+    let name = head.split(|&b| b == b' ' || b == b'\t').next()?;
+    let name = crate::extract_base_header(name);
+    let mut fields = name.split(|&b| b == b':');
+    let instrument = fields.next()?;
+    let run_number = fields.next()?;
+    let flowcell = fields.next()?;
+    let lane = parse_u32(fields.next()?)?;
+    let tile = parse_u32(fields.next()?)?;
+    let x = parse_u32(fields.next()?)?;
+    let y = parse_u32(fields.next()?)?;
+    if fields.next().is_some() || instrument.is_empty() || flowcell.is_empty() {
+        return None;
+    }
+    Some(IlluminaHeader { instrument, run_number, flowcell, lane, tile, x, y })
+}
+
+impl IlluminaHeader<'_> {
+    /// 判断两条 read 是否来自同一次测序（仪器、run 编号、flowcell 都相同）
+    pub fn same_run(&self, other: &IlluminaHeader) -> bool {
+        self.instrument == other.instrument
+            && self.run_number == other.run_number
+            && self.flowcell == other.flowcell
+    }
+
+    /// `instrument:run:flowcell`，用于提示信息
+    pub fn run_id(&self) -> String {
+        format!("{}:{}:{}",
+                String::from_utf8_lossy(self.instrument),
+                String::from_utf8_lossy(self.run_number),
+                String::from_utf8_lossy(self.flowcell))
+    }
+}
@@ -0,0 +1,103 @@
+// inspect.rs - 按拆分时的布局标注 R2 中的各个片段（`inspect` 子命令）
+
+use crate::anchor::Anchor;
+use crate::pipeline::{BarcodeWindow, BARCODE_LEN};
+use crate::reverse_complement;
+use crate::whitelist::{BarcodeMatch, Whitelist};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// 标注行的图例
+pub const LEGEND: &str = "G = genomic (R3), S = spacer, B = barcode, . = not written";
+
+/// 拆分 R2 的布局：固定位置的条形码窗口，或按锚定序列定位
+pub struct Layout {
+    pub window: BarcodeWindow,
+    /// R3 在 R2 中的范围；按锚定序列定位时不用
+    pub r3_range: Range<usize>,
+    pub anchor: Option<Anchor>,
+}
+
+/// 一条 R2 中各片段的位置；read 太短时截到 read 末端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segments {
+    pub genomic: Range<usize>,
+    /// R3 与条形码之间不写出的碱基（如锚定序列）
+    pub spacer: Range<usize>,
+    pub barcode: Range<usize>,
+}
+
+impl Layout {
+    /// 一行描述，如 `R3 = R2[0..150], barcode = R2[150..166] (reverse complement)`
+    pub fn describe(&self) -> String {
+        let orientation = if self.window.reverse_complement { "reverse complement" } else { "forward" };
+        match &self.anchor {
+            Some(anchor) => format!("R3 = bases before anchor {} (up to {} mismatches), barcode = {} bp after it ({})",
+                                    String::from_utf8_lossy(anchor.sequence()), anchor.max_mismatches(),
+                                    BARCODE_LEN, orientation),
+            None => format!("R3 = R2[{}..{}], barcode = R2[{}..{}] ({})", self.r3_range.start, self.r3_range.end,
+                            self.window.offset, self.window.offset + BARCODE_LEN, orientation),
+        }
+    }
+
+    /// 按布局划分 `r2_seq`；找不到锚定序列时为 `None`
+    pub fn segments(&self, r2_seq: &[u8]) -> Option<Segments> {
+        let len = r2_seq.len();
+        if let Some(anchor) = &self.anchor {
+            let hit = anchor.locate(r2_seq, BARCODE_LEN)?;
+            return Some(Segments { genomic: 0..hit.start, spacer: hit.start..hit.end, barcode: hit.end..hit.end + BARCODE_LEN });
+        }
+        let clip = |range: Range<usize>| range.start.min(len)..range.end.min(len);
+        Some(Segments {
+            genomic: clip(self.r3_range.clone()),
+            spacer: clip(self.r3_range.end..self.window.offset),
+            barcode: clip(self.window.offset..self.window.offset + BARCODE_LEN),
+        })
+    }
+}
+
+impl Segments {
+    /// 与 read 等长的标注行，见 [`LEGEND`]
+    pub fn annotation(&self, len: usize) -> String {
+        (0..len).map(|i| {
+            if self.genomic.contains(&i) { 'G' }
+            else if self.spacer.contains(&i) { 'S' }
+            else if self.barcode.contains(&i) { 'B' }
+            else { '.' }
+        }).collect()
+    }
+}
+
+/// 一条 R2 的说明：序列、标注行、取出的条形码（转换方向前后），给出白名单时另有比对结果
+pub fn describe_read(name: &[u8], r2_seq: &[u8], layout: &Layout, whitelist: Option<&Whitelist>) -> String {
+    let mut text = format!("{} ({} bp)\n", String::from_utf8_lossy(name), r2_seq.len());
+    let _ = writeln!(text, "  R2        {}", String::from_utf8_lossy(r2_seq));
+    let Some(segments) = layout.segments(r2_seq) else {
+        text.push_str("  layout    anchor not found: the pair would be filtered\n");
+        return text;
+    };
+    let _ = writeln!(text, "  layout    {}", segments.annotation(r2_seq.len()));
+    let raw = &r2_seq[segments.barcode.clone()];
+    if raw.len() < BARCODE_LEN {
+        let _ = writeln!(text, "  barcode   {} (read ends {} bases into the {} bp barcode)",
+                         String::from_utf8_lossy(raw), raw.len(), BARCODE_LEN);
+        return text;
+    }
+    let barcode = if layout.window.reverse_complement { reverse_complement(raw) } else { raw.to_ascii_uppercase() };
+    if layout.window.reverse_complement {
+        let _ = writeln!(text, "  barcode   {} as read, {} after reverse complement",
+                         String::from_utf8_lossy(raw), String::from_utf8_lossy(&barcode));
+    } else {
+        let _ = writeln!(text, "  barcode   {} (forward, used as read)", String::from_utf8_lossy(&barcode));
+    }
+    if let Some(whitelist) = whitelist {
+        let result = match whitelist.correct(&barcode) {
+            BarcodeMatch::Exact => "exact match".to_string(),
+            BarcodeMatch::NRescued(hit) => format!("N rescued to {}", String::from_utf8_lossy(&hit)),
+            BarcodeMatch::Corrected(hit) => format!("corrected to {} (1 mismatch)", String::from_utf8_lossy(&hit)),
+            BarcodeMatch::NoMatch => "no match".to_string(),
+        };
+        let _ = writeln!(text, "  whitelist {}", result);
+    }
+    text
+}
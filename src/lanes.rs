@@ -0,0 +1,85 @@
+// lanes.rs - 按 read header 中的 lane 字段分组统计
+
+use crate::progress::FilterReason;
+use crate::stats::LaneStats;
+use std::collections::BTreeMap;
+
+/// header 不是 Illumina 格式时使用的 lane 名
+pub const UNKNOWN_LANE: &str = "unknown";
+
+/// 按 lane 分组输出（`--split-lanes`）时 lane 对应的组序号；`None` 为 header 无法解析的 pair
+pub fn lane_group(lane: Option<u32>) -> usize {
+    lane.map_or(usize::MAX, |l| l as usize)
+}
+
+/// [`lane_group`] 的组名，即输出子目录名：`L001`、`L002`……，无法解析的为 [`UNKNOWN_LANE`]
+pub fn lane_group_name(group: usize) -> String {
+    match u32::try_from(group) {
+        Ok(lane) if group != usize::MAX => format!("L{:03}", lane),
+        _ => UNKNOWN_LANE.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct LaneCounts {
+    pairs: u64,
+    filtered: [u64; FilterReason::ALL.len()],
+    /// 条形码命中白名单（含纠错）的 pair 数
+    whitelist_matched: u64,
+}
+
+/// 每个 lane 的 pair 数、过滤计数与白名单命中数
+///
+/// 每个处理线程各自累加，结束时合并；键为 `None` 的是 header 无法解析的 pair。
+#[derive(Debug, Clone, Default)]
+pub struct LaneTally {
+    lanes: BTreeMap<Option<u32>, LaneCounts>,
+}
+
+impl LaneTally {
+    /// 记录一个 pair 的处理结果；`filtered` 为 `None` 表示保留
+    pub fn record(&mut self, lane: Option<u32>, filtered: Option<FilterReason>) {
+        let counts = self.lanes.entry(lane).or_default();
+        counts.pairs += 1;
+        match filtered {
+            Some(reason) => counts.filtered[reason as usize] += 1,
+            None => counts.whitelist_matched += 1,
+        }
+        // 这两种过滤发生在白名单之后，条形码已命中
+        if matches!(filtered, Some(FilterReason::NotSelected | FilterReason::TooFewReads)) {
+            counts.whitelist_matched += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &LaneTally) {
+        for (lane, theirs) in &other.lanes {
+            let mine = self.lanes.entry(*lane).or_default();
+            mine.pairs += theirs.pairs;
+            mine.whitelist_matched += theirs.whitelist_matched;
+            for (a, b) in mine.filtered.iter_mut().zip(&theirs.filtered) {
+                *a += b;
+            }
+        }
+    }
+
+    /// 按 lane 编号排序的统计，无法解析的排在最后；`whitelist` 表示启用了白名单纠错
+    pub fn to_stats(&self, whitelist: bool) -> Vec<LaneStats> {
+        let mut lanes: Vec<_> = self.lanes.iter().filter(|(lane, _)| lane.is_some()).collect();
+        lanes.extend(self.lanes.get_key_value(&None));
+        lanes.into_iter().map(|(lane, counts)| {
+            let no_match = counts.filtered[FilterReason::BarcodeNoMatch as usize];
+            let tested = counts.whitelist_matched + no_match;
+            LaneStats {
+                lane: lane.map_or_else(|| UNKNOWN_LANE.to_string(), |l| l.to_string()),
+                pairs_read: counts.pairs,
+                pairs_filtered: counts.filtered.iter().sum(),
+                filtered_by_reason: FilterReason::ALL.iter()
+                    .map(|r| (r.label().to_string(), counts.filtered[*r as usize]))
+                    .collect(),
+                whitelist_match_rate: whitelist.then(|| {
+                    if tested > 0 { counts.whitelist_matched as f64 / tested as f64 } else { 0.0 }
+                }),
+            }
+        }).collect()
+    }
+}
@@ -1,5 +1,307 @@
 // lib.rs - 库函数
 
+pub mod adaptive_batch;
+pub mod affinity;
+pub mod anchor;
+pub mod bam;
+pub mod barcode_counts;
+pub mod barcode_select;
+pub mod bgzf;
+pub mod cleanup;
+pub mod complexity;
+pub mod contaminants;
+pub mod duplicate_names;
+pub mod failure;
+pub mod fastq_io;
+pub mod groups;
+pub mod header;
+pub mod hll;
+pub mod inspect;
+pub mod lanes;
+pub mod low_complexity;
+pub mod metrics;
+pub mod numa;
+pub mod offset_scan;
+pub mod optical;
+pub mod overrepresented;
+pub mod parallel_gz;
+pub mod pipeline;
+pub mod preflight;
+pub mod progress;
+pub mod qc;
+pub mod read_names;
+pub mod readthrough;
+pub mod record_pool;
+pub mod remote;
+pub mod run;
+pub mod run_info;
+pub mod simulate;
+pub mod stats;
+pub mod tiles;
+pub mod whitelist;
+
+pub use pipeline::{run_pipeline, run_pipeline_from_channel, BarcodeWindow, PipelineConfig, PipelineWriters, SharedBuffer};
+
+use fastq::OwnedRecord;
+use flate2::write::GzEncoder;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// 写入时把字节数累加到共享计数器的 `Write` 包装
+///
+/// 计数器可以在其他线程中读取（例如进度显示），多个包装也可以共用一个计数器。
+///
+/// ```
+/// use scatac_barcode_splitter::CountingWriter;
+/// use std::io::Write;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+///
+/// let bytes = Arc::new(AtomicU64::new(0));
+/// let mut writer = CountingWriter::new(Vec::new(), Arc::clone(&bytes));
+/// writer.write_all(b"@read1\nACGT\n").unwrap();
+/// assert_eq!(writer.bytes_written(), 12);
+/// assert_eq!(bytes.load(Ordering::Relaxed), 12);
+/// assert_eq!(writer.into_inner(), b"@read1\nACGT\n");
+/// ```
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W, bytes: Arc<AtomicU64>) -> Self {
+        CountingWriter { inner, bytes }
+    }
+
+    /// 计数器的当前值（包括共用该计数器的其他包装写出的字节）
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 把读写错误加上文件路径的 `Read`/`Write` 包装
+///
+/// 读写线程只拿到 `Box<dyn Read>`/`Box<dyn Write>`，否则报错时不知道是哪个文件。
+pub struct PathContext<T> {
+    inner: T,
+    path: String,
+}
+
+impl<T> PathContext<T> {
+    pub fn new(inner: T, path: impl Into<String>) -> Self {
+        PathContext { inner, path: path.into() }
+    }
+
+    fn context(&self, action: &str, e: io::Error) -> io::Error {
+        io::Error::new(e.kind(), format!("Failed to {} {}: {}", action, self.path, e))
+    }
+}
+
+impl<R: Read> Read for PathContext<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|e| self.context("read", e))
+    }
+}
+
+impl<W: Write> Write for PathContext<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(|e| self.context("write", e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|e| self.context("write", e))
+    }
+}
+
+/// 写完后需要显式收尾的 `Write`
+///
+/// gzip 在数据之后还要写出最后一个 deflate 块和 CRC、长度；drop 时才写出会丢掉其中的错误（如磁盘已满），
+/// 留下截断的文件。输出全部写完后调用 [`finish`](FinishWrite::finish) 收尾并拿到错误。默认实现只 flush。
+pub trait FinishWrite: Write + Send {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: FinishWrite + ?Sized> FinishWrite for Box<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl FinishWrite for Box<dyn Write + Send> {}
+impl FinishWrite for Vec<u8> {}
+impl FinishWrite for io::Sink {}
+impl FinishWrite for std::fs::File {}
+
+impl<W: FinishWrite> FinishWrite for BufWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().finish()
+    }
+}
+
+impl<W: FinishWrite> FinishWrite for CountingWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl<W: Write + Send> FinishWrite for GzEncoder<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: FinishWrite> FinishWrite for PathContext<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish().map_err(|e| self.context("write", e))
+    }
+}
+
+/// 写出 FASTQ 记录的包装：记录先格式化到复用的缓冲区，再写入内部的 `BufWriter`
+///
+/// [`write_batch`](FastqWriter::write_batch) 把整个 batch 格式化后一次写出；缓冲区按
+/// [`with_buffer_capacity`](FastqWriter::with_buffer_capacity) 预分配，之后只在记录更长时扩容。
+/// 输出本身已有缓冲时（如 [`run::create_writer`] 返回的写入端）用 [`with_capacities`](FastqWriter::with_capacities)
+/// 把 `BufWriter` 的容量设为 0，避免重复缓冲。
+///
+/// ```
+/// use fastq::OwnedRecord;
+/// use scatac_barcode_splitter::FastqWriter;
+///
+/// let record = OwnedRecord { head: b"read1".to_vec(), seq: b"ACGT".to_vec(), sep: None, qual: b"IIII".to_vec() };
+/// let mut writer = FastqWriter::new(Vec::new());
+/// writer.write_record(&record).unwrap();
+/// writer.write_batch(&[record]).unwrap();
+/// assert_eq!(writer.into_inner().unwrap(), b"@read1\nACGT\n+\nIIII\n@read1\nACGT\n+\nIIII\n");
+/// ```
+pub struct FastqWriter<W: Write> {
+    inner: BufWriter<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> FastqWriter<W> {
+    /// 缓冲区预留一条典型记录的大小
+    pub fn new(inner: W) -> Self {
+        Self::with_buffer_capacity(inner, 512)
+    }
+
+    pub fn with_buffer_capacity(inner: W, capacity: usize) -> Self {
+        FastqWriter { inner: BufWriter::new(inner), buffer: Vec::with_capacity(capacity) }
+    }
+
+    /// 同时指定内部 `BufWriter` 与格式化缓冲区的容量；`writer_capacity` 为 0 时格式化后的记录直接写入 `inner`
+    pub fn with_capacities(inner: W, writer_capacity: usize, buffer_capacity: usize) -> Self {
+        FastqWriter { inner: BufWriter::with_capacity(writer_capacity, inner), buffer: Vec::with_capacity(buffer_capacity) }
+    }
+
+    pub fn write_record(&mut self, record: &OwnedRecord) -> io::Result<()> {
+        self.buffer.clear();
+        fastq_io::write_fastq_record(&mut self.buffer, record)?;
+        self.inner.write_all(&self.buffer)
+    }
+
+    /// 整个 batch 格式化到缓冲区后一次写出
+    pub fn write_batch(&mut self, records: &[OwnedRecord]) -> io::Result<()> {
+        self.buffer.clear();
+        for record in records {
+            fastq_io::write_fastq_record(&mut self.buffer, record)?;
+        }
+        self.inner.write_all(&self.buffer)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// 内部的 writer；还留在 `BufWriter` 中的记录不在其中
+    pub fn get_ref(&self) -> &W {
+        self.inner.get_ref()
+    }
+
+    /// 写出缓冲的内容并 flush 内部的 writer，再取回它；flush 出错时返回该错误
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        self.inner.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+/// 读取时把字节数累加到共享计数器的 `BufRead` 包装
+///
+/// 通过 `read` 与 `fill_buf`/`consume` 读出的字节都会计数。
+///
+/// ```
+/// use scatac_barcode_splitter::CountingReader;
+/// use std::io::{BufRead, Read};
+/// use std::sync::Arc;
+///
+/// let mut reader = CountingReader::new(&b"@read1\nACGT\n+\nIIII\n"[..], Arc::default());
+/// let mut line = String::new();
+/// reader.read_line(&mut line).unwrap();
+/// assert_eq!(reader.bytes_read(), 7);
+/// let mut rest = Vec::new();
+/// reader.read_to_end(&mut rest).unwrap();
+/// assert_eq!(reader.bytes_read(), 19);
+/// ```
+pub struct CountingReader<R: BufRead> {
+    inner: R,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<R: BufRead> CountingReader<R> {
+    pub fn new(inner: R, bytes: Arc<AtomicU64>) -> Self {
+        CountingReader { inner, bytes }
+    }
+
+    /// 计数器的当前值（包括共用该计数器的其他包装读取的字节）
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.bytes.fetch_add(amt as u64, Ordering::Relaxed);
+    }
+}
+
 /// DNA 序列反向互补函数
 /// 
 /// 将输入的 DNA 序列进行反向互补转换：
@@ -8,16 +310,98 @@
 /// - 其他字符转为 N
 /// - 自动转大写并反向序列
 pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
-    seq.iter().rev().map(|b| match b.to_ascii_uppercase() {
+    seq.iter().rev().map(|&b| complement(b)).collect()
+}
+
+/// 与 [`reverse_complement`] 相同，但直接改写 `seq`，不分配内存
+pub fn reverse_complement_in_place(seq: &mut [u8]) {
+    seq.reverse();
+    for b in seq.iter_mut() {
+        *b = complement(*b);
+    }
+}
+
+fn complement(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
         b'A' => b'T',
         b'T' => b'A',
         b'G' => b'C',
         b'C' => b'G',
         _    => b'N',
+    }
+}
+
+/// IUPAC 碱基的互补碱基（大写）；非 IUPAC 字符返回 N
+fn complement_iupac(b: u8) -> u8 {
+    match b.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'G' => b'C',
+        b'C' => b'G',
+        b'R' => b'Y', // A/G ↔ C/T
+        b'Y' => b'R',
+        b'S' => b'S', // G/C
+        b'W' => b'W', // A/T
+        b'K' => b'M', // G/T ↔ A/C
+        b'M' => b'K',
+        b'B' => b'V', // C/G/T ↔ A/C/G
+        b'V' => b'B',
+        b'D' => b'H', // A/G/T ↔ A/C/T
+        b'H' => b'D',
+        _    => b'N',
+    }
+}
+
+/// 支持 IUPAC 简并碱基的反向互补
+///
+/// 与 [`reverse_complement`] 不同，简并碱基（R、Y、S、W、K、M、B、D、H、V）
+/// 会被转换为对应的互补简并碱基，而不是 N。
+pub fn reverse_complement_iupac(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| complement_iupac(b)).collect()
+}
+
+/// 把序列规范化为大写 IUPAC 字符，非 IUPAC 字符转为 N
+pub fn canonicalize_iupac(seq: &[u8]) -> Vec<u8> {
+    seq.iter().map(|&b| match b.to_ascii_uppercase() {
+        c @ (b'A' | b'C' | b'G' | b'T' | b'R' | b'Y' | b'S' | b'W'
+            | b'K' | b'M' | b'B' | b'D' | b'H' | b'V') => c,
+        _ => b'N',
     }).collect()
 }
 
 /// 提取 FASTQ header 的基础 ID（移除 /1 或 /2 后缀）
 pub fn extract_base_header(head: &[u8]) -> &[u8] {
     if head.ends_with(b"/1") || head.ends_with(b"/2") { &head[..head.len()-2] } else { head }
-}
\ No newline at end of file
+}
+
+/// read 名称：header 中第一个空白之前的部分，再去掉 /1 或 /2
+pub fn read_name(head: &[u8]) -> &[u8] {
+    let end = head.iter().position(|b| b.is_ascii_whitespace()).unwrap_or(head.len());
+    extract_base_header(&head[..end])
+}
+
+/// header 中 read 名之后的注释，连同前面的分隔符（空格或 tab）；没有注释时为空
+pub fn header_comment(head: &[u8]) -> &[u8] {
+    let start = head.iter().position(|b| b.is_ascii_whitespace()).unwrap_or(head.len());
+    &head[start..]
+}
+
+/// 单个处理线程的工作量
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WorkerStats {
+    pub worker_id: usize,
+    pub records: u64,
+    pub wall_ms: u64,
+}
+
+/// 找出处理量明显偏少（不到平均值一半）的线程，返回其 `worker_id`
+pub fn find_lagging_workers(workers: &[WorkerStats]) -> Vec<usize> {
+    if workers.len() < 2 {
+        return Vec::new();
+    }
+    let mean = workers.iter().map(|w| w.records).sum::<u64>() as f64 / workers.len() as f64;
+    workers.iter()
+        .filter(|w| (w.records as f64) < mean * 0.5)
+        .map(|w| w.worker_id)
+        .collect()
+}
@@ -0,0 +1,38 @@
+// low_complexity.rs - 基因组 read 的低复杂度过滤（--filter-low-complexity）
+
+/// 建议的阈值：随机序列约为 0.9，(AT)n 约为 0.17，poly-A 为 0
+pub const SUGGESTED_MIN_COMPLEXITY: f64 = 0.5;
+
+/// 三核苷酸的香农熵，按可能取到的最大值归一化到 0–1
+///
+/// 最大值为 log2(min(64, 三核苷酸个数))，因此短序列也能取到 1。含 N 的三核苷酸不计；
+/// 可用的三核苷酸少于 2 个时返回 0。
+pub fn trinucleotide_entropy(seq: &[u8]) -> f64 {
+    let mut counts = [0u32; 64];
+    let mut total = 0u32;
+    for triplet in seq.windows(3) {
+        let code = triplet.iter().try_fold(0usize, |code, &base| {
+            let value = match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+            Some(code * 4 + value)
+        });
+        if let Some(code) = code {
+            counts[code] += 1;
+            total += 1;
+        }
+    }
+    if total < 2 {
+        return 0.0;
+    }
+    let n = f64::from(total);
+    let entropy: f64 = counts.iter().filter(|&&c| c > 0).map(|&c| {
+        let p = f64::from(c) / n;
+        -p * p.log2()
+    }).sum();
+    entropy / n.min(64.0).log2()
+}
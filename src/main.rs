@@ -1,354 +1,2272 @@
-use anyhow::Result;
-use clap::Parser;
-use crossbeam_channel::{bounded, Receiver, Sender};
-use fastq::{each_zipped, OwnedRecord, Parser as FastqParser, Record};
-use flate2::read::MultiGzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use scatac_barcode_splitter::{reverse_complement, extract_base_header};
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use fastq::{OwnedRecord, Record};
+use scatac_barcode_splitter::{extract_base_header, find_lagging_workers, read_name, reverse_complement, FinishWrite};
+use scatac_barcode_splitter::adaptive_batch::BatchTarget;
+use scatac_barcode_splitter::affinity;
+use scatac_barcode_splitter::anchor::Anchor;
+use scatac_barcode_splitter::bam::{detect_input_format, open_ubam, InputFormat};
+use scatac_barcode_splitter::barcode_counts::{BarcodeCounter, MinReadsFilter};
+use scatac_barcode_splitter::failure::{Classify, Failure};
+use scatac_barcode_splitter::header::parse_illumina_header;
+use scatac_barcode_splitter::inspect::{self, Layout};
+use scatac_barcode_splitter::fastq_io::{empty_record, open_fastq_counted, open_fastq_reader, read_fastq_record};
+use scatac_barcode_splitter::metrics::MetricsWriter;
+use scatac_barcode_splitter::numa;
+use scatac_barcode_splitter::offset_scan::{best_window, scan_barcode_offsets, OffsetScanRow};
+use scatac_barcode_splitter::contaminants::{ContaminantIndex, ContaminantScreen};
+use scatac_barcode_splitter::optical::OpticalDupPolicy;
+use scatac_barcode_splitter::overrepresented::OverrepresentedConfig;
+use scatac_barcode_splitter::preflight;
+use scatac_barcode_splitter::pipeline::{r3_range, run_pipeline_with_reader, BarcodeWindow, PipelineConfig, PipelineWriters,
+                                        CHANNEL_DEPTH, R2_LEN};
+use scatac_barcode_splitter::progress::{FilterReason, PipelineCounters, ProgressReporter, ProgressStyle};
+use scatac_barcode_splitter::qc::FastqQc;
+use scatac_barcode_splitter::read_names::ReadNameList;
+use scatac_barcode_splitter::tiles::TileExclusion;
+use scatac_barcode_splitter::readthrough::ReadthroughPolicy;
+use scatac_barcode_splitter::record_pool::RecordPool;
+use scatac_barcode_splitter::barcode_select::BarcodeSelection;
+use scatac_barcode_splitter::cleanup;
+use scatac_barcode_splitter::groups::{BarcodeGroups, MAX_SHARD_PREFIX, UNASSIGNED_GROUP};
+use scatac_barcode_splitter::remote;
+use scatac_barcode_splitter::simulate::{self, Defect, SimulateConfig, Simulator};
+use scatac_barcode_splitter::run::{create_writer, default_io_threads, output_compression, output_path, read_fastq_lanes,
+                                   read_ubam_lanes, run, InputReader, RunConfig, ThreadTopology};
+use scatac_barcode_splitter::run_info::{format_rfc3339, hostname, DoneFile, InputFileInfo, RunInfo};
+use scatac_barcode_splitter::stats::{RunStats, StageTimings};
+use scatac_barcode_splitter::whitelist::Whitelist;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufWriter, Read, Write};
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::io::{self, BufReader, BufWriter, Cursor, IsTerminal, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+// 大量短命的小分配（FASTQ 记录）下系统分配器碎片较多，`jemalloc` 或 `mimalloc` feature 改用对应的分配器
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("features `jemalloc` and `mimalloc` are mutually exclusive; enable at most one allocator");
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc"), not(target_env = "msvc")))]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 
 #[derive(Parser)]
-#[command(name = "fastq_processor")]
-#[command(about = "Process R1 and R2 FASTQ files")]
-struct Args {
-    #[arg(short = '1', long, help = "Input R1 FASTQ file")]
-    r1_input: PathBuf,
-    
+#[command(name = "scatac-barcode-splitter", version)]
+#[command(about = "Split MGI scATAC-seq R1/R2 FASTQ files into 10x-style R1/R2/R3")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Split R1/R2 FASTQ files into R1, R2 (barcode) and R3 (genomic) outputs
+    Split(Box<SplitArgs>),
+    /// Validate and re-emit already-split R1/barcode/R3 FASTQ files under 10x-style names
+    Passthrough(PassthroughArgs),
+    /// Rebuild the original 166 bp R2 from split barcode (R2) and genomic (R3) outputs
+    Recombine(RecombineArgs),
+    /// Check split outputs for completeness: matching headers, 16 bp R2, R3 <= 150 bp, quality lengths
+    Validate(ValidateArgs),
+    /// Recompute quality metrics (per-position quality, GC, lengths, barcode frequency) from existing output files
+    Stats(StatsArgs),
+    /// Generate synthetic R1/R2 FASTQ pairs with known barcodes (and optional defects) for testing
+    Simulate(SimulateArgs),
+    /// Show how the first R2 reads would be split: genomic, spacer and barcode bases, and the extracted barcode
+    Inspect(InspectArgs),
+    /// Print the version
+    Version,
+}
+
+#[derive(clap::Args)]
+struct SimulateArgs {
+    #[arg(short = 'n', long, value_name = "N", default_value = "1000", help = "Number of read pairs")]
+    pairs: u64,
+
+    #[arg(long, value_name = "LEN", default_value = "50", value_parser = clap::value_parser!(u16).range(1..),
+          help = "R1 length")]
+    r1_len: u16,
+
+    #[arg(long, value_name = "LEN", default_value_t = R2_LEN as u16,
+          value_parser = clap::value_parser!(u16).range(BARCODE_LEN as i64..), help = "R2 length")]
+    r2_len: u16,
+
+    #[arg(long, value_name = "N", help = "0-based start of the barcode in R2 [default: the last 16 bp]")]
+    barcode_offset: Option<u16>,
+
+    #[arg(long, default_value = "false", help = "Write the barcode into R2 as is instead of reverse-complemented")]
+    no_rc_barcode: bool,
+
+    #[arg(long, value_name = "FILE", conflicts_with = "barcodes",
+          help = "Draw barcodes from this whitelist (one per line, `-1` suffixes ignored, .gz supported)")]
+    whitelist: Option<PathBuf>,
+
+    #[arg(long, value_name = "N", default_value = "100", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Number of random barcodes to draw from when no --whitelist is given")]
+    barcodes: u64,
+
+    #[arg(long, value_name = "FILE", help = "Write the barcodes in use to FILE (one per line), e.g. for --whitelist")]
+    write_barcodes: Option<PathBuf>,
+
+    #[arg(long, value_name = "RATE", default_value = "0", value_parser = parse_rate,
+          help = "Per-base substitution rate in the barcode")]
+    barcode_error_rate: f64,
+
+    #[arg(long, value_name = "RATE", default_value = "0", value_parser = parse_rate,
+          help = "Per-base rate of N calls in the barcode (applied after substitutions)")]
+    barcode_n_rate: f64,
+
+    #[arg(long, value_name = "RATE", default_value = "0", value_parser = parse_rate,
+          help = "Fraction of pairs with a deliberate defect: truncated R2, mismatched R1/R2 names or an unknown barcode")]
+    defect_rate: f64,
+
+    #[arg(long, default_value = "42", help = "Random seed; the same seed and options give the same reads")]
+    seed: u64,
+
+    #[arg(short = '1', long, value_name = "FILE", help = "R1 output (.gz compresses)")]
+    r1: PathBuf,
+
+    #[arg(short = '2', long, value_name = "FILE", help = "R2 output (.gz compresses)")]
+    r2: PathBuf,
+
+    #[arg(long, value_name = "FILE", help = "Write the truth table (read name, true barcode, defect) as TSV")]
+    truth: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct InspectArgs {
     #[arg(short = '2', long, help = "Input R2 FASTQ file")]
     r2_input: PathBuf,
-    
+
+    #[arg(short = 'n', long, value_name = "N", default_value = "10", value_parser = parse_positive,
+          help = "Number of reads to show")]
+    reads: usize,
+
+    #[arg(long, value_name = "POS", default_value_t = R2_LEN - BARCODE_LEN, conflicts_with = "anchor",
+          help = "First base (0-based) of the barcode in R2")]
+    bc_start: usize,
+
+    #[arg(long, default_value = "false", help = "The barcode is in forward orientation in R2: do not reverse-complement it")]
+    no_rc_barcode: bool,
+
+    #[arg(long, value_name = "POS", default_value_t = 0, conflicts_with = "anchor",
+          help = "First base (0-based) of R2 written to R3")]
+    r3_start: usize,
+
+    #[arg(long, value_name = "POS", conflicts_with = "anchor",
+          help = "End (exclusive) of the R2 region written to R3 [default: the barcode start]")]
+    r3_end: Option<usize>,
+
+    #[arg(long, value_name = "SEQ", help = "Locate the barcode as the 16 bases after this constant sequence, as split --anchor")]
+    anchor: Option<String>,
+
+    #[arg(long, value_name = "K", default_value_t = 1, requires = "anchor",
+          help = "Mismatches allowed when searching for --anchor")]
+    anchor_max_mismatch: usize,
+
+    #[arg(short = 'w', long, help = "Barcode whitelist: also show whether each barcode matches or would be corrected")]
+    whitelist: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct ValidateArgs {
+    #[arg(long, help = "R1 output file")]
+    r1: PathBuf,
+
+    #[arg(long, help = "R2 (barcode) output file")]
+    r2: PathBuf,
+
+    #[arg(long, help = "R3 (genomic) output file")]
+    r3: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct PassthroughArgs {
+    #[arg(short = '1', long, help = "Input R1 FASTQ file")]
+    r1_input: PathBuf,
+
+    #[arg(long, help = "Input barcode FASTQ file (written as R2)")]
+    barcode: PathBuf,
+
+    #[arg(short = '3', long, help = "Input R3 (genomic) FASTQ file")]
+    r3_input: PathBuf,
+
     #[arg(short = 'o', long, help = "Output prefix")]
     output_prefix: String,
+
+    #[arg(long, default_value = "false", help = "Reverse-complement the barcode reads (and reverse their qualities)")]
+    rc_barcode: bool,
+
+    #[arg(short = 'c', long, default_value = "false", help = "Compress output files with gzip")]
+    compress: bool,
+
+    #[arg(short = 'n', long, default_value = "001", value_parser = parse_number_suffix,
+          help = "Number suffix for output files (e.g., 001, 002)")]
+    number_suffix: String,
+
+    #[arg(long, value_parser = parse_thread_count,
+          help = "gzip compression threads per output file when --compress is set [default: cores/8, 1-4]")]
+    write_threads: Option<usize>,
+
+    #[arg(long, value_name = "FILE", help = "Write run statistics (counts, timing) as JSON")]
+    stats_json: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct RecombineArgs {
+    #[arg(long, help = "Barcode FASTQ written by split (R2, or the _CR_ file for uncorrected barcodes)")]
+    barcode: PathBuf,
+
+    #[arg(long, help = "Genomic FASTQ written by split (R3)")]
+    genomic: PathBuf,
+
+    #[arg(short = 'o', long, help = "Output R2 FASTQ file (.gz to compress)")]
+    output: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct StatsArgs {
+    #[arg(long, help = "R1 output file")]
+    r1: Option<PathBuf>,
+
+    #[arg(long, help = "R2 (barcode) output file")]
+    r2: Option<PathBuf>,
+
+    #[arg(long, help = "R3 (genomic) output file")]
+    r3: Option<PathBuf>,
+
+    #[arg(long, help = "Directory to write the metric tables (TSV) into; only a summary is printed without it")]
+    output_dir: Option<PathBuf>,
+}
+
+#[derive(clap::Args, serde::Serialize)]
+#[command(group(clap::ArgGroup::new("barcode_filter").args(["select_barcodes", "min_reads_per_barcode"]).multiple(true)))]
+struct SplitArgs {
+    #[arg(short = '1', long, num_args = 1.., required = true,
+          help = "Input R1 FASTQ file(s), or unaligned BAM file(s) holding both mates; several files require --lane-merge")]
+    r1_input: Vec<PathBuf>,
     
-    #[arg(short = 't', long, default_value = "4", help = "Number of threads")]
-    threads: usize,
+    #[arg(short = '2', long, num_args = 1.., help = "Input R2 FASTQ file(s), in the same lane order as R1 (not used with uBAM input)")]
+    r2_input: Vec<PathBuf>,
     
-    #[arg(short = 'b', long, default_value = "200000", help = "Batch size for processing")]
+    #[arg(long, value_enum, default_value_t = InputFormatArg::Auto,
+          help = "Input format: auto (detect from the file contents), fastq or ubam")]
+    input_format: InputFormatArg,
+    
+    #[arg(long, default_value = "false", help = "Merge multiple lanes (one R1/R2 pair per lane) into a single output set")]
+    lane_merge: bool,
+    
+    #[arg(short = 'o', long, required_unless_present_any = ["benchmark", "prescan"], help = "Output prefix")]
+    output_prefix: Option<String>,
+    
+    #[arg(short = 't', long, visible_alias = "process-threads", value_parser = parse_thread_count,
+          help = "Number of processing threads [default: half of the CPU cores]")]
+    threads: Option<usize>,
+    
+    #[arg(long, value_parser = parse_thread_count,
+          help = "Decompression threads per BGZF input [default: cores/8, 1-4]")]
+    read_threads: Option<usize>,
+    
+    #[arg(long, value_parser = parse_thread_count,
+          help = "gzip compression threads per output file when --compress is set [default: cores/8, 1-4]")]
+    write_threads: Option<usize>,
+
+    #[arg(long, default_value = "false",
+          help = "Pin the reader and writer threads to the NUMA node of the disk holding the outputs; does nothing \
+                  when the node cannot be determined or the build lacks the `numa` feature (Linux only)")]
+    numa_local: bool,
+
+    #[arg(long, value_name = "CPUS", value_parser = parse_cpu_list,
+          help = "Pin the processing threads to these CPUs, e.g. 0-7 or 0,2,4,6 (Linux only)")]
+    affinity: Option<CpuList>,
+    
+    #[arg(short = 'b', long, default_value = "200000", value_parser = parse_positive, help = "Batch size for processing")]
     batch_size: usize,
     
+    #[arg(long, value_name = "GB", value_parser = parse_memory_gb,
+          help = "Memory budget; lowers --batch-size so that in-flight batches stay within it")]
+    max_memory_gb: Option<f64>,
+    
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Abort with an error if processing a single batch takes longer than MS milliseconds (stuck thread)")]
+    batch_timeout_ms: Option<u64>,
+
+    #[arg(long, default_value = "false",
+          help = "Adjust the batch size while running (starting from --batch-size) by 10% steps so that \
+                  processing one batch takes --batch-target-ms")]
+    adaptive_batch: bool,
+
+    #[arg(long, value_name = "MIN..MAX", default_value = "100..500", requires = "adaptive_batch",
+          value_parser = parse_inclusive_range,
+          help = "Target processing time of one batch in milliseconds for --adaptive-batch")]
+    batch_target_ms: RangeInclusive<usize>,
+
+    #[arg(long, default_value = "false",
+          help = "Format each output batch into a buffer pre-sized from --batch-size and the expected read lengths, \
+                  then write it at once (avoids growing the buffer mid-batch)")]
+    preallocate_writer_buffer: bool,
+
+    #[arg(long, value_name = "N", value_parser = parse_positive,
+          help = "Keep up to N record buffers in a pool: the reader takes records from it and the writer threads \
+                  return them after writing, instead of allocating every record anew")]
+    record_pool: Option<usize>,
+    
     #[arg(short = 'v', long, default_value = "false", help = "Verbose output showing progress")]
     verbose: bool,
+
+    #[arg(short = 'q', long, default_value = "false", conflicts_with_all = ["verbose", "benchmark"],
+          help = "Print no summary and show no progress; errors and warnings are still printed")]
+    quiet: bool,
+    
+    #[arg(long, value_enum, default_value_t = ProgressMode::Auto,
+          help = "Progress display on stderr: auto (bar on a terminal, log otherwise), none, bar or log")]
+    progress: ProgressMode,
+
+    #[arg(long, value_name = "SECS", default_value = "10", value_parser = parse_progress_interval,
+          help = "Seconds between progress lines in log mode")]
+    progress_interval: u64,
     
     #[arg(short = 'c', long, default_value = "false", help = "Compress output files with gzip")]
     compress: bool,
     
-    #[arg(short = 'n', long, default_value = "001", help = "Number suffix for output files (e.g., 001, 002)")]
+    #[arg(short = 'n', long, default_value = "001", value_parser = parse_number_suffix,
+          help = "Number suffix for output files (e.g., 001, 002)")]
     number_suffix: String,
+
+    #[arg(short = 'w', long, help = "Barcode whitelist (one barcode per line, .gz supported); enables barcode correction")]
+    whitelist: Option<PathBuf>,
+
+    #[arg(long, value_name = "Q", requires = "whitelist", help = "Mask barcode bases with quality below Q as N before whitelist correction")]
+    bc_mask_qual: Option<u8>,
+
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "whitelist",
+          value_parser = clap::value_parser!(u8).range(0..16),
+          help = "Accept R2 reads up to N bases short: complete the truncated barcode from the whitelist entry \
+                  uniquely sharing the remaining sequence")]
+    allow_truncated_bc: u8,
+
+    #[arg(long, default_value = "false", requires = "whitelist",
+          help = "For barcodes that 1-mismatch correction cannot match, also try a single insertion or deletion \
+                  and accept unique whitelist hits (slower; only runs on unmatched barcodes)")]
+    correct_indels: bool,
+
+    #[arg(long, default_value = "false", conflicts_with = "apply_best",
+          help = "The barcode is in forward orientation in R2 (e.g. sci-ATAC-seq): do not reverse-complement it or reverse its qualities")]
+    no_rc_barcode: bool,
+
+    #[arg(long, value_name = "SEQ",
+          conflicts_with_all = ["r3_start", "r3_end", "scan_bc_offset", "two_pass", "allow_truncated_bc"],
+          help = "Locate the barcode by a constant sequence (e.g. the ME) in R2 instead of fixed coordinates: \
+                  bases before it are written to R3 and the 16 bases after it are the barcode; \
+                  pairs without the anchor are filtered")]
+    anchor: Option<String>,
+
+    #[arg(long, value_name = "K", default_value_t = 1, requires = "anchor",
+          help = "Mismatches allowed when searching for --anchor")]
+    anchor_max_mismatch: usize,
+
+    #[arg(long, default_value = "false",
+          help = "Write R3 as the reverse complement of the genomic insert (with reversed qualities)")]
+    reverse_r3: bool,
+
+    #[arg(long, value_name = "POS", default_value_t = 0,
+          help = "First base (0-based) of R2 written to R3, e.g. 10 to trim leading adapter sequence")]
+    r3_start: usize,
+
+    #[arg(long, value_name = "POS",
+          help = "End (exclusive) of the R2 region written to R3 [default: the barcode offset]")]
+    r3_end: Option<usize>,
+
+    #[arg(long, default_value = "false",
+          help = "Swap the R2 and R3 outputs: write the genomic read to R2 and the barcode to R3")]
+    swap_r2_r3: bool,
+
+    #[arg(long, default_value = "false", requires = "whitelist",
+          help = "Read the inputs twice: first count exact whitelist barcodes, then use those counts to resolve \
+                  ambiguous corrections in favour of the much more abundant barcode (inputs must be regular files)")]
+    two_pass: bool,
+
+    #[arg(long, value_name = "RANGE", requires = "whitelist", value_parser = parse_inclusive_range,
+          help = "Scan barcode offsets START..END (inclusive) in both orientations on the first R2 reads, \
+                  print the whitelist hit rate of each and exit")]
+    scan_bc_offset: Option<RangeInclusive<usize>>,
+
+    #[arg(long, default_value = "false", requires = "scan_bc_offset",
+          help = "After --scan-bc-offset, split using the barcode window with the highest hit rate instead of exiting")]
+    apply_best: bool,
+
+    #[arg(long, default_value = "false", conflicts_with_all = ["benchmark", "scan_bc_offset", "two_pass"],
+          help = "Read only R2, print how many reads are 166 bp and the distribution of other lengths, and exit \
+                  without creating any files")]
+    prescan: bool,
+
+    #[arg(long = "head", visible_alias = "max-records", value_name = "N", value_parser = parse_positive,
+          help = "Read only the first N read pairs, then stop reading the inputs. --max-records is a deprecated \
+                  alias kept for parity with other tools; use --head")]
+    head: Option<usize>,
+
+    #[arg(long, value_enum, value_name = "POLICY",
+          help = "Search the barcode window for Tn5 adapter (ME) read-through: off, count the hits, or drop those pairs \
+                  [default: count with --whitelist, otherwise off]")]
+    readthrough: Option<ReadthroughArg>,
+
+    #[arg(long, value_name = "THRESHOLD", value_parser = parse_complexity,
+          help = "Drop pairs whose genomic read (R3, after trimming) has a normalised trinucleotide entropy \
+                  below THRESHOLD (0-1; random sequence scores about 0.9, (AT)n repeats about 0.17; \
+                  0.5 is a reasonable cutoff)")]
+    filter_low_complexity: Option<f64>,
+
+    #[arg(long, value_name = "DIST", value_parser = clap::value_parser!(u32).range(1..),
+          help = "Flag optical duplicates: pairs from the same tile with the same barcode and fragment start \
+                  whose header x/y coordinates lie within DIST pixels of an earlier pair")]
+    mark_optical_dups: Option<u32>,
+
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = OpticalDupArg::Count, requires = "mark_optical_dups",
+          help = "What to do with optical duplicates: count them only, or drop them")]
+    optical_dup_policy: OpticalDupArg,
+
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Sample every Nth written R3 read and report sequences (first 50 bp) that make up more than \
+                  --overrepresented-fraction of the sample, e.g. adapter dimers or phiX")]
+    overrepresented_every: Option<u64>,
+
+    #[arg(long, value_name = "FRACTION", default_value = "0.001", value_parser = parse_fraction,
+          requires = "overrepresented_every",
+          help = "Report a sequence as overrepresented above this fraction of the sampled R3 reads")]
+    overrepresented_fraction: f64,
+
+    #[arg(long, value_name = "FASTA",
+          help = "Screen a sample of the written R3 reads for these contaminant sequences (e.g. phiX, adapter dimers) \
+                  by shared 21-mers and report the fraction of reads matching each one")]
+    contaminants: Option<PathBuf>,
+
+    #[arg(long, value_name = "N", default_value = "100", value_parser = clap::value_parser!(u64).range(1..),
+          requires = "contaminants", help = "Screen every Nth written R3 read against --contaminants")]
+    contaminant_every: u64,
+
+    #[arg(long, value_name = "N", default_value = "3", value_parser = clap::value_parser!(u64).range(1..),
+          requires = "contaminants", help = "Count a read as matching a contaminant when they share at least N k-mers")]
+    contaminant_min_kmers: u64,
+
+    #[arg(long, value_name = "FRACTION", default_value = "0.01", value_parser = parse_fraction,
+          requires = "contaminants", help = "Warn when more than this fraction of screened reads match a contaminant")]
+    contaminant_warn: f64,
+
+    #[arg(long, default_value = "false",
+          help = "Estimate from a hash sample of the read names how many input pairs repeat an earlier name \
+                  (e.g. a lane concatenated twice) and warn above --duplicate-names-warn (an error with --strict)")]
+    check_duplicate_names: bool,
+
+    #[arg(long, value_name = "FRACTION", default_value = "0.001", value_parser = parse_fraction,
+          requires = "check_duplicate_names",
+          help = "Warn when more than this fraction of input pairs repeat an earlier read name")]
+    duplicate_names_warn: f64,
+
+    #[arg(long, default_value = "false", requires = "whitelist", help = "Also write the uncorrected barcodes to a separate FASTQ (_CR_ file)")]
+    keep_raw_barcode: bool,
+
+    #[arg(long, default_value = "false",
+          help = "Write filtered pairs too, with `<TAB>FT:Z:<reason>` appended to their read names in every output, \
+                  so that downstream tools can decide what to drop; they are still counted as filtered")]
+    soft_filter: bool,
+
+    #[arg(long, default_value = "false",
+          help = "Keep the text after '+' on the third FASTQ line: R1 outputs keep R1's, the barcode and genomic \
+                  outputs both keep R2's [default: write a bare '+']")]
+    preserve_plus_line: bool,
+
+    #[arg(long, default_value = "false",
+          help = "Keep the comment after the read name (e.g. Casava's `1:N:0:ACGT`): R1 outputs keep R1's, the \
+                  barcode and genomic outputs keep R2's; R1 and R2 are then paired by the read name alone")]
+    keep_header_comments: bool,
+
+    #[arg(long, value_name = "PREFIX", num_args = 0..=1, default_missing_value = "read", value_parser = parse_read_prefix,
+          help = "Rename the written pairs to PREFIX:1, PREFIX:2, ... in output order, identically in every output \
+                  [PREFIX default: read]")]
+    rename_reads: Option<String>,
+
+    #[arg(long, value_name = "FILE", requires = "rename_reads",
+          help = "Write the `original<TAB>renamed` read names of --rename-reads to FILE (e.g. names.tsv.gz)")]
+    rename_map: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE",
+          help = "Only keep pairs whose read name is listed in FILE (one per line, /1 /2 suffixes and comments ignored, .gz supported)")]
+    read_names: Option<PathBuf>,
+
+    #[arg(long, value_name = "TILES",
+          help = "Drop pairs from these flowcell tiles: a comma-separated list (e.g. 1101,1102) or a file of tile IDs; \
+                  pairs whose header has no tile field are kept")]
+    exclude_tiles: Option<String>,
+
+    #[arg(long, value_name = "FILE",
+          help = "Only write pairs whose (corrected) barcode is listed in FILE (one per line, `-1` suffixes ignored, .gz supported)")]
+    select_barcodes: Option<PathBuf>,
+
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..),
+          help = "Drop pairs whose (corrected) barcode has fewer than N reads in total; \
+                  reads the inputs twice (inputs must be regular files)")]
+    min_reads_per_barcode: Option<u64>,
+
+    #[arg(long, value_enum, default_value = "drop", requires = "barcode_filter",
+          help = "What to do with pairs whose barcode is not selected or has too few reads: drop them, \
+                  or write them to PREFIX_unassigned_* files")]
+    unselected: UnselectedPolicy,
+
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["keep_raw_barcode", "unselected"],
+          help = "Write one R1/R2/R3 set per group given a `barcode<TAB>group` TSV (PREFIX_GROUP_S1_L001_R1_001.fastq); \
+                  unlisted barcodes go to the `unassigned` group")]
+    split_by: Option<PathBuf>,
+
+    #[arg(long, default_value = "false", conflicts_with_all = ["split_by", "keep_raw_barcode", "unselected"],
+          help = "Write one R1/R2/R3 set per Illumina lane into DIR/L001/, DIR/L002/, ... (DIR is the directory of \
+                  the output prefix); pairs whose header has no lane field go to DIR/unknown/")]
+    split_lanes: bool,
+
+    #[arg(long, default_value = "false",
+          conflicts_with_all = ["split_by", "split_lanes", "keep_raw_barcode", "unselected"],
+          help = "Write one R1/R2/R3 set per barcode (PREFIX_BARCODE_S1_L001_R1_001.fastq) and their pair counts to \
+                  PREFIX_per_barcode_counts.tsv; the barcodes come from --select-barcodes, or else --whitelist")]
+    split_per_barcode: bool,
+
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u8).range(1..=MAX_SHARD_PREFIX as i64),
+          conflicts_with_all = ["split_by", "split_lanes", "split_per_barcode", "keep_raw_barcode", "unselected"],
+          help = "Write one R1/R2/R3 set per first K bases (1-4) of the corrected barcode \
+                  (PREFIX.shard_ACGT_S1_L001_R1_001.fastq); barcodes with N in those bases go to PREFIX.shard_N_*")]
+    shard_by_bc_prefix: Option<u8>,
+
+    #[arg(long, value_name = "N", default_value = "384", value_parser = clap::value_parser!(u64).range(3..),
+          requires = "split_per_barcode",
+          help = "Maximum number of files kept open with --split-per-barcode; the least recently written barcodes \
+                  are closed and later reopened for appending")]
+    max_open_files: u64,
+
+    #[arg(long, value_name = "N", default_value = "10000", requires = "split_per_barcode", value_parser = parse_positive,
+          help = "Refuse --split-per-barcode when the barcode list is longer than this")]
+    max_barcodes: usize,
+
+    #[arg(long, value_name = "N", default_value = "128", requires = "split_by", value_parser = parse_positive,
+          help = "Maximum number of groups that may receive reads with --split-by (3 open files per group)")]
+    max_open_groups: usize,
+
+    #[arg(long, default_value = "false",
+          help = "Before splitting, check that R1 and R2 list the same reads in the same, sorted order")]
+    check_pair_order: bool,
+
+    #[arg(long, default_value = "false", conflicts_with = "benchmark",
+          help = "Before splitting, check that the output filesystem has room for the outputs, estimated from the \
+                  input sizes and --compress; abort if it clearly does not, warn if it is close")]
+    check_space: bool,
+
+    #[arg(long, default_value = "false",
+          help = "If the run fails (error, panic, SIGINT/SIGTERM/SIGHUP), delete every output file it created, \
+                  including stats and report files, instead of leaving partial outputs as *.incomplete")]
+    cleanup_on_error: bool,
+
+    #[arg(long, default_value = "false", help = "Treat input consistency warnings (e.g. R1/R2 from different runs, duplicate read names, unpaired records) as errors")]
+    strict: bool,
+
+    #[arg(long, value_name = "FILE",
+          help = "Periodically write Prometheus textfile metrics (pairs read/written/filtered, bytes written, heartbeat)")]
+    metrics_file: Option<PathBuf>,
+
+    #[arg(long, value_name = "SECS", default_value = "15", value_parser = parse_progress_interval,
+          help = "Seconds between metrics file updates")]
+    metrics_interval: u64,
+
+    #[arg(long, value_name = "FILE",
+          help = "Write per-barcode read counts as TSV (with raw/corrected columns when --whitelist is set)")]
+    barcode_counts: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", help = "Write per-barcode read counts as Parquet (requires the `parquet` feature)")]
+    barcode_counts_parquet: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE",
+          help = "Write barcode rank plot data as CSV (rank,barcode,count,cumulative_fraction), sorted by descending count")]
+    rank_plot_data: Option<PathBuf>,
+
+    #[arg(long, value_name = "N", requires = "rank_plot_data", value_parser = clap::value_parser!(u64).range(2..),
+          help = "Keep only about N log-spaced ranks in --rank-plot-data [default: every barcode]")]
+    rank_plot_points: Option<u64>,
+
+    #[arg(long, value_name = "FILE", help = "Write run statistics (counts, per-stage timings) as JSON")]
+    stats_json: Option<PathBuf>,
+
+    #[arg(long, default_value = "false",
+          conflicts_with_all = ["output_prefix", "compress", "write_threads", "number_suffix", "keep_raw_barcode",
+                                "stats_json", "run_info_checksums", "metrics_file", "barcode_counts",
+                                "barcode_counts_parquet", "rank_plot_data", "split_by", "split_lanes",
+                                "split_per_barcode", "shard_by_bc_prefix", "rename_map", "done_file"],
+          help = "Run reading and processing only, discard the results without creating any files, and report throughput")]
+    benchmark: bool,
+
+    #[arg(long, default_value = "false", help = "Do not write PREFIX_run_info.json")]
+    no_run_info: bool,
+
+    #[arg(long, default_value = "false", conflicts_with = "no_run_info",
+          help = "Include SHA-256 checksums of the inputs in the run info (reads every input once more)")]
+    run_info_checksums: bool,
+
+    #[arg(long, value_name = "FILE",
+          help = "After every output, stats and run info file is complete, write a small JSON sentinel (exit status, \
+                  record counts, time) to FILE; never written when the run fails. Refuses to run if FILE exists")]
+    done_file: Option<PathBuf>,
+
+    #[arg(long, default_value = "false", requires = "done_file",
+          help = "Remove an existing --done-file at startup instead of refusing to run")]
+    force: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum InputFormatArg {
+    Auto,
+    Fastq,
+    Ubam,
+}
+
+/// `--select-barcodes` 未选中的 pair 的去向
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum UnselectedPolicy {
+    Drop,
+    Unassigned,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ReadthroughArg {
+    Off,
+    Count,
+    Drop,
 }
 
-// gzip 或 plain FASTQ 都能自动判断
-fn open_fastq<P: AsRef<Path>>(p: P) -> Box<dyn Read + Send> {
-    let f = File::open(p.as_ref()).unwrap();
-    match p.as_ref().extension().and_then(|s| s.to_str()) {
-        Some("gz") => Box::new(MultiGzDecoder::new(f)),
-        _          => Box::new(f),
+impl From<ReadthroughArg> for ReadthroughPolicy {
+    fn from(arg: ReadthroughArg) -> Self {
+        match arg {
+            ReadthroughArg::Off => ReadthroughPolicy::Off,
+            ReadthroughArg::Count => ReadthroughPolicy::Count,
+            ReadthroughArg::Drop => ReadthroughPolicy::Drop,
+        }
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OpticalDupArg {
+    Count,
+    Drop,
+}
+
+impl From<OpticalDupArg> for OpticalDupPolicy {
+    fn from(arg: OpticalDupArg) -> Self {
+        match arg {
+            OpticalDupArg::Count => OpticalDupPolicy::Count,
+            OpticalDupArg::Drop => OpticalDupPolicy::Drop,
+        }
+    }
+}
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ProgressMode {
+    Auto,
+    None,
+    Bar,
+    Log,
+}
 
-/// 把两条 FASTQ 读成 batch，发到下游
-fn reader_thread(
-    r1_path: &Path,
-    r2_path: &Path,
-    batch_len: usize,
-    tx: Sender<(Vec<OwnedRecord>, Vec<OwnedRecord>)>,
-) -> Result<()> {
-    // 构造两个 parser
-    let p1 = FastqParser::new(open_fastq(r1_path));
-    let p2 = FastqParser::new(open_fastq(r2_path));
-
-    let mut r1_batch = Vec::with_capacity(batch_len);
-    let mut r2_batch = Vec::with_capacity(batch_len);
-
-    // fastq‑rs 原生的"成对遍历"——每回调一次就是一对 read
-    each_zipped(p1, p2, |opt1, opt2| {
-        match (opt1, opt2) {
-            (Some(r1), Some(r2)) => {
-                r1_batch.push(r1.to_owned_record()); // OwnedRecord = 结构体版 FASTQ
-                r2_batch.push(r2.to_owned_record());
-                // 满了就发
-                if r1_batch.len() == batch_len {
-                    tx.send((r1_batch.split_off(0), r2_batch.split_off(0))).unwrap();
-                }
-                (true, true) // 两个 parser 都继续
-            }
-            // 文件长度不一致时提前终止
-            _ => (false, false),
+impl ProgressMode {
+    /// auto 在 stderr 为终端时显示进度条，否则输出日志行
+    fn style(self) -> Option<ProgressStyle> {
+        match self {
+            ProgressMode::None => None,
+            ProgressMode::Bar => Some(ProgressStyle::Bar),
+            ProgressMode::Log => Some(ProgressStyle::Log),
+            ProgressMode::Auto if std::io::stderr().is_terminal() => Some(ProgressStyle::Bar),
+            ProgressMode::Auto => Some(ProgressStyle::Log),
         }
-    })?;
+    }
+}
 
-    if !r1_batch.is_empty() {
-        tx.send((r1_batch, r2_batch)).unwrap();
+fn parse_progress_interval(s: &str) -> Result<u64, String> {
+    match s.parse::<u64>() {
+        Ok(0) => Err("progress interval must be at least 1 second".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
     }
-    Ok(())
 }
 
-fn create_writer(path: &PathBuf) -> Result<Box<dyn Write + Send>> {
-    let file = File::create(path)?;
+/// 解析 `START..END`（两端都包含）
+fn parse_inclusive_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = s.split_once("..").ok_or_else(|| format!("expected START..END, got '{}'", s))?;
+    let start: usize = start.parse().map_err(|e| format!("invalid start '{}': {}", start, e))?;
+    let end: usize = end.trim_start_matches('=').parse().map_err(|e| format!("invalid end '{}': {}", end, e))?;
+    if start > end {
+        return Err(format!("start {} is greater than end {}", start, end));
+    }
+    Ok(start..=end)
+}
 
-    if path.extension().and_then(|s| s.to_str()) == Some("gz") {
-        // ① 更低压缩等级：level 1≈4～5 倍速度
-        let encoder = GzEncoder::new(file, Compression::new(1));
-        // ② 更大的 BufWriter：1 MiB 而非 8 KiB，减少 sys‑call 次数
-        Ok(Box::new(BufWriter::with_capacity(4 << 20, encoder)))
-    } else {
-        Ok(Box::new(BufWriter::with_capacity(4 << 20, file)))
+fn parse_complexity(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(t) if (0.0..=1.0).contains(&t) => Ok(t),
+        Ok(_) => Err("complexity threshold must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
     }
 }
 
-struct ProcessedRecord {
-    r1_out: OwnedRecord,
-    r2_out: OwnedRecord,
-    r3_out: OwnedRecord,
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(r) if (0.0..=1.0).contains(&r) => Ok(r),
+        Ok(_) => Err("rate must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
 }
 
-fn process_pair(
-    r1: OwnedRecord,
-    r2: OwnedRecord,
-) -> Option<(OwnedRecord, OwnedRecord, OwnedRecord)> {
-    if r2.seq().len() != 166 { return None; }
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f > 0.0 && f < 1.0 => Ok(f),
+        Ok(_) => Err("fraction must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    let id1 = extract_base_header(r1.head());
-    let id2 = extract_base_header(r2.head());
-    if id1 != id2 { return None; }
+fn parse_read_prefix(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(|c: char| c.is_whitespace()) {
+        return Err("prefix must be non-empty and contain no whitespace".to_string());
+    }
+    Ok(s.to_string())
+}
 
-    // ---------- R1 ----------
-    let id1_vec = id1.to_vec();
-    let mut out1 = r1;             // 复用内存；只需截 ID
-    out1.head = id1_vec.clone();
+fn parse_memory_gb(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(gb) if gb > 0.0 && gb.is_finite() => Ok(gb),
+        Ok(_) => Err("memory budget must be a positive number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    // ---------- R2 ----------
-    let (tail_seq, head_seq) = r2.seq().split_at(150); // 0..150, 150..166
-    let (tail_qual, head_qual) = r2.qual().split_at(150);
+/// 线程数参数在解析阶段就拒绝 0
+fn parse_thread_count(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("thread count must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    let out2 = OwnedRecord {
-        head : id1_vec.clone(),
-        seq  : reverse_complement(head_seq),
-        qual : head_qual.iter().rev().cloned().collect(),
-        sep  : None,
-    };
+/// batch 大小、组数等计数参数，0 没有意义
+fn parse_positive(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    // ---------- R3 ----------
-    let out3 = OwnedRecord {
-        head : id1_vec,
-        seq  : tail_seq.to_vec(),
-        qual : tail_qual.to_vec(),
-        sep  : None,
-    };
-    Some((out1, out2, out3))
+/// 输出文件名中的编号：cellranger 等工具只接受 `_001` 这样的三位数字
+fn parse_number_suffix(s: &str) -> Result<String, String> {
+    if s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(s.to_string());
+    }
+    Err("must be three digits such as 001 or 002; cellranger rejects other FASTQ file names".to_string())
 }
 
-fn process_batch(r1_batch: Vec<OwnedRecord>, r2_batch: Vec<OwnedRecord>) -> Vec<ProcessedRecord> {
-    let mut results = Vec::new();
-    
-    for (r1, r2) in r1_batch.into_iter().zip(r2_batch.into_iter()) {
-        if let Some((r1_out, r2_out, r3_out)) = process_pair(r1, r2) {
-            results.push(ProcessedRecord {
-                r1_out,
-                r2_out,
-                r3_out,
-            });
+/// `--affinity` 的 CPU 列表
+#[derive(Debug, Clone, serde::Serialize)]
+struct CpuList(Vec<usize>);
+
+fn parse_cpu_list(s: &str) -> Result<CpuList, String> {
+    match affinity::parse_cpu_list(s) {
+        Some(cpus) if !cpus.is_empty() => Ok(CpuList(cpus)),
+        _ => Err("expected CPU numbers and ranges such as 0-7 or 0,2,4,6".to_string()),
+    }
+}
+
+/// 各阶段的线程数；未指定的值按 CPU 核数推算
+fn thread_topology(args: &SplitArgs) -> ThreadTopology {
+    ThreadTopology::resolve(args.read_threads, args.threads, args.write_threads)
+}
+
+/// 内存中一对 read（R1 + 166bp R2 及其 header、Vec 开销）的估计大小
+const ESTIMATED_PAIR_BYTES: usize = 1000;
+
+/// 按内存预算计算 batch 大小上限：`预算 / (处理线程数 × channel 容量 × 每对 read 大小)`
+fn memory_limited_batch_size(max_memory_gb: f64, threads: usize) -> usize {
+    let limit = max_memory_gb * 1e9 / (threads * CHANNEL_DEPTH * ESTIMATED_PAIR_BYTES) as f64;
+    (limit as usize).max(1)
+}
+
+/// `--scan-bc-offset` 抽样的 R2 条数
+const OFFSET_SCAN_READS: usize = 100_000;
+
+/// 读出 R2 开头的至多 `n` 条记录
+///
+/// 返回这些记录的序列，以及一个从文件开头重放的读取端：已读出的记录先被原样写回，
+/// 之后接着读文件剩余部分，因此输入只读一遍（也适用于无法重新打开的输入）。
+fn sample_r2(
+    path: &PathBuf,
+    n: usize,
+    read_threads: usize,
+    counters: &Arc<PipelineCounters>,
+) -> Result<(Vec<Vec<u8>>, InputReader)> {
+    let mut reader = BufReader::with_capacity(1 << 20, open_fastq_counted(path, read_threads, Some(Arc::clone(counters)))?);
+    let mut replay = Vec::new();
+    let mut seqs = Vec::new();
+    let mut record = empty_record();
+    while seqs.len() < n
+        && read_fastq_record(&mut reader, &mut record).with_context(|| format!("Failed to read {}", path.display()))?
+    {
+        record.write(&mut replay)?;
+        seqs.push(record.seq.clone());
+    }
+    Ok((seqs, Box::new(Cursor::new(replay).chain(reader))))
+}
+
+/// 条形码某个位置上单一碱基超过该比例时警告
+const DOMINANT_BASE_WARN: f64 = 0.9;
+/// read 数少于此值时碱基组成没有参考意义，不警告
+const MIN_COMPOSITION_READS: u64 = 1000;
+/// 没有任何输出的 batch 超过该比例时警告
+const EMPTY_BATCH_WARN: f64 = 0.9;
+
+/// 打印条形码窗口每个位置的碱基组成（verbose），并对明显偏向单一碱基的位置给出警告
+///
+/// 真实的条形码在每个位置上四种碱基大致均衡；基因组序列或接头则会明显偏斜，
+/// 通常说明条形码窗口的位置不对。
+fn report_barcode_composition(stats: &RunStats, verbose: bool) {
+    if verbose && !stats.barcode_composition.is_empty() {
+        eprintln!("Barcode base composition (%):");
+        eprintln!("  {:>3}  {:>5}  {:>5}  {:>5}  {:>5}  {:>5}", "Pos", "A", "C", "G", "T", "N");
+        for (i, f) in stats.barcode_composition.iter().enumerate() {
+            eprintln!("  {:>3}  {:>5.1}  {:>5.1}  {:>5.1}  {:>5.1}  {:>5.1}",
+                     i + 1, f.a * 100.0, f.c * 100.0, f.g * 100.0, f.t * 100.0, f.n * 100.0);
+        }
+    }
+    if stats.pairs_read < MIN_COMPOSITION_READS {
+        return;
+    }
+    for (i, f) in stats.barcode_composition.iter().enumerate() {
+        let (base, fraction) = f.dominant();
+        if fraction > DOMINANT_BASE_WARN {
+            eprintln!("WARNING: Barcode position {} is {:.1}% {}; the barcode window may be misplaced (see --scan-bc-offset)",
+                      i + 1, fraction * 100.0, base);
         }
     }
-    
-    results
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    
-    // Set up output file paths
-    let extension = if args.compress { ".fastq.gz" } else { ".fastq" };
-    let r1_output = PathBuf::from(format!("{}_S1_L001_R1_{}{}", args.output_prefix, args.number_suffix, extension));
-    let r2_output = PathBuf::from(format!("{}_S1_L001_R2_{}{}", args.output_prefix, args.number_suffix, extension));
-    let r3_output = PathBuf::from(format!("{}_S1_L001_R3_{}{}", args.output_prefix, args.number_suffix, extension));
-    
-    // Clone for printing later
-    let r1_output_display = r1_output.clone();
-    let r2_output_display = r2_output.clone();
-    let r3_output_display = r3_output.clone();
-    
-    if args.verbose {
-        println!("Starting batch processing with batch size: {}", args.batch_size);
+fn print_offset_scan(rows: &[OffsetScanRow], sampled: usize) {
+    eprintln!("Barcode offset scan ({} R2 reads, exact whitelist matches):", sampled);
+    eprintln!("  {:>6}  {:<18}  {:>8}", "Offset", "Orientation", "Hit rate");
+    for row in rows {
+        eprintln!("  {:>6}  {:<18}  {:>7.2}%", row.window.offset, orientation_label(row.window), row.hit_rate() * 100.0);
     }
-    
-    // Create channels for batch processing - 增加缓冲区大小
-    let (batch_tx, batch_rx): (Sender<(Vec<OwnedRecord>, Vec<OwnedRecord>)>, Receiver<(Vec<OwnedRecord>, Vec<OwnedRecord>)>) = bounded(50);
-    let (output_tx, output_rx): (Sender<Vec<ProcessedRecord>>, Receiver<Vec<ProcessedRecord>>) = bounded(50);
-    
-    // Statistics
-    let processed_count = Arc::new(Mutex::new(0usize));
-    let filtered_count = Arc::new(Mutex::new(0usize));
-    let total_read = Arc::new(Mutex::new(0usize));
-    
-    // Start reader thread
-    let r1_input = args.r1_input.clone();
-    let r2_input = args.r2_input.clone();
-    let batch_size = args.batch_size;
-    let verbose = args.verbose;
-    let _read_count = Arc::clone(&total_read);
-    let reader_handle = thread::spawn(move || -> Result<()> {
-        reader_thread(&r1_input, &r2_input, batch_size, batch_tx)?;
-        if verbose {
-            println!("Finished reading record pairs");
-        }
-        Ok(())
-    });
-    
-    // Start processing threads
-    let mut processing_handles = Vec::new();
-    for _ in 0..args.threads {
-        let rx = batch_rx.clone();
-        let tx = output_tx.clone();
-        let proc_count = Arc::clone(&processed_count);
-        let filt_count = Arc::clone(&filtered_count);
-        
-        let handle = thread::spawn(move || {
-            while let Ok((r1_batch, r2_batch)) = rx.recv() {
-                let total_in_batch = r2_batch.len();
-                let results = process_batch(r1_batch, r2_batch);
-                
-                let processed_in_batch = results.len();
-                let filtered_in_batch = total_in_batch - processed_in_batch;
-                
-                *proc_count.lock().unwrap() += processed_in_batch;
-                *filt_count.lock().unwrap() += filtered_in_batch;
-                
-                if !results.is_empty() {
-                    if tx.send(results).is_err() {
-                        break;
-                    }
-                }
+}
+
+fn orientation_label(window: BarcodeWindow) -> &'static str {
+    if window.reverse_complement { "reverse complement" } else { "forward" }
+}
+
+/// `--two-pass` 的第一遍：统计 R2 中与白名单完全匹配的条形码
+///
+/// 只看长度符合要求的 R2，不做纠错，也不写出任何记录；给出 `--head` 时同样只读前 N 条。
+fn count_exact_barcodes(
+    args: &SplitArgs,
+    input_format: InputFormat,
+    window: BarcodeWindow,
+    whitelist: &Whitelist,
+    read_threads: usize,
+) -> Result<HashMap<Vec<u8>, u64>> {
+    let mut counts = HashMap::new();
+    let mut add = |seq: &[u8]| {
+        if seq.len() != R2_LEN {
+            return;
+        }
+        if let Some(bc) = window.extract(seq).filter(|bc| whitelist.contains(bc)) {
+            *counts.entry(bc).or_insert(0) += 1;
+        }
+    };
+    let mut remaining = args.head.unwrap_or(usize::MAX);
+    if input_format == InputFormat::Ubam {
+        for path in &args.r1_input {
+            if remaining == 0 {
+                break;
             }
-        });
-        processing_handles.push(handle);
-    }
-    
-    // Create separate channels for each output file
-    let (r1_tx, r1_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(50);
-    let (r2_tx, r2_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(50);
-    let (r3_tx, r3_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(50);
-    
-    // Distribution thread - 分发处理结果到各个写入线程
-    let verbose_dist = args.verbose;
-    let dist_handle = {
-        let r1_tx_clone = r1_tx.clone();
-        let r2_tx_clone = r2_tx.clone();
-        let r3_tx_clone = r3_tx.clone();
-        thread::spawn(move || -> Result<()> {
-            let mut written_count = 0;
-            while let Ok(batch_results) = output_rx.recv() {
-                let mut r1_batch = Vec::new();
-                let mut r2_batch = Vec::new();
-                let mut r3_batch = Vec::new();
-                
-                for processed in batch_results {
-                    r1_batch.push(processed.r1_out);
-                    r2_batch.push(processed.r2_out);
-                    r3_batch.push(processed.r3_out);
-                    written_count += 1;
-                }
-                
-                // 并行发送到各个写入线程
-                if !r1_batch.is_empty() {
-                    r1_tx_clone.send(r1_batch).map_err(|_| anyhow::anyhow!("Failed to send R1 batch"))?;
-                    r2_tx_clone.send(r2_batch).map_err(|_| anyhow::anyhow!("Failed to send R2 batch"))?;
-                    r3_tx_clone.send(r3_batch).map_err(|_| anyhow::anyhow!("Failed to send R3 batch"))?;
-                }
-                
-                if verbose_dist && written_count % 100000 == 0 {
-                    println!("Written {} records...", written_count);
-                }
+            let mut reader = open_ubam(path, read_threads, None)?;
+            while remaining > 0 {
+                let Some((_, r2)) = reader.next_pair().with_context(|| format!("Failed to read {}", path.display()))? else {
+                    break;
+                };
+                add(&r2.seq);
+                remaining -= 1;
             }
-            if verbose_dist {
-                println!("Finished writing {} records", written_count);
+        }
+    } else {
+        for path in &args.r2_input {
+            if remaining == 0 {
+                break;
             }
-            Ok(())
-        })
-    };
-    
-    // Start separate writer threads for each output file
-    let r1_writer_handle = {
-        let r1_output = r1_output.clone();
-        thread::spawn(move || -> Result<()> {
-            let mut writer = create_writer(&r1_output)?;
-            while let Ok(batch) = r1_rx.recv() {
-                for record in batch {
-                    record.write(&mut writer)?;   // fastq‑rs 一条调用完成
-                }
+            let mut reader = BufReader::with_capacity(1 << 20, open_fastq_counted(path, read_threads, None)?);
+            let mut record = empty_record();
+            while remaining > 0
+                && read_fastq_record(&mut reader, &mut record).with_context(|| format!("Failed to read {}", path.display()))? {
+                add(&record.seq);
+                remaining -= 1;
             }
-            Ok(())
-        })
-    };
-    
-    let r2_writer_handle = {
-        let r2_output = r2_output.clone();
-        thread::spawn(move || -> Result<()> {
-            let mut writer = create_writer(&r2_output)?;
-            while let Ok(batch) = r2_rx.recv() {
-                for record in batch {
-                    record.write(&mut writer)?;   // fastq‑rs 一条调用完成
-                }
+        }
+    }
+    Ok(counts)
+}
+
+/// `--prescan`：只读 R2，统计各长度的 read 数
+fn prescan_r2_lengths(args: &SplitArgs, input_format: InputFormat, read_threads: usize) -> Result<BTreeMap<usize, u64>> {
+    let mut lengths = BTreeMap::new();
+    if input_format == InputFormat::Ubam {
+        for path in &args.r1_input {
+            let mut reader = open_ubam(path, read_threads, None)?;
+            while let Some((_, r2)) = reader.next_pair().with_context(|| format!("Failed to read {}", path.display()))? {
+                *lengths.entry(r2.seq.len()).or_insert(0) += 1;
             }
-            Ok(())
-        })
-    };
-    
-    let r3_writer_handle = {
-        let r3_output = r3_output.clone();
-        thread::spawn(move || -> Result<()> {
-            let mut writer = create_writer(&r3_output)?;
-            while let Ok(batch) = r3_rx.recv() {
-                for record in batch {
-                    record.write(&mut writer)?;   // fastq‑rs 一条调用完成
-                }
+        }
+    } else {
+        for path in &args.r2_input {
+            let mut reader = BufReader::with_capacity(1 << 20, open_fastq_counted(path, read_threads, None)?);
+            let mut record = empty_record();
+            while read_fastq_record(&mut reader, &mut record).with_context(|| format!("Failed to read {}", path.display()))? {
+                *lengths.entry(record.seq.len()).or_insert(0) += 1;
             }
-            Ok(())
-        })
-    };
-    
-    // Wait for reader to finish
-    reader_handle.join().unwrap()?;
-    
-    // Wait for all processing threads to finish
-    for handle in processing_handles {
-        handle.join().unwrap();
+        }
+    }
+    Ok(lengths)
+}
+
+fn print_prescan(lengths: &BTreeMap<usize, u64>) {
+    let total: u64 = lengths.values().sum();
+    let expected = lengths.get(&R2_LEN).copied().unwrap_or(0);
+    let percent = |n: u64| n as f64 / total.max(1) as f64 * 100.0;
+    eprintln!("R2 reads: {}", total);
+    eprintln!("  {} bp: {} ({:.2}%)", R2_LEN, expected, percent(expected));
+    eprintln!("  Other lengths: {} ({:.2}%)", total - expected, percent(total - expected));
+    if expected == total {
+        return;
+    }
+    eprintln!("Other R2 lengths:");
+    for (&len, &count) in lengths.iter().filter(|(&len, _)| len != R2_LEN) {
+        eprintln!("  {} bp: {} ({:.2}%)", len, count, percent(count));
+    }
+    eprintln!("WARNING: {} R2 reads are not {} bp and would be filtered", total - expected, R2_LEN);
+}
+
+/// `--min-reads-per-barcode` 的预先计数：按拆分时的规则处理一遍输入，只统计纠错后的条形码
+fn count_corrected_barcodes(
+    config: PipelineConfig,
+    args: &SplitArgs,
+    input_format: InputFormat,
+    read_threads: usize,
+) -> Result<BarcodeCounter> {
+    let (r1_input, r2_input) = (args.r1_input.clone(), args.r2_input.clone());
+    let counters = Arc::clone(&config.counters);
+    let sinks = PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink());
+    let stats = run_pipeline_with_reader(config, move |sender| match input_format {
+        InputFormat::Ubam => read_ubam_lanes(sender, &r1_input, read_threads, &counters),
+        _ => read_fastq_lanes(sender, &r1_input, &r2_input, None, read_threads, &counters, false),
+    }, sinks)?;
+    Ok(stats.barcode_counts.unwrap_or_default())
+}
+
+/// 启动前检查 clap 无法表达的参数组合，报错时指出相关的参数
+fn validate_split_args(args: &SplitArgs) -> Result<()> {
+    if let Some(seq) = &args.anchor {
+        Anchor::new(seq, args.anchor_max_mismatch).context("Invalid --anchor or --anchor-max-mismatch")?;
+        return Ok(());
+    }
+    let offset = BarcodeWindow::default().offset;
+    match args.r3_end {
+        Some(end) if args.r3_start >= end => {
+            anyhow::bail!("--r3-start {} must be less than --r3-end {}", args.r3_start, end)
+        }
+        // --scan-bc-offset 时条形码位置在抽样后才确定
+        _ if args.scan_bc_offset.is_some() => {}
+        Some(end) if end > offset => {
+            anyhow::bail!("--r3-end {} extends into the barcode, which starts at R2 offset {} (R3 region {}..{})",
+                          end, offset, args.r3_start, end)
+        }
+        None if args.r3_start >= offset => {
+            anyhow::bail!("--r3-start {} leaves no R3 bases before the barcode at R2 offset {}", args.r3_start, offset)
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 确定输入格式并检查输入文件个数
+fn resolve_input_format(args: &SplitArgs) -> Result<InputFormat> {
+    let format = match args.input_format {
+        InputFormatArg::Fastq => InputFormat::Fastq,
+        InputFormatArg::Ubam => InputFormat::Ubam,
+        InputFormatArg::Auto => detect_input_format(&args.r1_input[0])?,
+    };
+    match format {
+        InputFormat::Cram => Err(Failure::Usage.error(anyhow::anyhow!(
+            "{} is CRAM, which is not supported; convert it to unaligned BAM first (e.g. `samtools view -b`)",
+            args.r1_input[0].display()))),
+        InputFormat::Ubam if !args.r2_input.is_empty() => {
+            Err(Failure::Usage.error(anyhow::anyhow!("Unaligned BAM input holds both mates; do not pass -2/--r2-input")))
+        }
+        InputFormat::Ubam if args.check_pair_order => {
+            Err(Failure::Usage.error(anyhow::anyhow!("--check-pair-order only applies to separate R1/R2 FASTQ inputs")))
+        }
+        InputFormat::Fastq if args.r1_input.len() != args.r2_input.len() => {
+            Err(Failure::Usage.error(anyhow::anyhow!("Got {} R1 inputs but {} R2 inputs; each lane needs one R1 and one R2 file",
+                                                     args.r1_input.len(), args.r2_input.len())))
+        }
+        _ => Ok(format),
+    }
+}
+
+/// `--split-per-barcode` 的分组：每个（挑选或白名单中的）条形码一组
+fn per_barcode_groups(
+    args: &SplitArgs,
+    select_barcodes: Option<&BarcodeSelection>,
+    whitelist: Option<&Whitelist>,
+) -> Result<BarcodeGroups> {
+    let (barcodes, source): (Vec<&[u8]>, _) = match (select_barcodes, whitelist) {
+        (Some(list), _) => (list.barcodes().collect(), "--select-barcodes"),
+        (None, Some(whitelist)) => (whitelist.barcodes().collect(), "--whitelist"),
+        (None, None) => return Err(Failure::Usage.error(anyhow::anyhow!("--split-per-barcode needs --select-barcodes or --whitelist to bound the number of outputs"))),
+    };
+    if barcodes.len() > args.max_barcodes {
+        return Err(Failure::Usage.error(anyhow::anyhow!("--split-per-barcode would write {} barcodes from {} (more than --max-barcodes {})",
+                                                        barcodes.len(), source, args.max_barcodes)));
+    }
+    if remote::is_remote(std::path::Path::new(args.output_prefix.as_deref().unwrap_or_default())) {
+        return Err(Failure::Usage.error(anyhow::anyhow!("--split-per-barcode cannot write to object storage (outputs are reopened for appending)")));
+    }
+    Ok(BarcodeGroups::per_barcode(barcodes))
+}
+
+/// 写出 `--split-per-barcode` 每个条形码的 pair 数（`barcode<TAB>pairs`，没有 read 的为 0）
+fn write_per_barcode_counts(path: &PathBuf, barcodes: &[String], counts: &BTreeMap<String, u64>) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+    writeln!(out, "barcode\tpairs")?;
+    for barcode in barcodes.iter().filter(|b| *b != UNASSIGNED_GROUP || counts.contains_key(*b)) {
+        writeln!(out, "{}\t{}", barcode, counts.get(barcode).copied().unwrap_or_default())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// 库中经 `log` 输出的消息写到标准错误：默认只显示警告，`--verbose` 时显示读写进度，
+/// `RUST_LOG` 可以覆盖
+fn init_logging(verbose: bool) {
+    let level = if verbose { log::LevelFilter::Debug } else { log::LevelFilter::Warn };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            log::Level::Error => writeln!(buf, "ERROR: {}", record.args()),
+            log::Level::Warn => writeln!(buf, "WARNING: {}", record.args()),
+            _ => writeln!(buf, "{}", record.args()),
+        })
+        .init();
+}
+
+/// 用法错误由 clap 以 2 退出；其余错误按 [`Failure`] 选择退出码（见 README 的“退出码”）
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(matches!(&cli.command, Command::Split(args) if args.verbose));
+    match run_command(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(Failure::exit_code_of(&e))
+        }
+    }
+}
+
+fn run_command(command: Command) -> Result<()> {
+    match command {
+        Command::Split(args) if args.cleanup_on_error => {
+            // 信号在启动任何线程之前屏蔽；panic 也删除部分输出后再继续展开
+            cleanup::remove_on_signal();
+            match std::panic::catch_unwind(|| run_split(*args)) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(e)) => {
+                    cleanup::remove_partial_outputs();
+                    Err(e)
+                }
+                Err(panic) => {
+                    cleanup::remove_partial_outputs();
+                    std::panic::resume_unwind(panic)
+                }
+            }
+        }
+        Command::Split(args) => run_split(*args),
+        Command::Passthrough(args) => run_passthrough(args),
+        Command::Recombine(args) => run_recombine(args),
+        Command::Validate(args) => run_validate(args),
+        Command::Stats(args) => run_stats(args),
+        Command::Simulate(args) => run_simulate(args),
+        Command::Inspect(args) => run_inspect(args),
+        Command::Version => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+    }
+}
+
+/// R2 条形码长度与 R3 基因组片段的最大长度
+const BARCODE_LEN: usize = 16;
+const MAX_GENOMIC_LEN: usize = 150;
+
+/// 检查同一位置的 R1/R2/R3 记录，返回发现的问题（为空表示合法）
+fn check_output_records(records: &[OwnedRecord; 3]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let id = extract_base_header(&records[0].head);
+    for (label, record) in ["R2", "R3"].iter().zip(&records[1..]) {
+        if extract_base_header(&record.head) != id {
+            problems.push(format!("{} header '{}' does not match R1 header '{}'", label,
+                                  String::from_utf8_lossy(&record.head), String::from_utf8_lossy(id)));
+        }
+    }
+    if records[1].seq.len() != BARCODE_LEN {
+        problems.push(format!("R2 is {} bp, expected {}", records[1].seq.len(), BARCODE_LEN));
+    }
+    if records[2].seq.len() > MAX_GENOMIC_LEN {
+        problems.push(format!("R3 is {} bp, longer than {}", records[2].seq.len(), MAX_GENOMIC_LEN));
+    }
+    for (label, record) in ["R1", "R2", "R3"].iter().zip(records) {
+        if record.qual.len() != record.seq.len() {
+            problems.push(format!("{} quality length {} differs from sequence length {}",
+                                  label, record.qual.len(), record.seq.len()));
+        }
+    }
+    problems
+}
+
+/// 逐条同步读取三个输出文件，检查 header、长度与质量值是否一致
+fn run_validate(args: ValidateArgs) -> Result<()> {
+    /// 最多打印的问题记录数
+    const MAX_REPORTED: usize = 10;
+
+    let mut readers = [
+        open_fastq_reader(&args.r1)?,
+        open_fastq_reader(&args.r2)?,
+        open_fastq_reader(&args.r3)?,
+    ];
+    let paths = [&args.r1, &args.r2, &args.r3];
+    let mut records = [empty_record(), empty_record(), empty_record()];
+    let mut count = 0usize;
+    let mut invalid = 0usize;
+
+    loop {
+        let mut more = [false; 3];
+        for i in 0..3 {
+            more[i] = read_fastq_record(&mut readers[i], &mut records[i])
+                .map_err(|e| record_error(paths[i], count + 1, e))?;
+        }
+        if more.iter().all(|&m| !m) {
+            break;
+        }
+        if more.iter().any(|&m| !m) {
+            let ended = paths.iter().zip(more).filter(|(_, m)| !m).map(|(p, _)| p.display().to_string());
+            return Err(Failure::Pairing.error(anyhow::anyhow!("Output files have different record counts: {} ended after {} records",
+                                                              ended.collect::<Vec<_>>().join(", "), count)));
+        }
+        count += 1;
+        let problems = check_output_records(&records);
+        if !problems.is_empty() {
+            invalid += 1;
+            if invalid <= MAX_REPORTED {
+                eprintln!("Record {}: {}", count, problems.join("; "));
+            }
+        }
+    }
+    if invalid > MAX_REPORTED {
+        eprintln!("... {} more invalid records not shown", invalid - MAX_REPORTED);
+    }
+
+    println!("Valid records: {}", count - invalid);
+    println!("Invalid records: {}", invalid);
+    if invalid > 0 {
+        return Err(Failure::MalformedInput.error(anyhow::anyhow!("{} of {} records failed validation", invalid, count)));
+    }
+    Ok(())
+}
+
+/// 同步读取已拆分好的 R1/条形码/R3，逐条核对 read 名后按 10x 命名重新写出
+///
+/// 任一文件提前结束或 read 名不一致时立即中止，并给出出错位置的 read 名。
+fn run_passthrough(args: PassthroughArgs) -> Result<()> {
+    let start_instant = Instant::now();
+    let paths = [&args.r1_input, &args.barcode, &args.r3_input];
+    let mut readers = [
+        open_fastq_reader(&args.r1_input)?,
+        open_fastq_reader(&args.barcode)?,
+        open_fastq_reader(&args.r3_input)?,
+    ];
+    let outputs = ["R1", "R2", "R3"].map(|read| output_path(&args.output_prefix, read, &args.number_suffix, args.compress));
+
+    let counters = PipelineCounters::default();
+    let write_threads = args.write_threads.unwrap_or_else(default_io_threads);
+    let mut writers = Vec::with_capacity(3);
+    let mut uploads = Vec::new();
+    for (i, path) in outputs.iter().enumerate() {
+        let (writer, upload) = create_writer(path, write_threads, Arc::clone(&counters.bytes_written[i]),
+                                             Arc::clone(&counters.uncompressed_bytes_written[i]))?;
+        writers.push(writer);
+        uploads.extend(upload);
+    }
+
+    let mut records = [empty_record(), empty_record(), empty_record()];
+    let mut count = 0u64;
+    loop {
+        let mut more = [false; 3];
+        for i in 0..3 {
+            more[i] = read_fastq_record(&mut readers[i], &mut records[i])
+                .map_err(|e| record_error(paths[i], count + 1, e))?;
+        }
+        if more.iter().all(|&m| !m) {
+            break;
+        }
+        let name = |i: usize| if more[i] { String::from_utf8_lossy(read_name(&records[i].head)).into_owned() } else {
+            "<end of file>".to_string()
+        };
+        if more.iter().any(|&m| !m)
+            || read_name(&records[0].head) != read_name(&records[1].head)
+            || read_name(&records[0].head) != read_name(&records[2].head)
+        {
+            return Err(Failure::Pairing.error(anyhow::anyhow!("Inputs are out of step at record {}: R1 has '{}', barcode has '{}', R3 has '{}'",
+                                                              count + 1, name(0), name(1), name(2))));
+        }
+        count += 1;
+
+        if args.rc_barcode {
+            records[1].seq = reverse_complement(&records[1].seq);
+            records[1].qual.reverse();
+        }
+        for (record, writer) in records.iter().zip(&mut writers) {
+            record.write(writer)?;
+        }
+    }
+    for mut writer in writers {
+        writer.finish()?;
+    }
+    for upload in uploads {
+        upload.complete()?;
+    }
+    counters.pairs_read.store(count, Ordering::Relaxed);
+    counters.pairs_written.store(count, Ordering::Relaxed);
+
+    let snapshot = counters.snapshot();
+    eprintln!("Processing complete!");
+    eprintln!("Processed records: {}", count);
+    for (label, (path, bytes)) in ["R1", "R2", "R3"].iter().zip(outputs.iter().zip(snapshot.bytes_written)) {
+        eprintln!("  {}: {} ({} bytes)", label, path.display(), bytes);
+    }
+    if let Some(path) = &args.stats_json {
+        let stats = RunStats {
+            lane_pairs: vec![count],
+            wall_ms: start_instant.elapsed().as_millis() as u64,
+            output_compression: args.compress.then(|| output_compression(&counters)),
+            ..RunStats::from_snapshot(&snapshot)
+        };
+        stats.write(path)?;
+        eprintln!("  Stats: {}", path.display());
+    }
+    Ok(())
+}
+
+/// 拆分的逆过程：基因组片段 + 反向互补回去的条形码拼回原始 R2
+///
+/// 两个输入的 read 名必须逐条一致，否则中止。
+fn run_recombine(args: RecombineArgs) -> Result<()> {
+    let paths = [&args.genomic, &args.barcode];
+    let mut readers = [open_fastq_reader(&args.genomic)?, open_fastq_reader(&args.barcode)?];
+    let (mut writer, upload) = create_writer(&args.output, default_io_threads(), Arc::default(), Arc::default())?;
+
+    let mut records = [empty_record(), empty_record()];
+    let mut r2 = empty_record();
+    let mut count = 0u64;
+    loop {
+        let mut more = [false; 2];
+        for i in 0..2 {
+            more[i] = read_fastq_record(&mut readers[i], &mut records[i])
+                .map_err(|e| record_error(paths[i], count + 1, e))?;
+        }
+        match more {
+            [false, false] => break,
+            [true, true] => {}
+            _ => return Err(Failure::Pairing.error(anyhow::anyhow!("{} and {} have different numbers of records (first {} agree)",
+                                                                   args.genomic.display(), args.barcode.display(), count))),
+        }
+        count += 1;
+        let [genomic, barcode] = &records;
+        if read_name(&genomic.head) != read_name(&barcode.head) {
+            return Err(Failure::Pairing.error(anyhow::anyhow!("Inputs are out of step at record {}: genomic has '{}' but barcode has '{}'", count,
+                                                              String::from_utf8_lossy(read_name(&genomic.head)), String::from_utf8_lossy(read_name(&barcode.head)))));
+        }
+
+        r2.head.clone_from(&genomic.head);
+        r2.seq.clear();
+        r2.seq.extend_from_slice(&genomic.seq);
+        r2.seq.extend(reverse_complement(&barcode.seq));
+        r2.qual.clear();
+        r2.qual.extend_from_slice(&genomic.qual);
+        r2.qual.extend(barcode.qual.iter().rev());
+        r2.write(&mut writer)?;
+    }
+    writer.finish()?;
+    drop(writer);
+    if let Some(upload) = upload {
+        upload.complete()?;
+    }
+
+    eprintln!("Recombined records: {}", count);
+    eprintln!("Output: {}", args.output.display());
+    Ok(())
+}
+
+/// 生成带已知条形码与缺陷的模拟 R1/R2，可另外写出每条 read 的真实标注
+fn run_simulate(args: SimulateArgs) -> Result<()> {
+    let simulator = Simulator::new(SimulateConfig {
+        pairs: args.pairs,
+        r1_len: args.r1_len as usize,
+        r2_len: args.r2_len as usize,
+        barcode_offset: args.barcode_offset.map(usize::from),
+        reverse_complement: !args.no_rc_barcode,
+        barcodes: args.whitelist.as_deref().map(simulate::load_barcodes).transpose()?,
+        random_barcodes: args.barcodes,
+        barcode_error_rate: args.barcode_error_rate,
+        barcode_n_rate: args.barcode_n_rate,
+        defect_rate: args.defect_rate,
+        seed: args.seed,
+    })?;
+    if let Some(path) = &args.write_barcodes {
+        simulate::write_barcodes(path, simulator.barcodes())?;
+    }
+    let summary = simulate::simulate_to_files(simulator, &args.r1, &args.r2, args.truth.as_deref())?;
+    eprintln!("Simulated {} pairs", summary.pairs);
+    for (defect, n) in Defect::ALL.iter().zip(summary.defects).filter(|(_, n)| *n > 0) {
+        eprintln!("  {}: {}", defect.label(), n);
+    }
+    Ok(())
+}
+
+/// 抽取前几条 R2，按条形码、R3 等区域逐段标注，用于检查 read 结构
+fn run_inspect(args: InspectArgs) -> Result<()> {
+    let window = BarcodeWindow { offset: args.bc_start, reverse_complement: !args.no_rc_barcode };
+    let layout = Layout {
+        window,
+        r3_range: if args.anchor.is_some() { 0..0 } else { r3_range(window, args.r3_start, args.r3_end)? },
+        anchor: args.anchor.as_deref().map(|seq| Anchor::new(seq, args.anchor_max_mismatch)).transpose()?,
+    };
+    let whitelist = args.whitelist.as_ref().map(Whitelist::load).transpose()?;
+    let mut reader = open_fastq_reader(&args.r2_input)?;
+    let mut record = empty_record();
+    println!("Layout: {}", layout.describe());
+    println!("Legend: {}", inspect::LEGEND);
+    for _ in 0..args.reads {
+        if !read_fastq_record(&mut reader, &mut record).with_context(|| format!("Failed to read {}", args.r2_input.display()))? {
+            break;
+        }
+        println!();
+        print!("{}", inspect::describe_read(&record.head, &record.seq, &layout, whitelist.as_ref()));
+    }
+    Ok(())
+}
+
+/// 从已有输出文件重新计算质量指标
+fn run_stats(args: StatsArgs) -> Result<()> {
+    let inputs = [("R1", &args.r1), ("R2", &args.r2), ("R3", &args.r3)];
+    if inputs.iter().all(|(_, p)| p.is_none()) {
+        return Err(Failure::Usage.error(anyhow::anyhow!("Give at least one of --r1, --r2, --r3")));
+    }
+    if let Some(dir) = &args.output_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    for (label, path) in inputs {
+        let Some(path) = path else { continue };
+        let mut reader = open_fastq_reader(path)?;
+        let mut record = empty_record();
+        // 只有条形码文件（R2）统计序列频数
+        let mut qc = FastqQc::new(label == "R2");
+        while read_fastq_record(&mut reader, &mut record)
+            .map_err(|e| record_error(path, qc.records + 1, e))?
+        {
+            qc.add(&record.seq, &record.qual);
+        }
+
+        println!("{} ({}):", label, path.display());
+        println!("  Records: {}", qc.records);
+        println!("  Bases: {}", qc.bases);
+        if qc.records > 0 {
+            let min_len = qc.length_distribution.keys().next().unwrap();
+            let max_len = qc.length_distribution.keys().next_back().unwrap();
+            let quals = qc.mean_quality_by_position();
+            println!("  Read length: min {}, max {}, mean {:.1}", min_len, max_len, qc.bases as f64 / qc.records as f64);
+            println!("  GC content: {:.2}%", qc.gc_fraction() * 100.0);
+            println!("  Mean quality: {:.2}", quals.iter().sum::<f64>() / quals.len().max(1) as f64);
+        }
+        if qc.count_sequences {
+            println!("  Distinct barcodes: {}", qc.sequence_counts.len());
+        }
+        if let Some(dir) = &args.output_dir {
+            qc.write_tsv(dir, label)?;
+        }
+    }
+    if let Some(dir) = &args.output_dir {
+        eprintln!("Metric tables written to {}", dir.display());
+    }
+    Ok(())
+}
+
+/// 启动前检查输入、输出路径是否冲突，以及（`--check-space`）输出磁盘的剩余空间
+///
+/// 所有问题在一条错误中一起报告；剩余空间只是略多于估计值时只警告。
+fn preflight_checks(args: &SplitArgs, outputs: &[PathBuf]) -> Result<()> {
+    let mut problems = preflight::path_collisions(&args.r1_input, &args.r2_input, outputs);
+    // 路径冲突是参数错误；空间不足时清理后可以重试，按输出错误退出
+    let mut failure = Failure::Usage;
+    if args.check_space {
+        if let Some(first) = outputs.first() {
+            let dir = first.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+            let inputs: Vec<PathBuf> = args.r1_input.iter().chain(&args.r2_input).cloned().collect();
+            let needed = preflight::estimate_output_bytes(&inputs, args.compress);
+            let gb = |bytes: u64| bytes as f64 / 1e9;
+            match preflight::available_space(dir) {
+                Some(free) if free < needed => {
+                    problems.push(format!("{} has {:.1} GB free, but the outputs need about {:.1} GB",
+                                          dir.display(), gb(free), gb(needed)));
+                    failure = Failure::OutputIo;
+                }
+                Some(free) if (free as f64) < needed as f64 * preflight::SPACE_HEADROOM => eprintln!(
+                    "WARNING: {} has {:.1} GB free and the outputs need about {:.1} GB; the estimate is approximate",
+                    dir.display(), gb(free), gb(needed)),
+                Some(free) => if args.verbose {
+                    eprintln!("Free space check passed: {:.1} GB free, about {:.1} GB needed", gb(free), gb(needed));
+                },
+                None => eprintln!("WARNING: Cannot determine the free space of {}; --check-space skipped", dir.display()),
+            }
+        }
+    }
+    match problems.as_slice() {
+        [] => Ok(()),
+        [problem] => Err(failure.error(anyhow::anyhow!("{}", problem))),
+        _ => Err(failure.error(anyhow::anyhow!("Pre-flight checks failed:\n  {}", problems.join("\n  ")))),
+    }
+}
+
+/// 逐条读取时的错误，带上文件与记录序号；保留 `io::ErrorKind`，格式错误仍按 [`Failure::MalformedInput`] 退出
+fn record_error(path: &std::path::Path, record: impl std::fmt::Display, e: io::Error) -> anyhow::Error {
+    io::Error::new(e.kind(), format!("{}: record {}: {}", path.display(), record, e)).into()
+}
+
+/// 启动前检查每个 lane 的 R1 与 R2 第一条 read 是否来自同一次测序
+///
+/// 只比较 Illumina 格式的 header（仪器、run 编号、flowcell），其他格式直接跳过。
+fn check_same_run(r1_path: &PathBuf, r2_path: &PathBuf, strict: bool) -> Result<()> {
+    let mut r1 = empty_record();
+    let mut r2 = empty_record();
+    if !read_fastq_record(&mut open_fastq_reader(r1_path)?, &mut r1)?
+        || !read_fastq_record(&mut open_fastq_reader(r2_path)?, &mut r2)?
+    {
+        return Ok(());
+    }
+    let (Some(h1), Some(h2)) = (parse_illumina_header(&r1.head), parse_illumina_header(&r2.head)) else {
+        return Ok(());
+    };
+    if h1.same_run(&h2) {
+        return Ok(());
+    }
+    let msg = format!("R1 and R2 appear to come from different sequencing runs:\n  R1 {}: {}\n  R2 {}: {}",
+                      r1_path.display(), h1.run_id(), r2_path.display(), h2.run_id());
+    if strict {
+        return Err(Failure::Pairing.error(anyhow::anyhow!(msg)));
+    }
+    eprintln!("WARNING: {}", msg);
+    eprintln!("WARNING: Expect most pairs to be filtered for header mismatch (use --strict to abort instead)");
+    Ok(())
+}
+
+/// 预先扫描一个 lane：R1 与 R2 的 read 名称必须逐条相同，且按字典序非降序排列
+///
+/// 在第一处不符合的位置报错，返回检查过的 pair 数。
+fn check_pair_order(r1_path: &PathBuf, r2_path: &PathBuf) -> Result<u64> {
+    let mut r1_reader = open_fastq_reader(r1_path)?;
+    let mut r2_reader = open_fastq_reader(r2_path)?;
+    let (mut r1, mut r2) = (empty_record(), empty_record());
+    let mut previous: Vec<u8> = Vec::new();
+    let mut pairs = 0u64;
+
+    loop {
+        let more1 = read_fastq_record(&mut r1_reader, &mut r1)
+            .map_err(|e| record_error(r1_path, pairs + 1, e))?;
+        let more2 = read_fastq_record(&mut r2_reader, &mut r2)
+            .map_err(|e| record_error(r2_path, pairs + 1, e))?;
+        match (more1, more2) {
+            (false, false) => return Ok(pairs),
+            (true, true) => {}
+            _ => return Err(Failure::Pairing.error(anyhow::anyhow!("{} and {} have different numbers of records (first {} pairs agree)",
+                                                                   r1_path.display(), r2_path.display(), pairs))),
+        }
+        pairs += 1;
+
+        let (name1, name2) = (read_name(&r1.head), read_name(&r2.head));
+        if name1 != name2 {
+            return Err(Failure::Pairing.error(anyhow::anyhow!("R1 and R2 are out of step at pair {}: R1 has '{}' but R2 has '{}'",
+                                                              pairs, String::from_utf8_lossy(name1), String::from_utf8_lossy(name2))));
+        }
+        if pairs > 1 && name1 < previous.as_slice() {
+            return Err(Failure::Pairing.error(anyhow::anyhow!("Inputs are not sorted at pair {}: '{}' comes after '{}'",
+                                                              pairs, String::from_utf8_lossy(name1), String::from_utf8_lossy(&previous))));
+        }
+        previous.clear();
+        previous.extend_from_slice(name1);
+    }
+}
+
+/// 写入 `PREFIX_run_info.json`
+fn write_run_info(
+    args: &SplitArgs,
+    topology: &ThreadTopology,
+    batch_size: usize,
+    barcode_window: BarcodeWindow,
+    start_time: SystemTime,
+    outputs: &[PathBuf],
+    path: &PathBuf,
+) -> Result<()> {
+    let mut options = serde_json::to_value(args)?;
+    // 记录实际生效的线程数与 batch 大小，而不是未指定时的 null 或被内存预算调整前的值
+    options["threads"] = topology.process.into();
+    options["read_threads"] = topology.read.into();
+    options["write_threads"] = topology.write.into();
+    options["batch_size"] = batch_size.into();
+    options["barcode_offset"] = barcode_window.offset.into();
+    options["barcode_reverse_complement"] = barcode_window.reverse_complement.into();
+
+    let inputs = args.r1_input.iter().chain(&args.r2_input)
+        .map(|p| InputFileInfo::collect(p, args.run_info_checksums))
+        .collect::<Result<Vec<_>>>()?;
+
+    let info = RunInfo {
+        command_line: std::env::args().collect(),
+        options,
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: env!("GIT_HASH").to_string(),
+        start_time: format_rfc3339(start_time),
+        end_time: format_rfc3339(SystemTime::now()),
+        hostname: hostname(),
+        inputs,
+        outputs: outputs.to_vec(),
+    };
+    info.write(path)
+}
+
+#[cfg(feature = "parquet")]
+fn write_barcode_counts_parquet(counter: &BarcodeCounter, path: &PathBuf) -> Result<()> {
+    counter.write_parquet(path)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_barcode_counts_parquet(_counter: &BarcodeCounter, _path: &PathBuf) -> Result<()> {
+    unreachable!("rejected at startup without the parquet feature")
+}
+
+/// `--benchmark` 的吞吐量报告
+fn print_benchmark_report(counters: &PipelineCounters, timings: &StageTimings, workers: usize) {
+    let secs = |ms: u64| ms as f64 / 1000.0;
+    let ns_secs = |ns: &AtomicU64| ns.load(Ordering::Relaxed) as f64 / 1e9;
+    let rate = |pairs: u64, ms: u64| if ms > 0 { pairs as f64 / secs(ms) } else { 0.0 };
+    let pairs = counters.pairs_read();
+
+    eprintln!("Benchmark (no files written):");
+    eprintln!("  Read stage: {} pairs in {:.2}s ({:.0} pairs/s), blocked sending to processors {:.2}s",
+             pairs, secs(timings.reader_wall_ms), rate(pairs, timings.reader_wall_ms),
+             ns_secs(&counters.reader_send_wait_ns));
+    eprintln!("  Processing stage: {} pairs in {:.2}s ({:.0} pairs/s, {} workers), \
+              waiting for input {:.2}s, blocked sending output {:.2}s (summed over workers)",
+             pairs, secs(timings.worker_wall_ms.max), rate(pairs, timings.worker_wall_ms.max), workers,
+             ns_secs(&counters.worker_recv_wait_ns), ns_secs(&counters.worker_send_wait_ns));
+    eprintln!("  Discarded output: {} bytes", counters.snapshot().bytes_written.iter().sum::<u64>());
+}
+
+/// `split` 结束时打印到标准错误的汇总（`--quiet` 时不打印）
+///
+/// `first_pass` 为 `--two-pass` 第一遍的 read 数、条形码数与耗时；`unassigned` 表示未选中的 pair 另行写出。
+fn print_split_summary(
+    args: &SplitArgs,
+    stats: &RunStats,
+    first_pass: Option<(u64, usize, Duration)>,
+    unassigned: bool,
+) {
+    let final_processed = stats.pairs_written as usize;
+    let final_filtered = stats.pairs_filtered as usize;
+    eprintln!("Processing complete!");
+    eprintln!("Processed records: {}", final_processed);
+    if args.soft_filter {
+        eprintln!("Flagged records (written with an FT:Z tag): {}", final_filtered);
+    } else {
+        eprintln!("Filtered out records: {}", final_filtered);
+    }
+    // 按原因分开，便于判断是长度、header 还是条形码的问题
+    for reason in FilterReason::ALL {
+        let n = stats.filtered_by_reason.get(reason.label()).copied().unwrap_or_default();
+        if n > 0 {
+            eprintln!("  {}: {} ({:.2}% of input pairs)", reason.label(), n, n as f64 / stats.pairs_read.max(1) as f64 * 100.0);
+        }
+    }
+    if stats.unpaired > 0 {
+        eprintln!("Unpaired records (no mate in the other file): {}", stats.unpaired);
+    }
+    if let Some((reads, barcodes, elapsed)) = first_pass {
+        eprintln!("First pass: {} reads with exact whitelist barcodes ({} barcodes) in {:.2}s",
+                 reads, barcodes, elapsed.as_secs_f64());
+    }
+    eprintln!("Input read: R1 {:.1} MB, R2 {:.1} MB ({:.1} MB/s)",
+             stats.r1_read_mb, stats.r2_read_mb, stats.total_throughput_mb_s);
+    match &stats.output_compression {
+        Some(compression) => eprintln!("Output written: R1 {:.1} MB, R2 {:.1} MB, R3 {:.1} MB (compression ratio {:.2}x)",
+                                      stats.r1_output_mb, stats.r2_output_mb, stats.r3_output_mb, compression.ratio),
+        None => eprintln!("Output written: R1 {:.1} MB, R2 {:.1} MB, R3 {:.1} MB",
+                         stats.r1_output_mb, stats.r2_output_mb, stats.r3_output_mb),
+    }
+    if args.lane_merge {
+        eprintln!("Records per lane:");
+        for (path, count) in args.r1_input.iter().zip(&stats.lane_pairs) {
+            eprintln!("  {}: {}", path.display(), count);
+        }
+    }
+    if !stats.lanes.is_empty() {
+        eprintln!("Per-lane statistics (lane field of read headers):");
+        for lane in &stats.lanes {
+            let fraction = lane.pairs_filtered as f64 / lane.pairs_read.max(1) as f64;
+            let reasons: Vec<String> = lane.filtered_by_reason.iter()
+                .filter(|(_, &n)| n > 0)
+                .map(|(reason, n)| format!("{} {}", reason, n))
+                .collect();
+            let mut line = format!("  {}: {} pairs, {} filtered ({:.2}%)",
+                                   lane.lane, lane.pairs_read, lane.pairs_filtered, fraction * 100.0);
+            if !reasons.is_empty() {
+                line.push_str(&format!(" [{}]", reasons.join(", ")));
+            }
+            if let Some(rate) = lane.whitelist_match_rate {
+                line.push_str(&format!(", whitelist match {:.2}%", rate * 100.0));
+            }
+            eprintln!("{}", line);
+        }
+    }
+    /// 汇总中列出的 tile 数
+    const SUMMARY_TILES: usize = 3;
+    let bad_tiles: Vec<String> = stats.worst_tiles.iter()
+        .filter(|t| t.pairs_filtered > 0)
+        .take(SUMMARY_TILES)
+        .map(|t| format!("{}:{} {:.2}% of {}", t.lane, t.tile, t.filter_rate * 100.0, t.pairs_read))
+        .collect();
+    if !bad_tiles.is_empty() {
+        eprintln!("Highest tile filter rates (lane:tile): {}", bad_tiles.join(", "));
+    }
+    if args.exclude_tiles.is_some() {
+        eprintln!("Excluded tiles: {} pairs dropped",
+                 stats.filtered_by_reason.get(FilterReason::ExcludedTile.label()).copied().unwrap_or_default());
+    }
+    if let Some(bc) = &stats.barcode_correction {
+        eprintln!("Barcode whitelist matching:");
+        eprintln!("  Exact: {}", bc.exact);
+        eprintln!("  Corrected (1 mismatch): {}", bc.corrected);
+        eprintln!("  Rescued (single N): {}", bc.n_rescued);
+        if args.correct_indels {
+            eprintln!("  Corrected (1 indel): {}", bc.indel_corrected);
+        }
+        eprintln!("  No match (filtered): {}", bc.no_match);
+        if args.allow_truncated_bc > 0 {
+            eprintln!("  Truncated, rescued by unique prefix: {}", bc.truncated_rescued);
+            eprintln!("  Truncated, ambiguous prefix (filtered): {}", bc.truncated_ambiguous);
+        }
+        if args.bc_mask_qual.is_some() {
+            eprintln!("  Reads with masked bases: {} ({} rescued)", bc.masked_reads, bc.masked_rescued);
+        }
+        if args.keep_raw_barcode {
+            let fraction = if final_processed > 0 { bc.raw_differs as f64 / final_processed as f64 } else { 0.0 };
+            eprintln!("  Raw differs from corrected: {} ({:.2}%)", bc.raw_differs, fraction * 100.0);
+        }
+    }
+    if let Some(names) = &stats.read_names {
+        /// 最多列出的未找到的名字数
+        const MAX_LISTED: usize = 10;
+        eprintln!("Read names: {} of {} listed found, {} missing", names.found, names.listed, names.missing.len());
+        for name in names.missing.iter().take(MAX_LISTED) {
+            eprintln!("  Missing: {}", name);
+        }
+        if names.missing.len() > MAX_LISTED {
+            eprintln!("  ... {} more missing names not shown", names.missing.len() - MAX_LISTED);
+        }
+    }
+    if let Some(sel) = &stats.barcode_selection {
+        eprintln!("Barcode selection: {} selected, {} not selected ({}), {} of {} listed barcodes never observed",
+                 sel.selected_pairs, sel.unselected_pairs,
+                 if unassigned { "written to unassigned outputs" } else { "dropped" },
+                 sel.never_observed, sel.listed);
+    }
+    if let Some(rare) = &stats.barcode_min_reads {
+        eprintln!("Barcodes with fewer than {} reads: {} barcodes, {} pairs ({})",
+                 rare.min_reads, rare.removed_barcodes, rare.removed_pairs,
+                 if unassigned { "written to unassigned outputs" } else { "dropped" });
+    }
+    if let Some(rt) = &stats.adapter_readthrough {
+        eprintln!("Adapter read-through in barcode window: {} pairs ({:.2}%, {})",
+                 rt.pairs, rt.fraction * 100.0, if rt.dropped { "dropped" } else { "kept" });
+    }
+    let distinct = &stats.distinct_barcodes;
+    if distinct.approximate {
+        eprintln!("Distinct barcodes: ~{} (approximate, ±{:.1}%)", distinct.count,
+                 distinct.relative_error.unwrap_or_default() * 100.0);
+    } else {
+        eprintln!("Distinct barcodes: {}", distinct.count);
+    }
+    if let Some(c) = &stats.library_complexity {
+        eprintln!("Library complexity: {} pairs, ~{} distinct fragments, saturation {:.1}%, ~{} projected at 2x depth",
+                 c.total_reads, c.distinct_fragments, c.saturation * 100.0, c.projected_unique_2x);
+    }
+    if let Some(min) = args.filter_low_complexity {
+        eprintln!("Low-complexity R3 (entropy below {}): {} pairs dropped", min,
+                 stats.filtered_by_reason.get(FilterReason::LowComplexity.label()).copied().unwrap_or_default());
+    }
+    if let Some(adaptive) = &stats.adaptive_batch {
+        eprintln!("Adaptive batch size: {} -> {} (range {}-{}, {} adjustments)",
+                 adaptive.initial, adaptive.final_size, adaptive.smallest, adaptive.largest, adaptive.adjustments);
+    }
+    if let Some(anchor) = &stats.anchor {
+        eprintln!("Anchor {} (up to {} mismatches): {} pairs without the anchor (filtered), \
+                  {} with several hits (3'-most used)",
+                 anchor.sequence, anchor.max_mismatches, anchor.not_found, anchor.ambiguous);
+    }
+    if let Some(optical) = &stats.optical_duplicates {
+        eprintln!("Optical duplicates (within {} px): {} of {} pairs ({:.2}%, {})",
+                 optical.max_distance, optical.duplicates, optical.examined, optical.fraction * 100.0,
+                 if optical.dropped { "dropped" } else { "kept" });
+    }
+    if let Some(over) = &stats.overrepresented {
+        eprintln!("Overrepresented R3 sequences (> {:.2}% of {} sampled reads): {}",
+                 over.min_fraction * 100.0, over.sampled, over.sequences.len());
+        for seq in &over.sequences {
+            eprintln!("  {} {} ({:.2}%)", seq.sequence, seq.count, seq.fraction * 100.0);
+        }
+    }
+    if let Some(screen) = &stats.contaminants {
+        eprintln!("Contaminant screen ({} sampled R3 reads, >= {} shared {}-mers):",
+                 screen.sampled, screen.min_kmers, screen.kmer_len);
+        for hit in &screen.contaminants {
+            eprintln!("  {}: {} ({:.2}%)", hit.name, hit.reads, hit.fraction * 100.0);
+        }
+    }
+    if let Some(dups) = &stats.duplicate_names {
+        eprintln!("Duplicate read names: ~{} of {} pairs ({:.3}%, {} of {} sampled names repeated)",
+                 dups.estimated_duplicates, dups.names, dups.fraction * 100.0, dups.sampled_duplicates, dups.sampled);
+    }
+    if let Some(counts) = stats.group_counts.as_ref().filter(|_| args.split_per_barcode) {
+        // 条形码可能上千个，逐个的计数只写入 PREFIX_per_barcode_counts.tsv
+        eprintln!("Per-barcode outputs: {} barcodes received reads", counts.len());
+    } else if let Some(counts) = &stats.group_counts {
+        let unit = if args.split_lanes { "lane" } else if args.shard_by_bc_prefix.is_some() { "shard" } else { "group" };
+        eprintln!("Records per {}:", unit);
+        for (group, count) in counts {
+            eprintln!("  {}: {}", group, count);
+        }
+    }
+}
+
+fn run_split(args: SplitArgs) -> Result<()> {
+    let start_time = SystemTime::now();
+    let start_instant = Instant::now();
+    validate_split_args(&args).classify(Failure::Usage)?;
+    let input_format = resolve_input_format(&args)?;
+    if args.r1_input.len() > 1 && !args.lane_merge {
+        return Err(Failure::Usage.error(anyhow::anyhow!("Multiple input files given; pass --lane-merge to merge lanes into a single output")));
+    }
+    if args.prescan {
+        let lengths = prescan_r2_lengths(&args, input_format, thread_topology(&args).read)?;
+        print_prescan(&lengths);
+        return Ok(());
+    }
+    let rereading_flag = if args.two_pass {
+        Some("--two-pass")
+    } else {
+        args.min_reads_per_barcode.map(|_| "--min-reads-per-barcode")
+    };
+    if let Some(flag) = rereading_flag {
+        for path in args.r1_input.iter().chain(&args.r2_input) {
+            let regular = path.as_os_str() != "-" && std::fs::metadata(path).is_ok_and(|m| m.is_file());
+            if !regular {
+                return Err(Failure::Usage.error(anyhow::anyhow!("{} reads the inputs twice, but {} is not a regular file (stdin and pipes cannot be re-read)",
+                                                                flag, path.display())));
+            }
+        }
+    }
+    if args.barcode_counts_parquet.is_some() && !cfg!(feature = "parquet") {
+        return Err(Failure::Usage.error(anyhow::anyhow!("--barcode-counts-parquet requires a build with the `parquet` feature")));
+    }
+    
+    // 输入与输出；处理参数在下面准备好后填入
+    let output_prefix = args.output_prefix.clone().unwrap_or_default();
+    let mut run_config = RunConfig {
+        r1_inputs: args.r1_input.clone(),
+        r2_inputs: args.r2_input.clone(),
+        input_format,
+        output_prefix: output_prefix.clone(),
+        number_suffix: args.number_suffix.clone(),
+        compress: args.compress,
+        keep_raw_barcode: args.keep_raw_barcode,
+        unassigned_outputs: args.unselected == UnselectedPolicy::Unassigned,
+        rename_map: args.rename_map.clone(),
+        max_open_groups: if args.split_per_barcode { (args.max_open_files / 3) as usize } else { args.max_open_groups },
+        evict_groups: args.split_per_barcode,
+        benchmark: args.benchmark,
+        mark_incomplete: !args.cleanup_on_error,
+        ..RunConfig::default()
+    };
+    let [r1_output, r2_output, r3_output] = run_config.main_outputs();
+    let raw_bc_output = run_config.raw_barcode_output();
+    let unassigned_outputs = run_config.unassigned_outputs();
+    let local_outputs: Vec<PathBuf> = [&r1_output, &r2_output, &r3_output].into_iter().cloned()
+        .chain(raw_bc_output.clone())
+        .chain(unassigned_outputs.iter().flatten().cloned())
+        .filter(|path| !args.benchmark && !remote::is_remote(path))
+        .collect();
+    preflight_checks(&args, &local_outputs)?;
+    // 上一次运行留下的完成标记必须先去掉，否则本次失败时仍会被当作已完成
+    if let Some(path) = args.done_file.as_ref().filter(|path| !remote::is_remote(path) && path.exists()) {
+        if !args.force {
+            return Err(Failure::Usage.error(anyhow::anyhow!("{} exists, so a previous run already completed; pass --force to remove it and run again",
+                                                            path.display())));
+        }
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        if args.verbose {
+            eprintln!("Removed previous done file {}", path.display());
+        }
+    }
+    for (r1_path, r2_path) in args.r1_input.iter().zip(&args.r2_input) {
+        check_same_run(r1_path, r2_path, args.strict)?;
+        if args.check_pair_order {
+            let pairs = check_pair_order(r1_path, r2_path)?;
+            if args.verbose {
+                eprintln!("Pair order check passed for {} pairs in {}", pairs, r1_path.display());
+            }
+        }
+    }
+    
+    let mut whitelist = match &args.whitelist {
+        Some(path) => {
+            let wl = Whitelist::load(path)?;
+            if args.verbose {
+                eprintln!("Loaded {} whitelist barcodes from {}", wl.len(), path.display());
+            }
+            Some(wl)
+        }
+        None => None,
+    };
+    let read_names = match &args.read_names {
+        Some(path) => {
+            let list = ReadNameList::load(path)?;
+            if args.verbose {
+                eprintln!("Loaded {} read names from {}", list.len(), path.display());
+            }
+            Some(list)
+        }
+        None => None,
+    };
+    let exclude_tiles = match &args.exclude_tiles {
+        Some(arg) => {
+            let tiles = TileExclusion::parse(arg).context("Invalid --exclude-tiles")?;
+            if args.verbose {
+                eprintln!("Excluding {} tiles", tiles.len());
+            }
+            Some(tiles)
+        }
+        None => None,
+    };
+    let select_barcodes = match &args.select_barcodes {
+        Some(path) => {
+            let list = BarcodeSelection::load(path)?;
+            if args.verbose {
+                eprintln!("Loaded {} barcodes to select from {}", list.len(), path.display());
+            }
+            Some(list)
+        }
+        None => None,
+    };
+    let contaminants = match &args.contaminants {
+        Some(path) => {
+            let index = ContaminantIndex::load(path)?;
+            if args.verbose {
+                eprintln!("Loaded {} contaminant sequences from {}", index.names().len(), path.display());
+            }
+            Some(ContaminantScreen::new(index, args.contaminant_every, args.contaminant_min_kmers as usize))
+        }
+        None => None,
+    };
+    let groups = match &args.split_by {
+        Some(path) => {
+            let groups = BarcodeGroups::load(path)?;
+            if args.verbose {
+                eprintln!("Loaded {} groups from {}", groups.names().len(), path.display());
+            }
+            Some(groups)
+        }
+        None if args.split_per_barcode => Some(per_barcode_groups(&args, select_barcodes.as_ref(), whitelist.as_ref())?),
+        None => None,
+    };
+    // 每个条形码一组时记下全部条形码，结束时没有 read 的也写入计数表
+    let per_barcode_names = groups.as_ref().filter(|_| args.split_per_barcode).map(|g| g.names().to_vec());
+    let topology = thread_topology(&args);
+    let mut batch_size = args.batch_size;
+    let memory_limit = args.max_memory_gb.map(|gb| memory_limited_batch_size(gb, topology.process));
+    if let (Some(gb), Some(limit)) = (args.max_memory_gb, memory_limit) {
+        if limit < batch_size {
+            batch_size = limit;
+            if args.verbose {
+                eprintln!("Batch size lowered to {} to fit --max-memory-gb {}", batch_size, gb);
+            }
+        }
+    }
+    // Statistics - 进度显示与最终汇总共用同一组计数器
+    let counters = Arc::new(PipelineCounters::default());
+
+    // 抽样扫描条形码位置；继续拆分时已读出的 R2 开头由 sample_r2 重放给流水线
+    let mut barcode_window = BarcodeWindow { reverse_complement: !args.no_rc_barcode, ..BarcodeWindow::default() };
+    let mut first_r2 = None;
+    if let Some(offsets) = &args.scan_bc_offset {
+        if input_format != InputFormat::Fastq {
+            return Err(Failure::Usage.error(anyhow::anyhow!("--scan-bc-offset supports FASTQ input only")));
+        }
+        let (seqs, replay) = sample_r2(&args.r2_input[0], OFFSET_SCAN_READS, topology.read, &counters)?;
+        let rows = scan_barcode_offsets(&seqs, whitelist.as_ref().unwrap(), offsets.clone());
+        let Some(best) = best_window(&rows) else {
+            anyhow::bail!("No candidate barcode window matched the whitelist");
+        };
+        if !args.quiet {
+            print_offset_scan(&rows, seqs.len());
+            eprintln!("Best barcode window: offset {}, {} ({:.2}%)",
+                     best.window.offset, orientation_label(best.window), best.hit_rate() * 100.0);
+        }
+        if !args.apply_best {
+            return Ok(());
+        }
+        barcode_window = best.window;
+        first_r2 = Some(replay);
+    }
+    r3_range(barcode_window, args.r3_start, args.r3_end)?;
+    let anchor = args.anchor.as_deref().map(|seq| Anchor::new(seq, args.anchor_max_mismatch)).transpose()?;
+
+    let readthrough = match args.readthrough {
+        Some(arg) => ReadthroughPolicy::from(arg),
+        None if whitelist.is_some() => ReadthroughPolicy::Count,
+        None => ReadthroughPolicy::Off,
+    };
+
+    // 第一遍只统计完全匹配的条形码，作为第二遍纠错的先验
+    let first_pass_start = Instant::now();
+    let mut first_pass = None;
+    if args.two_pass {
+        let counts = count_exact_barcodes(&args, input_format, barcode_window,
+                                          whitelist.as_ref().unwrap(), topology.read)?;
+        let reads: u64 = counts.values().sum();
+        first_pass = Some((reads, counts.len(), first_pass_start.elapsed()));
+        whitelist.as_mut().unwrap().set_priors(counts);
+    }
+    // 按纠错后的条形码计数（有先验时已用上），再决定哪些条形码的 read 数不足
+    let min_reads_per_barcode = match args.min_reads_per_barcode {
+        Some(min_reads) => {
+            let config = PipelineConfig {
+                whitelist: whitelist.clone(),
+                bc_mask_qual: args.bc_mask_qual,
+                allow_truncated_bc: args.allow_truncated_bc.into(),
+                correct_indels: args.correct_indels,
+                barcode_window,
+                anchor: anchor.clone(),
+                readthrough,
+                head: args.head,
+                batch_size,
+                threads: topology.process,
+                count_barcodes: true,
+                ..PipelineConfig::default()
+            };
+            let counts = count_corrected_barcodes(config, &args, input_format, topology.read)?;
+            Some(MinReadsFilter::new(&counts, min_reads))
+        }
+        None => None,
+    };
+    let first_pass_ms = (args.two_pass || args.min_reads_per_barcode.is_some())
+        .then(|| first_pass_start.elapsed().as_millis() as u64);
+
+    if args.verbose {
+        eprintln!("Starting batch processing with batch size: {}", batch_size);
+        eprintln!("Thread topology: 1 reader ({} decompression thread(s) per BGZF input), {} processor(s), \
+                  1 distributor, {} writer(s) ({} compression thread(s) each)",
+                 topology.read, topology.process,
+                 3 + raw_bc_output.iter().count() + unassigned_outputs.iter().flatten().count(),
+                 if args.compress { topology.write } else { 0 });
     }
     
-    // Close output channel to signal distribution thread to finish
-    drop(output_tx);
-    
-    // Wait for distribution thread to finish
-    dist_handle.join().unwrap()?;
-    
-    // Close writer channels to signal writers to finish
-    drop(r1_tx);
-    drop(r2_tx);
-    drop(r3_tx);
-    
-    // Wait for all writer threads to finish
-    r1_writer_handle.join().unwrap()?;
-    r2_writer_handle.join().unwrap()?;
-    r3_writer_handle.join().unwrap()?;
-    
-    let final_processed = *processed_count.lock().unwrap();
-    let final_filtered = *filtered_count.lock().unwrap();
-    
-    println!("Processing complete!");
-    println!("Processed records: {}", final_processed);
-    println!("Filtered out records: {}", final_filtered);
-    println!("Output files:");
-    println!("  R1: {}", r1_output_display.display());
-    println!("  R2: {}", r2_output_display.display());
-    println!("  R3: {}", r3_output_display.display());
+    let worker_cpus = args.affinity.as_ref().and_then(|CpuList(cpus)| {
+        let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let (valid, missing): (Vec<usize>, Vec<usize>) = cpus.iter().partition(|&&cpu| cpu < cores);
+        if !missing.is_empty() {
+            eprintln!("WARNING: --affinity lists CPUs {:?} but only {} are available; they are ignored", missing, cores);
+        }
+        if valid.is_empty() {
+            eprintln!("WARNING: No CPU in --affinity is available; processing threads are not pinned");
+        }
+        (!valid.is_empty()).then_some(valid)
+    });
+    // 输出尚未创建，实际查询的是输出所在目录的磁盘
+    let io_node = args.numa_local.then(|| numa::disk_node(&r1_output)).flatten();
+    if args.verbose && args.numa_local {
+        match &io_node {
+            Some(node) => eprintln!("Pinning reader and writer threads to NUMA node {} ({} CPUs)",
+                                    node.node, node.cpus.len()),
+            None => eprintln!("NUMA node of the output disk unknown; reader and writer threads are not pinned"),
+        }
+    }
+    let config = PipelineConfig {
+        whitelist,
+        bc_mask_qual: args.bc_mask_qual,
+        allow_truncated_bc: args.allow_truncated_bc.into(),
+        correct_indels: args.correct_indels,
+        read_names,
+        exclude_tiles,
+        select_barcodes,
+        min_reads_per_barcode,
+        groups,
+        split_lanes: args.split_lanes,
+        shard_by_bc_prefix: args.shard_by_bc_prefix.map(usize::from),
+        barcode_window,
+        anchor,
+        readthrough,
+        min_complexity: args.filter_low_complexity,
+        mark_optical_dups: args.mark_optical_dups,
+        optical_dup_policy: args.optical_dup_policy.into(),
+        overrepresented: args.overrepresented_every.map(|every| OverrepresentedConfig {
+            every,
+            min_fraction: args.overrepresented_fraction,
+        }),
+        contaminants,
+        check_duplicate_names: args.check_duplicate_names,
+        rename_reads: args.rename_reads.clone(),
+        soft_filter: args.soft_filter,
+        preserve_plus_line: args.preserve_plus_line,
+        keep_header_comments: args.keep_header_comments,
+        r3_start: args.r3_start,
+        r3_end: args.r3_end,
+        reverse_r3: args.reverse_r3,
+        swap_r2_r3: args.swap_r2_r3,
+        head: args.head,
+        batch_size,
+        // 按内存预算算出的大小同时是动态调整的上限
+        adaptive_batch: args.adaptive_batch.then(|| BatchTarget {
+            min: Duration::from_millis(*args.batch_target_ms.start() as u64),
+            max: Duration::from_millis(*args.batch_target_ms.end() as u64),
+            max_batch_size: memory_limit,
+        }),
+        preallocate_writer_buffer: args.preallocate_writer_buffer,
+        record_pool: args.record_pool.map(|capacity| Arc::new(RecordPool::new(capacity, R2_LEN))),
+        threads: topology.process,
+        worker_cpus,
+        io_cpus: io_node.map(|node| node.cpus),
+        batch_timeout: args.batch_timeout_ms.map(Duration::from_millis),
+        count_barcodes: args.barcode_counts.is_some() || args.barcode_counts_parquet.is_some()
+            || args.rank_plot_data.is_some(),
+        counters: Arc::clone(&counters),
+    };
+    
+    run_config.topology = topology;
+    run_config.first_r2 = first_r2;
+    run_config.pipeline = config;
+    let grouped_output = run_config.grouped_output();
+
+    let total_input_bytes = args.r1_input.iter().chain(&args.r2_input)
+        .map(|p| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0))
+        .sum();
+    let progress = args.progress.style().filter(|_| !args.quiet).map(|style| {
+        ProgressReporter::spawn(style, Arc::clone(&counters), total_input_bytes, Duration::from_secs(args.progress_interval))
+    });
+    let metrics = args.metrics_file.clone().map(|path| {
+        cleanup::register(&path);
+        MetricsWriter::spawn(path, Arc::clone(&counters), Duration::from_secs(args.metrics_interval))
+    });
+    
+    let mut stats = run(run_config)?;
+    stats.wall_ms = start_instant.elapsed().as_millis() as u64;
+    stats.first_pass_ms = first_pass_ms;
+
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    if let Some(metrics) = metrics {
+        metrics.finish();
+    }
+    if !args.quiet {
+        print_split_summary(&args, &stats, first_pass, unassigned_outputs.is_some());
+    }
+    report_barcode_composition(&stats, args.verbose);
+    // batch 数太少时各线程的工作量本来就不均匀，不做判断
+    if stats.pairs_read >= (4 * topology.process * batch_size) as u64 {
+        for id in find_lagging_workers(&stats.worker_stats) {
+            let w = &stats.worker_stats[id];
+            eprintln!("WARNING: Processing thread {} handled only {} records in {} ms (load imbalance)",
+                      id, w.records, w.wall_ms);
+        }
+    }
+    if let Some(screen) = &stats.contaminants {
+        for hit in screen.contaminants.iter().filter(|hit| hit.fraction > args.contaminant_warn) {
+            eprintln!("WARNING: {:.2}% of screened R3 reads match contaminant '{}' (threshold {:.2}%)",
+                      hit.fraction * 100.0, hit.name, args.contaminant_warn * 100.0);
+        }
+    }
+    // 重复的 read 名通常意味着上游把同一批数据拼接了两次，总是报告（--quiet 时也一样）
+    let duplicate_names = stats.duplicate_names.as_ref().filter(|dups| dups.fraction > args.duplicate_names_warn)
+        .map(|dups| format!("{:.2}% of input pairs (~{}) repeat an earlier read name (threshold {:.2}%); \
+                             the input may contain the same reads twice", dups.fraction * 100.0,
+                            dups.estimated_duplicates, args.duplicate_names_warn * 100.0));
+    if let Some(msg) = &duplicate_names {
+        eprintln!("WARNING: {}", msg);
+    }
+    // R1、R2 记录数不同通常意味着文件被截断或不是同一对，同样总是报告
+    let unpaired = stats.first_unpaired.as_ref().map(|first| format!(
+        "{} records have no mate in the other input file (first: {}); R1 and R2 may be truncated or mismatched",
+        stats.unpaired, first));
+    if let Some(msg) = &unpaired {
+        eprintln!("WARNING: {}", msg);
+    }
+    if stats.batches_processed > 0
+        && stats.empty_output_batches as f64 > EMPTY_BATCH_WARN * stats.batches_processed as f64 {
+        eprintln!("WARNING: {} of {} batches had every pair filtered (check the filtering options and input layout)",
+                  stats.empty_output_batches, stats.batches_processed);
+    }
+    if args.verbose {
+        eprintln!("Per-thread processing:");
+        for w in &stats.worker_stats {
+            eprintln!("  Thread {}: {} records in {} ms", w.worker_id, w.records, w.wall_ms);
+        }
+        if let Some(bc) = stats.barcode_correction.as_ref().filter(|_| args.correct_indels) {
+            eprintln!("Indel correction: {} barcodes tried in {} ms (summed over threads)",
+                      bc.indel_attempted, bc.indel_ms);
+        }
+    }
+    if args.benchmark {
+        print_benchmark_report(&counters, &stats.timings, topology.process);
+        return Ok(());
+    }
+    let group_outputs = std::mem::take(&mut stats.group_outputs);
+    let mut outputs = if group_outputs.is_empty() {
+        vec![r1_output.clone(), r2_output.clone(), r3_output.clone()]
+    } else {
+        group_outputs.clone()
+    };
+    outputs.extend(raw_bc_output.clone());
+    outputs.extend(unassigned_outputs.iter().flatten().cloned());
+    outputs.extend(args.rename_map.clone());
+    let per_barcode_counts = per_barcode_names.map(|names| -> Result<PathBuf> {
+        let path = PathBuf::from(format!("{}_per_barcode_counts.tsv", output_prefix));
+        cleanup::register(&path);
+        write_per_barcode_counts(&path, &names, stats.group_counts.as_ref().unwrap_or(&BTreeMap::new()))?;
+        Ok(path)
+    }).transpose()?;
+    outputs.extend(per_barcode_counts.clone());
+    if let Some(counter) = &stats.barcode_counts {
+        if let Some(path) = &args.barcode_counts {
+            cleanup::register(path);
+            counter.write_tsv(path)?;
+            outputs.push(path.clone());
+        }
+        if let Some(path) = &args.barcode_counts_parquet {
+            cleanup::register(path);
+            write_barcode_counts_parquet(counter, path)?;
+            outputs.push(path.clone());
+        }
+        if let Some(path) = &args.rank_plot_data {
+            cleanup::register(path);
+            counter.write_rank_plot(path, args.rank_plot_points.map(|n| n as usize))?;
+            outputs.push(path.clone());
+        }
+    }
+    if let Some(path) = &args.stats_json {
+        cleanup::register(path);
+        stats.write(path)?;
+        outputs.push(path.clone());
+    }
+    if !args.no_run_info {
+        let run_info_path = PathBuf::from(format!("{}_run_info.json", output_prefix));
+        cleanup::register(&run_info_path);
+        write_run_info(&args, &topology, batch_size, barcode_window, start_time, &outputs, &run_info_path)?;
+        outputs.push(run_info_path);
+    }
+    
+    if !args.quiet {
+        eprintln!("Output files:");
+        if let Some(path) = &per_barcode_counts {
+            eprintln!("  {} per-barcode FASTQ files", group_outputs.len());
+            eprintln!("  Per-barcode counts: {}", path.display());
+        } else if grouped_output {
+            for path in &group_outputs {
+                eprintln!("  {}", path.display());
+            }
+        } else {
+            eprintln!("  R1: {}", r1_output.display());
+            eprintln!("  R2: {}", r2_output.display());
+            eprintln!("  R3: {}", r3_output.display());
+        }
+        if let Some(path) = &raw_bc_output {
+            eprintln!("  Raw barcode: {}", path.display());
+        }
+        if let Some(paths) = &unassigned_outputs {
+            for (label, path) in ["R1", "R2", "R3"].iter().zip(paths) {
+                eprintln!("  Unassigned {}: {}", label, path.display());
+            }
+        }
+        if let Some(path) = &args.rename_map {
+            eprintln!("  Read name map: {}", path.display());
+        }
+        if let Some(path) = &args.barcode_counts {
+            eprintln!("  Barcode counts: {}", path.display());
+        }
+        if let Some(path) = &args.barcode_counts_parquet {
+            eprintln!("  Barcode counts (Parquet): {}", path.display());
+        }
+        if let Some(path) = &args.rank_plot_data {
+            eprintln!("  Rank plot data: {}", path.display());
+        }
+        if let Some(path) = &args.stats_json {
+            eprintln!("  Stats: {}", path.display());
+        }
+        if !args.no_run_info {
+            eprintln!("  Run info: {}", outputs.last().unwrap().display());
+        }
+    }
+    // 输出已写完，--strict 时仍以错误结束，但输出是完整的
+    cleanup::outputs_complete();
+    if args.strict {
+        if let Some(msg) = duplicate_names {
+            return Err(Failure::Threshold.error(anyhow::anyhow!(msg)));
+        }
+        if let Some(msg) = unpaired {
+            return Err(Failure::Pairing.error(anyhow::anyhow!(msg)));
+        }
+    }
+    // 最后一步：之前任何一步失败都不会写出
+    if let Some(path) = &args.done_file {
+        DoneFile::new(&stats, SystemTime::now()).write(path)?;
+    }
     
     Ok(())
 }
@@ -0,0 +1,79 @@
+// metrics.rs - Prometheus textfile 格式的运行指标
+
+use crate::progress::{CounterSnapshot, FilterReason, PipelineCounters, Periodic, OUTPUT_LABELS};
+use crate::remote::write_output;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 转义 label 值中的 `\`、`"` 与换行（Prometheus exposition format 的要求）
+pub fn escape_label_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 把计数器快照渲染成 Prometheus exposition format
+pub fn render_prometheus(snapshot: &CounterSnapshot, heartbeat: SystemTime) -> String {
+    let mut out = String::new();
+    let mut counter = |name: &str, help: &str, samples: &[(Option<(&str, &str)>, u64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (label, value) in samples {
+            match label {
+                Some((key, v)) => { let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, key, escape_label_value(v), value); }
+                None => { let _ = writeln!(out, "{} {}", name, value); }
+            }
+        }
+    };
+
+    counter("scatac_pairs_read_total", "Read pairs read from the inputs.", &[(None, snapshot.pairs_read)]);
+    counter("scatac_pairs_written_total", "Read pairs written to the outputs.", &[(None, snapshot.pairs_written)]);
+    let filtered: Vec<_> = FilterReason::ALL.iter()
+        .map(|r| (Some(("reason", r.label())), snapshot.filtered_by_reason[*r as usize]))
+        .collect();
+    counter("scatac_pairs_filtered_total", "Read pairs filtered out, by reason.", &filtered);
+    let bytes: Vec<_> = OUTPUT_LABELS.iter().zip(snapshot.bytes_written)
+        .map(|(label, n)| (Some(("output", *label)), n))
+        .collect();
+    counter("scatac_bytes_written_total", "Bytes written to each output file.", &bytes);
+
+    let heartbeat = heartbeat.duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0);
+    let _ = writeln!(out, "# HELP scatac_heartbeat_timestamp_seconds Unix time of the last metrics update.");
+    let _ = writeln!(out, "# TYPE scatac_heartbeat_timestamp_seconds gauge");
+    let _ = writeln!(out, "scatac_heartbeat_timestamp_seconds {:.3}", heartbeat);
+    out
+}
+
+/// 定期把指标原子地写入文件，供 node-exporter textfile collector 采集
+pub struct MetricsWriter {
+    ticker: Periodic,
+}
+
+impl MetricsWriter {
+    pub fn spawn(path: PathBuf, counters: Arc<PipelineCounters>, interval: Duration) -> Self {
+        let write = move || {
+            let text = render_prometheus(&counters.snapshot(), SystemTime::now());
+            if let Err(e) = write_output(&path, text.as_bytes()) {
+                log::warn!("Failed to write metrics to {}: {}", path.display(), e);
+            }
+        };
+        // 启动时先写一次，采集端不必等到第一个周期
+        write();
+        let ticker = Periodic::spawn(interval, move |_| write());
+        MetricsWriter { ticker }
+    }
+
+    /// 停止并写入最终值
+    pub fn finish(self) {
+        self.ticker.finish();
+    }
+}
@@ -0,0 +1,58 @@
+// numa.rs - 把读取与写入线程绑定到输出所在磁盘的 NUMA 节点（--numa-local）
+//
+// 需要在 Linux 上启用 `numa` feature：磁盘所在节点从 sysfs 查询，线程由 [`crate::affinity`] 绑定。
+// 其他情况下以及无法确定节点时什么也不做。
+
+use std::path::Path;
+
+/// 一个 NUMA 节点及其 CPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumaNode {
+    pub node: usize,
+    pub cpus: Vec<usize>,
+}
+
+/// `path`（或其所在目录，输出尚未创建时）所在块设备的 NUMA 节点；无法确定时为 `None`
+pub fn disk_node(path: &Path) -> Option<NumaNode> {
+    #[cfg(all(feature = "numa", target_os = "linux"))]
+    {
+        imp::disk_node(path)
+    }
+    #[cfg(not(all(feature = "numa", target_os = "linux")))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+#[cfg(all(feature = "numa", target_os = "linux"))]
+mod imp {
+    use super::NumaNode;
+    use crate::affinity::parse_cpu_list;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub fn disk_node(path: &Path) -> Option<NumaNode> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => fs::metadata(path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")))
+                .ok()?,
+        };
+        let dev = metadata.dev();
+        // 与 glibc 的 major()/minor() 相同
+        let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+        let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+        // 分区与 nvme namespace 本身没有 numa_node，向上找到所在的 PCI 设备
+        let device = fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+        let node = device.ancestors()
+            .find_map(|dir| fs::read_to_string(dir.join("numa_node")).ok())?
+            .trim()
+            .parse::<i64>()
+            .ok()?;
+        // 单节点机器上为 -1
+        let node = usize::try_from(node).ok()?;
+        let cpus = parse_cpu_list(&fs::read_to_string(format!("/sys/devices/system/node/node{}/cpulist", node)).ok()?)?;
+        (!cpus.is_empty()).then_some(NumaNode { node, cpus })
+    }
+}
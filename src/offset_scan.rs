@@ -0,0 +1,50 @@
+// offset_scan.rs - 扫描候选的条形码位置（--scan-bc-offset）
+
+use crate::pipeline::BarcodeWindow;
+use crate::whitelist::Whitelist;
+use std::ops::RangeInclusive;
+
+/// 一个候选窗口的白名单命中情况
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffsetScanRow {
+    pub window: BarcodeWindow,
+    /// 长度足以覆盖该窗口的 read 数
+    pub reads: u64,
+    /// 窗口中的序列与白名单完全匹配的 read 数
+    pub hits: u64,
+}
+
+impl OffsetScanRow {
+    pub fn hit_rate(&self) -> f64 {
+        if self.reads == 0 { 0.0 } else { self.hits as f64 / self.reads as f64 }
+    }
+}
+
+/// 对 `offsets` 中的每个位置，分别按正向与反向互补统计抽样 R2 的白名单命中率
+///
+/// 结果按位置排序，同一位置先反向互补（默认布局）后正向。
+pub fn scan_barcode_offsets(r2_seqs: &[Vec<u8>], whitelist: &Whitelist, offsets: RangeInclusive<usize>) -> Vec<OffsetScanRow> {
+    let mut rows = Vec::new();
+    for offset in offsets {
+        for rc in [true, false] {
+            let window = BarcodeWindow { offset, reverse_complement: rc };
+            let mut row = OffsetScanRow { window, reads: 0, hits: 0 };
+            for bc in r2_seqs.iter().filter_map(|seq| window.extract(seq)) {
+                row.reads += 1;
+                row.hits += whitelist.contains(&bc) as u64;
+            }
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+/// 命中率最高的窗口；并列时取靠前的一个，没有任何命中时为 `None`
+pub fn best_window(rows: &[OffsetScanRow]) -> Option<&OffsetScanRow> {
+    rows.iter()
+        .filter(|row| row.hits > 0)
+        .fold(None, |best: Option<&OffsetScanRow>, row| match best {
+            Some(b) if b.hit_rate() >= row.hit_rate() => Some(b),
+            _ => Some(row),
+        })
+}
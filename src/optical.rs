@@ -0,0 +1,99 @@
+// optical.rs - 按 tile 内 x/y 坐标识别光学（ExAmp）重复
+
+use std::collections::{HashMap, VecDeque};
+
+/// 同时保留坐标的 tile 数；FASTQ 基本按 tile 顺序排列，更早的 tile 被淘汰
+pub const MAX_OPEN_TILES: usize = 8;
+
+/// 单个 tile 最多保留的坐标数；超过时清空该 tile，之后的重复只与清空后的 read 比较
+pub const MAX_TILE_READS: usize = 2_000_000;
+
+/// 对光学重复的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OpticalDupPolicy {
+    /// 只计数，照常输出
+    #[default]
+    Count,
+    /// 按 [`FilterReason::OpticalDuplicate`](crate::progress::FilterReason::OpticalDuplicate) 过滤
+    Drop,
+}
+
+#[derive(Default)]
+struct TileState {
+    /// 条形码与片段开头 → 已见过的 read 坐标
+    groups: HashMap<Vec<u8>, Vec<(u32, u32)>>,
+    reads: usize,
+}
+
+/// 光学重复检测器
+///
+/// 同一 (lane, tile) 内条形码与片段开头都相同、坐标距离不超过 `max_distance` 像素的
+/// 后来的 read 记为光学重复。只在一个线程中使用；内存由 [`MAX_OPEN_TILES`] 与
+/// [`MAX_TILE_READS`] 限制。
+pub struct OpticalDupDetector {
+    max_distance_sq: u64,
+    tiles: HashMap<(u32, u32), TileState>,
+    /// 最近使用的 tile 在最后
+    order: VecDeque<(u32, u32)>,
+    examined: u64,
+    duplicates: u64,
+}
+
+impl OpticalDupDetector {
+    pub fn new(max_distance: u32) -> Self {
+        OpticalDupDetector {
+            max_distance_sq: u64::from(max_distance).pow(2),
+            tiles: HashMap::new(),
+            order: VecDeque::new(),
+            examined: 0,
+            duplicates: 0,
+        }
+    }
+
+    /// 检查一个 read；`key` 为条形码与片段开头。是光学重复时返回 true
+    pub fn check(&mut self, lane: u32, tile: u32, x: u32, y: u32, key: &[u8]) -> bool {
+        self.examined += 1;
+        let tile_key = (lane, tile);
+        if self.order.back() != Some(&tile_key) {
+            if let Some(pos) = self.order.iter().position(|t| *t == tile_key) {
+                self.order.remove(pos);
+            } else if self.order.len() == MAX_OPEN_TILES {
+                let evicted = self.order.pop_front().unwrap();
+                self.tiles.remove(&evicted);
+            }
+            self.order.push_back(tile_key);
+        }
+        let state = self.tiles.entry(tile_key).or_default();
+        if state.reads >= MAX_TILE_READS {
+            *state = TileState::default();
+        }
+
+        let max_distance_sq = self.max_distance_sq;
+        let near = |&(x0, y0): &(u32, u32)| {
+            let (dx, dy) = (u64::from(x.abs_diff(x0)), u64::from(y.abs_diff(y0)));
+            dx * dx + dy * dy <= max_distance_sq
+        };
+        match state.groups.get_mut(key) {
+            Some(seen) if seen.iter().any(near) => {
+                self.duplicates += 1;
+                return true;
+            }
+            Some(seen) => seen.push((x, y)),
+            None => {
+                state.groups.insert(key.to_vec(), vec![(x, y)]);
+            }
+        }
+        state.reads += 1;
+        false
+    }
+
+    /// 检查过的 read 数
+    pub fn examined(&self) -> u64 {
+        self.examined
+    }
+
+    /// 其中的光学重复数
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
@@ -0,0 +1,114 @@
+// overrepresented.rs - 抽样统计基因组输出中的过表达序列（--overrepresented-every）
+
+use crate::stats::{OverrepresentedSequence, OverrepresentedStats};
+use std::collections::HashMap;
+
+/// 按 read 开头的这么多碱基区分序列，与 FastQC 相同
+pub const PREFIX_LEN: usize = 50;
+
+/// 同时跟踪的不同序列数上限；超过时淘汰计数最少的一半
+pub const MAX_TRACKED: usize = 100_000;
+
+/// 默认报告阈值：占抽样 read 的比例，与 FastQC 相同
+pub const DEFAULT_MIN_FRACTION: f64 = 0.001;
+
+/// 抽样方式与报告阈值
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverrepresentedConfig {
+    /// 每隔多少条 read 抽一条
+    pub every: u64,
+    /// 计数超过抽样 read 数的这个比例时报告
+    pub min_fraction: f64,
+}
+
+impl Default for OverrepresentedConfig {
+    fn default() -> Self {
+        OverrepresentedConfig { every: 100, min_fraction: DEFAULT_MIN_FRACTION }
+    }
+}
+
+/// 过表达序列计数器
+///
+/// 每 `every` 条 read 抽一条，按前 [`PREFIX_LEN`] bp 精确计数。跟踪的序列数达到 [`MAX_TRACKED`]
+/// 时淘汰计数最少的一半，因此被淘汰过又重新出现的序列的计数偏低；真正过表达的序列计数高，
+/// 不会被淘汰。只在一个线程中使用。
+pub struct OverrepresentedCounter {
+    config: OverrepresentedConfig,
+    max_tracked: usize,
+    seen: u64,
+    sampled: u64,
+    counts: HashMap<Vec<u8>, u64>,
+    evicted: u64,
+}
+
+impl OverrepresentedCounter {
+    pub fn new(config: OverrepresentedConfig) -> Self {
+        Self::with_max_tracked(config, MAX_TRACKED)
+    }
+
+    /// 指定跟踪的序列数上限
+    pub fn with_max_tracked(config: OverrepresentedConfig, max_tracked: usize) -> Self {
+        OverrepresentedCounter {
+            config: OverrepresentedConfig { every: config.every.max(1), ..config },
+            max_tracked: max_tracked.max(2),
+            seen: 0,
+            sampled: 0,
+            counts: HashMap::new(),
+            evicted: 0,
+        }
+    }
+
+    /// 记录一条 read；只有被抽中的才计数
+    pub fn add(&mut self, seq: &[u8]) {
+        self.seen += 1;
+        if !self.seen.is_multiple_of(self.config.every) {
+            return;
+        }
+        self.sampled += 1;
+        let key = &seq[..seq.len().min(PREFIX_LEN)];
+        if let Some(count) = self.counts.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        if self.counts.len() >= self.max_tracked {
+            self.evict();
+        }
+        self.counts.insert(key.to_vec(), 1);
+    }
+
+    /// 去掉计数不超过中位数的序列
+    fn evict(&mut self) {
+        let mut counts: Vec<u64> = self.counts.values().copied().collect();
+        let mid = counts.len() / 2;
+        let threshold = *counts.select_nth_unstable(mid).1;
+        let before = self.counts.len();
+        self.counts.retain(|_, count| *count > threshold);
+        self.evicted += (before - self.counts.len()) as u64;
+    }
+
+    /// 抽中的 read 数
+    pub fn sampled(&self) -> u64 {
+        self.sampled
+    }
+
+    /// 计数超过阈值的序列，按计数从高到低排列
+    pub fn to_stats(&self) -> OverrepresentedStats {
+        let min_count = self.config.min_fraction * self.sampled as f64;
+        let mut sequences: Vec<OverrepresentedSequence> = self.counts.iter()
+            .filter(|(_, &count)| count as f64 > min_count)
+            .map(|(seq, &count)| OverrepresentedSequence {
+                sequence: String::from_utf8_lossy(seq).into_owned(),
+                count,
+                fraction: count as f64 / self.sampled as f64,
+            })
+            .collect();
+        sequences.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.sequence.cmp(&b.sequence)));
+        OverrepresentedStats {
+            sample_every: self.config.every,
+            sampled: self.sampled,
+            min_fraction: self.config.min_fraction,
+            evicted: self.evicted,
+            sequences,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// parallel_gz.rs - 多线程 gzip 压缩写入
+
+use crate::FinishWrite;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::thread;
+
+/// 每个压缩任务的输入大小
+const CHUNK_SIZE: usize = 1 << 20;
+
+type ChunkJob = (Vec<u8>, Sender<io::Result<Vec<u8>>>);
+
+/// 多线程 gzip 写入器
+///
+/// 输入按 1 MiB 切块，每块由线程池独立压缩成一个 gzip member，再按顺序写入
+/// `inner`。多 member 的 gzip 是合法格式，`zcat`、`MultiGzDecoder` 都能直接读取。
+/// 调用 `flush()` 会等待所有在途块写完；写完后调用 [`finish`](ParallelGzWriter::finish)
+/// 收尾。drop 时也会尽量收尾，但错误只能通过 `finish()` 拿到。
+pub struct ParallelGzWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+    jobs: Option<Sender<ChunkJob>>,
+    pending: VecDeque<Receiver<io::Result<Vec<u8>>>>,
+    max_pending: usize,
+    level: Compression,
+    /// 是否已经向 `inner` 写过 gzip member
+    started: bool,
+}
+
+impl<W: Write> ParallelGzWriter<W> {
+    pub fn new(inner: W, threads: usize, level: Compression) -> Self {
+        let threads = threads.max(1);
+        let (job_tx, job_rx): (Sender<ChunkJob>, Receiver<ChunkJob>) = bounded(threads * 2);
+        for _ in 0..threads {
+            let job_rx = job_rx.clone();
+            thread::spawn(move || {
+                while let Ok((chunk, result_tx)) = job_rx.recv() {
+                    let mut enc = GzEncoder::new(Vec::with_capacity(chunk.len() / 2), level);
+                    let result = enc.write_all(&chunk).and_then(|_| enc.finish());
+                    let _ = result_tx.send(result);
+                }
+            });
+        }
+        ParallelGzWriter {
+            inner,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+            jobs: Some(job_tx),
+            pending: VecDeque::new(),
+            max_pending: threads * 2,
+            level,
+            started: false,
+        }
+    }
+
+    /// 写出剩余的块并关闭压缩线程；之后不能再写入
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.jobs.take();
+        Ok(())
+    }
+
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE));
+        let (result_tx, result_rx) = bounded(1);
+        self.jobs
+            .as_ref()
+            .expect("writer already finished")
+            .send((chunk, result_tx))
+            .map_err(|_| io::Error::other("gzip compression workers exited"))?;
+        self.pending.push_back(result_rx);
+        while self.pending.len() > self.max_pending {
+            self.write_next()?;
+        }
+        Ok(())
+    }
+
+    fn write_next(&mut self) -> io::Result<()> {
+        if let Some(result_rx) = self.pending.pop_front() {
+            let compressed = result_rx
+                .recv()
+                .map_err(|_| io::Error::other("gzip compression worker exited"))??;
+            self.inner.write_all(&compressed)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ParallelGzWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == CHUNK_SIZE {
+            self.submit()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()?;
+        while !self.pending.is_empty() {
+            self.write_next()?;
+        }
+        if !self.started {
+            // 没有任何数据时也要输出一个合法的（空）gzip 文件
+            self.inner.write_all(&GzEncoder::new(Vec::new(), self.level).finish()?)?;
+            self.started = true;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Send> FinishWrite for ParallelGzWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        ParallelGzWriter::finish(self)
+    }
+}
+
+impl<W: Write> Drop for ParallelGzWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        // 关闭任务通道，让压缩线程退出
+        self.jobs.take();
+    }
+}
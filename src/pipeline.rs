@@ -0,0 +1,1638 @@
+// pipeline.rs - 读取 → 处理 → 分发 → 写入的多线程流水线
+//
+// 读取线程把 read 对攒成 batch，多个处理线程并行拆分，分发线程按输出文件
+// 重新分组，每个输出文件各有一个写入线程。
+
+use crate::adaptive_batch::{AdaptiveBatchSize, BatchTarget};
+use crate::affinity;
+use crate::anchor::Anchor;
+use crate::barcode_counts::{BarcodeCounter, MinReadsFilter};
+use crate::barcode_select::BarcodeSelection;
+use crate::groups::{prefix_shard, prefix_shard_names, BarcodeGroups, GroupWriters, MAX_SHARD_PREFIX};
+use crate::fastq_io::{empty_record, read_fastq_record};
+use crate::header::parse_illumina_header;
+use crate::hll::HyperLogLog;
+use crate::lanes::{lane_group, lane_group_name, LaneTally};
+use crate::low_complexity::trinucleotide_entropy;
+use crate::contaminants::ContaminantScreen;
+use crate::duplicate_names::DuplicateNames;
+use crate::failure::{Classify, Failure};
+use crate::optical::{OpticalDupDetector, OpticalDupPolicy};
+use crate::overrepresented::{OverrepresentedConfig, OverrepresentedCounter};
+use crate::progress::{FilterReason, PipelineCounters};
+use crate::qc::BaseComposition;
+use crate::read_names::ReadNameList;
+use crate::readthrough::{has_me_readthrough, ReadthroughPolicy};
+use crate::record_pool::RecordPool;
+use crate::stats::{
+    AnchorStats, BarcodeCorrectionStats, BarcodeMinReadsStats, BarcodeSelectionStats, DistinctBarcodes, LibraryComplexity,
+    OpticalDuplicateStats, ReadthroughStats, ReadNameStats, RunStats, StageTimings, WorkerWallMs,
+};
+use crate::tiles::{TileExclusion, TileTally, WORST_TILES};
+use crate::whitelist::{correct_barcode, correct_barcode_indel, correct_truncated_barcode, CorrectionStats, Whitelist};
+use crate::{extract_base_header, header_comment, read_name, reverse_complement, reverse_complement_in_place, FastqWriter,
+            FinishWrite, WorkerStats};
+use anyhow::{Context, Result};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use fastq::{each_zipped, OwnedRecord, Parser, Record};
+use parking_lot::Mutex;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 各阶段之间 channel 的容量（以 batch 计）
+pub const CHANNEL_DEPTH: usize = 50;
+
+/// 输入 R2 的长度与其中条形码的长度
+pub const R2_LEN: usize = 166;
+pub const BARCODE_LEN: usize = 16;
+/// 估计写入缓冲区大小时假定的 R1 长度（R1 长度事先未知）
+const EXPECTED_R1_LEN: usize = 50;
+/// 一条输出记录中序列与质量值之外的字节数估计：header、'+' 行与换行
+const RECORD_OVERHEAD: usize = 64;
+/// 区分片段时使用的 R1、R3 开头的碱基数（见 [`LibraryComplexity`]）
+const FRAGMENT_PREFIX_LEN: usize = 20;
+
+/// 条形码在 R2 中的位置与方向；R2 中条形码之前的部分作为 R3 输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarcodeWindow {
+    /// 条形码第一个碱基在 R2 中的位置（从 0 开始）
+    pub offset: usize,
+    /// 条形码在 R2 中是反向互补的（输出前反向互补，质量值倒序）
+    pub reverse_complement: bool,
+}
+
+impl Default for BarcodeWindow {
+    /// 默认布局：150 bp 基因组片段 + 16 bp 反向互补的条形码
+    fn default() -> Self {
+        BarcodeWindow { offset: R2_LEN - BARCODE_LEN, reverse_complement: true }
+    }
+}
+
+/// R3 在 R2 中的范围：默认为条形码之前的全部碱基
+///
+/// 要求 `start < end <= window.offset`，即 R3 非空且不与条形码重叠。
+pub fn r3_range(window: BarcodeWindow, start: usize, end: Option<usize>) -> Result<Range<usize>> {
+    let end = end.unwrap_or(window.offset);
+    anyhow::ensure!(start < end && end <= window.offset,
+                    "R3 region {}..{} must be non-empty and end at or before the barcode at offset {}",
+                    start, end, window.offset);
+    Ok(start..end)
+}
+
+impl BarcodeWindow {
+    /// 按输出方向截取 R2 序列中的条形码；序列太短时为 `None`
+    pub fn extract(&self, r2_seq: &[u8]) -> Option<Vec<u8>> {
+        let bc = r2_seq.get(self.offset..self.offset + BARCODE_LEN)?;
+        Some(if self.reverse_complement { reverse_complement(bc) } else { bc.to_ascii_uppercase() })
+    }
+}
+
+/// 流水线的处理参数
+pub struct PipelineConfig {
+    /// 条形码白名单；为 `None` 时不做纠错
+    pub whitelist: Option<Whitelist>,
+    /// 纠错前把质量值低于该值的条形码碱基屏蔽为 N
+    pub bc_mask_qual: Option<u8>,
+    /// 允许 R2 末端缺少的碱基数：缺少的条形码碱基按剩余部分在白名单中唯一查找补全（需要白名单）
+    pub allow_truncated_bc: usize,
+    /// 替换纠错失败的条形码再尝试单个插入/缺失纠错（需要白名单）
+    pub correct_indels: bool,
+    /// 只保留 read 名在列表中的 pair，其余按 [`FilterReason::NotListed`] 过滤
+    pub read_names: Option<ReadNameList>,
+    /// 丢弃来自这些 tile 的 pair，按 [`FilterReason::ExcludedTile`] 过滤
+    pub exclude_tiles: Option<TileExclusion>,
+    /// 只把（纠错后）条形码在列表中的 pair 写入主输出；其余写入
+    /// [`PipelineWriters::unselected`]，没有该输出时按 [`FilterReason::NotSelected`] 过滤
+    pub select_barcodes: Option<BarcodeSelection>,
+    /// 去掉（纠错后）条形码总 read 数不足的 pair：有 [`PipelineWriters::unselected`] 时写入其中，
+    /// 否则按 [`FilterReason::TooFewReads`] 过滤
+    pub min_reads_per_barcode: Option<MinReadsFilter>,
+    /// 按条形码所属的组写入 [`PipelineWriters::groups`]，而不是 R1/R2/R3
+    pub groups: Option<BarcodeGroups>,
+    /// 按 R1 header 中的 lane 写入 [`PipelineWriters::groups`]，组名见 [`lane_group_name`]；不能与 `groups` 同时使用
+    pub split_lanes: bool,
+    /// 按（纠错后）条形码的前 K 个碱基分片写入 [`PipelineWriters::groups`]，组名见 [`prefix_shard_names`]；
+    /// K 为 1 到 [`MAX_SHARD_PREFIX`]，不能与 `groups`、`split_lanes` 同时使用
+    pub shard_by_bc_prefix: Option<usize>,
+    /// 条形码在 R2 中的位置
+    pub barcode_window: BarcodeWindow,
+    /// 按锚定序列定位条形码：其前为 R3，其后为条形码，找不到时按 [`FilterReason::AnchorNotFound`] 过滤；
+    /// 设置时不使用 `barcode_window.offset` 与 R3 区间
+    pub anchor: Option<Anchor>,
+    /// 条形码窗口中有接头通读的 pair 的处理方式
+    pub readthrough: ReadthroughPolicy,
+    /// R3 的三核苷酸熵（见 [`trinucleotide_entropy`]）低于该值时按 [`FilterReason::LowComplexity`] 过滤
+    pub min_complexity: Option<f64>,
+    /// 把同一 tile 内坐标距离不超过该值（像素）的相同条形码与片段记为光学重复
+    pub mark_optical_dups: Option<u32>,
+    /// 光学重复的处理方式
+    pub optical_dup_policy: OpticalDupPolicy,
+    /// 抽样统计写出的基因组 read 中的过表达序列，结果放在 [`RunStats::overrepresented`]
+    pub overrepresented: Option<OverrepresentedConfig>,
+    /// 抽样筛查写出的基因组 read 中的已知污染序列，结果放在 [`RunStats::contaminants`]
+    pub contaminants: Option<ContaminantScreen>,
+    /// 抽样估计输入中重复出现的 read 名比例（全部输入 pair，包括被过滤的），结果放在 [`RunStats::duplicate_names`]
+    pub check_duplicate_names: bool,
+    /// 把写出的 pair 的名字依次改为 `PREFIX:1`、`PREFIX:2`……（按写出顺序，R1/R2/R3 相同）
+    pub rename_reads: Option<String>,
+    /// 被过滤的 pair 照样写出，header 后加 `\tFT:Z:<原因>`（仍按原因计入过滤数），见 [`FilterReason::label`]
+    pub soft_filter: bool,
+    /// 保留输入 '+' 行后面的内容：R1 输出沿用 R1 的，R2、R3（以及原始条形码）沿用 R2 的；
+    /// 需要用 [`read_fastq_pairs_with_plus_lines`] 读取（[`run_pipeline`] 会自动选择）
+    pub preserve_plus_line: bool,
+    /// 输出 header 保留输入的注释（如 Casava 的 `1:N:0:ACGT`）：R1 输出沿用 R1 的，R2、R3 输出沿用 R2 的；
+    /// 此时 R1/R2 只比较注释之前的 read 名
+    pub keep_header_comments: bool,
+    /// R3 取 R2 的 `r3_start..r3_end`；`r3_end` 为 `None` 时到条形码之前为止，见 [`r3_range`]
+    pub r3_start: usize,
+    pub r3_end: Option<usize>,
+    /// 输出 R3 的反向互补（质量值倒序）
+    pub reverse_r3: bool,
+    /// 条形码写入 R3、基因组片段写入 R2
+    pub swap_r2_r3: bool,
+    /// 只读取前 N 个 pair，之后读取端停止、不再读取其余输入；只对流水线自己读取的输入有效
+    pub head: Option<usize>,
+    /// 每个 batch 的 read 对数
+    pub batch_size: usize,
+    /// 按处理耗时动态调整 batch 大小，`batch_size` 为初始值；只对流水线自己读取的输入有效
+    pub adaptive_batch: Option<BatchTarget>,
+    /// 写入线程先把整个 batch 格式化到按 `batch_size` 与预计记录长度预分配的缓冲区，再一次写出
+    /// （分组输出不使用）
+    pub preallocate_writer_buffer: bool,
+    /// 读取端从池中取记录，写入线程写完后放回，减少每条 read 的分配；只对流水线自己读取的输入有效（分组输出不放回）
+    pub record_pool: Option<Arc<RecordPool>>,
+    /// 处理线程数
+    pub threads: usize,
+    /// 把处理线程绑定到这些 CPU
+    pub worker_cpus: Option<Vec<usize>>,
+    /// 把读取与写入线程绑定到这些 CPU（见 [`crate::numa`]）
+    pub io_cpus: Option<Vec<usize>>,
+    /// 单个 batch 的处理时间上限；超过时认为处理线程卡住，流水线以错误结束
+    pub batch_timeout: Option<Duration>,
+    /// 统计每个条形码的 read 数，结果放在 [`RunStats::barcode_counts`]
+    pub count_barcodes: bool,
+    /// 流水线更新的计数器；进度显示、指标输出可共享同一组
+    pub counters: Arc<PipelineCounters>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            whitelist: None,
+            bc_mask_qual: None,
+            allow_truncated_bc: 0,
+            correct_indels: false,
+            read_names: None,
+            exclude_tiles: None,
+            select_barcodes: None,
+            min_reads_per_barcode: None,
+            groups: None,
+            split_lanes: false,
+            shard_by_bc_prefix: None,
+            barcode_window: BarcodeWindow::default(),
+            anchor: None,
+            min_complexity: None,
+            readthrough: ReadthroughPolicy::Off,
+            mark_optical_dups: None,
+            optical_dup_policy: OpticalDupPolicy::Count,
+            overrepresented: None,
+            contaminants: None,
+            check_duplicate_names: false,
+            rename_reads: None,
+            soft_filter: false,
+            preserve_plus_line: false,
+            keep_header_comments: false,
+            r3_start: 0,
+            r3_end: None,
+            reverse_r3: false,
+            swap_r2_r3: false,
+            head: None,
+            batch_size: 200_000,
+            adaptive_batch: None,
+            preallocate_writer_buffer: false,
+            record_pool: None,
+            threads: thread::available_parallelism().map(|n| (n.get() / 2).max(1)).unwrap_or(2),
+            worker_cpus: None,
+            io_cpus: None,
+            batch_timeout: None,
+            count_barcodes: false,
+            counters: Arc::new(PipelineCounters::default()),
+        }
+    }
+}
+
+/// 流水线的输出：R1、R2（条形码）、R3（基因组片段）各一个写入端
+///
+/// 输出格式由写入端决定（文件、gzip、对象存储或内存），流水线只负责写 FASTQ 文本，
+/// 写完后调用 [`FinishWrite::finish`] 收尾，任何一个输出收尾失败都会让流水线报错。
+pub struct PipelineWriters {
+    pub r1: Box<dyn FinishWrite>,
+    /// 条形码
+    pub r2: Box<dyn FinishWrite>,
+    /// 基因组片段
+    pub r3: Box<dyn FinishWrite>,
+    /// 纠错前的原始条形码（只在有白名单时写出）
+    pub raw_barcode: Option<Box<dyn FinishWrite>>,
+    /// 条形码未被 `select_barcodes` 选中或 read 数不足的 pair 的 R1、R2、R3
+    pub unselected: Option<[Box<dyn FinishWrite>; 3]>,
+    /// 按组输出；与 [`PipelineConfig::groups`] 同时给出
+    pub groups: Option<GroupWriters>,
+    /// 改名时原名与新名的对照表（TSV）；与 [`PipelineConfig::rename_reads`] 同时给出
+    pub read_name_map: Option<Box<dyn FinishWrite>>,
+}
+
+impl PipelineWriters {
+    pub fn new(
+        r1: impl FinishWrite + 'static,
+        r2: impl FinishWrite + 'static,
+        r3: impl FinishWrite + 'static,
+    ) -> Self {
+        PipelineWriters {
+            r1: Box::new(r1), r2: Box::new(r2), r3: Box::new(r3),
+            raw_barcode: None, unselected: None, groups: None, read_name_map: None,
+        }
+    }
+
+    /// 另外写出纠错前的原始条形码
+    pub fn with_raw_barcode(mut self, writer: impl FinishWrite + 'static) -> Self {
+        self.raw_barcode = Some(Box::new(writer));
+        self
+    }
+
+    /// 另外写出未被选中的 pair，而不是丢弃
+    pub fn with_unselected(
+        mut self,
+        r1: impl FinishWrite + 'static,
+        r2: impl FinishWrite + 'static,
+        r3: impl FinishWrite + 'static,
+    ) -> Self {
+        self.unselected = Some([Box::new(r1), Box::new(r2), Box::new(r3)]);
+        self
+    }
+
+    /// 按组写出（选中的）pair，R1/R2/R3 不再收到记录
+    pub fn with_groups(mut self, groups: GroupWriters) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    /// 改名时另外写出 `原名<TAB>新名` 对照表
+    pub fn with_read_name_map(mut self, writer: impl FinishWrite + 'static) -> Self {
+        self.read_name_map = Some(Box::new(writer));
+        self
+    }
+}
+
+/// 可在多个所有者之间共享的内存缓冲区
+///
+/// 流水线结束后写入端已被 drop，用保留的克隆读取写入的内容。
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        SharedBuffer::default()
+    }
+
+    /// 目前为止写入的全部字节
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().clone()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FinishWrite for SharedBuffer {}
+
+/// 一个 batch 的 R1 与 R2 记录，按下标配对
+pub type RecordBatch = (Vec<OwnedRecord>, Vec<OwnedRecord>);
+/// 读取线程的结果：每个 lane 的 pair 数、R1 与 R2 的字节数、耗时（毫秒）
+type ReaderOutcome = (Vec<u64>, [u64; 2], u64);
+/// 组序号与该 pair 的 R1、R2、R3
+type GroupedPair = (usize, [OwnedRecord; 3]);
+/// 分发线程的结果：耗时（毫秒），以及按写出顺序检查 read 的各个统计
+type DistributorOutcome = (u64, Option<OpticalDupDetector>, Option<OverrepresentedCounter>, Option<ContaminantScreen>);
+
+/// 处理线程已停止、读取端无法再发送时的错误；此时失败的原因在下游，读取端只是随之停止
+#[derive(Debug)]
+struct PipelineStopped;
+
+impl std::fmt::Display for PipelineStopped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Processing threads stopped before all reads were sent")
+    }
+}
+
+impl std::error::Error for PipelineStopped {}
+
+/// 读取端已发出 [`PipelineConfig::head`] 个 pair；读取函数收到后停止读取并返回它，流水线按正常结束处理
+#[derive(Debug)]
+struct HeadReached;
+
+impl std::fmt::Display for HeadReached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Read the requested number of pairs")
+    }
+}
+
+impl std::error::Error for HeadReached {}
+
+/// 读取端：把 read 对攒成 batch 发给处理线程，并按 lane 计数
+pub struct PairSender {
+    tx: Sender<RecordBatch>,
+    counters: Arc<PipelineCounters>,
+    batch_len: usize,
+    /// 设置时 batch 大小取其当前值，而不是 `batch_len`
+    adaptive: Option<Arc<AdaptiveBatchSize>>,
+    /// 见 [`PipelineConfig::record_pool`]
+    pool: Option<Arc<RecordPool>>,
+    r1_batch: Vec<OwnedRecord>,
+    r2_batch: Vec<OwnedRecord>,
+    lane_pairs: Vec<u64>,
+    current_lane: u64,
+    /// 已发出的 R1、R2 记录的字节数（header、序列、质量值）
+    record_bytes: [u64; 2],
+    /// 还能接收的 pair 数，见 [`PipelineConfig::head`]
+    remaining: Option<usize>,
+}
+
+impl PairSender {
+    fn new(
+        tx: Sender<RecordBatch>,
+        counters: Arc<PipelineCounters>,
+        batch_len: usize,
+        adaptive: Option<Arc<AdaptiveBatchSize>>,
+        pool: Option<Arc<RecordPool>>,
+        head: Option<usize>,
+    ) -> Self {
+        PairSender {
+            tx,
+            counters,
+            batch_len,
+            adaptive,
+            pool,
+            r1_batch: Vec::with_capacity(batch_len),
+            r2_batch: Vec::with_capacity(batch_len),
+            lane_pairs: Vec::new(),
+            current_lane: 0,
+            record_bytes: [0; 2],
+            remaining: head,
+        }
+    }
+
+    /// 加入一个 pair；达到 [`PipelineConfig::head`] 后返回错误，读取函数应随之返回该错误
+    pub fn push(&mut self, r1: OwnedRecord, r2: OwnedRecord) -> Result<()> {
+        if self.remaining == Some(0) {
+            return Err(HeadReached.into());
+        }
+        self.r1_batch.push(r1);
+        self.r2_batch.push(r2);
+        self.current_lane += 1;
+        // 满了就发
+        let batch_len = self.adaptive.as_ref().map_or(self.batch_len, |a| a.get());
+        if self.r1_batch.len() >= batch_len {
+            self.send()?;
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+            if *remaining == 0 {
+                return Err(HeadReached.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// 一条空记录，设置了 [`PipelineConfig::record_pool`] 时从池中取
+    pub fn empty_record(&self) -> OwnedRecord {
+        self.pool.as_ref().map_or_else(empty_record, |pool| pool.checkout().into_inner())
+    }
+
+    /// 复制解析出的记录；同样优先复用池中的记录
+    pub fn owned_record<R: Record>(&self, record: &R) -> OwnedRecord {
+        let Some(pool) = &self.pool else {
+            return record.to_owned_record();
+        };
+        let mut owned = pool.checkout().into_inner();
+        owned.head.extend_from_slice(record.head());
+        owned.seq.extend_from_slice(record.seq());
+        owned.qual.extend_from_slice(record.qual());
+        owned
+    }
+
+    /// 记录一条在另一个文件中没有对应记录的 read，不发给处理线程
+    pub fn unpaired(&mut self, record: &OwnedRecord) {
+        self.counters.record_unpaired(&record.head);
+    }
+
+    /// 结束当前 lane；batch 可以跨 lane
+    pub fn end_lane(&mut self) {
+        self.lane_pairs.push(std::mem::take(&mut self.current_lane));
+    }
+
+    fn send(&mut self) -> Result<()> {
+        self.counters.pairs_read.fetch_add(self.r1_batch.len() as u64, Ordering::Relaxed);
+        for (bytes, batch) in self.record_bytes.iter_mut().zip([&self.r1_batch, &self.r2_batch]) {
+            *bytes += record_bytes(batch);
+        }
+        let wait = Instant::now();
+        self.tx.send((self.r1_batch.split_off(0), self.r2_batch.split_off(0)))
+            .map_err(|_| PipelineStopped)?;
+        PipelineCounters::add_wait(&self.counters.reader_send_wait_ns, wait);
+        Ok(())
+    }
+
+    /// 发出最后一个不满的 batch，返回每个 lane 的 pair 数与 R1、R2 的字节数
+    fn finish(mut self) -> Result<(Vec<u64>, [u64; 2])> {
+        if !self.r1_batch.is_empty() {
+            self.send()?;
+        }
+        Ok((self.lane_pairs, self.record_bytes))
+    }
+}
+
+/// 记录的字节数（header、序列、质量值）
+fn record_bytes(batch: &[OwnedRecord]) -> u64 {
+    batch.iter().map(|r| (r.head.len() + r.seq.len() + r.qual.len()) as u64).sum()
+}
+
+/// 成对读取一个 lane 的 R1/R2 FASTQ；一个文件先结束时，另一个文件多出的记录记为没有配对
+pub fn read_fastq_pairs<R1: Read, R2: Read>(sender: &mut PairSender, r1: R1, r2: R2) -> Result<()> {
+    let mut error = None;
+    let mut pairs = 0u64;
+    // fastq‑rs 原生的"成对遍历"——每回调一次就是一对 read
+    each_zipped(Parser::new(r1), Parser::new(r2), |opt1, opt2| {
+        match (opt1, opt2) {
+            (Some(r1), Some(r2)) => match sender.push(sender.owned_record(&r1), sender.owned_record(&r2)) {
+                Ok(()) => {
+                    pairs += 1;
+                    (true, true)
+                }
+                Err(e) => {
+                    error = Some(e);
+                    (false, false)
+                }
+            },
+            // 文件长度不一致：读完较长的文件，多出的记录只计数
+            (Some(record), None) | (None, Some(record)) => {
+                sender.unpaired(&record.to_owned_record());
+                (true, true)
+            }
+            (None, None) => (false, false),
+        }
+    }).with_context(|| format!("Failed at record pair {}", pairs + 1))?;
+    sender.end_lane();
+    error.map_or(Ok(()), Err)
+}
+
+/// 与 [`read_fastq_pairs`] 相同，但保留 '+' 行后面的内容（放在 `sep` 中），用于 [`PipelineConfig::preserve_plus_line`]
+pub fn read_fastq_pairs_with_plus_lines<R1: Read, R2: Read>(sender: &mut PairSender, r1: R1, r2: R2) -> Result<()> {
+    let mut r1 = io::BufReader::with_capacity(1 << 20, r1);
+    let mut r2 = io::BufReader::with_capacity(1 << 20, r2);
+    for pair in 1u64.. {
+        let (mut record1, mut record2) = (sender.empty_record(), sender.empty_record());
+        let has_r1 = read_fastq_record(&mut r1, &mut record1).with_context(|| format!("Failed to read R1 record {}", pair))?;
+        let has_r2 = read_fastq_record(&mut r2, &mut record2).with_context(|| format!("Failed to read R2 record {}", pair))?;
+        // 一个文件先结束时读完另一个，多出的记录只计数
+        match (has_r1, has_r2) {
+            (true, true) => sender.push(record1, record2)?,
+            (true, false) => sender.unpaired(&record1),
+            (false, true) => sender.unpaired(&record2),
+            (false, false) => break,
+        }
+    }
+    sender.end_lane();
+    Ok(())
+}
+
+/// 处理线程共享的只读配置
+struct ProcessorConfig {
+    whitelist: Option<Whitelist>,
+    bc_mask_qual: Option<u8>,
+    allow_truncated_bc: usize,
+    correct_indels: bool,
+    read_names: Option<ReadNameList>,
+    exclude_tiles: Option<TileExclusion>,
+    select_barcodes: Option<BarcodeSelection>,
+    min_reads_per_barcode: Option<MinReadsFilter>,
+    groups: Option<BarcodeGroups>,
+    split_lanes: bool,
+    shard_by_bc_prefix: Option<usize>,
+    barcode_window: BarcodeWindow,
+    anchor: Option<Anchor>,
+    readthrough: ReadthroughPolicy,
+    min_complexity: Option<f64>,
+    check_duplicate_names: bool,
+    soft_filter: bool,
+    preserve_plus_line: bool,
+    keep_header_comments: bool,
+    /// R3 在 R2 中的范围（按锚定序列定位时不使用）
+    r3_range: Range<usize>,
+    reverse_r3: bool,
+    /// 未选中或 read 数不足的 pair 是否另行写出
+    keep_unselected: bool,
+    keep_raw_barcode: bool,
+}
+
+struct ProcessedRecord {
+    r1_out: OwnedRecord,
+    r2_out: OwnedRecord,
+    r3_out: OwnedRecord,
+    /// 纠错前的原始条形码（仅 keep_raw_barcode 时存在）
+    raw_bc_out: Option<OwnedRecord>,
+    /// 条形码是否被白名单纠错改写
+    bc_corrected: bool,
+    /// 条形码是否被 `select_barcodes` 选中（未启用时总是 true）
+    selected: bool,
+    /// 条形码的总 read 数是否低于 `min_reads_per_barcode`
+    rare: bool,
+    /// 条形码窗口中是否有接头通读（只在计数而不过滤时为 true）
+    readthrough: bool,
+    /// 条形码或 lane 所属的组（只在按组输出时存在）
+    group: Option<usize>,
+    /// `soft_filter` 时被过滤、只加标记写出的原因
+    soft_filtered: Option<FilterReason>,
+}
+
+/// 处理一对 read；只借用输入，被过滤时调用方仍持有原始记录（`soft_filter` 时原样写出）
+///
+/// 返回的 `r1_out`、`r3_out` 只有 header，序列、质量与 `+` 行由调用方用 [`take_read_buffers`]
+/// 从 R1 与 R2 移入，不复制；另外返回 R3 在 R2 中的范围。
+fn process_pair(
+    r1: &OwnedRecord,
+    r2: &OwnedRecord,
+    config: &ProcessorConfig,
+    bc_stats: &mut CorrectionStats,
+    found_names: &mut HashSet<Vec<u8>>,
+    tally: &mut WorkerTally,
+) -> Result<(ProcessedRecord, Range<usize>), FilterReason> {
+    if let Some(list) = &config.read_names {
+        let name = list.lookup(r1.head()).ok_or(FilterReason::NotListed)?;
+        found_names.insert(name.to_vec());
+    }
+    // 允许 R2 末端缺少最多 allow_truncated_bc 个碱基（只会截掉条形码）；按锚定序列定位时只限制最大长度
+    let r2_len = r2.seq().len();
+    let short = config.anchor.is_none() && R2_LEN - r2_len.min(R2_LEN) > config.allow_truncated_bc;
+    if r2_len > R2_LEN || short { return Err(FilterReason::Length); }
+
+    let (id1, id2) = if config.keep_header_comments {
+        (read_name(r1.head()), read_name(r2.head()))
+    } else {
+        (extract_base_header(r1.head()), extract_base_header(r2.head()))
+    };
+    if id1 != id2 { return Err(FilterReason::HeaderMismatch); }
+
+    // ---------- R1 ----------
+    // 保留注释时 R2、R3 的 header 为 read 名加 R2 的注释
+    let id1_vec = if config.keep_header_comments { [id1, header_comment(r2.head())].concat() } else { id1.to_vec() };
+    let r1_head = if config.keep_header_comments { [id1, header_comment(r1.head())].concat() } else { id1.to_vec() };
+    // 序列、质量与 '+' 行沿用 R1 的内存，只需截 ID
+    let out1 = OwnedRecord {
+        head : r1_head,
+        seq  : Vec::new(),
+        qual : Vec::new(),
+        sep  : None,
+    };
+    // 由 R2 得到的输出都沿用 R2 的 '+' 行；R3 直接取 R2 的，其余复制
+    let plus = if config.preserve_plus_line { r2.sep.clone() } else { None };
+
+    // ---------- R2 ----------
+    // 默认 0..150 为基因组片段，150..166 为条形码；按锚定序列定位时锚定序列之前为基因组片段
+    let window = config.barcode_window;
+    let (bc_range, r3_range) = match &config.anchor {
+        Some(anchor) => {
+            let hit = anchor.locate(r2.seq(), BARCODE_LEN).ok_or(FilterReason::AnchorNotFound)?;
+            if hit.hits > 1 {
+                tally.anchor_ambiguous += 1;
+            }
+            (hit.end..hit.end + BARCODE_LEN, 0..hit.start)
+        }
+        None => (window.offset..(window.offset + BARCODE_LEN).min(r2_len), config.r3_range.clone()),
+    };
+    let missing = BARCODE_LEN - bc_range.len();
+    let flank = r2.seq().get(bc_range.end).copied();
+    if config.min_complexity.is_some_and(|min| trinucleotide_entropy(&r2.seq()[r3_range.clone()]) < min) {
+        return Err(FilterReason::LowComplexity);
+    }
+    let (head_seq, head_qual) = (&r2.seq()[bc_range.clone()], &r2.qual()[bc_range]);
+    let readthrough = config.readthrough != ReadthroughPolicy::Off && has_me_readthrough(head_seq);
+    if readthrough && config.readthrough == ReadthroughPolicy::Drop {
+        return Err(FilterReason::AdapterReadthrough);
+    }
+
+    let (mut bc_seq, mut bc_qual): (Vec<u8>, Vec<u8>) = if window.reverse_complement {
+        (reverse_complement(head_seq), head_qual.iter().rev().cloned().collect())
+    } else {
+        (head_seq.to_ascii_uppercase(), head_qual.to_vec())
+    };
+    // 截短的条形码各位置对不齐，不计入碱基组成
+    if missing == 0 {
+        tally.composition.add(&bc_seq);
+    }
+    let mut raw_bc_out = None;
+    let mut bc_corrected = false;
+    if let Some(whitelist) = &config.whitelist {
+        // 只改写序列，质量值保留原样
+        let corrected = if missing > 0 {
+            correct_truncated_barcode(whitelist, &bc_seq, window.reverse_complement, bc_stats)
+        } else {
+            correct_barcode(whitelist, &bc_seq, &bc_qual, config.bc_mask_qual, bc_stats).or_else(|| {
+                if !config.correct_indels {
+                    return None;
+                }
+                // 条形码在 R2 中靠近 read 开头的一端位置固定；另一端窗口外的碱基用于补齐长度
+                let flank = flank.map(|b| {
+                    if window.reverse_complement { reverse_complement(&[b])[0] } else { b.to_ascii_uppercase() }
+                });
+                correct_barcode_indel(whitelist, &bc_seq, !window.reverse_complement, flank, bc_stats)
+            })
+        };
+        let corrected = corrected.ok_or(FilterReason::BarcodeNoMatch)?;
+        bc_corrected = corrected != bc_seq;
+        if config.keep_raw_barcode {
+            raw_bc_out = Some(OwnedRecord {
+                head : id1_vec.clone(),
+                seq  : std::mem::replace(&mut bc_seq, corrected),
+                qual : bc_qual.clone(),
+                sep  : plus.clone(),
+            });
+        } else {
+            bc_seq = corrected;
+        }
+        // 补上的碱基没有测到，质量值记为最低的 '!'
+        if missing > 0 {
+            let at = if window.reverse_complement { 0 } else { bc_qual.len() };
+            bc_qual.splice(at..at, std::iter::repeat_n(b'!', missing));
+        }
+    }
+
+    // 与预先计数时一样按纠错后的条形码判断
+    let rare = config.min_reads_per_barcode.as_ref().is_some_and(|filter| !filter.passes(&bc_seq));
+    if rare && !config.keep_unselected {
+        return Err(FilterReason::TooFewReads);
+    }
+
+    // 在纠错之后挑选，单个错误的条形码也能被选中
+    let selected = config.select_barcodes.as_ref().is_none_or(|sel| sel.contains(&bc_seq));
+    if !selected && !config.keep_unselected {
+        return Err(FilterReason::NotSelected);
+    }
+
+    let group = output_group(config, &bc_seq);
+
+    let out2 = OwnedRecord {
+        head : id1_vec.clone(),
+        seq  : bc_seq,
+        qual : bc_qual,
+        sep  : plus,
+    };
+
+    // ---------- R3 ----------
+    // 序列与 '+' 行取自 R2，见 take_read_buffers
+    let out3 = OwnedRecord {
+        head : id1_vec,
+        seq  : Vec::new(),
+        qual : Vec::new(),
+        sep  : None,
+    };
+    Ok((ProcessedRecord {
+        r1_out: out1,
+        r2_out: out2,
+        r3_out: out3,
+        raw_bc_out,
+        bc_corrected,
+        selected,
+        rare,
+        readthrough,
+        group,
+        soft_filtered: None,
+    }, r3_range))
+}
+
+/// 把 R1 整条与 R2 的 `r3_range` 部分移入 `processed` 的 R1、R3 输出，保留 '+' 行时连同 '+' 行
+///
+/// R3 直接截取 R2 的内存（默认 R3 是 R2 的开头，只需截短），`reverse_r3` 时原地反向互补；
+/// 条形码此前已复制出来，R2 的内存可以复用。
+fn take_read_buffers(processed: &mut ProcessedRecord, r1: OwnedRecord, r2: OwnedRecord, r3_range: Range<usize>,
+                     config: &ProcessorConfig) {
+    processed.r1_out.seq = r1.seq;
+    processed.r1_out.qual = r1.qual;
+    if config.preserve_plus_line {
+        processed.r1_out.sep = r1.sep;
+        processed.r3_out.sep = r2.sep;
+    }
+    let (mut seq, mut qual) = (r2.seq, r2.qual);
+    for buf in [&mut seq, &mut qual] {
+        buf.truncate(r3_range.end);
+        buf.drain(..r3_range.start);
+    }
+    if config.reverse_r3 {
+        reverse_complement_in_place(&mut seq);
+        qual.reverse();
+    }
+    processed.r3_out.seq = seq;
+    processed.r3_out.qual = qual;
+}
+
+/// 按条形码分组或分片时纠错后的条形码所属的组
+fn output_group(config: &ProcessorConfig, bc_seq: &[u8]) -> Option<usize> {
+    match config.shard_by_bc_prefix {
+        Some(k) => Some(prefix_shard(bc_seq, k)),
+        None => config.groups.as_ref().map(|groups| groups.group_of(bc_seq)),
+    }
+}
+
+/// `soft_filter` 时被过滤的 pair 原样写出，三个输出的 header 后都加 `\tFT:Z:<原因>`
+///
+/// 条形码取固定窗口中未纠错的序列，R3 取 R3 范围内能取到的部分；按锚定序列定位或 R2 过短取不到条形码时
+/// 条形码记为全 N（质量 `!`），按锚定序列定位时 R3 为整条 R2。
+fn soft_filtered_record(r1: OwnedRecord, mut r2: OwnedRecord, reason: FilterReason, config: &ProcessorConfig) -> ProcessedRecord {
+    let tag = [b"\tFT:Z:", reason.label().as_bytes()].concat();
+    let (r1_head, head) = if config.keep_header_comments {
+        let name = read_name(r1.head());
+        ([name, header_comment(r1.head()), &tag].concat(), [name, header_comment(r2.head()), &tag].concat())
+    } else {
+        let head = [extract_base_header(r1.head()), &tag].concat();
+        (head.clone(), head)
+    };
+
+    let window = config.barcode_window;
+    let bc_range = window.offset..window.offset + BARCODE_LEN;
+    let (bc_seq, bc_qual) = match r2.seq().get(bc_range.clone()).zip(r2.qual().get(bc_range)) {
+        Some((seq, qual)) if config.anchor.is_none() => if window.reverse_complement {
+            (reverse_complement(seq), qual.iter().rev().cloned().collect())
+        } else {
+            (seq.to_ascii_uppercase(), qual.to_vec())
+        },
+        _ => (vec![b'N'; BARCODE_LEN], vec![b'!'; BARCODE_LEN]),
+    };
+    let len = r2.seq().len().min(r2.qual().len());
+    let r3_range = match config.anchor {
+        Some(_) => 0..len,
+        None => config.r3_range.start.min(len)..config.r3_range.end.min(len),
+    };
+    let (tail_seq, tail_qual) = (&r2.seq()[r3_range.clone()], &r2.qual()[r3_range]);
+    let (r3_seq, r3_qual) = if config.reverse_r3 {
+        (reverse_complement(tail_seq), tail_qual.iter().rev().cloned().collect())
+    } else {
+        (tail_seq.to_vec(), tail_qual.to_vec())
+    };
+
+    let group = output_group(config, &bc_seq);
+    let plus = if config.preserve_plus_line { r2.sep.take() } else { None };
+    let r2_out = OwnedRecord { head: head.clone(), seq: bc_seq, qual: bc_qual, sep: plus.clone() };
+    let r1_sep = if config.preserve_plus_line { r1.sep } else { None };
+    ProcessedRecord {
+        r1_out: OwnedRecord { head: r1_head, sep: r1_sep, ..r1 },
+        raw_bc_out: config.keep_raw_barcode.then(|| r2_out.clone()),
+        r2_out,
+        r3_out: OwnedRecord { head, seq: r3_seq, qual: r3_qual, sep: plus },
+        bc_corrected: false,
+        selected: true,
+        rare: false,
+        readthrough: false,
+        group,
+        soft_filtered: Some(reason),
+    }
+}
+
+/// 区分片段的键：条形码与 R1、R3 开头（见 [`LibraryComplexity`]）
+fn fragment_key(key: &mut Vec<u8>, r: &ProcessedRecord) {
+    key.clear();
+    key.extend_from_slice(&r.r2_out.seq);
+    for seq in [&r.r1_out.seq, &r.r3_out.seq] {
+        key.push(b'|');
+        key.extend_from_slice(&seq[..seq.len().min(FRAGMENT_PREFIX_LEN)]);
+    }
+}
+
+/// 按 R1 header 中的坐标检查是否为光学重复；header 中没有坐标时不检查
+fn is_optical_duplicate(detector: &mut OpticalDupDetector, r: &ProcessedRecord, key: &mut Vec<u8>) -> bool {
+    let Some(h) = parse_illumina_header(&r.r1_out.head) else { return false };
+    fragment_key(key, r);
+    detector.check(h.lane, h.tile, h.x, h.y, key)
+}
+
+/// 处理线程在整个运行期间各自累加、结束时才合并的统计
+#[derive(Default)]
+struct WorkerTally {
+    composition: BaseComposition,
+    lanes: LaneTally,
+    tiles: TileTally,
+    duplicate_names: DuplicateNames,
+    /// R2 中有多处锚定序列的 pair 数
+    anchor_ambiguous: u64,
+}
+
+fn process_batch(
+    r1_batch: Vec<OwnedRecord>,
+    r2_batch: Vec<OwnedRecord>,
+    config: &ProcessorConfig,
+    bc_stats: &mut CorrectionStats,
+    found_names: &mut HashSet<Vec<u8>>,
+    tally: &mut WorkerTally,
+    counters: &PipelineCounters,
+) -> Vec<ProcessedRecord> {
+    let mut results = Vec::new();
+    let mut filtered = [0u64; FilterReason::ALL.len()];
+
+    for (r1, r2) in r1_batch.into_iter().zip(r2_batch) {
+        if config.check_duplicate_names {
+            tally.duplicate_names.add(extract_base_header(r1.head()));
+        }
+        let header = parse_illumina_header(r1.head()).map(|h| (h.lane, h.tile));
+        let lane = header.map(|(lane, _)| lane);
+        let excluded = header.zip(config.exclude_tiles.as_ref()).is_some_and(|((_, tile), ex)| ex.contains(tile));
+        let outcome = if excluded {
+            Err(FilterReason::ExcludedTile)
+        } else {
+            process_pair(&r1, &r2, config, bc_stats, found_names, tally)
+        };
+        if let (Some((lane, tile)), false) = (header, excluded) {
+            tally.tiles.record(lane, tile, outcome.is_err());
+        }
+        let mut processed = match outcome {
+            Ok((mut processed, r3_range)) => {
+                tally.lanes.record(lane, None);
+                take_read_buffers(&mut processed, r1, r2, r3_range, config);
+                processed
+            }
+            Err(reason) => {
+                tally.lanes.record(lane, Some(reason));
+                filtered[reason as usize] += 1;
+                if !config.soft_filter {
+                    continue;
+                }
+                soft_filtered_record(r1, r2, reason, config)
+            }
+        };
+        if config.split_lanes {
+            processed.group = Some(lane_group(lane));
+        }
+        results.push(processed);
+    }
+    for (reason, n) in FilterReason::ALL.into_iter().zip(filtered) {
+        if n > 0 {
+            counters.record_filtered(reason, n);
+        }
+    }
+
+    results
+}
+
+/// 一个 batch 的处理结果与该 batch 的纠错计数、找到的 read 名
+type BatchOutcome = (Vec<ProcessedRecord>, CorrectionStats, HashSet<Vec<u8>>);
+
+/// 在单独的线程中处理一个 batch，超过 `timeout` 仍未完成时返回错误
+///
+/// 线程无法从外部终止：超时的线程被放弃，由调用方结束流水线。
+fn process_batch_with_timeout(
+    batch: RecordBatch,
+    config: &Arc<ProcessorConfig>,
+    counters: &Arc<PipelineCounters>,
+    tally: &mut WorkerTally,
+    timeout: Duration,
+) -> Result<BatchOutcome> {
+    let (tx, rx) = mpsc::channel();
+    let (config, counters, mut moved) = (Arc::clone(config), Arc::clone(counters), std::mem::take(tally));
+    thread::spawn(move || {
+        let (mut bc_stats, mut found) = (CorrectionStats::default(), HashSet::new());
+        let results = process_batch(batch.0, batch.1, &config, &mut bc_stats, &mut found, &mut moved, &counters);
+        // 超时后接收端已不存在，结果直接丢弃
+        let _ = tx.send(((results, bc_stats, found), moved));
+    });
+    match rx.recv_timeout(timeout) {
+        Ok((outcome, moved)) => {
+            *tally = moved;
+            Ok(outcome)
+        }
+        Err(RecvTimeoutError::Timeout) => anyhow::bail!(
+            "Processing a batch took longer than {} ms; a processing thread appears to be stuck", timeout.as_millis()),
+        Err(RecvTimeoutError::Disconnected) => anyhow::bail!("A batch processing thread panicked"),
+    }
+}
+
+/// `select_barcodes` 的累计结果
+#[derive(Default)]
+struct SelectionTally {
+    selected_pairs: u64,
+    /// 另行写出的未选中 pair（丢弃的记在过滤计数中）
+    unselected_pairs: u64,
+    /// 至少出现过一次的已选条形码
+    observed: HashSet<Vec<u8>>,
+}
+
+impl SelectionTally {
+    fn merge(&mut self, other: SelectionTally) {
+        self.selected_pairs += other.selected_pairs;
+        self.unselected_pairs += other.unselected_pairs;
+        self.observed.extend(other.observed);
+    }
+}
+
+/// 序列长 `seq_len` 的 `batch_size` 条记录格式化后的估计字节数
+pub fn estimated_batch_bytes(batch_size: usize, seq_len: usize) -> usize {
+    batch_size * (2 * seq_len + RECORD_OVERHEAD)
+}
+
+/// 启动一个写入线程，把收到的 batch 依次写入 `writer`，返回线程耗时（毫秒）
+///
+/// `buffer_capacity` 不为 `None` 时先把整个 batch 格式化到预分配该大小的缓冲区再一次写出，
+/// 缓冲区在 batch 之间复用。
+/// 写入出错时设置 `aborted`，让分发与处理线程尽快停止，而不是继续处理、写入其余输出；
+/// 其他阶段出错设置 `aborted` 后也不再写出收到的 batch。
+fn spawn_writer(
+    writer: Box<dyn FinishWrite>,
+    rx: Receiver<Vec<OwnedRecord>>,
+    aborted: Arc<AtomicBool>,
+    cpus: Option<Vec<usize>>,
+    buffer_capacity: Option<usize>,
+    pool: Option<Arc<RecordPool>>,
+) -> thread::JoinHandle<Result<u64>> {
+    thread::spawn(move || -> Result<u64> {
+        if let Some(cpus) = &cpus {
+            affinity::pin_current_thread(cpus);
+        }
+        let start = Instant::now();
+        // 写入端本身已有缓冲，不再经过 FastqWriter 的 BufWriter
+        let mut writer = FastqWriter::with_capacities(writer, 0, buffer_capacity.unwrap_or(512));
+        let mut write_all = || -> io::Result<()> {
+            while let Ok(batch) = rx.recv() {
+                if aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                if buffer_capacity.is_some() {
+                    writer.write_batch(&batch)?;
+                } else {
+                    for record in &batch {
+                        writer.write_record(record)?;
+                    }
+                }
+                if let Some(pool) = &pool {
+                    batch.into_iter().for_each(|record| pool.recycle(record));
+                }
+            }
+            Ok(())
+        };
+        // 显式收尾，gzip 结尾写入失败时同样报错
+        write_all().and_then(|()| writer.into_inner()?.finish())
+            .inspect_err(|_| aborted.store(true, Ordering::Relaxed)).classify(Failure::OutputIo)?;
+        Ok(start.elapsed().as_millis() as u64)
+    })
+}
+
+/// 拆分一对 R1/R2 FASTQ 流，结果写入 `writers`
+pub fn run_pipeline(
+    config: PipelineConfig,
+    r1: impl BufRead + Send + 'static,
+    r2: impl BufRead + Send + 'static,
+    writers: PipelineWriters,
+) -> Result<RunStats> {
+    if config.preserve_plus_line {
+        return run_pipeline_with_reader(config, move |sender| read_fastq_pairs_with_plus_lines(sender, r1, r2), writers);
+    }
+    run_pipeline_with_reader(config, move |sender| read_fastq_pairs(sender, r1, r2), writers)
+}
+
+/// 与 [`run_pipeline`] 相同，但 read 对由 `read` 在读取线程中提供
+///
+/// 用于多个 lane 串联或非 FASTQ 输入；`read` 每读完一个 lane 应调用 [`PairSender::end_lane`]。
+pub fn run_pipeline_with_reader<F>(config: PipelineConfig, read: F, writers: PipelineWriters) -> Result<RunStats>
+where
+    F: FnOnce(&mut PairSender) -> Result<()> + Send + 'static,
+{
+    let start = Instant::now();
+    let (batch_tx, batch_rx): (Sender<RecordBatch>, Receiver<RecordBatch>) = bounded(CHANNEL_DEPTH);
+    let (reader_counters, batch_size) = (Arc::clone(&config.counters), config.batch_size);
+    let adaptive = config.adaptive_batch.clone().map(|target| Arc::new(AdaptiveBatchSize::new(batch_size, target)));
+    let reader_adaptive = adaptive.clone();
+    // 读取出错、处理线程超时或写入线程出错后处理、分发与写入线程都停止，读取端随之结束
+    let aborted = Arc::new(AtomicBool::new(false));
+    let reader_aborted = Arc::clone(&aborted);
+    let reader_cpus = config.io_cpus.clone();
+    let reader_pool = config.record_pool.clone();
+    let head = config.head;
+    let reader_handle = thread::spawn(move || -> Result<ReaderOutcome> {
+        if let Some(cpus) = &reader_cpus {
+            affinity::pin_current_thread(cpus);
+        }
+        let start = Instant::now();
+        let mut sender = PairSender::new(batch_tx, reader_counters, batch_size, reader_adaptive, reader_pool, head);
+        match read(&mut sender) {
+            // 读够了 `head` 个 pair：读取函数可能没来得及结束当前 lane
+            Err(e) if e.downcast_ref::<HeadReached>().is_some() => {
+                if sender.current_lane > 0 {
+                    sender.end_lane();
+                }
+                log::info!("Stopped reading after the first {} pairs", head.unwrap_or_default());
+            }
+            // 输入出错时让下游立即停止，已排队的 read 不再写出
+            result => result.inspect_err(|e| {
+                if !is_pipeline_stopped(e) {
+                    reader_aborted.store(true, Ordering::Relaxed);
+                }
+            })?,
+        }
+        let (lane_pairs, record_bytes) = sender.finish()?;
+        log::info!("Finished reading record pairs");
+        Ok((lane_pairs, record_bytes, start.elapsed().as_millis() as u64))
+    });
+    run_stages(config, batch_rx, Some(reader_handle), adaptive, writers, start, aborted)
+}
+
+/// 从处理阶段开始运行流水线：read 对的 batch 由调用方发送到 `rx`
+///
+/// 用于嵌入已有 FASTQ 解析的程序，只复用拆分逻辑。所有发送端 drop 后流水线结束；
+/// 各 batch 中 R1 与 R2 按下标配对，多余的记录记为没有配对（[`RunStats::unpaired`]）。所有输入记为同一个 lane。
+/// 标准库的 `mpsc::Receiver` 不能被多个处理线程共享，需要先转发到 crossbeam channel。
+pub fn run_pipeline_from_channel(
+    rx: Receiver<RecordBatch>,
+    config: PipelineConfig,
+    writers: PipelineWriters,
+) -> Result<RunStats> {
+    // 没有读取端从池中取记录，写入线程也不必放回
+    let config = PipelineConfig { record_pool: None, ..config };
+    run_stages(config, rx, None, None, writers, Instant::now(), Arc::new(AtomicBool::new(false)))
+}
+
+/// 读取端的错误是否只是因为下游已经停止
+fn is_pipeline_stopped(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<PipelineStopped>().is_some()
+}
+
+/// 处理、分发与写入阶段；没有读取线程时由处理线程统计读入的 pair 数与字节数
+fn run_stages(
+    config: PipelineConfig,
+    batch_rx: Receiver<RecordBatch>,
+    reader_handle: Option<thread::JoinHandle<Result<ReaderOutcome>>>,
+    adaptive: Option<Arc<AdaptiveBatchSize>>,
+    writers: PipelineWriters,
+    start: Instant,
+    aborted: Arc<AtomicBool>,
+) -> Result<RunStats> {
+    let PipelineConfig {
+        mut whitelist, bc_mask_qual, allow_truncated_bc, correct_indels, read_names, exclude_tiles, select_barcodes,
+        min_reads_per_barcode, groups, split_lanes, shard_by_bc_prefix, barcode_window, anchor, readthrough,
+        min_complexity, mark_optical_dups, optical_dup_policy, overrepresented, mut contaminants, check_duplicate_names,
+        rename_reads, soft_filter, preserve_plus_line, keep_header_comments, r3_start, r3_end, reverse_r3, swap_r2_r3,
+        head: _, batch_size, adaptive_batch: _, preallocate_writer_buffer, record_pool, threads, worker_cpus, io_cpus, batch_timeout,
+        count_barcodes, counters,
+    } = config;
+    anyhow::ensure!(batch_size > 0 && threads > 0, "Batch size and processing threads must be at least 1");
+    anyhow::ensure!(barcode_window.offset + BARCODE_LEN <= R2_LEN,
+                    "Barcode window at offset {} does not fit in a {} bp R2", barcode_window.offset, R2_LEN);
+    let r3_range = r3_range(barcode_window, r3_start, r3_end)?;
+    let r3_len = r3_range.len();
+    if allow_truncated_bc > 0 {
+        anyhow::ensure!(allow_truncated_bc < BARCODE_LEN,
+                        "Cannot allow {} missing barcode bases with a {} bp barcode", allow_truncated_bc, BARCODE_LEN);
+        let Some(whitelist) = whitelist.as_mut() else {
+            anyhow::bail!("Truncated barcodes can only be rescued with a whitelist");
+        };
+        whitelist.build_partial_index();
+    }
+    anyhow::ensure!(!correct_indels || whitelist.is_some(), "Indel correction needs a whitelist");
+    if anchor.is_some() {
+        anyhow::ensure!(allow_truncated_bc == 0, "Truncated barcodes cannot be rescued when splitting at an anchor");
+        anyhow::ensure!(r3_start == 0 && r3_end.is_none(), "The R3 region is set by the anchor position");
+    }
+    let groupings = [groups.is_some(), split_lanes, shard_by_bc_prefix.is_some()];
+    anyhow::ensure!(groupings.iter().filter(|&&g| g).count() <= 1,
+                    "Only one of barcode groups, lanes and barcode prefix shards can split the output");
+    anyhow::ensure!(groupings.contains(&true) == writers.groups.is_some(),
+                    "Grouped output needs both PipelineWriters::groups and one of PipelineConfig::groups, \
+                     split_lanes or shard_by_bc_prefix");
+    if let Some(k) = shard_by_bc_prefix {
+        anyhow::ensure!((1..=MAX_SHARD_PREFIX).contains(&k),
+                        "Barcode prefix shards need a prefix of 1 to {} bases, not {}", MAX_SHARD_PREFIX, k);
+    }
+    let group_names: Vec<String> = match (&groups, shard_by_bc_prefix) {
+        (Some(groups), _) => groups.names().to_vec(),
+        (None, Some(k)) => prefix_shard_names(k),
+        (None, None) => Vec::new(),
+    };
+    let correcting = whitelist.is_some();
+    let keep_raw = writers.raw_barcode.is_some();
+    anyhow::ensure!(writers.read_name_map.is_none() || rename_reads.is_some(),
+                    "A read name map needs PipelineConfig::rename_reads");
+    let mut read_name_map = writers.read_name_map;
+    let keep_unselected = (select_barcodes.is_some() || min_reads_per_barcode.is_some()) && writers.unselected.is_some();
+    let config = Arc::new(ProcessorConfig {
+        whitelist, bc_mask_qual, allow_truncated_bc, correct_indels, read_names, exclude_tiles, select_barcodes,
+        min_reads_per_barcode, groups, split_lanes, shard_by_bc_prefix, barcode_window, anchor, readthrough,
+        min_complexity, check_duplicate_names, soft_filter, preserve_plus_line, keep_header_comments, r3_range,
+        reverse_r3,
+        keep_unselected, keep_raw_barcode: keep_raw,
+    });
+
+    let count_input = reader_handle.is_none();
+    let (output_tx, output_rx): (Sender<Vec<ProcessedRecord>>, Receiver<Vec<ProcessedRecord>>) = bounded(CHANNEL_DEPTH);
+
+    let barcode_stats = Arc::new(Mutex::new(CorrectionStats::default()));
+    let barcode_counter = Arc::new(Mutex::new(BarcodeCounter::new(correcting)));
+    let found_names = Arc::new(Mutex::new(HashSet::new()));
+    let selection = Arc::new(Mutex::new(SelectionTally::default()));
+    // 另行写出的 read 数不足的 pair（丢弃的记在过滤计数中）
+    let rare_kept = Arc::new(AtomicU64::new(0));
+    // 只计数时检测到通读的 pair（过滤的记在过滤计数中）
+    let readthrough_kept = Arc::new(AtomicU64::new(0));
+    let anchor_ambiguous = Arc::new(AtomicU64::new(0));
+    // 处理过的 batch 数，以及其中全部被过滤、没有结果可发送的 batch 数
+    let batches_processed = Arc::new(AtomicU64::new(0));
+    let empty_batches_skipped = Arc::new(AtomicU64::new(0));
+    let composition = Arc::new(Mutex::new(BaseComposition::default()));
+    // 不要求计数表时用固定内存的估计器统计不同条形码的个数
+    let distinct = Arc::new(Mutex::new(HyperLogLog::new()));
+    // 要求计数表时另外估计不同片段的个数，用于饱和度
+    let fragments = Arc::new(Mutex::new(HyperLogLog::new()));
+    let lanes = Arc::new(Mutex::new(LaneTally::default()));
+    let tiles = Arc::new(Mutex::new(TileTally::default()));
+    let duplicate_names = Arc::new(Mutex::new(DuplicateNames::new()));
+    // 没有读取线程时处理线程统计的 R1、R2 字节数
+    let input_bytes = Arc::new([AtomicU64::new(0), AtomicU64::new(0)]);
+
+    // Start processing threads
+    let mut processing_handles = Vec::new();
+    for worker_id in 0..threads {
+        let rx = batch_rx.clone();
+        let tx = output_tx.clone();
+        let counters = Arc::clone(&counters);
+        let bc_stats = Arc::clone(&barcode_stats);
+        let bc_counter = Arc::clone(&barcode_counter);
+        let found_names = Arc::clone(&found_names);
+        let selection = Arc::clone(&selection);
+        let rare_kept = Arc::clone(&rare_kept);
+        let readthrough_kept = Arc::clone(&readthrough_kept);
+        let anchor_ambiguous = Arc::clone(&anchor_ambiguous);
+        let adaptive = adaptive.clone();
+        let batches_processed = Arc::clone(&batches_processed);
+        let empty_batches_skipped = Arc::clone(&empty_batches_skipped);
+        let composition = Arc::clone(&composition);
+        let distinct = Arc::clone(&distinct);
+        let fragments = Arc::clone(&fragments);
+        let lanes = Arc::clone(&lanes);
+        let tiles = Arc::clone(&tiles);
+        let duplicate_names = Arc::clone(&duplicate_names);
+        let aborted = Arc::clone(&aborted);
+        let input_bytes = Arc::clone(&input_bytes);
+        let config = Arc::clone(&config);
+        let cpus = worker_cpus.clone();
+
+        let handle = thread::spawn(move || -> Result<WorkerStats> {
+            if let Some(cpus) = &cpus {
+                affinity::pin_current_thread(cpus);
+            }
+            let start = Instant::now();
+            let mut records = 0u64;
+            let mut local_tally = WorkerTally::default();
+            // 只在结束时汇总的统计先在线程内累加，处理循环中不加锁
+            let mut local_bc_stats = CorrectionStats::default();
+            let mut local_found = HashSet::new();
+            let mut local_selection = SelectionTally::default();
+            let (mut local_readthrough, mut local_rare) = (0u64, 0u64);
+            let mut local_distinct = HyperLogLog::new();
+            let mut local_fragments = HyperLogLog::new();
+            let mut key = Vec::new();
+            loop {
+                let wait = Instant::now();
+                let Ok((r1_batch, r2_batch)) = rx.recv() else { break };
+                if aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                PipelineCounters::add_wait(&counters.worker_recv_wait_ns, wait);
+                records += r2_batch.len() as u64;
+                let batch_len = r1_batch.len().min(r2_batch.len());
+                // 较长一侧多出的记录没有配对，不处理
+                let longer = if r1_batch.len() > r2_batch.len() { &r1_batch } else { &r2_batch };
+                for record in &longer[batch_len..] {
+                    counters.record_unpaired(&record.head);
+                }
+                if count_input {
+                    counters.pairs_read.fetch_add(batch_len as u64, Ordering::Relaxed);
+                    for (bytes, batch) in input_bytes.iter().zip([&r1_batch, &r2_batch]) {
+                        bytes.fetch_add(record_bytes(batch), Ordering::Relaxed);
+                    }
+                }
+                let processing = Instant::now();
+                let (results, batch_bc_stats, batch_found) = match batch_timeout {
+                    Some(timeout) => {
+                        process_batch_with_timeout((r1_batch, r2_batch), &config, &counters, &mut local_tally, timeout)
+                            .inspect_err(|_| aborted.store(true, Ordering::Relaxed))?
+                    }
+                    None => {
+                        let (mut bc_stats, mut found) = (CorrectionStats::default(), HashSet::new());
+                        let results = process_batch(r1_batch, r2_batch, &config, &mut bc_stats, &mut found,
+                                                    &mut local_tally, &counters);
+                        (results, bc_stats, found)
+                    }
+                };
+                if let Some(adaptive) = &adaptive {
+                    adaptive.observe(batch_len, processing.elapsed());
+                }
+                local_bc_stats.merge(&batch_bc_stats);
+                batches_processed.fetch_add(1, Ordering::Relaxed);
+                local_found.extend(batch_found);
+                if config.readthrough == ReadthroughPolicy::Count {
+                    local_readthrough += results.iter().filter(|r| r.readthrough).count() as u64;
+                }
+                if config.min_reads_per_barcode.is_some() {
+                    local_rare += results.iter().filter(|r| r.rare).count() as u64;
+                }
+                if config.select_barcodes.is_some() {
+                    let tally = &mut local_selection;
+                    for r in results.iter().filter(|r| !r.rare && r.soft_filtered.is_none()) {
+                        if r.selected {
+                            tally.selected_pairs += 1;
+                            if !tally.observed.contains(&r.r2_out.seq) {
+                                tally.observed.insert(r.r2_out.seq.clone());
+                            }
+                        } else {
+                            tally.unselected_pairs += 1;
+                        }
+                    }
+                }
+                if count_barcodes {
+                    let mut batch_counter = BarcodeCounter::new(correcting);
+                    for r in results.iter().filter(|r| r.soft_filtered.is_none()) {
+                        batch_counter.add(&r.r2_out.seq, r.bc_corrected);
+                        fragment_key(&mut key, r);
+                        local_fragments.add(&key);
+                    }
+                    bc_counter.lock().merge(&batch_counter);
+                } else {
+                    for r in results.iter().filter(|r| r.soft_filtered.is_none()) {
+                        local_distinct.add(&r.r2_out.seq);
+                    }
+                }
+
+                if results.is_empty() {
+                    empty_batches_skipped.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let wait = Instant::now();
+                    if tx.send(results).is_err() {
+                        break;
+                    }
+                    PipelineCounters::add_wait(&counters.worker_send_wait_ns, wait);
+                }
+            }
+            bc_stats.lock().merge(&local_bc_stats);
+            found_names.lock().extend(local_found);
+            selection.lock().merge(local_selection);
+            readthrough_kept.fetch_add(local_readthrough, Ordering::Relaxed);
+            rare_kept.fetch_add(local_rare, Ordering::Relaxed);
+            composition.lock().merge(&local_tally.composition);
+            distinct.lock().merge(&local_distinct);
+            fragments.lock().merge(&local_fragments);
+            lanes.lock().merge(&local_tally.lanes);
+            tiles.lock().merge(&local_tally.tiles);
+            duplicate_names.lock().merge(&local_tally.duplicate_names);
+            anchor_ambiguous.fetch_add(local_tally.anchor_ambiguous, Ordering::Relaxed);
+            Ok(WorkerStats { worker_id, records, wall_ms: start.elapsed().as_millis() as u64 })
+        });
+        processing_handles.push(handle);
+    }
+    drop(batch_rx);
+
+    // Create separate channels for each output file
+    let (r1_tx, r1_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(CHANNEL_DEPTH);
+    let (r2_tx, r2_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(CHANNEL_DEPTH);
+    let (r3_tx, r3_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(CHANNEL_DEPTH);
+    let (raw_bc_tx, raw_bc_rx): (Sender<Vec<OwnedRecord>>, Receiver<Vec<OwnedRecord>>) = bounded(CHANNEL_DEPTH);
+    let (unselected_txs, unselected_rxs): (Vec<Sender<Vec<OwnedRecord>>>, Vec<_>) =
+        (0..3).map(|_| bounded(CHANNEL_DEPTH)).unzip();
+    let (group_tx, group_rx): (Sender<Vec<GroupedPair>>, Receiver<Vec<GroupedPair>>) = bounded(CHANNEL_DEPTH);
+
+    // Distribution thread - 分发处理结果到各个写入线程
+    let dist_handle = {
+        let counters = Arc::clone(&counters);
+        let aborted = Arc::clone(&aborted);
+        thread::spawn(move || -> Result<DistributorOutcome> {
+            let start = Instant::now();
+            let mut written_count = 0;
+            let mut optical = mark_optical_dups.map(OpticalDupDetector::new);
+            // 在这里按写出顺序抽样，结果与线程数无关
+            let mut overrepresented = overrepresented.map(OverrepresentedCounter::new);
+            let mut key = Vec::new();
+            while let Ok(batch_results) = output_rx.recv() {
+                // 出错的写入线程报告具体原因
+                anyhow::ensure!(!aborted.load(Ordering::Relaxed), "Stopped because the pipeline was aborted");
+                let mut optical_dropped = 0;
+                let mut r1_batch = Vec::new();
+                let mut r2_batch = Vec::new();
+                let mut r3_batch = Vec::new();
+                let mut raw_bc_batch = Vec::new();
+                let mut unselected_batches = [Vec::new(), Vec::new(), Vec::new()];
+                let mut group_batch = Vec::new();
+
+                for mut processed in batch_results {
+                    // 只检查写入主输出或分组输出的 pair，在交换 R2/R3 之前
+                    let to_main = processed.selected && !processed.rare && processed.soft_filtered.is_none();
+                    if let Some(detector) = optical.as_mut().filter(|_| to_main) {
+                        if is_optical_duplicate(detector, &processed, &mut key)
+                            && optical_dup_policy == OpticalDupPolicy::Drop
+                        {
+                            optical_dropped += 1;
+                            if !soft_filter {
+                                continue;
+                            }
+                            let tag = format!("\tFT:Z:{}", FilterReason::OpticalDuplicate.label());
+                            for record in [&mut processed.r1_out, &mut processed.r2_out, &mut processed.r3_out]
+                                .into_iter().chain(processed.raw_bc_out.as_mut())
+                            {
+                                record.head.extend_from_slice(tag.as_bytes());
+                            }
+                            processed.soft_filtered = Some(FilterReason::OpticalDuplicate);
+                        }
+                    }
+                    written_count += 1;
+                    if let Some(prefix) = &rename_reads {
+                        // 序号即写出顺序；只替换 read 名，保留的注释与 soft_filter 的标记不变
+                        let name_end = |head: &[u8]| head.iter()
+                            .position(|&b| if keep_header_comments { b.is_ascii_whitespace() } else { b == b'\t' })
+                            .unwrap_or(head.len());
+                        let name = format!("{}:{}", prefix, written_count).into_bytes();
+                        if let Some(map) = read_name_map.as_mut() {
+                            let head = &processed.r1_out.head;
+                            map.write_all(&head[..name_end(head)])?;
+                            map.write_all(b"\t")?;
+                            map.write_all(&name)?;
+                            map.write_all(b"\n")?;
+                        }
+                        for record in [&mut processed.r1_out, &mut processed.r2_out, &mut processed.r3_out]
+                            .into_iter().chain(processed.raw_bc_out.as_mut())
+                        {
+                            let end = name_end(&record.head);
+                            record.head.splice(..end, name.iter().copied());
+                        }
+                    }
+                    if processed.soft_filtered.is_none() {
+                        if let Some(counter) = overrepresented.as_mut() {
+                            counter.add(&processed.r3_out.seq);
+                        }
+                        if let Some(screen) = contaminants.as_mut() {
+                            screen.add(&processed.r3_out.seq);
+                        }
+                    }
+                    // 统计已在处理线程中完成，这里只改变写入的文件
+                    if swap_r2_r3 {
+                        std::mem::swap(&mut processed.r2_out, &mut processed.r3_out);
+                    }
+                    if !processed.selected || processed.rare {
+                        for (batch, record) in unselected_batches.iter_mut()
+                            .zip([processed.r1_out, processed.r2_out, processed.r3_out])
+                        {
+                            batch.push(record);
+                        }
+                        continue;
+                    }
+                    if let Some(group) = processed.group {
+                        group_batch.push((group, [processed.r1_out, processed.r2_out, processed.r3_out]));
+                        continue;
+                    }
+                    r1_batch.push(processed.r1_out);
+                    r2_batch.push(processed.r2_out);
+                    r3_batch.push(processed.r3_out);
+                    raw_bc_batch.extend(processed.raw_bc_out);
+                }
+
+                // 并行发送到各个写入线程
+                let r1_batch_len = r1_batch.len();
+                if r1_batch_len > 0 {
+                    r1_tx.send(r1_batch).map_err(|_| anyhow::anyhow!("Failed to send R1 batch"))?;
+                    r2_tx.send(r2_batch).map_err(|_| anyhow::anyhow!("Failed to send R2 batch"))?;
+                    r3_tx.send(r3_batch).map_err(|_| anyhow::anyhow!("Failed to send R3 batch"))?;
+                    if keep_raw {
+                        raw_bc_tx.send(raw_bc_batch).map_err(|_| anyhow::anyhow!("Failed to send raw barcode batch"))?;
+                    }
+                    counters.pairs_written.fetch_add(r1_batch_len as u64, Ordering::Relaxed);
+                }
+                let unselected_len = unselected_batches[0].len();
+                if unselected_len > 0 {
+                    for (tx, batch) in unselected_txs.iter().zip(unselected_batches) {
+                        tx.send(batch).map_err(|_| anyhow::anyhow!("Failed to send unselected batch"))?;
+                    }
+                    counters.pairs_written.fetch_add(unselected_len as u64, Ordering::Relaxed);
+                }
+                let group_len = group_batch.len();
+                if group_len > 0 {
+                    group_tx.send(group_batch).map_err(|_| anyhow::anyhow!("Failed to send grouped batch"))?;
+                    counters.pairs_written.fetch_add(group_len as u64, Ordering::Relaxed);
+                }
+                if optical_dropped > 0 {
+                    counters.record_filtered(FilterReason::OpticalDuplicate, optical_dropped);
+                }
+
+                if written_count % 100000 == 0 {
+                    log::debug!("Written {} records...", written_count);
+                }
+            }
+            log::info!("Finished writing {} records", written_count);
+            if let Some(mut map) = read_name_map {
+                map.finish()?;
+            }
+            // 返回时关闭各写入线程的 channel
+            Ok((start.elapsed().as_millis() as u64, optical, overrepresented, contaminants))
+        })
+    };
+
+    // Start separate writer threads for each output file
+    // 预分配时按各输出的预计序列长度估计一个 batch 的字节数（按锚定序列定位时 R3 不会更长）
+    let (r2_len, r3_len) = if swap_r2_r3 { (r3_len, BARCODE_LEN) } else { (BARCODE_LEN, r3_len) };
+    let output_lens = [EXPECTED_R1_LEN, r2_len, r3_len];
+    let start_writer = |writer, rx, seq_len| {
+        let capacity = preallocate_writer_buffer.then(|| estimated_batch_bytes(batch_size, seq_len));
+        spawn_writer(writer, rx, Arc::clone(&aborted), io_cpus.clone(), capacity, record_pool.clone())
+    };
+    let r1_writer_handle = start_writer(writers.r1, r1_rx, output_lens[0]);
+    let r2_writer_handle = start_writer(writers.r2, r2_rx, output_lens[1]);
+    let r3_writer_handle = start_writer(writers.r3, r3_rx, output_lens[2]);
+    let raw_bc_writer_handle = writers.raw_barcode.map(|writer| start_writer(writer, raw_bc_rx, BARCODE_LEN));
+    let unselected_writer_handles: Vec<_> = writers.unselected.into_iter().flatten().zip(unselected_rxs).zip(output_lens)
+        .map(|((writer, rx), seq_len)| start_writer(writer, rx, seq_len))
+        .collect();
+    // 组数事先不确定，所有组共用一个写入线程
+    let group_aborted = Arc::clone(&aborted);
+    let group_cpus = io_cpus.clone();
+    let group_writer_handle = writers.groups.map(|mut groups| thread::spawn(move || -> Result<BTreeMap<String, u64>> {
+        if let Some(cpus) = &group_cpus {
+            affinity::pin_current_thread(cpus);
+        }
+        let mut write_all = || -> Result<()> {
+            while let Ok(batch) = group_rx.recv() {
+                if group_aborted.load(Ordering::Relaxed) {
+                    break;
+                }
+                for (group, records) in batch {
+                    if split_lanes {
+                        groups.write(group, || lane_group_name(group), records)?;
+                    } else {
+                        groups.write(group, || group_names[group].clone(), records)?;
+                    }
+                }
+            }
+            Ok(())
+        };
+        write_all().inspect_err(|_| group_aborted.store(true, Ordering::Relaxed))?;
+        groups.finish().inspect_err(|_| group_aborted.store(true, Ordering::Relaxed))
+    }));
+
+    // Wait for reader to finish
+    let reader_result = reader_handle.map(|handle| handle.join().unwrap());
+
+    // Wait for all processing threads to finish
+    let worker_results: Vec<Result<WorkerStats>> = processing_handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    // Close output channel to signal distribution thread to finish
+    drop(output_tx);
+
+    // Wait for distribution thread to finish
+    let distributor_result = dist_handle.join().unwrap();
+
+    // Wait for all writer threads to finish
+    let [r1_result, r2_result, r3_result] =
+        [r1_writer_handle, r2_writer_handle, r3_writer_handle].map(|handle| handle.join().unwrap());
+    let extra_results: Vec<Result<u64>> = raw_bc_writer_handle.into_iter().chain(unselected_writer_handles)
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    let group_counts = group_writer_handle.map(|handle| handle.join().unwrap());
+
+    // 读取出错时下游只是随之停止，先报告输入的错误
+    let reader_result = match reader_result {
+        Some(Err(e)) if !is_pipeline_stopped(&e) => return Err(e),
+        result => result,
+    };
+    // 写入线程出错时上游的发送也会失败，先报告下游的错误
+    let group_counts = group_counts.transpose()?;
+    for result in extra_results {
+        result?;
+    }
+    let (writer_r1_wall_ms, writer_r2_wall_ms, writer_r3_wall_ms) = (r1_result?, r2_result?, r3_result?);
+    let (distributor_wall_ms, optical, overrepresented, contaminants) = distributor_result?;
+    // 处理线程超时时读取端的发送也会失败
+    let worker_stats = worker_results.into_iter().collect::<Result<Vec<_>>>()?;
+    let worker_wall_ms: Vec<u64> = worker_stats.iter().map(|w| w.wall_ms).collect();
+    let (lane_pairs, [r1_bytes, r2_bytes], reader_wall_ms) = match reader_result {
+        Some(result) => result?,
+        None => (vec![counters.pairs_read()], input_bytes.each_ref().map(|b| b.load(Ordering::Relaxed)), 0),
+    };
+    let timings = StageTimings {
+        reader_wall_ms,
+        worker_wall_ms: WorkerWallMs::from_samples(&worker_wall_ms),
+        distributor_wall_ms,
+        writer_r1_wall_ms,
+        writer_r2_wall_ms,
+        writer_r3_wall_ms,
+    };
+
+    let barcode_stats = barcode_stats.lock();
+    let read_names = config.read_names.as_ref().map(|list| {
+        let found = found_names.lock();
+        ReadNameStats {
+            listed: list.len() as u64,
+            found: found.len() as u64,
+            missing: list.missing(&found).into_iter().map(|n| String::from_utf8_lossy(n).into_owned()).collect(),
+        }
+    });
+    let barcode_selection = config.select_barcodes.as_ref().map(|list| {
+        let tally = selection.lock();
+        let dropped = counters.snapshot().filtered_by_reason[FilterReason::NotSelected as usize];
+        BarcodeSelectionStats {
+            listed: list.len() as u64,
+            selected_pairs: tally.selected_pairs,
+            unselected_pairs: tally.unselected_pairs + dropped,
+            never_observed: (list.len() - tally.observed.len()) as u64,
+        }
+    });
+    let barcode_min_reads = config.min_reads_per_barcode.as_ref().map(|filter| {
+        let dropped = counters.snapshot().filtered_by_reason[FilterReason::TooFewReads as usize];
+        BarcodeMinReadsStats {
+            min_reads: filter.min_reads(),
+            removed_barcodes: filter.removed_barcodes(),
+            removed_pairs: rare_kept.load(Ordering::Relaxed) + dropped,
+        }
+    });
+    let adapter_readthrough = (config.readthrough != ReadthroughPolicy::Off).then(|| {
+        let snapshot = counters.snapshot();
+        let pairs = readthrough_kept.load(Ordering::Relaxed)
+            + snapshot.filtered_by_reason[FilterReason::AdapterReadthrough as usize];
+        ReadthroughStats {
+            pairs,
+            fraction: if snapshot.pairs_read > 0 { pairs as f64 / snapshot.pairs_read as f64 } else { 0.0 },
+            dropped: config.readthrough == ReadthroughPolicy::Drop,
+        }
+    });
+    let anchor = config.anchor.as_ref().map(|anchor| AnchorStats {
+        sequence: String::from_utf8_lossy(anchor.sequence()).into_owned(),
+        max_mismatches: anchor.max_mismatches(),
+        not_found: counters.snapshot().filtered_by_reason[FilterReason::AnchorNotFound as usize],
+        ambiguous: anchor_ambiguous.load(Ordering::Relaxed),
+    });
+    let optical_duplicates = optical.zip(mark_optical_dups).map(|(detector, max_distance)| OpticalDuplicateStats {
+        max_distance,
+        examined: detector.examined(),
+        duplicates: detector.duplicates(),
+        fraction: detector.duplicates() as f64 / detector.examined().max(1) as f64,
+        dropped: optical_dup_policy == OpticalDupPolicy::Drop,
+    });
+    let barcode_counter = std::mem::take(&mut *barcode_counter.lock());
+    let barcode_composition = composition.lock().fractions();
+    let distinct_barcodes = if count_barcodes {
+        DistinctBarcodes { count: barcode_counter.len() as u64, approximate: false, relative_error: None }
+    } else {
+        let estimate = distinct.lock().estimate();
+        DistinctBarcodes { count: estimate, approximate: true, relative_error: Some(HyperLogLog::RELATIVE_ERROR) }
+    };
+    let library_complexity = count_barcodes.then(|| {
+        LibraryComplexity::new(counters.snapshot().pairs_written, fragments.lock().estimate())
+    });
+    let lane_stats = lanes.lock().to_stats(correcting);
+    let worst_tiles = tiles.lock().worst(WORST_TILES);
+    let first_unpaired = counters.first_unpaired.lock().as_ref().map(|name| String::from_utf8_lossy(name).into_owned());
+    let wall_ms = start.elapsed().as_millis() as u64;
+    let mb = |bytes: u64| bytes as f64 / 1e6;
+    Ok(RunStats {
+        lane_pairs,
+        lanes: lane_stats,
+        worst_tiles,
+        r1_read_mb: mb(r1_bytes),
+        r2_read_mb: mb(r2_bytes),
+        total_throughput_mb_s: mb(r1_bytes + r2_bytes) / (wall_ms.max(1) as f64 / 1000.0),
+        barcode_correction: correcting.then(|| BarcodeCorrectionStats::from(&*barcode_stats)),
+        wall_ms,
+        timings,
+        worker_stats,
+        barcode_counts: count_barcodes.then_some(barcode_counter),
+        read_names,
+        barcode_selection,
+        barcode_min_reads,
+        adapter_readthrough,
+        group_counts,
+        barcode_composition,
+        distinct_barcodes,
+        library_complexity,
+        optical_duplicates,
+        anchor,
+        overrepresented: overrepresented.map(|counter| counter.to_stats()),
+        contaminants: contaminants.map(|screen| screen.to_stats()),
+        duplicate_names: check_duplicate_names.then(|| duplicate_names.lock().to_stats()),
+        first_unpaired,
+        adaptive_batch: adaptive.map(|a| a.stats()),
+        batches_processed: batches_processed.load(Ordering::Relaxed),
+        empty_output_batches: empty_batches_skipped.load(Ordering::Relaxed),
+        ..RunStats::from_snapshot(&counters.snapshot())
+    })
+}
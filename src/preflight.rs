@@ -0,0 +1,100 @@
+// preflight.rs - 启动前检查输入与输出路径、输出磁盘的剩余空间（--check-space）
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// gzip 压缩 FASTQ 的典型压缩比
+pub const GZIP_RATIO: f64 = 4.0;
+/// 输出比输入多出的比例：R3 另有一份 header 与 `+` 行
+pub const OUTPUT_EXPANSION: f64 = 1.15;
+/// 估计值只是近似；剩余空间不到估计值的这个倍数时警告
+pub const SPACE_HEADROOM: f64 = 1.25;
+
+/// `a` 与 `b` 是否为同一个文件（Unix 上比较设备号与 inode，能识别硬链接与符号链接）；任一不存在时为 `false`
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// 输入与输出路径的冲突：同一个文件作为两个输入，或某个输出就是输入；标准输入（`-`）不检查
+///
+/// 返回所有问题，每条一句。
+pub fn path_collisions(r1_inputs: &[PathBuf], r2_inputs: &[PathBuf], outputs: &[PathBuf]) -> Vec<String> {
+    let inputs: Vec<(&str, &PathBuf)> = r1_inputs.iter().map(|path| ("R1", path))
+        .chain(r2_inputs.iter().map(|path| ("R2", path)))
+        .filter(|(_, path)| path.as_os_str() != "-")
+        .collect();
+    let mut problems = Vec::new();
+    for (i, (label, path)) in inputs.iter().enumerate() {
+        for (other_label, other) in &inputs[i + 1..] {
+            if same_file(path, other) {
+                problems.push(format!("{} input {} and {} input {} are the same file",
+                                      label, path.display(), other_label, other.display()));
+            }
+        }
+    }
+    for output in outputs {
+        for (label, path) in &inputs {
+            if same_file(output, path) {
+                problems.push(format!("Output {} would overwrite {} input {}", output.display(), label, path.display()));
+            }
+        }
+    }
+    problems
+}
+
+/// 文件是否以 gzip 的 magic bytes 开头（BGZF 与 BAM 也是）
+pub fn is_gzip(path: &Path) -> bool {
+    let mut magic = [0u8; 2];
+    File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && magic == [0x1f, 0x8b]
+}
+
+/// 按输入大小估计输出需要的字节数；压缩的输入按 [`GZIP_RATIO`] 折算成未压缩大小，读不到的输入（如标准输入）不计
+pub fn estimate_output_bytes(inputs: &[PathBuf], compress: bool) -> u64 {
+    let uncompressed: f64 = inputs.iter()
+        .filter_map(|path| {
+            let size = fs::metadata(path).ok().filter(|m| m.is_file())?.len() as f64;
+            Some(if is_gzip(path) { size * GZIP_RATIO } else { size })
+        })
+        .sum();
+    let output = uncompressed * OUTPUT_EXPANSION;
+    (if compress { output / GZIP_RATIO } else { output }) as u64
+}
+
+/// `dir` 所在文件系统上非 root 用户可用的字节数；无法确定（或非 Unix 系统）时为 `None`
+pub fn available_space(dir: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+        // SAFETY: statvfs 只写入传入的结构体；path 是以 NUL 结尾的 C 字符串
+        unsafe {
+            let mut stat: libc::statvfs = std::mem::zeroed();
+            if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+                return None;
+            }
+            // 各平台上字段的整数类型不同
+            #[allow(clippy::unnecessary_cast)]
+            Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        None
+    }
+}
@@ -0,0 +1,283 @@
+// progress.rs - 运行计数器与进度显示
+
+use crate::read_name;
+use crate::run_info::format_rfc3339;
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// read pair 被过滤的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// R2 长度不是 166bp
+    Length,
+    /// R1 与 R2 的 header 不一致
+    HeaderMismatch,
+    /// 条形码无法匹配白名单
+    BarcodeNoMatch,
+    /// read 名不在 `--read-names` 列表中
+    NotListed,
+    /// 条形码不在 `--select-barcodes` 列表中（未选中的 pair 不另行写出时）
+    NotSelected,
+    /// 条形码的总 read 数低于 `--min-reads-per-barcode`（不另行写出时）
+    TooFewReads,
+    /// 条形码窗口中读到了接头（ME）通读
+    AdapterReadthrough,
+    /// read 来自 `--exclude-tiles` 列出的 tile
+    ExcludedTile,
+    /// 光学重复（`--mark-optical-dups` 且按 drop 处理时）
+    OpticalDuplicate,
+    /// R2 中找不到 `--anchor` 锚定序列
+    AnchorNotFound,
+    /// R3 的复杂度低于 `--filter-low-complexity`
+    LowComplexity,
+}
+
+impl FilterReason {
+    pub const ALL: [FilterReason; 11] = [
+        FilterReason::Length,
+        FilterReason::HeaderMismatch,
+        FilterReason::BarcodeNoMatch,
+        FilterReason::NotListed,
+        FilterReason::NotSelected,
+        FilterReason::TooFewReads,
+        FilterReason::AdapterReadthrough,
+        FilterReason::ExcludedTile,
+        FilterReason::OpticalDuplicate,
+        FilterReason::AnchorNotFound,
+        FilterReason::LowComplexity,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FilterReason::Length => "length",
+            FilterReason::HeaderMismatch => "header_mismatch",
+            FilterReason::BarcodeNoMatch => "barcode_no_match",
+            FilterReason::NotListed => "not_listed",
+            FilterReason::NotSelected => "barcode_not_selected",
+            FilterReason::TooFewReads => "barcode_too_few_reads",
+            FilterReason::AdapterReadthrough => "adapter_readthrough",
+            FilterReason::ExcludedTile => "excluded_tile",
+            FilterReason::OpticalDuplicate => "optical_duplicate",
+            FilterReason::AnchorNotFound => "anchor_not_found",
+            FilterReason::LowComplexity => "low_complexity",
+        }
+    }
+}
+
+/// 输出文件的标签，与 [`PipelineCounters::bytes_written`] 一一对应
+pub const OUTPUT_LABELS: [&str; 4] = ["R1", "R2", "R3", "CR"];
+
+/// 各线程共享的计数器；进度显示、指标文件与最终汇总都从这里取数
+#[derive(Debug, Default)]
+pub struct PipelineCounters {
+    pub pairs_read: AtomicU64,
+    pub pairs_written: AtomicU64,
+    pub pairs_filtered: AtomicU64,
+    /// 按 [`FilterReason::ALL`] 顺序的过滤计数
+    pub filtered_by_reason: [AtomicU64; FilterReason::ALL.len()],
+    /// 另一个文件中没有对应记录的 read 数（R1、R2 记录数不同，或 batch 长度不同），也计入 `pairs_read`
+    pub unpaired: AtomicU64,
+    /// 第一条没有配对的 read 的名字
+    pub first_unpaired: Mutex<Option<Vec<u8>>>,
+    /// 各输出文件实际写出的字节数（压缩后），顺序同 [`OUTPUT_LABELS`]
+    pub bytes_written: [Arc<AtomicU64>; 4],
+    /// 各输出压缩前的字节数（只在输出 gzip 时计数），顺序同 [`OUTPUT_LABELS`]
+    pub uncompressed_bytes_written: [Arc<AtomicU64>; 4],
+    /// 已从输入文件读取的（压缩）字节数，用于估算进度百分比
+    pub input_bytes_read: Arc<AtomicU64>,
+    /// 读取线程阻塞在发送 batch 上的时间（纳秒）
+    pub reader_send_wait_ns: AtomicU64,
+    /// 处理线程等待输入 batch / 阻塞在发送结果上的时间之和（纳秒）
+    pub worker_recv_wait_ns: AtomicU64,
+    pub worker_send_wait_ns: AtomicU64,
+}
+
+impl PipelineCounters {
+    pub fn pairs_read(&self) -> u64 {
+        self.pairs_read.load(Ordering::Relaxed)
+    }
+
+    pub fn pairs_written(&self) -> u64 {
+        self.pairs_written.load(Ordering::Relaxed)
+    }
+
+    pub fn pairs_filtered(&self) -> u64 {
+        self.pairs_filtered.load(Ordering::Relaxed)
+    }
+
+    pub fn input_bytes_read(&self) -> u64 {
+        self.input_bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub fn record_filtered(&self, reason: FilterReason, n: u64) {
+        self.pairs_filtered.fetch_add(n, Ordering::Relaxed);
+        self.filtered_by_reason[reason as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// 记录一条没有配对的 read（`head` 为其 header）
+    pub fn record_unpaired(&self, head: &[u8]) {
+        self.pairs_read.fetch_add(1, Ordering::Relaxed);
+        self.unpaired.fetch_add(1, Ordering::Relaxed);
+        self.first_unpaired.lock().get_or_insert_with(|| read_name(head).to_vec());
+    }
+
+    pub fn unpaired(&self) -> u64 {
+        self.unpaired.load(Ordering::Relaxed)
+    }
+
+    /// 某一时刻的计数器取值；所有展示方式都基于快照，保证数字一致
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            pairs_read: self.pairs_read(),
+            pairs_written: self.pairs_written(),
+            pairs_filtered: self.pairs_filtered(),
+            filtered_by_reason: std::array::from_fn(|i| self.filtered_by_reason[i].load(Ordering::Relaxed)),
+            unpaired: self.unpaired(),
+            bytes_written: std::array::from_fn(|i| self.bytes_written[i].load(Ordering::Relaxed)),
+            input_bytes_read: self.input_bytes_read(),
+        }
+    }
+
+    /// 把 `since` 到现在的时间累加到某个等待计时器上
+    pub fn add_wait(counter: &AtomicU64, since: Instant) {
+        counter.fetch_add(since.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// [`PipelineCounters`] 的快照
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CounterSnapshot {
+    pub pairs_read: u64,
+    pub pairs_written: u64,
+    pub pairs_filtered: u64,
+    pub filtered_by_reason: [u64; FilterReason::ALL.len()],
+    pub unpaired: u64,
+    pub bytes_written: [u64; 4],
+    pub input_bytes_read: u64,
+}
+
+impl CounterSnapshot {
+    /// 按输入字节估算的完成百分比，`total_input_bytes` 为 0 时无法估算
+    pub fn percent(&self, total_input_bytes: u64) -> Option<f64> {
+        (total_input_bytes > 0)
+            .then(|| (self.input_bytes_read as f64 / total_input_bytes as f64 * 100.0).min(100.0))
+    }
+}
+
+
+/// 进度显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStyle {
+    /// 终端上原地刷新的进度条
+    Bar,
+    /// 每隔固定时间打印一行 `key=value` 日志
+    Log,
+}
+
+/// 每隔 `interval` 在后台线程调用一次 `tick(false)`，停止时最后调用一次 `tick(true)`
+pub struct Periodic {
+    stop: Option<Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Periodic {
+    pub fn spawn<F: FnMut(bool) + Send + 'static>(interval: Duration, mut tick: F) -> Self {
+        let (stop_tx, stop_rx) = bounded::<()>(0);
+        let handle = thread::spawn(move || loop {
+            let finished = !matches!(stop_rx.recv_timeout(interval), Err(RecvTimeoutError::Timeout));
+            tick(finished);
+            if finished {
+                return;
+            }
+        });
+        Periodic { stop: Some(stop_tx), handle: Some(handle) }
+    }
+
+    /// 停止并等待最后一次调用完成
+    pub fn finish(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Periodic {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// 后台进度显示线程，`finish()` 时输出最后一次状态
+pub struct ProgressReporter {
+    ticker: Periodic,
+}
+
+impl ProgressReporter {
+    /// `total_input_bytes` 为 0 时不显示百分比
+    pub fn spawn(style: ProgressStyle, counters: Arc<PipelineCounters>, total_input_bytes: u64, interval: Duration) -> Self {
+        let tick = match style {
+            ProgressStyle::Bar => Duration::from_millis(200),
+            ProgressStyle::Log => interval,
+        };
+        let start = Instant::now();
+        let ticker = Periodic::spawn(tick, move |finished| {
+            let snapshot = counters.snapshot();
+            let mut line = match style {
+                ProgressStyle::Bar => format!("\r{}", bar_line(&snapshot, total_input_bytes, start.elapsed())),
+                ProgressStyle::Log => format!("{}\n", log_line(&snapshot, total_input_bytes, start.elapsed())),
+            };
+            if finished && style == ProgressStyle::Bar {
+                line.push('\n');
+            }
+            let mut stderr = io::stderr().lock();
+            let _ = stderr.write_all(line.as_bytes());
+            let _ = stderr.flush();
+        });
+        ProgressReporter { ticker }
+    }
+
+    /// 停止刷新并输出最终状态
+    pub fn finish(self) {
+        self.ticker.finish();
+    }
+}
+
+fn rate(pairs: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { pairs as f64 / secs } else { 0.0 }
+}
+
+/// 日志模式的一行，例如
+/// `[2024-05-01T08:30:00Z] progress pairs_read=1000 pairs_written=990 rate=500/s percent=12.5`
+pub fn log_line(snapshot: &CounterSnapshot, total_input_bytes: u64, elapsed: Duration) -> String {
+    let mut line = format!("[{}] progress pairs_read={} pairs_written={} rate={:.0}/s",
+                           format_rfc3339(SystemTime::now()), snapshot.pairs_read, snapshot.pairs_written,
+                           rate(snapshot.pairs_read, elapsed));
+    if let Some(pct) = snapshot.percent(total_input_bytes) {
+        line.push_str(&format!(" percent={:.1}", pct));
+    }
+    line
+}
+
+fn bar_line(snapshot: &CounterSnapshot, total_input_bytes: u64, elapsed: Duration) -> String {
+    const WIDTH: usize = 30;
+    let stats = format!("{} pairs read, {} written, {:.0} pairs/s",
+                        snapshot.pairs_read, snapshot.pairs_written, rate(snapshot.pairs_read, elapsed));
+    match snapshot.percent(total_input_bytes) {
+        Some(pct) => {
+            let filled = (pct / 100.0 * WIDTH as f64) as usize;
+            format!("[{}{}] {:5.1}%  {}", "=".repeat(filled), " ".repeat(WIDTH - filled), pct, stats)
+        }
+        None => stats,
+    }
+}
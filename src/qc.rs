@@ -0,0 +1,172 @@
+// qc.rs - 输出文件的质量指标统计
+
+use crate::failure::{Classify, Failure};
+use crate::stats::BaseFractions;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// 条形码窗口每个位置的 A/C/G/T/N 计数
+///
+/// 每个处理线程各自累加，结束时合并。
+#[derive(Debug, Clone, Default)]
+pub struct BaseComposition {
+    counts: Vec<[u64; 5]>,
+}
+
+impl BaseComposition {
+    pub fn add(&mut self, seq: &[u8]) {
+        if self.counts.len() < seq.len() {
+            self.counts.resize(seq.len(), [0; 5]);
+        }
+        for (counts, &b) in self.counts.iter_mut().zip(seq) {
+            let idx = match b.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _    => 4,
+            };
+            counts[idx] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &BaseComposition) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), [0; 5]);
+        }
+        for (mine, theirs) in self.counts.iter_mut().zip(&other.counts) {
+            for (a, b) in mine.iter_mut().zip(theirs) {
+                *a += b;
+            }
+        }
+    }
+
+    /// 每个位置各碱基的比例
+    pub fn fractions(&self) -> Vec<BaseFractions> {
+        self.counts.iter().map(|c| {
+            let total = c.iter().sum::<u64>().max(1) as f64;
+            let f = |i: usize| c[i] as f64 / total;
+            BaseFractions { a: f(0), c: f(1), g: f(2), t: f(3), n: f(4) }
+        }).collect()
+    }
+}
+
+/// 单个 FASTQ 文件的质量指标累加器
+#[derive(Debug, Default, Clone)]
+pub struct FastqQc {
+    pub records: u64,
+    pub bases: u64,
+    /// 每个位置的质量值（Phred）之和与碱基数
+    qual_sum: Vec<u64>,
+    qual_count: Vec<u64>,
+    /// A/C/G/T 计数中 G+C 的个数
+    gc_bases: u64,
+    acgt_bases: u64,
+    /// 每条 read 的 GC 百分比（0–100）分布
+    pub gc_histogram: BTreeMap<u32, u64>,
+    pub length_distribution: BTreeMap<usize, u64>,
+    /// 序列频数；只在 `count_sequences` 为真时统计（条形码文件）
+    pub sequence_counts: HashMap<Vec<u8>, u64>,
+    pub count_sequences: bool,
+}
+
+impl FastqQc {
+    pub fn new(count_sequences: bool) -> Self {
+        FastqQc { count_sequences, ..Default::default() }
+    }
+
+    pub fn add(&mut self, seq: &[u8], qual: &[u8]) {
+        self.records += 1;
+        self.bases += seq.len() as u64;
+        *self.length_distribution.entry(seq.len()).or_insert(0) += 1;
+
+        if self.qual_sum.len() < qual.len() {
+            self.qual_sum.resize(qual.len(), 0);
+            self.qual_count.resize(qual.len(), 0);
+        }
+        for (i, &q) in qual.iter().enumerate() {
+            self.qual_sum[i] += q.saturating_sub(33) as u64;
+            self.qual_count[i] += 1;
+        }
+
+        let (mut gc, mut acgt) = (0u64, 0u64);
+        for &b in seq {
+            match b.to_ascii_uppercase() {
+                b'G' | b'C' => { gc += 1; acgt += 1; }
+                b'A' | b'T' => acgt += 1,
+                _ => {}
+            }
+        }
+        self.gc_bases += gc;
+        self.acgt_bases += acgt;
+        if let Some(pct) = (gc * 100).checked_div(acgt) {
+            *self.gc_histogram.entry(pct as u32).or_insert(0) += 1;
+        }
+
+        if self.count_sequences {
+            *self.sequence_counts.entry(seq.to_vec()).or_insert(0) += 1;
+        }
+    }
+
+    /// 每个位置的平均质量值
+    pub fn mean_quality_by_position(&self) -> Vec<f64> {
+        self.qual_sum.iter().zip(&self.qual_count)
+            .map(|(&s, &c)| if c > 0 { s as f64 / c as f64 } else { 0.0 })
+            .collect()
+    }
+
+    /// 全部 A/C/G/T 碱基中 G+C 的比例
+    pub fn gc_fraction(&self) -> f64 {
+        if self.acgt_bases == 0 { 0.0 } else { self.gc_bases as f64 / self.acgt_bases as f64 }
+    }
+
+    /// 序列频数，按次数降序、序列升序排列
+    pub fn sorted_sequence_counts(&self) -> Vec<(&[u8], u64)> {
+        let mut counts: Vec<(&[u8], u64)> = self.sequence_counts.iter().map(|(k, &v)| (k.as_slice(), v)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts
+    }
+
+    /// 把各项指标写成 `<dir>/<label>_*.tsv`
+    pub fn write_tsv(&self, dir: &Path, label: &str) -> Result<()> {
+        let create = |name: &str| -> Result<BufWriter<File>> {
+            let path = dir.join(format!("{}_{}.tsv", label, name));
+            Ok(BufWriter::new(File::create(&path).with_context(|| format!("Failed to create {}", path.display()))
+                .classify(Failure::OutputIo)?))
+        };
+
+        let mut w = create("quality_by_position")?;
+        writeln!(w, "position\tmean_quality")?;
+        for (i, q) in self.mean_quality_by_position().iter().enumerate() {
+            writeln!(w, "{}\t{:.2}", i + 1, q)?;
+        }
+        w.flush()?;
+
+        let mut w = create("gc_content")?;
+        writeln!(w, "gc_percent\treads")?;
+        for (gc, n) in &self.gc_histogram {
+            writeln!(w, "{}\t{}", gc, n)?;
+        }
+        w.flush()?;
+
+        let mut w = create("length_distribution")?;
+        writeln!(w, "length\treads")?;
+        for (len, n) in &self.length_distribution {
+            writeln!(w, "{}\t{}", len, n)?;
+        }
+        w.flush()?;
+
+        if self.count_sequences {
+            let mut w = create("barcode_counts")?;
+            writeln!(w, "barcode\treads")?;
+            for (bc, n) in self.sorted_sequence_counts() {
+                writeln!(w, "{}\t{}", String::from_utf8_lossy(bc), n)?;
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+}
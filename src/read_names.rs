@@ -0,0 +1,67 @@
+// read_names.rs - 按 read 名挑选 pair（--read-names）
+
+use crate::failure::{Classify, Failure};
+use crate::read_name;
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// 要保留的 read 名
+///
+/// 每行一个名字（支持 .gz）。名字与 FASTQ header 都按 [`read_name`] 规范化：
+/// 去掉空白后的注释（如 Casava 的 `1:N:0:ATCACG`）和 `/1`、`/2` 后缀。
+#[derive(Debug, Clone, Default)]
+pub struct ReadNameList {
+    names: HashSet<Vec<u8>>,
+}
+
+impl ReadNameList {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut names = HashSet::new();
+        for line in reader.lines() {
+            let line = line?;
+            // 允许直接粘贴 FASTQ header
+            let name = line.trim().strip_prefix('@').unwrap_or(line.trim());
+            if !name.is_empty() {
+                names.insert(read_name(name.as_bytes()).to_vec());
+            }
+        }
+        Ok(ReadNameList { names })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open read name list {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Box::new(MultiGzDecoder::new(f)),
+            _          => Box::new(f),
+        };
+        Self::from_reader(BufReader::new(reader))
+            .with_context(|| format!("Failed to load read name list {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// header 对应的 read 名是否在列表中；命中时返回规范化后的名字
+    pub fn lookup<'a>(&self, head: &'a [u8]) -> Option<&'a [u8]> {
+        let name = read_name(head);
+        self.names.contains(name).then_some(name)
+    }
+
+    /// 列表中不在 `found` 里的名字（排序后）
+    pub fn missing(&self, found: &HashSet<Vec<u8>>) -> Vec<&[u8]> {
+        let mut missing: Vec<&[u8]> = self.names.iter().filter(|n| !found.contains(*n)).map(|n| n.as_slice()).collect();
+        missing.sort_unstable();
+        missing
+    }
+}
@@ -0,0 +1,54 @@
+// readthrough.rs - 条形码窗口中的接头通读检测
+
+/// 片段过短时在 read 3' 端读到的 Tn5 mosaic end（ME 的反向互补）
+pub const ME_READTHROUGH: &[u8] = b"CTGTCTCTTATACACATCT";
+
+/// 判定为通读时与 ME 重叠的最少碱基数
+const MIN_OVERLAP: usize = 12;
+
+/// 允许的错配数（N 计为错配）
+const MAX_MISMATCHES: usize = 1;
+
+/// 对检测到通读的 pair 的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadthroughPolicy {
+    /// 不检测
+    #[default]
+    Off,
+    /// 只计数，照常输出
+    Count,
+    /// 按 [`FilterReason::AdapterReadthrough`](crate::progress::FilterReason::AdapterReadthrough) 过滤
+    Drop,
+}
+
+/// `window`（R2 上按读出方向的条形码窗口）中是否有 ME 通读
+///
+/// 通读的接头从窗口内某个位置开始一直延续到 read 末尾，也可能在窗口之前就已开始；
+/// 与 ME 重叠至少 [`MIN_OVERLAP`] 个碱基、最多 [`MAX_MISMATCHES`] 个错配时判定为通读。
+pub fn has_me_readthrough(window: &[u8]) -> bool {
+    let max_start = window.len().saturating_sub(MIN_OVERLAP);
+    // start 为 ME 相对窗口起点的位置；负值表示 ME 在窗口之前开始
+    let min_start = -(ME_READTHROUGH.len().saturating_sub(MIN_OVERLAP) as isize);
+    (min_start..=max_start as isize).any(|start| {
+        let (w, me) = if start >= 0 {
+            (&window[start as usize..], ME_READTHROUGH)
+        } else {
+            (window, &ME_READTHROUGH[(-start) as usize..])
+        };
+        let overlap = w.len().min(me.len());
+        overlap >= MIN_OVERLAP && mismatches(&w[..overlap], &me[..overlap]) <= MAX_MISMATCHES
+    })
+}
+
+/// `seq` 中与 `adapter` 完整比对、错配不超过 `max_mismatches` 的所有起始位置（从 5' 到 3'）
+///
+/// 不区分大小写，N 计为错配。
+pub fn find_adapter<'a>(seq: &'a [u8], adapter: &'a [u8], max_mismatches: usize) -> impl Iterator<Item = usize> + 'a {
+    let last = (seq.len() + 1).saturating_sub(adapter.len());
+    (0..last).filter(move |&i| mismatches(&seq[i..i + adapter.len()], adapter) <= max_mismatches)
+}
+
+/// 逐位比较的错配数；`b` 为大写的 ACGT 序列，所以 `a` 中的 N 总是错配
+fn mismatches(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).filter(|(x, y)| x.to_ascii_uppercase() != **y).count()
+}
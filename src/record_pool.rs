@@ -0,0 +1,88 @@
+// record_pool.rs - 复用 FASTQ 记录的缓冲区，减少高吞吐时反复分配、释放
+
+use crate::fastq_io::empty_record;
+use crossbeam_channel::{bounded, Receiver, Sender};
+use fastq::OwnedRecord;
+use std::ops::{Deref, DerefMut};
+
+/// 预留给 read 名称的字节数
+const HEAD_CAPACITY: usize = 64;
+
+/// 固定容量的记录池；可在线程间共享
+///
+/// 池空时 [`checkout`](RecordPool::checkout) 新分配一条记录，池满时归还的记录直接释放，所以容量只限制闲置的记录数。
+pub struct RecordPool {
+    tx: Sender<OwnedRecord>,
+    rx: Receiver<OwnedRecord>,
+}
+
+impl RecordPool {
+    /// 预先分配 `capacity` 条记录，序列与质量值各预留 `seq_len` 字节
+    pub fn new(capacity: usize, seq_len: usize) -> Self {
+        let (tx, rx) = bounded(capacity);
+        for _ in 0..capacity {
+            let _ = tx.try_send(OwnedRecord {
+                head: Vec::with_capacity(HEAD_CAPACITY),
+                seq: Vec::with_capacity(seq_len),
+                sep: None,
+                qual: Vec::with_capacity(seq_len),
+            });
+        }
+        RecordPool { tx, rx }
+    }
+
+    /// 取出一条空记录，drop 时放回池中
+    pub fn checkout(&self) -> PooledRecord<'_> {
+        let record = self.rx.try_recv().unwrap_or_else(|_| empty_record());
+        PooledRecord { record: Some(record), pool: self }
+    }
+
+    /// 清空记录并放回池中，保留各字段已分配的容量
+    pub fn recycle(&self, mut record: OwnedRecord) {
+        record.head.clear();
+        record.seq.clear();
+        record.qual.clear();
+        record.sep = None;
+        let _ = self.tx.try_send(record);
+    }
+
+    /// 池中闲置的记录数
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+/// 从 [`RecordPool`] 取出的记录
+pub struct PooledRecord<'a> {
+    record: Option<OwnedRecord>,
+    pool: &'a RecordPool,
+}
+
+impl PooledRecord<'_> {
+    /// 取走记录、不再自动放回，如发给其他线程；用完后可以用 [`RecordPool::recycle`] 放回
+    pub fn into_inner(mut self) -> OwnedRecord {
+        self.record.take().unwrap()
+    }
+}
+
+impl Deref for PooledRecord<'_> {
+    type Target = OwnedRecord;
+
+    fn deref(&self) -> &OwnedRecord {
+        self.record.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledRecord<'_> {
+    fn deref_mut(&mut self) -> &mut OwnedRecord {
+        self.record.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledRecord<'_> {
+    fn drop(&mut self) {
+        if let Some(record) = self.record.take() {
+            self.pool.recycle(record);
+        }
+    }
+}
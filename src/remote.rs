@@ -0,0 +1,226 @@
+// remote.rs - 直接写入对象存储（s3:// 与 gs://）
+//
+// 需要启用 `cloud` feature；凭证按 AWS / GCP SDK 的标准环境变量链获取。
+
+use anyhow::Result;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// 输出路径是否为对象存储 URL
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.starts_with("s3://") || s.starts_with("gs://"))
+}
+
+/// 一个进行中的多段上传
+///
+/// 写入端 [`RemoteWriter`] 只负责上传分段；只有调用 [`RemoteUpload::complete`]
+/// 后对象才会出现在 bucket 中。未完成就 drop 时会中止上传并清理已上传的分段，
+/// 因此运行中途出错不会留下半截文件。
+pub struct RemoteUpload {
+    #[cfg(feature = "cloud")]
+    inner: imp::Upload,
+}
+
+/// 向多段上传写入数据的 `Write` 实现
+pub struct RemoteWriter {
+    #[cfg(feature = "cloud")]
+    inner: imp::Writer,
+}
+
+/// 为 `url` 开始一次多段上传
+pub fn create(url: &Path) -> Result<(RemoteWriter, RemoteUpload)> {
+    #[cfg(feature = "cloud")]
+    {
+        let (writer, upload) = imp::create(url)?;
+        Ok((RemoteWriter { inner: writer }, RemoteUpload { inner: upload }))
+    }
+    #[cfg(not(feature = "cloud"))]
+    {
+        anyhow::bail!("Cannot write to {}: this build does not include the `cloud` feature", url.display())
+    }
+}
+
+/// 一次性上传一个小对象（run info、统计 JSON）
+pub fn put(url: &Path, contents: &[u8]) -> Result<()> {
+    #[cfg(feature = "cloud")]
+    {
+        imp::put(url, contents)
+    }
+    #[cfg(not(feature = "cloud"))]
+    {
+        let _ = contents;
+        anyhow::bail!("Cannot write to {}: this build does not include the `cloud` feature", url.display())
+    }
+}
+
+/// 本地路径原子写入，对象存储 URL 则直接上传
+pub fn write_output(path: &Path, contents: &[u8]) -> Result<()> {
+    if is_remote(path) {
+        put(path, contents)
+    } else {
+        crate::run_info::write_atomic(path, contents)
+    }
+}
+
+impl RemoteUpload {
+    /// 等待所有分段上传完成并提交对象
+    pub fn complete(self) -> Result<()> {
+        #[cfg(feature = "cloud")]
+        {
+            self.inner.complete()
+        }
+        #[cfg(not(feature = "cloud"))]
+        {
+            Ok(())
+        }
+    }
+}
+
+impl Write for RemoteWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        #[cfg(feature = "cloud")]
+        {
+            self.inner.write(buf)
+        }
+        #[cfg(not(feature = "cloud"))]
+        {
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "cloud")]
+mod imp {
+    use anyhow::{Context, Result};
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::path::Path as ObjectPath;
+    use object_store::{ObjectStore, PutPayload, WriteMultipart};
+    use std::io;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex, OnceLock};
+    use tokio::runtime::Runtime;
+
+    /// 每个分段的大小（S3 要求除最后一段外不小于 5 MiB）
+    const PART_SIZE: usize = 16 << 20;
+    /// 每个对象同时在途的分段数
+    const MAX_CONCURRENT_PARTS: usize = 4;
+
+    struct State {
+        upload: Option<WriteMultipart>,
+        /// 写入阶段遇到的第一个错误；存在时拒绝提交
+        error: Option<String>,
+    }
+
+    pub struct Writer {
+        state: Arc<Mutex<State>>,
+    }
+
+    pub struct Upload {
+        url: String,
+        state: Arc<Mutex<State>>,
+    }
+
+    fn runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()
+                .expect("failed to start the object store runtime")
+        })
+    }
+
+    /// 解析 `s3://bucket/key` / `gs://bucket/key`
+    fn open_store(url: &Path) -> Result<(Arc<dyn ObjectStore>, ObjectPath)> {
+        let s = url.to_str().context("Object store URL is not valid UTF-8")?;
+        let (scheme, rest) = s.split_once("://").context("Object store URL has no scheme")?;
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() || key.is_empty() {
+            anyhow::bail!("Object store URL must look like {}://bucket/key: {}", scheme, s);
+        }
+        let store: Arc<dyn ObjectStore> = match scheme {
+            "s3" => Arc::new(AmazonS3Builder::from_env().with_bucket_name(bucket).build()?),
+            "gs" => Arc::new(GoogleCloudStorageBuilder::from_env().with_bucket_name(bucket).build()?),
+            _ => anyhow::bail!("Unsupported object store scheme: {}", scheme),
+        };
+        Ok((store, ObjectPath::from(key)))
+    }
+
+    pub fn create(url: &Path) -> Result<(Writer, Upload)> {
+        let (store, key) = open_store(url)?;
+        let upload = runtime()
+            .block_on(store.put_multipart(&key))
+            .with_context(|| format!("Failed to start upload to {}", url.display()))?;
+        let state = Arc::new(Mutex::new(State {
+            upload: Some(WriteMultipart::new_with_chunk_size(upload, PART_SIZE)),
+            error: None,
+        }));
+        Ok((
+            Writer { state: Arc::clone(&state) },
+            Upload { url: url.display().to_string(), state },
+        ))
+    }
+
+    pub fn put(url: &Path, contents: &[u8]) -> Result<()> {
+        let (store, key) = open_store(url)?;
+        runtime()
+            .block_on(store.put(&key, PutPayload::from(contents.to_vec())))
+            .with_context(|| format!("Failed to upload {}", url.display()))?;
+        Ok(())
+    }
+
+    impl Writer {
+        pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            if let Some(e) = &state.error {
+                return Err(io::Error::other(e.clone()));
+            }
+            let upload = state.upload.as_mut().ok_or_else(|| io::Error::other("upload already finished"))?;
+            let rt = runtime();
+            // 在途分段过多时等待，避免整个输出堆积在内存中
+            if let Err(e) = rt.block_on(upload.wait_for_capacity(MAX_CONCURRENT_PARTS)) {
+                state.error = Some(e.to_string());
+                return Err(io::Error::other(e));
+            }
+            let _guard = rt.enter();
+            upload.write(buf);
+            Ok(buf.len())
+        }
+    }
+
+    impl Upload {
+        pub fn complete(self) -> Result<()> {
+            let mut state = self.state.lock().unwrap();
+            if let Some(e) = state.error.take() {
+                // upload 留在 state 中，由 drop 负责中止
+                anyhow::bail!("Upload to {} failed: {}", self.url, e);
+            }
+            let Some(mut upload) = state.upload.take() else { return Ok(()) };
+            let rt = runtime();
+            if let Err(e) = rt.block_on(upload.wait_for_capacity(0)) {
+                let _ = rt.block_on(upload.abort());
+                return Err(e).with_context(|| format!("Upload to {} failed", self.url));
+            }
+            rt.block_on(upload.finish()).with_context(|| format!("Failed to complete upload to {}", self.url))?;
+            Ok(())
+        }
+    }
+
+    impl Drop for Upload {
+        fn drop(&mut self) {
+            let upload = self.state.lock().map(|mut s| s.upload.take()).unwrap_or(None);
+            if let Some(upload) = upload {
+                if let Err(e) = runtime().block_on(upload.abort()) {
+                    log::warn!("Failed to abort incomplete upload to {}: {}", self.url, e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+// run.rs - 按文件拆分的完整流程（split 子命令的核心）：打开输入与输出、运行流水线、提交上传
+//
+// 参数检查、预扫描（--scan-bc-offset、--two-pass 等）与汇总报告留在命令行程序中；`run` 也可以在进程内直接调用。
+
+use crate::bam::{open_ubam, InputFormat};
+use crate::cleanup;
+use crate::failure::{Classify, Failure};
+use crate::fastq_io::open_fastq_counted;
+use crate::groups::{prefix_shard_names, GroupWriters};
+use crate::lanes::UNKNOWN_LANE;
+use crate::parallel_gz::ParallelGzWriter;
+use crate::pipeline::{read_fastq_pairs, read_fastq_pairs_with_plus_lines, run_pipeline_with_reader, PairSender,
+                      PipelineConfig, PipelineWriters};
+use crate::progress::PipelineCounters;
+use crate::remote::{self, RemoteUpload};
+use crate::stats::{OutputCompressionStats, RunStats};
+use crate::{CountingWriter, FinishWrite, PathContext};
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// 解压后的输入流
+pub type InputReader = Box<dyn Read + Send>;
+
+/// 各阶段的线程数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadTopology {
+    /// 每个 BGZF 输入的解压线程数
+    pub read: usize,
+    /// 处理线程数
+    pub process: usize,
+    /// 每个 gzip 输出的压缩线程数
+    pub write: usize,
+}
+
+impl ThreadTopology {
+    /// 未指定的值按 CPU 核数推算
+    pub fn resolve(read: Option<usize>, process: Option<usize>, write: Option<usize>) -> Self {
+        let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        let io_default = default_io_threads();
+        ThreadTopology {
+            read: read.unwrap_or(io_default),
+            process: process.unwrap_or((cores / 2).max(1)),
+            write: write.unwrap_or(io_default),
+        }
+    }
+}
+
+impl Default for ThreadTopology {
+    fn default() -> Self {
+        ThreadTopology::resolve(None, None, None)
+    }
+}
+
+/// 未指定时每个 BGZF 输入的解压线程数与每个输出的压缩线程数
+pub fn default_io_threads() -> usize {
+    let cores = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+    (cores / 8).clamp(1, 4)
+}
+
+/// 10x 命名的输出文件：`PREFIX_S1_L001_{read}_{suffix}.fastq[.gz]`
+pub fn output_path(prefix: &str, read: &str, number_suffix: &str, compress: bool) -> PathBuf {
+    lane_output_path(prefix, "L001", read, number_suffix, compress)
+}
+
+/// 文件名中 lane 为 `lane`（如 `L002`）的输出路径
+pub fn lane_output_path(prefix: &str, lane: &str, read: &str, number_suffix: &str, compress: bool) -> PathBuf {
+    let extension = if compress { ".fastq.gz" } else { ".fastq" };
+    PathBuf::from(format!("{}_S1_{}_{}_{}{}", prefix, lane, read, number_suffix, extension))
+}
+
+/// 一次拆分的输入、输出与处理参数
+pub struct RunConfig {
+    /// 每个 lane 一个 R1 输入，依次读取；未比对 BAM 输入时为 BAM 文件
+    pub r1_inputs: Vec<PathBuf>,
+    /// 与 `r1_inputs` 一一对应的 R2 输入；未比对 BAM 输入时为空
+    pub r2_inputs: Vec<PathBuf>,
+    pub input_format: InputFormat,
+    /// 代替第一个 R2 输入的读取端，如抽样后重放的 R2（FASTQ 输入）
+    pub first_r2: Option<InputReader>,
+    /// 输出文件名前缀，见 [`output_path`]
+    pub output_prefix: String,
+    pub number_suffix: String,
+    /// gzip 压缩输出
+    pub compress: bool,
+    /// 另外写出纠错前的原始条形码（`_CR_` 文件）
+    pub keep_raw_barcode: bool,
+    /// 未选中的 pair 写入 `PREFIX_unassigned_*`
+    pub unassigned_outputs: bool,
+    /// 改名时原名与新名的对照表（TSV）
+    pub rename_map: Option<PathBuf>,
+    /// 按组输出时同时打开的组数上限（每组 3 个文件）；按条形码前缀分片时不限
+    pub max_open_groups: usize,
+    /// 达到 `max_open_groups` 时关闭最久未写入的组，之后追加重新打开，而不是报错
+    pub evict_groups: bool,
+    /// 写入只计数的空设备，不创建任何文件
+    pub benchmark: bool,
+    /// 出错时把已创建的本地输出改名为 `<输出>.incomplete`；为 `false` 时留给调用方处理（如 [`cleanup`]）
+    pub mark_incomplete: bool,
+    /// 处理线程数取 `topology.process`，覆盖 `pipeline.threads`
+    pub topology: ThreadTopology,
+    pub pipeline: PipelineConfig,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            r1_inputs: Vec::new(),
+            r2_inputs: Vec::new(),
+            input_format: InputFormat::Fastq,
+            first_r2: None,
+            output_prefix: String::new(),
+            number_suffix: "001".to_string(),
+            compress: false,
+            keep_raw_barcode: false,
+            unassigned_outputs: false,
+            rename_map: None,
+            max_open_groups: 128,
+            evict_groups: false,
+            benchmark: false,
+            mark_incomplete: true,
+            topology: ThreadTopology::default(),
+            pipeline: PipelineConfig::default(),
+        }
+    }
+}
+
+impl RunConfig {
+    /// R1、R2、R3 输出的路径
+    pub fn main_outputs(&self) -> [PathBuf; 3] {
+        ["R1", "R2", "R3"].map(|read| output_path(&self.output_prefix, read, &self.number_suffix, self.compress))
+    }
+
+    /// 原始条形码输出；用 CR（SAM 中 raw barcode 的 tag）命名，避免被当作 R1/R2/R3 识别
+    pub fn raw_barcode_output(&self) -> Option<PathBuf> {
+        self.keep_raw_barcode.then(|| output_path(&self.output_prefix, "CR", &self.number_suffix, self.compress))
+    }
+
+    /// 未选中的 pair 的 R1、R2、R3 输出
+    pub fn unassigned_outputs(&self) -> Option<[PathBuf; 3]> {
+        self.unassigned_outputs.then(|| {
+            let prefix = format!("{}_unassigned", self.output_prefix);
+            ["R1", "R2", "R3"].map(|read| output_path(&prefix, read, &self.number_suffix, self.compress))
+        })
+    }
+
+    /// 按组或按 lane 输出：R1/R2/R3 不会收到记录，也不创建这三个文件
+    pub fn grouped_output(&self) -> bool {
+        self.pipeline.groups.is_some() || self.pipeline.split_lanes || self.pipeline.shard_by_bc_prefix.is_some()
+    }
+}
+
+/// 依次读取每个 lane 的 R1/R2 FASTQ
+///
+/// 给出 `first_r2` 时第一个 lane 的 R2 从它读取。
+pub fn read_fastq_lanes(
+    sender: &mut PairSender,
+    r1_paths: &[PathBuf],
+    r2_paths: &[PathBuf],
+    mut first_r2: Option<InputReader>,
+    read_threads: usize,
+    counters: &Arc<PipelineCounters>,
+    preserve_plus_line: bool,
+) -> Result<()> {
+    for (r1_path, r2_path) in r1_paths.iter().zip(r2_paths) {
+        let r1 = open_fastq_counted(r1_path, read_threads, Some(Arc::clone(counters)))?;
+        let r2 = match first_r2.take() {
+            Some(r2) => r2,
+            None => open_fastq_counted(r2_path, read_threads, Some(Arc::clone(counters)))?,
+        };
+        let result = if preserve_plus_line {
+            read_fastq_pairs_with_plus_lines(sender, r1, r2)
+        } else {
+            read_fastq_pairs(sender, r1, r2)
+        };
+        result.with_context(|| format!("Failed to read {} and {}", r1_path.display(), r2_path.display()))?;
+    }
+    Ok(())
+}
+
+/// 依次读取每个未比对 BAM 中相邻的 mate 对
+///
+/// 缺少 mate 或 FLAG 不一致的记录被跳过，数量在每个文件读完后以警告给出。
+pub fn read_ubam_lanes(
+    sender: &mut PairSender,
+    paths: &[PathBuf],
+    read_threads: usize,
+    counters: &Arc<PipelineCounters>,
+) -> Result<()> {
+    for path in paths {
+        let mut reader = open_ubam(path, read_threads, Some(Arc::clone(counters)))?;
+        while let Some((r1, r2)) = reader.next_pair().with_context(|| format!("Failed to read {}", path.display()))? {
+            sender.push(r1, r2)?;
+        }
+        sender.end_lane();
+        let skipped = &reader.stats;
+        if skipped.missing_mate > 0 || skipped.inconsistent_flags > 0 {
+            eprintln!("WARNING: {}: skipped {} records without an adjacent mate and {} with inconsistent pairing flags",
+                      path.display(), skipped.missing_mate, skipped.inconsistent_flags);
+        }
+    }
+    Ok(())
+}
+
+/// R1、R2、R3 输出压缩前后的字节数（见 [`create_writer`]）
+pub fn output_compression(counters: &PipelineCounters) -> OutputCompressionStats {
+    let load = |bytes: &[Arc<AtomicU64>; 4]| std::array::from_fn(|i| bytes[i].load(Ordering::Relaxed));
+    OutputCompressionStats::new(load(&counters.uncompressed_bytes_written), load(&counters.bytes_written))
+}
+
+/// 打开输出；写完后需调用 [`FinishWrite::finish`] 收尾。对象存储 URL 另外返回上传句柄，收尾后再调用 `complete()` 提交
+///
+/// 实际写出的字节数累加到 `bytes_written`；gzip 输出压缩前的字节数另外累加到 `uncompressed_bytes`。
+/// 写入错误带上路径，写入线程出错时能报告是哪个输出。
+pub fn create_writer(
+    path: &PathBuf,
+    write_threads: usize,
+    bytes_written: Arc<AtomicU64>,
+    uncompressed_bytes: Arc<AtomicU64>,
+) -> Result<(Box<dyn FinishWrite>, Option<RemoteUpload>)> {
+    let (file, upload): (Box<dyn Write + Send>, _) = if remote::is_remote(path) {
+        let (writer, upload) = remote::create(path).classify(Failure::OutputIo)?;
+        (Box::new(CountingWriter::new(writer, bytes_written)), Some(upload))
+    } else {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+            .classify(Failure::OutputIo)?;
+        cleanup::register(path);
+        (Box::new(CountingWriter::new(file, bytes_written)), None)
+    };
+
+    let writer: Box<dyn FinishWrite> = if path.extension().and_then(|s| s.to_str()) == Some("gz") && write_threads > 1 {
+        // 多线程压缩：输出为多 member gzip
+        let encoder = ParallelGzWriter::new(file, write_threads, Compression::new(1));
+        let encoder = CountingWriter::new(encoder, uncompressed_bytes);
+        Box::new(BufWriter::with_capacity(4 << 20, encoder))
+    } else if path.extension().and_then(|s| s.to_str()) == Some("gz") {
+        // ① 更低压缩等级：level 1≈4～5 倍速度
+        let encoder = CountingWriter::new(GzEncoder::new(file, Compression::new(1)), uncompressed_bytes);
+        // ② 更大的 BufWriter：1 MiB 而非 8 KiB，减少 sys‑call 次数
+        Box::new(BufWriter::with_capacity(4 << 20, encoder))
+    } else {
+        Box::new(BufWriter::with_capacity(4 << 20, file))
+    };
+    Ok((Box::new(PathContext::new(writer, path.display().to_string())), upload))
+}
+
+/// 以追加方式重新打开之前关闭的本地输出；gzip 输出追加一个新的 member
+pub fn append_writer(path: &PathBuf) -> Result<Box<dyn FinishWrite>> {
+    let file = std::fs::OpenOptions::new().append(true).open(path)
+        .with_context(|| format!("Failed to reopen {}", path.display())).classify(Failure::OutputIo)?;
+    let name = path.display().to_string();
+    if path.extension().and_then(|s| s.to_str()) == Some("gz") {
+        Ok(Box::new(PathContext::new(BufWriter::new(GzEncoder::new(file, Compression::new(1))), name)))
+    } else {
+        Ok(Box::new(PathContext::new(BufWriter::new(file), name)))
+    }
+}
+
+/// 流水线出错时把已创建的本地输出改名为 `<输出>.incomplete`，不留下看似完整的文件
+///
+/// 对象存储的输出不会提交，不需要处理。
+pub fn mark_incomplete(paths: &[PathBuf]) {
+    for path in paths.iter().filter(|path| !remote::is_remote(path) && path.exists()) {
+        let flagged = PathBuf::from(format!("{}.incomplete", path.display()));
+        match std::fs::rename(path, &flagged) {
+            Ok(()) => eprintln!("WARNING: Partial output left as {}", flagged.display()),
+            Err(e) => eprintln!("WARNING: Failed to rename partial output {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// 按 `config` 拆分：创建输出，读取全部输入并运行流水线，所有输出写完后提交对象存储上传
+///
+/// 返回的统计中 `wall_ms` 只包括这一步；按组输出时创建的文件在 [`RunStats::group_outputs`] 中。
+pub fn run(config: RunConfig) -> Result<RunStats> {
+    let [r1_output, r2_output, r3_output] = config.main_outputs();
+    let raw_bc_output = config.raw_barcode_output();
+    let unassigned_outputs = config.unassigned_outputs();
+    let grouped_output = config.grouped_output();
+    let RunConfig {
+        r1_inputs, r2_inputs, input_format, first_r2, output_prefix, number_suffix, compress, keep_raw_barcode: _,
+        unassigned_outputs: _, rename_map, max_open_groups, evict_groups, benchmark, mark_incomplete: mark_on_error,
+        topology, mut pipeline,
+    } = config;
+    pipeline.threads = topology.process;
+    let counters = Arc::clone(&pipeline.counters);
+    let split_lanes = pipeline.split_lanes;
+    let shard_by_bc_prefix = pipeline.shard_by_bc_prefix;
+    let preserve_plus_line = pipeline.preserve_plus_line;
+
+    // 第 i 个输出的两个字节计数器；`None` 时不计入按输出统计的字节数（metrics 只有固定的四个输出）
+    let output_counters = |output: Option<usize>| match output {
+        Some(i) => (Arc::clone(&counters.bytes_written[i]), Arc::clone(&counters.uncompressed_bytes_written[i])),
+        None => (Arc::default(), Arc::default()),
+    };
+    // 输出；benchmark 时写入只计数的空设备，不创建任何文件
+    let open_output = |path: &PathBuf, output: Option<usize>| -> Result<(Box<dyn FinishWrite>, Option<RemoteUpload>)> {
+        let (bytes_written, uncompressed_bytes) = output_counters(output);
+        if benchmark {
+            Ok((Box::new(CountingWriter::new(std::io::sink(), bytes_written)), None))
+        } else {
+            create_writer(path, topology.write, bytes_written, uncompressed_bytes)
+        }
+    };
+    let open_main_output = |path: &PathBuf, output: usize| -> Result<(Box<dyn FinishWrite>, Option<RemoteUpload>)> {
+        if grouped_output {
+            Ok((Box::new(std::io::sink()), None))
+        } else {
+            open_output(path, Some(output))
+        }
+    };
+    let mut uploads = Vec::new();
+    let (r1_writer, upload) = open_main_output(&r1_output, 0)?;
+    uploads.extend(upload);
+    let (r2_writer, upload) = open_main_output(&r2_output, 1)?;
+    uploads.extend(upload);
+    let (r3_writer, upload) = open_main_output(&r3_output, 2)?;
+    uploads.extend(upload);
+    let mut writers = PipelineWriters::new(r1_writer, r2_writer, r3_writer);
+    if let Some(path) = &raw_bc_output {
+        let (writer, upload) = open_output(path, Some(3))?;
+        uploads.extend(upload);
+        writers = writers.with_raw_barcode(writer);
+    }
+    if let Some(paths) = &unassigned_outputs {
+        let [r1, r2, r3] = paths.each_ref().map(|path| open_output(path, None));
+        let (r1, r2, r3) = (r1?, r2?, r3?);
+        uploads.extend([r1.1, r2.1, r3.1].into_iter().flatten());
+        writers = writers.with_unselected(r1.0, r2.0, r3.0);
+    }
+    if let Some(path) = &rename_map {
+        let (mut writer, upload) = open_output(path, None)?;
+        writeln!(writer, "original\trenamed")?;
+        uploads.extend(upload);
+        writers = writers.with_read_name_map(writer);
+    }
+    // 各组的输出在第一次出现时才创建；路径与上传记录下来供汇总和提交
+    let group_outputs: Arc<Mutex<Vec<PathBuf>>> = Arc::default();
+    let group_uploads: Arc<Mutex<Vec<RemoteUpload>>> = Arc::default();
+    if grouped_output {
+        let (prefix, suffix) = (output_prefix.clone(), number_suffix.clone());
+        let (outputs, uploads) = (Arc::clone(&group_outputs), Arc::clone(&group_uploads));
+        let sharded = shard_by_bc_prefix.is_some();
+        let factory = Box::new(move |group: &str, reopen: bool| -> Result<[Box<dyn FinishWrite>; 3]> {
+            // 按 lane 输出时写入 <前缀所在目录>/L00N/<前缀文件名>_S1_L00N_*，无法解析 lane 的写入 unknown/
+            let group_prefix = if split_lanes {
+                let prefix = PathBuf::from(&prefix);
+                let dir = prefix.parent().map(|p| p.join(group)).unwrap_or_else(|| PathBuf::from(group));
+                std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))
+                    .classify(Failure::OutputIo)?;
+                dir.join(prefix.file_name().unwrap_or_default()).to_string_lossy().into_owned()
+            } else if sharded {
+                format!("{}.shard_{}", prefix, group)
+            } else {
+                format!("{}_{}", prefix, group)
+            };
+            let lane = if split_lanes && group != UNKNOWN_LANE { group } else { "L001" };
+            let open = |read: &str| -> Result<Box<dyn FinishWrite>> {
+                let path = lane_output_path(&group_prefix, lane, read, &suffix, compress);
+                if reopen {
+                    return append_writer(&path);
+                }
+                // 所有组共用一个写入线程，不再为每个文件启动压缩线程
+                let (writer, upload) = create_writer(&path, 1, Arc::default(), Arc::default())?;
+                uploads.lock().extend(upload);
+                outputs.lock().push(path);
+                Ok(writer)
+            };
+            Ok([open("R1")?, open("R2")?, open("R3")?])
+        });
+        let group_writers = if let Some(k) = shard_by_bc_prefix {
+            // 分片数最多 4^4 + 1，全部保持打开
+            GroupWriters::new(factory, prefix_shard_names(k).len())
+        } else if evict_groups {
+            GroupWriters::new(factory, max_open_groups).with_lru_eviction()
+        } else {
+            GroupWriters::new(factory, max_open_groups)
+        };
+        writers = writers.with_groups(group_writers);
+    }
+
+    let read_threads = topology.read;
+    let reader_counters = Arc::clone(&counters);
+    let result = run_pipeline_with_reader(pipeline, move |sender| match input_format {
+        InputFormat::Ubam => read_ubam_lanes(sender, &r1_inputs, read_threads, &reader_counters),
+        _ => read_fastq_lanes(sender, &r1_inputs, &r2_inputs, first_r2, read_threads, &reader_counters,
+                              preserve_plus_line),
+    }, writers);
+    let mut stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            if !benchmark && mark_on_error {
+                // 按组输出时 R1/R2/R3 没有创建，同名文件可能是之前运行留下的
+                let main_outputs = [&r1_output, &r2_output, &r3_output].into_iter().filter(|_| !grouped_output);
+                let created: Vec<PathBuf> = main_outputs.chain(&raw_bc_output).chain(unassigned_outputs.iter().flatten())
+                    .chain(&rename_map).cloned()
+                    .chain(group_outputs.lock().iter().cloned())
+                    .collect();
+                mark_incomplete(&created);
+            }
+            return Err(e);
+        }
+    };
+    if compress && !benchmark {
+        stats.output_compression = Some(output_compression(&counters));
+    }
+    // 所有输出都写完后才提交对象存储上传；之前任何一步出错都会中止全部上传
+    for upload in uploads.into_iter().chain(std::mem::take(&mut *group_uploads.lock())) {
+        upload.complete()?;
+    }
+    stats.group_outputs = std::mem::take(&mut *group_outputs.lock());
+    Ok(stats)
+}
@@ -0,0 +1,146 @@
+// run_info.rs - 运行元数据（可复现性审计）
+
+use crate::failure::{Classify, Failure};
+use crate::stats::RunStats;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `PREFIX_run_info.json` 的内容
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RunInfo {
+    /// 完整命令行（含程序名）
+    pub command_line: Vec<String>,
+    /// 解析并补全默认值后的参数
+    pub options: serde_json::Value,
+    pub tool_version: String,
+    pub git_hash: String,
+    /// RFC 3339 UTC 时间
+    pub start_time: String,
+    pub end_time: String,
+    pub hostname: String,
+    pub inputs: Vec<InputFileInfo>,
+    pub outputs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InputFileInfo {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// 仅在要求计算校验和时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha256: Option<String>,
+}
+
+impl InputFileInfo {
+    pub fn collect<P: AsRef<Path>>(path: P, checksum: bool) -> Result<Self> {
+        let path = path.as_ref();
+        let size_bytes = fs::metadata(path)
+            .with_context(|| format!("Failed to stat {}", path.display()))?
+            .len();
+        let sha256 = if checksum { Some(sha256_file(path)?) } else { None };
+        Ok(InputFileInfo { path: path.to_path_buf(), size_bytes, sha256 })
+    }
+}
+
+/// 计算文件的 SHA-256（十六进制小写）
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let mut f = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// 当前主机名，获取失败时为空字符串
+pub fn hostname() -> String {
+    gethostname::gethostname().to_string_lossy().into_owned()
+}
+
+/// 把时间格式化为 RFC 3339 UTC，例如 `2024-05-01T08:30:00Z`
+pub fn format_rfc3339(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // 由 1970-01-01 起的天数换算公历日期（Howard Hinnant 的 civil_from_days）
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// 先写入同目录下的临时文件再重命名，保证读者看到的要么是完整文件要么没有文件
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut f = File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))
+            .classify(Failure::OutputIo)?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path).with_context(|| format!("Failed to rename {} to {}", tmp.display(), path.display()))?;
+    Ok(())
+}
+
+impl RunInfo {
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        crate::remote::write_output(path.as_ref(), &json)
+    }
+}
+
+/// `--done-file` 的内容：只在运行成功、所有输出都写完后写出，供工作流判断上一次运行是否完整
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DoneFile {
+    /// 总是 0；失败时不写
+    pub exit_status: i32,
+    pub pairs_read: u64,
+    pub pairs_written: u64,
+    pub pairs_filtered: u64,
+    pub unpaired: u64,
+    /// RFC 3339 UTC 时间
+    pub completed_at: String,
+}
+
+impl DoneFile {
+    pub fn new(stats: &RunStats, completed_at: SystemTime) -> Self {
+        DoneFile {
+            exit_status: 0,
+            pairs_read: stats.pairs_read,
+            pairs_written: stats.pairs_written,
+            pairs_filtered: stats.pairs_filtered,
+            unpaired: stats.unpaired,
+            completed_at: format_rfc3339(completed_at),
+        }
+    }
+
+    /// 原子写入（对象存储则直接上传），读者不会看到写了一半的文件
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut json = serde_json::to_vec(self)?;
+        json.push(b'\n');
+        crate::remote::write_output(path.as_ref(), &json)
+    }
+}
@@ -0,0 +1,337 @@
+// simulate.rs - 生成合成的 MGI scATAC-seq R1/R2 FASTQ（`simulate` 子命令与 gen_test_data），用于测试与基准
+
+use crate::failure::{Classify, Failure};
+use crate::pipeline::{BARCODE_LEN, R2_LEN};
+use crate::reverse_complement;
+use anyhow::{bail, Context, Result};
+use fastq::OwnedRecord;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const BASES: &[u8; 4] = b"ACGT";
+
+/// 模拟参数；相同的参数与 `seed` 生成相同的 read
+#[derive(Debug, Clone)]
+pub struct SimulateConfig {
+    pub pairs: u64,
+    pub r1_len: usize,
+    pub r2_len: usize,
+    /// 条形码在 R2 中的起点 [default: R2 的最后 16 bp]
+    pub barcode_offset: Option<usize>,
+    /// R2 中的条形码是否反向互补（MGI 的默认结构）
+    pub reverse_complement: bool,
+    /// 条形码来源；为 `None` 时随机生成 `random_barcodes` 个
+    pub barcodes: Option<Vec<Vec<u8>>>,
+    pub random_barcodes: u64,
+    /// 条形码中每个碱基被替换成另一个碱基的概率
+    pub barcode_error_rate: f64,
+    /// 条形码中每个碱基被替换成 N 的概率（在替换之后）
+    pub barcode_n_rate: f64,
+    /// 带有缺陷（见 [`Defect`]）的 pair 的比例
+    pub defect_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for SimulateConfig {
+    fn default() -> Self {
+        SimulateConfig {
+            pairs: 1000,
+            r1_len: 50,
+            r2_len: R2_LEN,
+            barcode_offset: None,
+            reverse_complement: true,
+            barcodes: None,
+            random_barcodes: 100,
+            barcode_error_rate: 0.0,
+            barcode_n_rate: 0.0,
+            defect_rate: 0.0,
+            seed: 42,
+        }
+    }
+}
+
+/// 故意加入的缺陷；`split` 按对应的原因过滤这些 pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defect {
+    /// R2 缺少条形码长度的碱基（`length`）
+    TruncatedR2,
+    /// R2 的名字与 R1 不同（`header_mismatch`）
+    NameMismatch,
+    /// 条形码不在条形码集合中（有白名单时为 `barcode_no_match`）
+    UnknownBarcode,
+}
+
+impl Defect {
+    pub const ALL: [Defect; 3] = [Defect::TruncatedR2, Defect::NameMismatch, Defect::UnknownBarcode];
+
+    /// 真值表中的名称
+    pub fn label(self) -> &'static str {
+        match self {
+            Defect::TruncatedR2 => "truncated_r2",
+            Defect::NameMismatch => "name_mismatch",
+            Defect::UnknownBarcode => "unknown_barcode",
+        }
+    }
+}
+
+/// 一对模拟的 read
+#[derive(Debug, Clone)]
+pub struct SimulatedPair {
+    /// 不含 `/1`、`/2` 的 read 名
+    pub name: String,
+    pub r1: OwnedRecord,
+    pub r2: OwnedRecord,
+    /// 加入测序错误之前的条形码
+    pub barcode: Vec<u8>,
+    pub defect: Option<Defect>,
+}
+
+/// 按 [`SimulateConfig`] 逐对生成 read 的迭代器
+pub struct Simulator {
+    config: SimulateConfig,
+    rng: StdRng,
+    barcodes: Vec<Vec<u8>>,
+    barcode_offset: usize,
+    next: u64,
+}
+
+impl Simulator {
+    pub fn new(config: SimulateConfig) -> Result<Self> {
+        if config.r1_len == 0 {
+            bail!("R1 length must be at least 1");
+        }
+        if config.r2_len < BARCODE_LEN {
+            bail!("R2 length {} is shorter than the {} bp barcode", config.r2_len, BARCODE_LEN);
+        }
+        let barcode_offset = config.barcode_offset.unwrap_or(config.r2_len - BARCODE_LEN);
+        if barcode_offset + BARCODE_LEN > config.r2_len {
+            bail!("Barcode at offset {} does not fit in a {} bp R2", barcode_offset, config.r2_len);
+        }
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let barcodes = match &config.barcodes {
+            Some(barcodes) if barcodes.is_empty() => bail!("No barcodes to draw from"),
+            Some(barcodes) => barcodes.clone(),
+            None if config.random_barcodes == 0 => bail!("No barcodes to draw from"),
+            None => random_barcodes(&mut rng, config.random_barcodes),
+        };
+        Ok(Simulator { config, rng, barcodes, barcode_offset, next: 0 })
+    }
+
+    /// 抽取条形码的集合（可作为 `--whitelist`）
+    pub fn barcodes(&self) -> &[Vec<u8>] {
+        &self.barcodes
+    }
+}
+
+impl Iterator for Simulator {
+    type Item = SimulatedPair;
+
+    fn next(&mut self) -> Option<SimulatedPair> {
+        if self.next == self.config.pairs {
+            return None;
+        }
+        let i = self.next;
+        self.next += 1;
+        let config = &self.config;
+        let rng = &mut self.rng;
+        // Illumina 格式的名字，坐标互相远离，不会被当作光学重复
+        let name = format!("SIM:1:FCSIM:1:1101:{}:{}", 1000 + (i % 1000) * 20, 1000 + (i / 1000) * 20);
+        let r1 = random_seq(rng, config.r1_len);
+        let mut r2 = random_seq(rng, config.r2_len - BARCODE_LEN);
+        let mut barcode = self.barcodes[rng.random_range(0..self.barcodes.len())].clone();
+        let mut observed = barcode.clone();
+        add_errors(rng, &mut observed, config.barcode_error_rate, config.barcode_n_rate);
+        let defect = (config.defect_rate > 0.0 && rng.random_bool(config.defect_rate))
+            .then(|| Defect::ALL[rng.random_range(0..Defect::ALL.len())]);
+        if defect == Some(Defect::UnknownBarcode) {
+            barcode = random_seq(rng, BARCODE_LEN);
+            observed = barcode.clone();
+        }
+        let observed = if config.reverse_complement { reverse_complement(&observed) } else { observed };
+        r2.splice(self.barcode_offset..self.barcode_offset, observed);
+        if defect == Some(Defect::TruncatedR2) {
+            r2.truncate(r2.len() - BARCODE_LEN);
+        }
+        let r2_name = match defect {
+            Some(Defect::NameMismatch) => name.replacen(":1101:", ":1102:", 1),
+            _ => name.clone(),
+        };
+        let record = |head: String, seq: Vec<u8>| OwnedRecord {
+            head: head.into_bytes(),
+            qual: vec![b'I'; seq.len()],
+            seq,
+            sep: None,
+        };
+        Some(SimulatedPair {
+            r1: record(format!("{}/1", name), r1),
+            r2: record(format!("{}/2", r2_name), r2),
+            name,
+            barcode,
+            defect,
+        })
+    }
+}
+
+/// 读取条形码列表：每行一个 16 bp 条形码，去掉 `-1` 等后缀，`#` 开头的行跳过；支持 .gz
+pub fn load_barcodes(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let f = File::open(path).with_context(|| format!("Failed to open whitelist {}", path.display()))
+        .classify(Failure::InputUnreadable)?;
+    let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+        Some("gz") => Box::new(MultiGzDecoder::new(f)),
+        _          => Box::new(f),
+    };
+    let mut barcodes = Vec::new();
+    for (line_no, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let bc = line.split(|c: char| c.is_whitespace() || c == '-').next().unwrap_or("");
+        if bc.is_empty() || bc.starts_with('#') {
+            continue;
+        }
+        if bc.len() != BARCODE_LEN {
+            bail!("{}: line {}: expected a {} bp barcode, found '{}'", path.display(), line_no + 1, BARCODE_LEN, bc);
+        }
+        barcodes.push(bc.as_bytes().to_ascii_uppercase());
+    }
+    if barcodes.is_empty() {
+        bail!("Whitelist {} is empty", path.display());
+    }
+    Ok(barcodes)
+}
+
+/// `n` 个互不相同的随机条形码
+fn random_barcodes(rng: &mut StdRng, n: u64) -> Vec<Vec<u8>> {
+    let mut seen = HashSet::new();
+    let mut barcodes = Vec::new();
+    while (barcodes.len() as u64) < n {
+        let bc = random_seq(rng, BARCODE_LEN);
+        if seen.insert(bc.clone()) {
+            barcodes.push(bc);
+        }
+    }
+    barcodes
+}
+
+fn random_seq(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| BASES[rng.random_range(0..4)]).collect()
+}
+
+/// 按 `substitution_rate` 把碱基随机替换成另一个碱基，再按 `n_rate` 替换成 N
+fn add_errors(rng: &mut StdRng, seq: &mut [u8], substitution_rate: f64, n_rate: f64) {
+    for base in seq.iter_mut() {
+        if substitution_rate > 0.0 && rng.random_bool(substitution_rate) {
+            let others: Vec<u8> = BASES.iter().copied().filter(|b| b != base).collect();
+            *base = others[rng.random_range(0..others.len())];
+        }
+        if n_rate > 0.0 && rng.random_bool(n_rate) {
+            *base = b'N';
+        }
+    }
+}
+
+/// 输出文件或标准输出；gzip 输出（`.gz` 扩展名）需要 [`Output::finish`] 写完尾部
+pub enum Output {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gz(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    pub fn create(path: &Path) -> Result<Self> {
+        let f = File::create(path).with_context(|| format!("Failed to create {}", path.display()))
+            .classify(Failure::OutputIo)?;
+        Ok(match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Output::Gz(GzEncoder::new(BufWriter::new(f), Compression::fast())),
+            _          => Output::Plain(BufWriter::new(Box::new(f))),
+        })
+    }
+
+    pub fn stdout() -> Self {
+        Output::Plain(BufWriter::new(Box::new(std::io::stdout())))
+    }
+
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Output::Plain(mut w) => w.flush()?,
+            Output::Gz(w) => w.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(w) => w.write(buf),
+            Output::Gz(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(w) => w.flush(),
+            Output::Gz(w) => w.flush(),
+        }
+    }
+}
+
+/// 写出一条 FASTQ 记录（`+` 行不重复名字）
+pub fn write_record<W: Write + ?Sized>(out: &mut W, record: &OwnedRecord) -> std::io::Result<()> {
+    out.write_all(b"@")?;
+    out.write_all(&record.head)?;
+    out.write_all(b"\n")?;
+    out.write_all(&record.seq)?;
+    out.write_all(b"\n+\n")?;
+    out.write_all(&record.qual)?;
+    out.write_all(b"\n")
+}
+
+/// 一行条形码一个地写出
+pub fn write_barcodes(path: &Path, barcodes: &[Vec<u8>]) -> Result<()> {
+    let mut out = Output::create(path)?;
+    for bc in barcodes {
+        out.write_all(bc)?;
+        writeln!(out)?;
+    }
+    out.finish()
+}
+
+/// 模拟结果的计数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimulationSummary {
+    pub pairs: u64,
+    /// 按 [`Defect::ALL`] 顺序
+    pub defects: [u64; 3],
+}
+
+/// 把 `simulator` 生成的 read 对写入 `r1`、`r2`（`.gz` 时压缩）；给出 `truth` 时另写真值表
+/// （`name<TAB>barcode<TAB>defect`，没有缺陷的为 `none`）
+pub fn simulate_to_files(simulator: Simulator, r1: &Path, r2: &Path, truth: Option<&Path>) -> Result<SimulationSummary> {
+    let (mut r1_out, mut r2_out) = (Output::create(r1)?, Output::create(r2)?);
+    let mut truth_out = truth.map(Output::create).transpose()?;
+    if let Some(out) = &mut truth_out {
+        writeln!(out, "name\tbarcode\tdefect")?;
+    }
+    let mut summary = SimulationSummary::default();
+    for pair in simulator {
+        write_record(&mut r1_out, &pair.r1)?;
+        write_record(&mut r2_out, &pair.r2)?;
+        if let Some(out) = &mut truth_out {
+            writeln!(out, "{}\t{}\t{}", pair.name, String::from_utf8_lossy(&pair.barcode),
+                     pair.defect.map_or("none", Defect::label))?;
+        }
+        summary.pairs += 1;
+        if let Some(defect) = pair.defect {
+            summary.defects[defect as usize] += 1;
+        }
+    }
+    r1_out.finish()?;
+    r2_out.finish()?;
+    truth_out.map_or(Ok(()), Output::finish)?;
+    Ok(summary)
+}
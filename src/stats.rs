@@ -0,0 +1,469 @@
+// stats.rs - 运行统计（JSON 输出）
+
+use crate::barcode_counts::BarcodeCounter;
+use crate::complexity::{projected_unique, saturation};
+use crate::progress::{CounterSnapshot, FilterReason};
+use crate::whitelist::CorrectionStats;
+use crate::WorkerStats;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `--stats-json` 的内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RunStats {
+    pub pairs_read: u64,
+    pub pairs_written: u64,
+    pub pairs_filtered: u64,
+    /// 按原因的过滤计数（`length`、`header_mismatch`、`barcode_no_match`）
+    #[serde(default)]
+    pub filtered_by_reason: BTreeMap<String, u64>,
+    /// 另一个文件中没有对应记录的 read 数，计入 `pairs_read`：
+    /// `pairs_written + pairs_filtered + unpaired == pairs_read`（soft filter 时被标记的 pair 同时计入前两项）
+    #[serde(default)]
+    pub unpaired: u64,
+    /// 第一条没有配对的 read 的名字
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub first_unpaired: Option<String>,
+    /// 按组输出时创建的 FASTQ 文件（见 [`crate::run::run`]），不写入 JSON
+    #[serde(skip)]
+    pub group_outputs: Vec<PathBuf>,
+    /// 每个 lane 读到的 pair 数，顺序与输入一致
+    pub lane_pairs: Vec<u64>,
+    /// 按 read header 中的 lane 字段分组的统计
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub lanes: Vec<LaneStats>,
+    /// 过滤比例最高的几个 tile（不含被 `--exclude-tiles` 丢弃的 pair）
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub worst_tiles: Vec<TileStats>,
+    /// 读入的 R1、R2 记录（header、序列、质量值）的数据量，单位 MB（10^6 字节）
+    #[serde(default)]
+    pub r1_read_mb: f64,
+    #[serde(default)]
+    pub r2_read_mb: f64,
+    /// 按流水线耗时计算的读入速度（MB/s），与 read 长度无关，便于比较不同数据集
+    #[serde(default)]
+    pub total_throughput_mb_s: f64,
+    /// 写出的 R1、R2、R3 文件大小（压缩后），单位 MB
+    #[serde(default)]
+    pub r1_output_mb: f64,
+    #[serde(default)]
+    pub r2_output_mb: f64,
+    #[serde(default)]
+    pub r3_output_mb: f64,
+    /// 仅在输出 gzip 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub output_compression: Option<OutputCompressionStats>,
+    /// 仅在启用白名单纠错时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub barcode_correction: Option<BarcodeCorrectionStats>,
+    /// 仅在给出 `--read-names` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub read_names: Option<ReadNameStats>,
+    /// 仅在给出 `--select-barcodes` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub barcode_selection: Option<BarcodeSelectionStats>,
+    /// 仅在检测接头通读时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub adapter_readthrough: Option<ReadthroughStats>,
+    /// 仅在给出 `--min-reads-per-barcode` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub barcode_min_reads: Option<BarcodeMinReadsStats>,
+    /// 每组写出的 pair 数（仅在给出 `--split-by` 时存在）
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub group_counts: Option<BTreeMap<String, u64>>,
+    /// 写出的不同条形码（纠错后）个数
+    #[serde(default)]
+    pub distinct_barcodes: DistinctBarcodes,
+    /// 仅在统计条形码计数时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub library_complexity: Option<LibraryComplexity>,
+    /// 仅在给出 `--mark-optical-dups` 时存在；与 `library_complexity` 中的（PCR）重复估计分开
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub optical_duplicates: Option<OpticalDuplicateStats>,
+    /// 仅在给出 `--anchor` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub anchor: Option<AnchorStats>,
+    /// 仅在给出 `--overrepresented-every` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub overrepresented: Option<OverrepresentedStats>,
+    /// 仅在给出 `--contaminants` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub contaminants: Option<ContaminantStats>,
+    /// 仅在给出 `--check-duplicate-names` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub duplicate_names: Option<DuplicateNameStats>,
+    /// 条形码窗口（纠错前）每个位置的碱基比例
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub barcode_composition: Vec<BaseFractions>,
+    pub wall_ms: u64,
+    /// `--two-pass` 与 `--min-reads-per-barcode` 预先读取输入（统计条形码）的耗时，已包含在 `wall_ms` 中
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub first_pass_ms: Option<u64>,
+    pub timings: StageTimings,
+    /// 每个处理线程的记录数与耗时
+    #[serde(default)]
+    pub worker_stats: Vec<WorkerStats>,
+    /// 仅在给出 `--adaptive-batch` 时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub adaptive_batch: Option<AdaptiveBatchStats>,
+    /// 处理线程处理过的 batch 数，以及其中所有 pair 都被过滤、没有输出的 batch 数
+    #[serde(default)]
+    pub batches_processed: u64,
+    #[serde(default)]
+    pub empty_output_batches: u64,
+    /// 每个条形码的 read 数（仅在要求统计时存在，另行输出，不写入 JSON）
+    #[serde(skip)]
+    pub barcode_counts: Option<BarcodeCounter>,
+}
+
+/// 白名单纠错计数
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BarcodeCorrectionStats {
+    pub exact: u64,
+    pub corrected: u64,
+    pub n_rescued: u64,
+    pub no_match: u64,
+    pub masked_reads: u64,
+    pub masked_rescued: u64,
+    pub raw_differs: u64,
+    /// `--allow-truncated-bc`：截短的条形码唯一命中、有多个候选（计入 `no_match`）的 read 数
+    #[serde(default)]
+    pub truncated_rescued: u64,
+    #[serde(default)]
+    pub truncated_ambiguous: u64,
+    /// `--correct-indels`：尝试 indel 纠错的 read 数、救回的 read 数与耗时（各线程累加）
+    #[serde(default)]
+    pub indel_attempted: u64,
+    #[serde(default)]
+    pub indel_corrected: u64,
+    #[serde(default)]
+    pub indel_ms: u64,
+}
+
+impl From<&CorrectionStats> for BarcodeCorrectionStats {
+    fn from(s: &CorrectionStats) -> Self {
+        BarcodeCorrectionStats {
+            exact: s.exact as u64,
+            corrected: s.corrected as u64,
+            n_rescued: s.n_rescued as u64,
+            no_match: s.no_match as u64,
+            masked_reads: s.masked_reads as u64,
+            masked_rescued: s.masked_rescued as u64,
+            raw_differs: s.rewritten as u64,
+            truncated_rescued: s.truncated_rescued as u64,
+            truncated_ambiguous: s.truncated_ambiguous as u64,
+            indel_attempted: s.indel_attempted as u64,
+            indel_corrected: s.indel_corrected as u64,
+            indel_ms: s.indel_ns / 1_000_000,
+        }
+    }
+}
+
+/// 一个 lane（read header 中的 lane 字段）的统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LaneStats {
+    /// lane 编号；header 不是 Illumina 格式时为 `unknown`
+    pub lane: String,
+    pub pairs_read: u64,
+    pub pairs_filtered: u64,
+    pub filtered_by_reason: BTreeMap<String, u64>,
+    /// 到达白名单这一步的 pair 中条形码命中（含纠错）的比例；仅在启用白名单纠错时存在
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub whitelist_match_rate: Option<f64>,
+}
+
+/// 一个 flowcell tile 的统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TileStats {
+    pub lane: u32,
+    pub tile: u32,
+    pub pairs_read: u64,
+    pub pairs_filtered: u64,
+    pub filter_rate: f64,
+}
+
+/// `--read-names` 列表的命中情况
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReadNameStats {
+    /// 列表中（去重后）的名字数
+    pub listed: u64,
+    /// 在输入中找到的名字数
+    pub found: u64,
+    /// 未找到的名字（排序后）
+    pub missing: Vec<String>,
+}
+
+/// `--select-barcodes` 的挑选结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BarcodeSelectionStats {
+    /// 列表中（去重后）的条形码数
+    pub listed: u64,
+    /// 条形码在列表中的 pair 数
+    pub selected_pairs: u64,
+    /// 其余通过过滤的 pair 数（丢弃或写入 unassigned 输出）
+    pub unselected_pairs: u64,
+    /// 列表中一次也没有出现的条形码数
+    pub never_observed: u64,
+}
+
+/// gzip 输出压缩前的数据量与压缩比
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OutputCompressionStats {
+    pub r1_raw_mb: f64,
+    pub r2_raw_mb: f64,
+    pub r3_raw_mb: f64,
+    /// R1、R2、R3 合计的压缩前 / 压缩后字节数
+    pub ratio: f64,
+}
+
+impl OutputCompressionStats {
+    /// `raw` 与 `compressed` 均为 R1、R2、R3 的字节数
+    pub fn new(raw: [u64; 3], compressed: [u64; 3]) -> Self {
+        let (raw_total, compressed_total) = (raw.iter().sum::<u64>(), compressed.iter().sum::<u64>());
+        OutputCompressionStats {
+            r1_raw_mb: raw[0] as f64 / 1e6,
+            r2_raw_mb: raw[1] as f64 / 1e6,
+            r3_raw_mb: raw[2] as f64 / 1e6,
+            ratio: if compressed_total > 0 { raw_total as f64 / compressed_total as f64 } else { 0.0 },
+        }
+    }
+}
+
+/// `--min-reads-per-barcode` 的过滤结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BarcodeMinReadsStats {
+    pub min_reads: u64,
+    /// 总 read 数低于阈值的条形码数
+    pub removed_barcodes: u64,
+    /// 这些条形码的 pair 数（丢弃或写入 unassigned 输出）
+    pub removed_pairs: u64,
+}
+
+/// 条形码窗口中的接头通读
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReadthroughStats {
+    /// 检测到通读的 pair 数（无论是否被过滤）
+    pub pairs: u64,
+    /// 占读入 pair 数的比例
+    pub fraction: f64,
+    /// 这些 pair 是否被过滤
+    pub dropped: bool,
+}
+
+/// 按处理耗时动态调整的 batch 大小（`--adaptive-batch`）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdaptiveBatchStats {
+    pub initial: u64,
+    /// 结束时的大小
+    pub final_size: u64,
+    /// 运行中出现过的最小、最大值
+    pub smallest: u64,
+    pub largest: u64,
+    pub adjustments: u64,
+    /// 处理一个 batch 的目标耗时范围
+    pub target_min_ms: u64,
+    pub target_max_ms: u64,
+}
+
+/// 按锚定序列定位条形码（`--anchor`）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AnchorStats {
+    pub sequence: String,
+    pub max_mismatches: usize,
+    /// 找不到锚定序列而被过滤的 pair 数
+    pub not_found: u64,
+    /// 有多处锚定序列、取了最靠 3' 端一处的 pair 数
+    pub ambiguous: u64,
+}
+
+/// 光学（ExAmp）重复
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OpticalDuplicateStats {
+    /// 判定为重复的最大坐标距离（像素）
+    pub max_distance: u32,
+    /// 检查过的 pair 数（写入主输出或分组输出、header 中有坐标的 pair）
+    pub examined: u64,
+    pub duplicates: u64,
+    /// `duplicates / examined`
+    pub fraction: f64,
+    /// 这些 pair 是否被过滤
+    pub dropped: bool,
+}
+
+/// 基因组输出（R3）中的过表达序列，按抽样的 read 统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OverrepresentedStats {
+    /// 每隔多少条写出的 read 抽一条
+    pub sample_every: u64,
+    pub sampled: u64,
+    /// 报告阈值（占 `sampled` 的比例）
+    pub min_fraction: f64,
+    /// 跟踪的序列数达到上限时淘汰的序列数；不为 0 时计数可能偏低
+    pub evicted: u64,
+    /// 按计数从高到低排列
+    pub sequences: Vec<OverrepresentedSequence>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OverrepresentedSequence {
+    /// read 开头的 50 bp（read 更短时为整条）
+    pub sequence: String,
+    pub count: u64,
+    /// `count / sampled`
+    pub fraction: f64,
+}
+
+/// 已知污染序列的抽样筛查结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ContaminantStats {
+    /// 每隔多少条写出的 read 抽一条
+    pub sample_every: u64,
+    pub sampled: u64,
+    pub kmer_len: usize,
+    /// 与污染序列共有这么多个 k-mer 时记为命中
+    pub min_kmers: usize,
+    /// 按污染文件中的顺序
+    pub contaminants: Vec<ContaminantHit>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ContaminantHit {
+    pub name: String,
+    /// 命中的抽样 read 数
+    pub reads: u64,
+    /// `reads / sampled`
+    pub fraction: f64,
+}
+
+/// 输入中重复 read 名的抽样估计（按名字哈希抽样，同名的 read 同时被抽中或同时不被抽中）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DuplicateNameStats {
+    /// 检查的 read 对数（全部输入，包括被过滤的）
+    pub names: u64,
+    /// 最终的抽样率
+    pub sample_rate: f64,
+    /// 抽中的 read 对数
+    pub sampled: u64,
+    /// 抽中的 read 中名字在前面已出现过的个数
+    pub sampled_duplicates: u64,
+    /// `sampled_duplicates / sampled`，即重复名比例的估计
+    pub fraction: f64,
+    /// `fraction * names`
+    pub estimated_duplicates: u64,
+}
+
+/// 测序饱和度与文库复杂度（只在统计条形码计数时存在）
+///
+/// 片段以（条形码，R1 与 R3 各自开头的一段序列）区分，不同片段数用 HyperLogLog 估计。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LibraryComplexity {
+    pub total_reads: u64,
+    /// 不同片段数（近似值）
+    pub distinct_fragments: u64,
+    /// 1 − distinct_fragments / total_reads
+    pub saturation: f64,
+    /// 按 Lander-Waterman 模型，测序深度加倍时预计的不同片段数
+    pub projected_unique_2x: u64,
+}
+
+impl LibraryComplexity {
+    pub fn new(total_reads: u64, distinct_fragments: u64) -> Self {
+        // HyperLogLog 的估计值可能略大于总数
+        let distinct_fragments = distinct_fragments.min(total_reads);
+        LibraryComplexity {
+            total_reads,
+            distinct_fragments,
+            saturation: saturation(total_reads, distinct_fragments),
+            projected_unique_2x: projected_unique(total_reads, distinct_fragments, 2.0).round() as u64,
+        }
+    }
+}
+
+/// 不同条形码的个数
+///
+/// 要求输出条形码计数表时是精确值，否则是 HyperLogLog 估计值。
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DistinctBarcodes {
+    pub count: u64,
+    pub approximate: bool,
+    /// 估计值的相对标准误差（仅在 `approximate` 时存在）
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub relative_error: Option<f64>,
+}
+
+/// 一个位置上各碱基的比例
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BaseFractions {
+    pub a: f64,
+    pub c: f64,
+    pub g: f64,
+    pub t: f64,
+    pub n: f64,
+}
+
+impl BaseFractions {
+    /// 比例最高的碱基及其比例
+    pub fn dominant(&self) -> (char, f64) {
+        [('A', self.a), ('C', self.c), ('G', self.g), ('T', self.t), ('N', self.n)]
+            .into_iter()
+            .fold(('A', f64::MIN), |best, x| if x.1 > best.1 { x } else { best })
+    }
+}
+
+/// 各阶段线程从开始到结束的耗时（毫秒），用于定位瓶颈
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StageTimings {
+    pub reader_wall_ms: u64,
+    pub worker_wall_ms: WorkerWallMs,
+    pub distributor_wall_ms: u64,
+    pub writer_r1_wall_ms: u64,
+    pub writer_r2_wall_ms: u64,
+    pub writer_r3_wall_ms: u64,
+}
+
+/// 所有处理线程耗时的中位数与最大值
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WorkerWallMs {
+    pub median: u64,
+    pub max: u64,
+}
+
+impl WorkerWallMs {
+    pub fn from_samples(samples: &[u64]) -> Self {
+        if samples.is_empty() {
+            return WorkerWallMs::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2 } else { sorted[mid] };
+        WorkerWallMs { median, max: sorted[sorted.len() - 1] }
+    }
+}
+
+impl RunStats {
+    /// 用计数器快照填充计数字段，其余字段保持默认
+    pub fn from_snapshot(snapshot: &CounterSnapshot) -> Self {
+        RunStats {
+            pairs_read: snapshot.pairs_read,
+            pairs_written: snapshot.pairs_written,
+            pairs_filtered: snapshot.pairs_filtered,
+            filtered_by_reason: FilterReason::ALL.iter()
+                .map(|r| (r.label().to_string(), snapshot.filtered_by_reason[*r as usize]))
+                .collect(),
+            unpaired: snapshot.unpaired,
+            r1_output_mb: snapshot.bytes_written[0] as f64 / 1e6,
+            r2_output_mb: snapshot.bytes_written[1] as f64 / 1e6,
+            r3_output_mb: snapshot.bytes_written[2] as f64 / 1e6,
+            ..Default::default()
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        crate::remote::write_output(path.as_ref(), &json)
+    }
+}
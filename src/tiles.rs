@@ -0,0 +1,116 @@
+// tiles.rs - 按 flowcell tile 统计与过滤（--exclude-tiles）
+
+use crate::failure::{Classify, Failure};
+use crate::stats::TileStats;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// [`TileTally::worst`] 默认给出的 tile 数
+pub const WORST_TILES: usize = 10;
+
+/// 要丢弃的 tile 编号
+///
+/// 只比较 tile 编号，不区分 lane；header 中没有可解析的 tile 字段的 pair 不受影响。
+#[derive(Debug, Clone, Default)]
+pub struct TileExclusion {
+    tiles: HashSet<u32>,
+}
+
+impl TileExclusion {
+    /// 以逗号或空白分隔的 tile 编号，可以分成多行
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut tiles = HashSet::new();
+        for line in reader.lines() {
+            let line = line?;
+            for field in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|f| !f.is_empty()) {
+                let tile = field.parse().with_context(|| format!("Invalid tile ID '{}'", field))?;
+                tiles.insert(tile);
+            }
+        }
+        if tiles.is_empty() {
+            bail!("Tile list is empty");
+        }
+        Ok(TileExclusion { tiles })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open tile list {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        Self::from_reader(BufReader::new(f))
+            .with_context(|| format!("Failed to load tile list {}", path.display()))
+    }
+
+    /// `--exclude-tiles` 的参数：已存在的文件按 [`load`](Self::load) 读取，否则当作逗号分隔的列表
+    pub fn parse(arg: &str) -> Result<Self> {
+        if Path::new(arg).is_file() {
+            Self::load(arg)
+        } else {
+            Self::from_reader(arg.as_bytes())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn contains(&self, tile: u32) -> bool {
+        self.tiles.contains(&tile)
+    }
+}
+
+/// 每个 (lane, tile) 的 pair 数与过滤数
+///
+/// 每个处理线程各自累加，结束时合并。被 `--exclude-tiles` 丢弃的 pair 不计入。
+#[derive(Debug, Clone, Default)]
+pub struct TileTally {
+    tiles: BTreeMap<(u32, u32), (u64, u64)>,
+}
+
+impl TileTally {
+    pub fn record(&mut self, lane: u32, tile: u32, filtered: bool) {
+        let (pairs, n_filtered) = self.tiles.entry((lane, tile)).or_default();
+        *pairs += 1;
+        *n_filtered += filtered as u64;
+    }
+
+    pub fn merge(&mut self, other: &TileTally) {
+        for (key, (pairs, filtered)) in &other.tiles {
+            let mine = self.tiles.entry(*key).or_default();
+            mine.0 += pairs;
+            mine.1 += filtered;
+        }
+    }
+
+    /// 出现过的 tile 数
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// 过滤比例最高的 `n` 个 tile；比例相同时过滤数多的在前
+    pub fn worst(&self, n: usize) -> Vec<TileStats> {
+        let mut tiles: Vec<TileStats> = self.tiles.iter().map(|(&(lane, tile), &(pairs, filtered))| TileStats {
+            lane,
+            tile,
+            pairs_read: pairs,
+            pairs_filtered: filtered,
+            filter_rate: filtered as f64 / pairs.max(1) as f64,
+        }).collect();
+        tiles.sort_by(|a, b| b.filter_rate.total_cmp(&a.filter_rate)
+            .then(b.pairs_filtered.cmp(&a.pairs_filtered))
+            .then((a.lane, a.tile).cmp(&(b.lane, b.tile))));
+        tiles.truncate(n);
+        tiles
+    }
+}
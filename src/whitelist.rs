@@ -0,0 +1,428 @@
+// whitelist.rs - 条形码白名单与纠错
+
+use crate::failure::{Classify, Failure};
+use anyhow::{bail, Context, Result};
+use flate2::read::MultiGzDecoder;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::time::Instant;
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// 有多个候选时，丰度最高的候选需超过其余候选之和的倍数
+const PRIOR_RATIO: u64 = 10;
+
+/// 条形码白名单
+///
+/// 每行一个条形码（支持 .gz），所有条形码长度必须一致。
+#[derive(Clone)]
+pub struct Whitelist {
+    barcodes: HashSet<Vec<u8>>,
+    len: usize,
+    /// 各条形码的丰度；为空时有歧义的纠错一律放弃
+    priors: HashMap<Vec<u8>, u64>,
+    /// 按前缀、后缀查找用的有序索引（首尾相接存放），见 [`build_partial_index`](Self::build_partial_index)
+    prefix_index: Vec<u8>,
+    /// 各条形码倒序后排序
+    suffix_index: Vec<u8>,
+}
+
+/// 截短的条形码（只有前缀或后缀）在白名单中的查找结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartialMatch {
+    /// 唯一的白名单条形码
+    Unique(Vec<u8>),
+    /// 多个白名单条形码共享这段序列
+    Ambiguous,
+    NoMatch,
+}
+
+/// 单个条形码与白名单比对的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BarcodeMatch {
+    /// 完全匹配
+    Exact,
+    /// 恰好一个 N，替换后唯一命中
+    NRescued(Vec<u8>),
+    /// 汉明距离 1 唯一命中
+    Corrected(Vec<u8>),
+    /// 未命中、多个候选或含多个 N
+    NoMatch,
+}
+
+impl Whitelist {
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut barcodes = HashSet::new();
+        let mut len = None;
+        for line in reader.lines() {
+            let line = line?;
+            let bc = line.trim();
+            if bc.is_empty() {
+                continue;
+            }
+            let bc = bc.as_bytes().to_ascii_uppercase();
+            match len {
+                None => len = Some(bc.len()),
+                Some(l) if l != bc.len() => {
+                    bail!("Whitelist barcodes have inconsistent lengths ({} vs {})", l, bc.len())
+                }
+                _ => {}
+            }
+            barcodes.insert(bc);
+        }
+        let Some(len) = len else { bail!("Whitelist is empty") };
+        Ok(Whitelist { barcodes, len, priors: HashMap::new(), prefix_index: Vec::new(), suffix_index: Vec::new() })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let f = File::open(path).with_context(|| format!("Failed to open whitelist {}", path.display()))
+            .classify(Failure::InputUnreadable)?;
+        let reader: Box<dyn Read> = match path.extension().and_then(|s| s.to_str()) {
+            Some("gz") => Box::new(MultiGzDecoder::new(f)),
+            _          => Box::new(f),
+        };
+        Self::from_reader(BufReader::new(reader))
+            .with_context(|| format!("Failed to load whitelist {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.barcodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.barcodes.is_empty()
+    }
+
+    /// 白名单中条形码的长度
+    pub fn barcode_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn contains(&self, bc: &[u8]) -> bool {
+        self.barcodes.contains(bc)
+    }
+
+    /// 白名单中的条形码，顺序不定
+    pub fn barcodes(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.barcodes.iter().map(|bc| bc.as_slice())
+    }
+
+    /// 设置各条形码的丰度（如 `--two-pass` 第一遍的完全匹配计数）
+    ///
+    /// 之后有多个候选的纠错不再直接放弃：丰度最高的候选超过其余候选之和的
+    /// [`PRIOR_RATIO`] 倍时取该候选。
+    pub fn set_priors(&mut self, counts: HashMap<Vec<u8>, u64>) {
+        self.priors = counts;
+    }
+
+    /// 建立 [`match_prefix`](Self::match_prefix)、[`match_suffix`](Self::match_suffix) 使用的有序索引
+    ///
+    /// 每个条形码在两个索引中各占 `barcode_len` 字节；重复调用不会重建。
+    pub fn build_partial_index(&mut self) {
+        if !self.prefix_index.is_empty() {
+            return;
+        }
+        let mut sorted: Vec<&Vec<u8>> = self.barcodes.iter().collect();
+        sorted.sort_unstable();
+        self.prefix_index = sorted.into_iter().flatten().copied().collect();
+        let mut reversed: Vec<Vec<u8>> = self.barcodes.iter().map(|bc| bc.iter().rev().copied().collect()).collect();
+        reversed.sort_unstable();
+        self.suffix_index = reversed.concat();
+    }
+
+    /// 以 `prefix` 开头的白名单条形码（需先调用 [`build_partial_index`](Self::build_partial_index)）
+    pub fn match_prefix(&self, prefix: &[u8]) -> PartialMatch {
+        match self.partial_range(&self.prefix_index, prefix) {
+            (start, 1) => PartialMatch::Unique(self.prefix_index[start * self.len..(start + 1) * self.len].to_vec()),
+            (_, 0) => PartialMatch::NoMatch,
+            _ => PartialMatch::Ambiguous,
+        }
+    }
+
+    /// 以 `suffix` 结尾的白名单条形码（需先调用 [`build_partial_index`](Self::build_partial_index)）
+    pub fn match_suffix(&self, suffix: &[u8]) -> PartialMatch {
+        let reversed: Vec<u8> = suffix.iter().rev().copied().collect();
+        match self.partial_range(&self.suffix_index, &reversed) {
+            (start, 1) => PartialMatch::Unique(
+                self.suffix_index[start * self.len..(start + 1) * self.len].iter().rev().copied().collect()),
+            (_, 0) => PartialMatch::NoMatch,
+            _ => PartialMatch::Ambiguous,
+        }
+    }
+
+    /// 在有序索引中二分查找以 `prefix` 开头的条目，返回第一个条目的下标与条目数
+    fn partial_range(&self, index: &[u8], prefix: &[u8]) -> (usize, usize) {
+        if prefix.len() > self.len {
+            return (0, 0);
+        }
+        let entry = |i: usize| &index[i * self.len..i * self.len + prefix.len()];
+        let partition_point = |pred: &dyn Fn(&[u8]) -> bool| {
+            let (mut lo, mut hi) = (0, index.len() / self.len);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                if pred(entry(mid)) { lo = mid + 1 } else { hi = mid }
+            }
+            lo
+        };
+        let start = partition_point(&|e| e < prefix);
+        let end = partition_point(&|e| e <= prefix);
+        (start, end - start)
+    }
+
+    /// 比对条形码：完全匹配 → 单 N 救回 → 汉明距离 1 纠错
+    pub fn correct(&self, bc: &[u8]) -> BarcodeMatch {
+        if bc.len() != self.len {
+            return BarcodeMatch::NoMatch;
+        }
+        if self.barcodes.contains(bc) {
+            return BarcodeMatch::Exact;
+        }
+
+        let n_count = bc.iter().filter(|&&b| b == b'N').count();
+        match n_count {
+            0 => match self.unique_neighbor(bc, |_| true) {
+                Some(hit) => BarcodeMatch::Corrected(hit),
+                None      => BarcodeMatch::NoMatch,
+            },
+            1 => match self.unique_neighbor(bc, |b| b == b'N') {
+                Some(hit) => BarcodeMatch::NRescued(hit),
+                None      => BarcodeMatch::NoMatch,
+            },
+            // 多个 N 无法可靠纠正
+            _ => BarcodeMatch::NoMatch,
+        }
+    }
+
+    /// 单个插入或缺失后唯一命中（或按丰度可以区分）的白名单条形码
+    ///
+    /// 条形码在 read 中一端的位置固定（`anchored_at_start` 为 true 时是开头），indel 使其后的碱基
+    /// 整体错开一位：插入时去掉一个碱基，在另一端补上窗口外相邻的碱基 `flank`（未知时尝试四种碱基）；
+    /// 缺失时在某处补一个碱基，去掉另一端多读进来的碱基。等价于替换末端碱基的候选不在此考虑。
+    pub fn correct_indel(&self, bc: &[u8], anchored_at_start: bool, flank: Option<u8>) -> Option<Vec<u8>> {
+        let len = bc.len();
+        if len != self.len || len < 2 {
+            return None;
+        }
+        // 统一成固定端在开头的方向
+        let orient = |s: &[u8]| -> Vec<u8> {
+            if anchored_at_start { s.to_vec() } else { s.iter().rev().copied().collect() }
+        };
+        let seq = orient(bc);
+        let flanks: &[u8] = match flank {
+            Some(b) if BASES.contains(&b) => &[b],
+            _ => &BASES,
+        };
+        let mut candidates = HashSet::new();
+        for i in 0..len - 1 {
+            // 插入：seq[i] 是多出的碱基
+            for &b in flanks {
+                candidates.insert([&seq[..i], &seq[i + 1..], &[b]].concat());
+            }
+            // 缺失：在 i 处补一个碱基
+            for &b in &BASES {
+                candidates.insert([&seq[..i], &[b], &seq[i..len - 1]].concat());
+            }
+        }
+        let hits: Vec<Vec<u8>> = candidates.into_iter()
+            .map(|c| orient(&c))
+            .filter(|c| self.barcodes.contains(c))
+            .collect();
+        match hits.len() {
+            0 => None,
+            1 => hits.into_iter().next(),
+            _ if self.priors.is_empty() => None,
+            _ => self.most_abundant(hits),
+        }
+    }
+
+    /// 在 `pick` 选中的位置上逐一替换碱基，只有唯一命中（或按丰度可以区分）时返回
+    fn unique_neighbor<F: Fn(u8) -> bool>(&self, bc: &[u8], pick: F) -> Option<Vec<u8>> {
+        let mut candidate = bc.to_vec();
+        let mut hits = Vec::new();
+        for i in 0..bc.len() {
+            if !pick(bc[i]) {
+                continue;
+            }
+            for &base in &BASES {
+                if base == bc[i] {
+                    continue;
+                }
+                candidate[i] = base;
+                if self.barcodes.contains(&candidate) {
+                    if !hits.is_empty() && self.priors.is_empty() {
+                        return None;
+                    }
+                    hits.push(candidate.clone());
+                }
+            }
+            candidate[i] = bc[i];
+        }
+        if hits.len() <= 1 {
+            return hits.pop();
+        }
+        self.most_abundant(hits)
+    }
+
+    /// 多个候选中丰度明显占优的一个
+    fn most_abundant(&self, hits: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        let counts: Vec<u64> = hits.iter().map(|h| self.priors.get(h).copied().unwrap_or(0)).collect();
+        let (best, &best_count) = counts.iter().enumerate().max_by_key(|&(_, c)| c)?;
+        let rest: u64 = counts.iter().sum::<u64>() - best_count;
+        (best_count > rest * PRIOR_RATIO).then(|| hits.into_iter().nth(best).unwrap())
+    }
+}
+
+/// 把质量低于 `min_qual`（Phred+33）的碱基替换为 N，返回被替换的个数
+pub fn mask_low_quality(seq: &mut [u8], qual: &[u8], min_qual: u8) -> usize {
+    let mut masked = 0;
+    for (base, &q) in seq.iter_mut().zip(qual) {
+        if q.saturating_sub(33) < min_qual && *base != b'N' {
+            *base = b'N';
+            masked += 1;
+        }
+    }
+    masked
+}
+
+/// 条形码纠错统计
+#[derive(Debug, Default, Clone)]
+pub struct CorrectionStats {
+    pub exact: usize,
+    pub n_rescued: usize,
+    pub corrected: usize,
+    pub no_match: usize,
+    /// 有碱基被低质量屏蔽的 read 数
+    pub masked_reads: usize,
+    /// 其中最终被白名单救回的 read 数
+    pub masked_rescued: usize,
+    /// 纠错后序列与原始观测不同的 read 数
+    pub rewritten: usize,
+    /// 条形码被截短、按剩余部分唯一命中白名单的 read 数
+    pub truncated_rescued: usize,
+    /// 条形码被截短、剩余部分对应多个白名单条形码的 read 数（计入 `no_match`）
+    pub truncated_ambiguous: usize,
+    /// 替换纠错失败后尝试 indel 纠错的 read 数
+    pub indel_attempted: usize,
+    /// 其中唯一命中、被救回的 read 数（不计入 `no_match`）
+    pub indel_corrected: usize,
+    /// indel 纠错所用的时间（纳秒，各线程累加）
+    pub indel_ns: u64,
+}
+
+impl CorrectionStats {
+    pub fn merge(&mut self, other: &CorrectionStats) {
+        self.exact += other.exact;
+        self.n_rescued += other.n_rescued;
+        self.corrected += other.corrected;
+        self.no_match += other.no_match;
+        self.masked_reads += other.masked_reads;
+        self.masked_rescued += other.masked_rescued;
+        self.rewritten += other.rewritten;
+        self.truncated_rescued += other.truncated_rescued;
+        self.truncated_ambiguous += other.truncated_ambiguous;
+        self.indel_attempted += other.indel_attempted;
+        self.indel_corrected += other.indel_corrected;
+        self.indel_ns += other.indel_ns;
+    }
+}
+
+/// 对提取出的条形码做（可选的）低质量屏蔽和白名单纠错
+///
+/// 只修改序列，质量值保持原样。返回纠正后的条形码；未命中时返回 `None`。
+pub fn correct_barcode(
+    whitelist: &Whitelist,
+    seq: &[u8],
+    qual: &[u8],
+    mask_qual: Option<u8>,
+    stats: &mut CorrectionStats,
+) -> Option<Vec<u8>> {
+    let mut bc = seq.to_vec();
+    let masked = match mask_qual {
+        Some(q) => mask_low_quality(&mut bc, qual, q),
+        None    => 0,
+    };
+
+    let result = match whitelist.correct(&bc) {
+        BarcodeMatch::Exact => {
+            stats.exact += 1;
+            Some(bc)
+        }
+        BarcodeMatch::NRescued(hit) => {
+            stats.n_rescued += 1;
+            Some(hit)
+        }
+        BarcodeMatch::Corrected(hit) => {
+            stats.corrected += 1;
+            Some(hit)
+        }
+        BarcodeMatch::NoMatch => {
+            stats.no_match += 1;
+            None
+        }
+    };
+
+    if result.as_deref().is_some_and(|hit| hit != seq) {
+        stats.rewritten += 1;
+    }
+    if masked > 0 {
+        stats.masked_reads += 1;
+        if result.is_some() {
+            stats.masked_rescued += 1;
+        }
+    }
+    result
+}
+
+/// 按截短条形码剩余的部分查找白名单，唯一命中时返回完整的白名单条形码
+///
+/// `missing_at_start` 表示缺失的是条形码开头（条形码在 R2 中反向互补时，R2 末端缺失的碱基
+/// 对应条形码开头），此时 `partial` 是条形码的后缀，否则是前缀。只接受完全匹配。
+pub fn correct_truncated_barcode(
+    whitelist: &Whitelist,
+    partial: &[u8],
+    missing_at_start: bool,
+    stats: &mut CorrectionStats,
+) -> Option<Vec<u8>> {
+    let result = if missing_at_start { whitelist.match_suffix(partial) } else { whitelist.match_prefix(partial) };
+    match result {
+        PartialMatch::Unique(hit) => {
+            stats.truncated_rescued += 1;
+            stats.rewritten += 1;
+            Some(hit)
+        }
+        PartialMatch::Ambiguous => {
+            stats.truncated_ambiguous += 1;
+            stats.no_match += 1;
+            None
+        }
+        PartialMatch::NoMatch => {
+            stats.no_match += 1;
+            None
+        }
+    }
+}
+
+/// 对 [`correct_barcode`] 未命中的条形码尝试单个 indel 纠错（见 [`Whitelist::correct_indel`]）
+///
+/// 命中时把这个 read 从 `no_match` 移到 `indel_corrected`。
+pub fn correct_barcode_indel(
+    whitelist: &Whitelist,
+    seq: &[u8],
+    anchored_at_start: bool,
+    flank: Option<u8>,
+    stats: &mut CorrectionStats,
+) -> Option<Vec<u8>> {
+    let start = Instant::now();
+    let result = whitelist.correct_indel(seq, anchored_at_start, flank);
+    stats.indel_ns += start.elapsed().as_nanos() as u64;
+    stats.indel_attempted += 1;
+    if result.is_some() {
+        stats.no_match -= 1;
+        stats.indel_corrected += 1;
+        stats.rewritten += 1;
+    }
+    result
+}
@@ -41,7 +41,7 @@ echo "测试数据已创建："
 ls -la test_data/
 
 echo -e "\n运行测试："
-./target/release/scatac-barcode-splitter  \
+./target/release/scatac-barcode-splitter split \
     -1 test_data/test_R1.fastq.gz \
     -2 test_data/test_R2.fastq.gz \
     -o test_output \
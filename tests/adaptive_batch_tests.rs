@@ -0,0 +1,41 @@
+use scatac_barcode_splitter::adaptive_batch::{AdaptiveBatchSize, BatchTarget, MAX_GROWTH};
+use std::time::Duration;
+
+fn target(max_batch_size: Option<usize>) -> BatchTarget {
+    BatchTarget { min: Duration::from_millis(100), max: Duration::from_millis(500), max_batch_size }
+}
+
+#[test]
+fn test_batch_size_moves_toward_target_time() {
+    let adaptive = AdaptiveBatchSize::new(1000, target(None));
+    // 1000 对用了 50 ms：太快，增加 10%
+    adaptive.observe(1000, Duration::from_millis(50));
+    assert_eq!(adaptive.get(), 1100);
+    // 在目标范围内不变
+    adaptive.observe(1100, Duration::from_millis(300));
+    assert_eq!(adaptive.get(), 1100);
+    // 不满的 batch 按比例折算：550 对 400 ms 相当于 1100 对 800 ms
+    adaptive.observe(550, Duration::from_millis(400));
+    assert_eq!(adaptive.get(), 990);
+    let stats = adaptive.stats();
+    assert_eq!((stats.initial, stats.final_size, stats.smallest, stats.largest, stats.adjustments),
+               (1000, 990, 990, 1100, 2));
+}
+
+#[test]
+fn test_batch_size_stays_within_bounds() {
+    let adaptive = AdaptiveBatchSize::new(100, target(Some(120)));
+    for _ in 0..10 {
+        adaptive.observe(100, Duration::from_millis(1));
+    }
+    assert_eq!(adaptive.get(), 120);
+    let adaptive = AdaptiveBatchSize::new(5, target(None));
+    for _ in 0..100 {
+        adaptive.observe(5, Duration::from_millis(1));
+    }
+    assert_eq!(adaptive.get(), 5 * MAX_GROWTH);
+    for _ in 0..200 {
+        adaptive.observe(1, Duration::from_secs(1));
+    }
+    assert_eq!(adaptive.get(), 1);
+}
@@ -0,0 +1,20 @@
+use scatac_barcode_splitter::affinity::{parse_cpu_list, pin_current_thread};
+use scatac_barcode_splitter::numa::disk_node;
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("0-3,8,10-11\n"), Some(vec![0, 1, 2, 3, 8, 10, 11]));
+    assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+    assert_eq!(parse_cpu_list(""), Some(vec![]));
+    assert_eq!(parse_cpu_list("3-1"), None);
+    assert_eq!(parse_cpu_list("a-b"), None);
+}
+
+#[test]
+fn test_unknown_node_falls_back() {
+    // 对象存储没有本地磁盘
+    assert_eq!(disk_node(std::path::Path::new("s3://bucket/out")), None);
+    // 绑定到当前线程可用的全部 CPU 不改变行为
+    let cpus: Vec<usize> = (0..std::thread::available_parallelism().map_or(1, |n| n.get())).collect();
+    pin_current_thread(&cpus);
+}
@@ -0,0 +1,31 @@
+use scatac_barcode_splitter::anchor::{Anchor, AnchorHit};
+use scatac_barcode_splitter::readthrough::find_adapter;
+
+#[test]
+fn test_find_adapter_allows_mismatches() {
+    let seq = b"GGGGAGATGTGTATAAGAGACAGTTTTagatgtgtataaNagacag";
+    let hits: Vec<usize> = find_adapter(seq, b"AGATGTGTATAAGAGACAG", 0).collect();
+    assert_eq!(hits, [4]);
+    // 小写与 N（计为错配）
+    let hits: Vec<usize> = find_adapter(seq, b"AGATGTGTATAAGAGACAG", 1).collect();
+    assert_eq!(hits, [4, 27]);
+    assert_eq!(find_adapter(b"ACG", b"ACGT", 0).count(), 0);
+}
+
+#[test]
+fn test_anchor_picks_three_prime_hit_with_room_for_barcode() {
+    let anchor = Anchor::new("acgtac", 0).unwrap();
+    let bc = "TTTTGGGGCCCCAAAA";
+    let read = format!("GGACGTACGGGGACGTAC{}", bc);
+    assert_eq!(anchor.locate(read.as_bytes(), bc.len()), Some(AnchorHit { start: 12, end: 18, hits: 2 }));
+    // 锚定序列之后放不下条形码、之前没有基因组片段的命中都不算
+    let read = format!("ACGTACGGGGACGTAC{}", &bc[..15]);
+    assert_eq!(anchor.locate(read.as_bytes(), bc.len()), None);
+}
+
+#[test]
+fn test_anchor_rejects_invalid_sequences() {
+    assert!(Anchor::new("", 0).is_err());
+    assert!(Anchor::new("ACGN", 0).is_err());
+    assert!(Anchor::new("ACG", 3).is_err());
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{path_str, run};
+use fastq::Record;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use scatac_barcode_splitter::bam::{detect_input_format, InputFormat, UbamPairReader};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// 编码一条无比对信息的 BAM 记录（质量值为 Phred 原值）
+fn encode_record(name: &str, flag: u16, seq: &[u8], qual: &[u8]) -> Vec<u8> {
+    encode_aligned_record(name, flag, &[], seq, qual, b"")
+}
+
+/// 带 CIGAR 与 tag 的 BAM 记录
+fn encode_aligned_record(name: &str, flag: u16, cigar: &[u32], seq: &[u8], qual: &[u8], tags: &[u8]) -> Vec<u8> {
+    let code = |b: u8| b"=ACMGRSVTWYHKDBN".iter().position(|&c| c == b).unwrap() as u8;
+    let mut data = Vec::new();
+    data.extend_from_slice(&(-1i32).to_le_bytes()); // refID
+    data.extend_from_slice(&(-1i32).to_le_bytes()); // pos
+    data.push(name.len() as u8 + 1);
+    data.push(0); // mapq
+    data.extend_from_slice(&4680u16.to_le_bytes()); // bin
+    data.extend_from_slice(&(cigar.len() as u16).to_le_bytes()); // n_cigar_op
+    data.extend_from_slice(&flag.to_le_bytes());
+    data.extend_from_slice(&(seq.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(-1i32).to_le_bytes()); // next refID
+    data.extend_from_slice(&(-1i32).to_le_bytes()); // next pos
+    data.extend_from_slice(&0i32.to_le_bytes()); // tlen
+    data.extend_from_slice(name.as_bytes());
+    data.push(0);
+    cigar.iter().for_each(|op| data.extend_from_slice(&op.to_le_bytes()));
+    for pair in seq.chunks(2) {
+        let lo = pair.get(1).map_or(0, |&b| code(b));
+        data.push(code(pair[0]) << 4 | lo);
+    }
+    data.extend_from_slice(qual);
+    data.extend_from_slice(tags);
+    let mut record = (data.len() as u32).to_le_bytes().to_vec();
+    record.extend(data);
+    record
+}
+
+fn write_bam(path: &Path, records: &[Vec<u8>]) {
+    let header = b"@HD\tVN:1.6\tSO:unsorted\n";
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(b"BAM\x01").unwrap();
+    gz.write_all(&(header.len() as u32).to_le_bytes()).unwrap();
+    gz.write_all(header).unwrap();
+    gz.write_all(&0u32.to_le_bytes()).unwrap();
+    for r in records {
+        gz.write_all(r).unwrap();
+    }
+    fs::write(path, gz.finish().unwrap()).unwrap();
+}
+
+#[test]
+fn test_pairs_reconstructed_from_flags() {
+    let records = vec![
+        encode_record("a", 0x1 | 0x40, b"ACGTN", &[30, 30, 30, 30, 2]),
+        encode_record("a", 0x1 | 0x80 | 0x10, b"AACG", &[10, 20, 30, 40]),
+        // 未配对
+        encode_record("b", 0x0, b"ACGT", &[30; 4]),
+        // 缺少 last mate
+        encode_record("c", 0x1 | 0x40, b"ACGT", &[30; 4]),
+        encode_record("d", 0x1 | 0x40, b"GGGG", &[30; 4]),
+        encode_record("d", 0x1 | 0x80, b"CCCC", &[30; 4]),
+    ];
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(b"BAM\x01\0\0\0\0\0\0\0\0").unwrap();
+    for r in &records {
+        gz.write_all(r).unwrap();
+    }
+    let bytes = gz.finish().unwrap();
+    let mut reader = UbamPairReader::new(flate2::read::MultiGzDecoder::new(&bytes[..])).unwrap();
+
+    let (r1, r2) = reader.next_pair().unwrap().unwrap();
+    assert_eq!(r1.head(), b"a");
+    assert_eq!(r1.seq(), b"ACGTN");
+    assert_eq!(r1.qual(), b"????#");
+    assert_eq!(r2.seq(), b"CGTT");
+    assert_eq!(r2.qual(), b"I?5+");
+    let (r1, r2) = reader.next_pair().unwrap().unwrap();
+    assert_eq!((r1.seq(), r2.seq()), (&b"GGGG"[..], &b"CCCC"[..]));
+    assert!(reader.next_pair().unwrap().is_none());
+    assert_eq!(reader.stats.inconsistent_flags, 1);
+    assert_eq!(reader.stats.missing_mate, 1);
+}
+
+/// 没有参考序列的 BAM 头加上记录，压缩为单个 gzip 成员
+fn gzip_bam(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(b"BAM\x01\0\0\0\0\0\0\0\0").unwrap();
+    for r in records {
+        gz.write_all(r).unwrap();
+    }
+    gz.finish().unwrap()
+}
+
+#[test]
+fn test_cigar_tags_and_missing_quality() {
+    // 10M2S，带 RG tag；R2 的质量值缺失（0xff）
+    let cigar = [10 << 4, 2 << 4 | 4];
+    let records = vec![
+        encode_aligned_record("a", 0x1 | 0x40, &cigar, b"ACGTACGTACGT", &[40; 12], b"RGZrg1\0"),
+        encode_aligned_record("a", 0x1 | 0x80, &cigar[..1], b"GGGGCCCCTT", &[0xff; 10], b"XIc\x05"),
+    ];
+    let bytes = gzip_bam(&records);
+    let mut reader = UbamPairReader::new(flate2::read::MultiGzDecoder::new(&bytes[..])).unwrap();
+
+    let (r1, r2) = reader.next_pair().unwrap().unwrap();
+    assert_eq!((r1.head(), r1.seq(), r1.qual()), (&b"a"[..], &b"ACGTACGTACGT"[..], &b"IIIIIIIIIIII"[..]));
+    assert_eq!((r2.seq(), r2.qual()), (&b"GGGGCCCCTT"[..], &b"!!!!!!!!!!"[..]));
+    assert!(reader.next_pair().unwrap().is_none());
+    assert_eq!(reader.stats, Default::default());
+}
+
+#[test]
+fn test_truncated_final_record_is_an_error() {
+    let records = vec![
+        encode_record("a", 0x1 | 0x40, b"ACGT", &[30; 4]),
+        encode_record("a", 0x1 | 0x80, b"ACGT", &[30; 4]),
+    ];
+    // 最后一条记录在解压后的数据中被截断，或 gzip 成员本身被截断
+    let mut cut = records.clone();
+    cut[1].truncate(20);
+    let full = gzip_bam(&records);
+    for bytes in [gzip_bam(&cut), full[..full.len() - 12].to_vec()] {
+        let mut reader = UbamPairReader::new(flate2::read::MultiGzDecoder::new(&bytes[..])).unwrap();
+        // 截断不能被当作正常的文件结束
+        let err = std::iter::from_fn(|| reader.next_pair().transpose()).find_map(Result::err).unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
+
+#[test]
+fn test_detect_input_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let bam = dir.path().join("in.bam");
+    write_bam(&bam, &[]);
+    assert_eq!(detect_input_format(&bam).unwrap(), InputFormat::Ubam);
+    let cram = dir.path().join("in.cram");
+    fs::write(&cram, b"CRAM\x03\x00").unwrap();
+    assert_eq!(detect_input_format(&cram).unwrap(), InputFormat::Cram);
+    let (r1, _) = common::write_fixture(dir.path(), 1, &["AAAACCCCGGGGTTTT"]);
+    assert_eq!(detect_input_format(&r1).unwrap(), InputFormat::Fastq);
+}
+
+#[test]
+fn test_split_ubam_matches_fastq_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT"]);
+
+    // 把 FASTQ fixture 转成 uBAM，再附加一条缺少 mate 的记录
+    let parse = |p: &Path| -> Vec<(String, Vec<u8>)> {
+        let text = fs::read_to_string(p).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        lines.chunks(4).map(|c| (c[0][1..c[0].len() - 2].to_string(), c[1].as_bytes().to_vec())).collect()
+    };
+    let mut records = Vec::new();
+    for ((name, s1), (_, s2)) in parse(&r1).into_iter().zip(parse(&r2)) {
+        records.push(encode_record(&name, 0x1 | 0x40, &s1, &vec![40; s1.len()]));
+        records.push(encode_record(&name, 0x1 | 0x80, &s2, &vec![40; s2.len()]));
+    }
+    records.push(encode_record("orphan", 0x1 | 0x80, b"ACGT", &[40; 4]));
+    let bam = dir.path().join("input.bam");
+    write_bam(&bam, &records);
+
+    let fq_prefix = dir.path().join("fq");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&fq_prefix)]);
+    assert!(out.status.success());
+    let bam_prefix = dir.path().join("bam");
+    let out = run(&["split", "-1", path_str(&bam), "-o", path_str(&bam_prefix)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("skipped 1 records without an adjacent mate"));
+
+    for read in ["R1", "R2", "R3"] {
+        let fq = fs::read(format!("{}_S1_L001_{}_001.fastq", path_str(&fq_prefix), read)).unwrap();
+        let bam = fs::read(format!("{}_S1_L001_{}_001.fastq", path_str(&bam_prefix), read)).unwrap();
+        assert_eq!(fq, bam, "{} differs", read);
+    }
+}
@@ -0,0 +1,54 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use scatac_barcode_splitter::bgzf::BgzfParallelReader;
+use std::io::{Read, Write};
+
+/// 按 BGZF 规范构造一个 block
+fn bgzf_block(data: &[u8]) -> Vec<u8> {
+    let mut enc = DeflateEncoder::new(Vec::new(), Compression::default());
+    enc.write_all(data).unwrap();
+    let cdata = enc.finish().unwrap();
+    let bsize = (18 + cdata.len() + 8 - 1) as u16;
+
+    let mut block = vec![31, 139, 8, 4, 0, 0, 0, 0, 0, 255, 6, 0, b'B', b'C', 2, 0];
+    block.extend_from_slice(&bsize.to_le_bytes());
+    block.extend_from_slice(&cdata);
+    let mut crc = Crc::new();
+    crc.update(data);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    block
+}
+
+fn bgzf_file(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut file: Vec<u8> = chunks.iter().flat_map(|c| bgzf_block(c)).collect();
+    file.extend(bgzf_block(b"")); // EOF block
+    file
+}
+
+#[test]
+fn test_bgzf_parallel_reader_preserves_order() {
+    // 300 个 block，跨越多个解压任务
+    let chunks: Vec<Vec<u8>> = (0..300).map(|i| format!("@read{}\nACGT\n+\nIIII\n", i).into_bytes()).collect();
+    let expected: Vec<u8> = chunks.concat();
+
+    for threads in [1, 4] {
+        let mut out = Vec::new();
+        BgzfParallelReader::new(std::io::Cursor::new(bgzf_file(&chunks)), threads)
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+}
+
+#[test]
+fn test_bgzf_parallel_reader_detects_corruption() {
+    let mut file = bgzf_file(&[b"@r\nACGT\n+\nIIII\n".to_vec()]);
+    // 破坏第一个 block 的 CRC
+    let crc_pos = bgzf_block(b"@r\nACGT\n+\nIIII\n").len() - 8;
+    file[crc_pos] ^= 0xff;
+
+    let mut out = Vec::new();
+    let err = BgzfParallelReader::new(std::io::Cursor::new(file), 2).read_to_end(&mut out).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,1248 @@
+mod common;
+
+use common::{fastq_text, path_str, run, Fixture, BARCODE};
+use std::fs;
+
+/// R1 与 R2 来自不同 flowcell 的单条 read
+fn write_cross_run_fixture(dir: &std::path::Path) -> (std::path::PathBuf, std::path::PathBuf) {
+    let r1 = dir.join("R1.fastq");
+    let r2 = dir.join("R2.fastq");
+    fs::write(&r1, format!("@A001:1:HAAAA:1:1101:1:1 1:N:0:A\n{}\n+\n{}\n", "A".repeat(50), "I".repeat(50))).unwrap();
+    fs::write(&r2, format!("@A001:1:HBBBB:1:1101:1:1 2:N:0:A\n{}\n+\n{}\n", "A".repeat(166), "I".repeat(166))).unwrap();
+    (r1, r2)
+}
+
+#[test]
+fn test_cross_run_inputs_warn() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = write_cross_run_fixture(dir.path());
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("A001:1:HAAAA") && stderr.contains("A001:1:HBBBB"), "{}", stderr);
+}
+
+#[test]
+fn test_cross_run_inputs_strict_aborts() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = write_cross_run_fixture(dir.path());
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--strict"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("different sequencing runs"));
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+}
+
+#[test]
+fn test_progress_log_matches_summary() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "log"]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let last = stderr.lines().rfind(|l| l.contains("] progress ")).unwrap();
+    assert!(last.contains("pairs_read=20 pairs_written=20") && last.contains("percent=100.0"), "{}", last);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Processed records: 20"));
+}
+
+#[test]
+fn test_progress_none_is_silent() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none"]);
+    assert!(out.status.success());
+    // stderr 上只有最终汇总，没有进度行
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!stderr.contains("progress") && stderr.contains("Processing complete!"), "{}", stderr);
+}
+
+#[test]
+fn test_validate_counts_invalid_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 4, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    assert!(run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)]).status.success());
+    let outputs: Vec<_> = ["R1", "R2", "R3"].iter()
+        .map(|r| dir.path().join(format!("out_S1_L001_{}_001.fastq", r)))
+        .collect();
+    let validate = |r2: &std::path::Path| {
+        run(&["validate", "--r1", path_str(&outputs[0]), "--r2", path_str(r2), "--r3", path_str(&outputs[2])])
+    };
+
+    let out = validate(&outputs[1]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Valid records: 4"));
+
+    // 第二条 R2 截短一个碱基（序列与质量同时截短）
+    let text = fs::read_to_string(&outputs[1]).unwrap();
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    lines[5].pop();
+    lines[7].pop();
+    let broken = dir.path().join("broken_R2.fastq");
+    fs::write(&broken, lines.join("\n") + "\n").unwrap();
+    let out = validate(&broken);
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Valid records: 3") && stdout.contains("Invalid records: 1"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Record 2: R2 is 15 bp"));
+}
+
+#[cfg(not(feature = "cloud"))]
+#[test]
+fn test_object_store_prefix_requires_cloud_feature() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", "s3://bucket/run1/out", "--progress", "none"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("`cloud` feature"));
+}
+
+#[test]
+fn test_unwritable_output_reports_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    // 以 root 运行时权限不起作用，用普通文件充当输出目录
+    let prefix = dir.path().join("not_a_dir").join("out");
+    fs::write(dir.path().join("not_a_dir"), "").unwrap();
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Failed to create") && stderr.contains("not_a_dir"), "{}", stderr);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_write_error_aborts_with_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 2000, &["AAAACCCCGGGGTTTT"]);
+    // 写入 /dev/full 总是返回 ENOSPC
+    let r3_output = dir.path().join("out_S1_L001_R3_001.fastq");
+    std::os::unix::fs::symlink("/dev/full", &r3_output).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains(&format!("Failed to write {}", r3_output.display())), "{}", stderr);
+    assert!(stderr.contains("No space left"), "{}", stderr);
+}
+
+#[test]
+fn test_corrupt_input_flags_partial_outputs() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5000, &["AAAACCCCGGGGTTTT"]);
+    // R2 压缩后把中间的一段改坏
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&fs::read(&r2).unwrap()).unwrap();
+    let mut gz = encoder.finish().unwrap();
+    let middle = gz.len() / 2;
+    gz[middle..middle + 64].fill(0xff);
+    let r2_gz = dir.path().join("input_R2.fastq.gz");
+    fs::write(&r2_gz, gz).unwrap();
+
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2_gz), "-o", path_str(&prefix), "--progress", "none"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains(&format!("Failed to read {}:", r2_gz.display())), "{}", stderr);
+    assert!(stderr.contains("Failed at record pair"), "{}", stderr);
+    // 不留下看似完整的输出
+    for read in ["R1", "R2", "R3"] {
+        let output = dir.path().join(format!("out_S1_L001_{}_001.fastq", read));
+        assert!(!output.exists(), "{} was finalized", output.display());
+        assert!(dir.path().join(format!("out_S1_L001_{}_001.fastq.incomplete", read)).exists());
+    }
+}
+
+#[test]
+fn test_truncated_r2_reports_unpaired() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    // R2 少最后三条记录
+    let text = fs::read_to_string(&r2).unwrap();
+    fs::write(&r2, text.lines().take(7 * 4).map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+    let prefix = dir.path().join("out");
+    let args = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none"];
+    let out = run(&args);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Processed records: 7"), "{}", stderr);
+    assert!(stderr.contains("Unpaired records (no mate in the other file): 3"), "{}", stderr);
+    assert!(stderr.contains("WARNING: 3 records have no mate in the other input file (first: read7)"), "{}", stderr);
+
+    let out = run(&[&args[..], &["--strict"]].concat());
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Error: 3 records have no mate"));
+}
+
+#[test]
+fn test_preflight_reports_all_collisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, _) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    // 同一个文件既是 -1 又是 -2，且与 R1 输出同名
+    let input = dir.path().join("out_S1_L001_R1_001.fastq");
+    fs::rename(&r1, &input).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&input), "-2", path_str(&input), "-o", path_str(&prefix), "--progress", "none"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Pre-flight checks failed"), "{}", stderr);
+    assert!(stderr.contains("are the same file"), "{}", stderr);
+    assert!(stderr.contains("would overwrite R1 input"), "{}", stderr);
+    // 输入没有被改动
+    assert!(fs::read_to_string(&input).unwrap().starts_with("@read0/1"));
+}
+
+#[test]
+fn test_cleanup_on_error_removes_outputs() {
+    use std::io::Write;
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5000, &["AAAACCCCGGGGTTTT"]);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&fs::read(&r2).unwrap()).unwrap();
+    let mut gz = encoder.finish().unwrap();
+    let middle = gz.len() / 2;
+    gz[middle..middle + 64].fill(0xff);
+    let r2_gz = dir.path().join("input_R2.fastq.gz");
+    fs::write(&r2_gz, gz).unwrap();
+
+    let prefix = dir.path().join("out");
+    let metrics = dir.path().join("out.prom");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2_gz), "-o", path_str(&prefix), "--progress", "none",
+                    "--metrics-file", path_str(&metrics), "--cleanup-on-error"]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains(&format!("Removed partial output {}", metrics.display())), "{}", stderr);
+    assert!(stderr.contains("Removed partial output") && stderr.contains("R3_001.fastq"), "{}", stderr);
+    // 只剩下输入
+    let mut left: Vec<String> = fs::read_dir(dir.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    left.sort();
+    assert_eq!(left, ["input_R1.fastq", "input_R2.fastq", "input_R2.fastq.gz"]);
+}
+
+#[test]
+fn test_done_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let done = dir.path().join("out.done");
+    let args = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none",
+                "--done-file", path_str(&done)];
+    let out = run(&args);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&done).unwrap()).unwrap();
+    assert_eq!(json["exit_status"], 0);
+    assert_eq!((json["pairs_read"].as_u64(), json["pairs_written"].as_u64()), (Some(10), Some(10)));
+    assert!(json["completed_at"].as_str().unwrap().ends_with('Z'));
+
+    // 已有完成标记时拒绝运行，--force 时先删除
+    let out = run(&args);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("pass --force"));
+    assert!(run(&[&args[..], &["--force"]].concat()).status.success());
+
+    // 输出写完后才失败（--strict）也不写
+    let text = fs::read_to_string(&r2).unwrap();
+    fs::write(&r2, text.lines().take(7 * 4).map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+    let out = run(&[&args[..], &["--force", "--strict"]].concat());
+    assert!(!out.status.success());
+    assert!(!done.exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_done_file_not_written_when_output_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let done = dir.path().join("out.done");
+    let args = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none",
+                "--done-file", path_str(&done)];
+    assert!(run(&args).status.success());
+    fs::remove_file(&done).unwrap();
+
+    // R3 写入 /dev/full：数据在收尾时才写出，写入失败也要报错，不留下完成标记
+    let r3 = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().contains("_R3_")).unwrap();
+    fs::remove_file(&r3).unwrap();
+    std::os::unix::fs::symlink("/dev/full", &r3).unwrap();
+    let out = run(&args);
+    assert_eq!(out.status.code(), Some(6), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!done.exists());
+}
+
+#[test]
+fn test_affinity_ignores_missing_cpus() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let args = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none"];
+    let out = run(&[&args[..], &["--affinity", "0,100000"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("--affinity lists CPUs [100000]"), "{}", stderr);
+    assert!(stderr.contains("Processed records: 10"), "{}", stderr);
+
+    let out = run(&[&args[..], &["--affinity", "7-2"]].concat());
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_benchmark_creates_no_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_scatac-barcode-splitter"))
+        .current_dir(dir.path())
+        .args(["split", "-1", path_str(&r1), "-2", path_str(&r2), "--benchmark"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Read stage: 10 pairs"));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "--benchmark", "--compress"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_max_memory_gb_lowers_batch_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    // 0.001 GB / (2 线程 × 50 × 1000 字节) = 10
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "-t", "2", "--max-memory-gb", "0.001", "--verbose"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Batch size lowered to 10"));
+    let info: serde_json::Value = serde_json::from_slice(&fs::read(dir.path().join("out_run_info.json")).unwrap()).unwrap();
+    assert_eq!(info["options"]["batch_size"], 10);
+}
+
+#[test]
+fn test_check_pair_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, ids: &[&str], mate: u8, len: usize| {
+        let path = dir.path().join(name);
+        let text: String = ids.iter()
+            .map(|id| format!("@{}/{}\n{}\n+\n{}\n", id, mate, "A".repeat(len), "I".repeat(len)))
+            .collect();
+        fs::write(&path, text).unwrap();
+        path
+    };
+    let prefix = dir.path().join("out");
+    let split = |r1: &std::path::Path, r2: &std::path::Path| {
+        run(&["split", "-1", path_str(r1), "-2", path_str(r2), "-o", path_str(&prefix), "--check-pair-order"])
+    };
+
+    let r1 = write("sorted_R1.fastq", &["r1", "r2", "r3"], 1, 50);
+    let r2 = write("sorted_R2.fastq", &["r1", "r2", "r3"], 2, 166);
+    assert!(split(&r1, &r2).status.success());
+
+    let r2 = write("swapped_R2.fastq", &["r1", "r3", "r2"], 2, 166);
+    let out = split(&r1, &r2);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("out of step at pair 2"));
+
+    let r1 = write("unsorted_R1.fastq", &["r2", "r1", "r3"], 1, 50);
+    let r2 = write("unsorted_R2.fastq", &["r2", "r1", "r3"], 2, 166);
+    let out = split(&r1, &r2);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("not sorted at pair 2"));
+}
+
+#[test]
+fn test_passthrough_reemits_split_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"]);
+    let split = dir.path().join("split");
+    assert!(run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&split)]).status.success());
+    let split_file = |read: &str| dir.path().join(format!("split_S1_L001_{}_001.fastq", read));
+
+    let out_prefix = dir.path().join("pt");
+    let out = run(&["passthrough", "-1", path_str(&split_file("R1")), "--barcode", path_str(&split_file("R2")),
+                    "-3", path_str(&split_file("R3")), "-o", path_str(&out_prefix), "--rc-barcode"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Processed records: 10"));
+
+    let pt_file = |read: &str| dir.path().join(format!("pt_S1_L001_{}_001.fastq", read));
+    for read in ["R1", "R3"] {
+        assert_eq!(fs::read(pt_file(read)).unwrap(), fs::read(split_file(read)).unwrap());
+    }
+    let barcodes = fs::read_to_string(pt_file("R2")).unwrap();
+    assert_eq!(barcodes.lines().nth(1), Some("AAAACCCCGGGGTTTT"));
+    assert_eq!(barcodes.lines().nth(5), Some("TCGTACGTACGTACGT"));
+}
+
+#[test]
+fn test_passthrough_aborts_when_out_of_step() {
+    let dir = tempfile::tempdir().unwrap();
+    let record = |name: &str| format!("@{}\nACGT\n+\nIIII\n", name);
+    let (r1, bc, r3) = (dir.path().join("r1.fq"), dir.path().join("bc.fq"), dir.path().join("r3.fq"));
+    fs::write(&r1, record("a") + &record("b")).unwrap();
+    fs::write(&bc, record("a") + &record("c")).unwrap();
+    fs::write(&r3, record("a") + &record("b")).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["passthrough", "-1", path_str(&r1), "--barcode", path_str(&bc), "-3", path_str(&r3),
+                    "-o", path_str(&prefix)]);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("record 2") && stderr.contains("barcode has 'c'"), "{}", stderr);
+}
+
+#[test]
+fn test_recombine_restores_original_r2() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"]);
+    let prefix = dir.path().join("out");
+    assert!(run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)]).status.success());
+
+    let recombined = dir.path().join("recombined_R2.fastq");
+    let out = run(&["recombine", "--barcode", path_str(&dir.path().join("out_S1_L001_R2_001.fastq")),
+                    "--genomic", path_str(&dir.path().join("out_S1_L001_R3_001.fastq")), "-o", path_str(&recombined)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    // header 中的 /2 在拆分时已去掉，只比较序列与质量值
+    let seq_and_qual = |text: String| -> Vec<String> {
+        text.lines().enumerate().filter(|(i, _)| i % 4 == 1 || i % 4 == 3).map(|(_, l)| l.to_string()).collect()
+    };
+    assert_eq!(seq_and_qual(fs::read_to_string(&recombined).unwrap()), seq_and_qual(fs::read_to_string(&r2).unwrap()));
+}
+
+#[test]
+fn test_read_names_selects_listed_pairs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT"]);
+    let names = dir.path().join("names.txt");
+    fs::write(&names, "read3/1\nread7\n@read12 1:N:0:ACGT\nread99\n").unwrap();
+    let prefix = dir.path().join("out");
+    let stats_path = dir.path().join("stats.json");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--read-names", path_str(&names), "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Read names: 3 of 4 listed found, 1 missing") && stderr.contains("Missing: read99"), "{}", stderr);
+
+    let r1_out = fs::read_to_string(dir.path().join("out_S1_L001_R1_001.fastq")).unwrap();
+    let heads: Vec<&str> = r1_out.lines().step_by(4).collect();
+    assert_eq!(heads, ["@read3", "@read7", "@read12"]);
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["not_listed"], 17);
+}
+
+#[test]
+fn test_select_barcodes_routes_unselected_pairs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 30, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT", "TTTTGGGGCCCCAAAA"]);
+    let list = dir.path().join("cells.txt");
+    fs::write(&list, "AAAACCCCGGGGTTTT-1\nGGGGGGGGGGGGGGGG-1\n").unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--select-barcodes", path_str(&list), "--unselected", "unassigned"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Barcode selection: 10 selected, 20 not selected (written to unassigned outputs), \
+                             1 of 2 listed barcodes never observed"), "{}", stderr);
+
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "AAAACCCCGGGGTTTT"));
+    let unassigned = fs::read_to_string(dir.path().join("out_unassigned_S1_L001_R3_001.fastq")).unwrap();
+    assert_eq!(unassigned.lines().count(), 20 * 4);
+}
+
+#[test]
+fn test_split_by_writes_one_set_per_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 30, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT", "TTTTGGGGCCCCAAAA"]);
+    let table = dir.path().join("clusters.tsv");
+    fs::write(&table, "barcode\tcluster\nAAAACCCCGGGGTTTT-1\tT cells\nACGTACGTACGTACGT-1\tB/cells\n").unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--split-by", path_str(&table)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for line in ["B_cells: 10", "T_cells: 10", "unassigned: 10"] {
+        assert!(stderr.contains(line), "{}", stderr);
+    }
+
+    let r2_out = fs::read_to_string(dir.path().join("out_T_cells_S1_L001_R2_001.fastq")).unwrap();
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "AAAACCCCGGGGTTTT"));
+    assert!(dir.path().join("out_unassigned_S1_L001_R3_001.fastq").exists());
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--split-by", path_str(&table), "--max-open-groups", "2"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("raise --max-open-groups"));
+}
+
+#[test]
+fn test_split_lanes_writes_one_directory_per_lane() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let mut pairs = Fixture::new(30, &[BARCODE]).records();
+    // lane 1、2 各 10 对，另有 10 对是非 Illumina header
+    for (i, (r1, r2)) in pairs.iter_mut().enumerate().filter(|(i, _)| i % 3 != 2) {
+        let name = format!("M0:7:FC:{}:1101:{}:1", i % 3 + 1, i);
+        (r1.head, r2.head) = (format!("{}/1", name).into_bytes(), format!("{}/2", name).into_bytes());
+    }
+    let (r1_text, r2_text) = fastq_text(&pairs);
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--split-lanes"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    for line in ["Records per lane:", "L001: 10", "L002: 10", "unknown: 10"] {
+        assert!(stderr.contains(line), "{}", stderr);
+    }
+
+    let r2_out = fs::read_to_string(dir.path().join("L002/out_S1_L002_R2_001.fastq")).unwrap();
+    assert_eq!(r2_out.lines().count(), 10 * 4);
+    assert!(r2_out.lines().step_by(4).all(|head| head.starts_with("@M0:7:FC:2:")));
+    assert!(dir.path().join("L001/out_S1_L001_R3_001.fastq").exists());
+    assert!(dir.path().join("unknown/out_S1_L001_R1_001.fastq").exists());
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+}
+
+#[test]
+fn test_split_per_barcode_reopens_evicted_outputs() {
+    use std::io::Read;
+    let dir = tempfile::tempdir().unwrap();
+    let barcodes = ["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT", "TTTTGGGGCCCCAAAA", "CCCCAAAAGGGGTTTT", "GGGGTTTTAAAACCCC"];
+    let (r1, r2) = common::write_fixture(dir.path(), 50, &barcodes);
+    let whitelist = dir.path().join("whitelist.txt");
+    fs::write(&whitelist, format!("{}\nGATCGATCGATCGATC\n", barcodes.join("\n"))).unwrap();
+    let prefix = dir.path().join("out");
+    // 同时只能打开两个条形码的输出，轮流出现的 5 个条形码反复被关闭、追加
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--compress",
+                    "--whitelist", path_str(&whitelist), "--split-per-barcode", "--max-open-files", "6"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Per-barcode outputs: 5 barcodes received reads"), "{}", stderr);
+
+    for bc in barcodes {
+        let compressed = fs::read(dir.path().join(format!("out_{}_S1_L001_R2_001.fastq.gz", bc))).unwrap();
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(&compressed[..]).read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().count(), 10 * 4, "{}", bc);
+        assert!(text.lines().skip(1).step_by(4).all(|seq| seq == bc));
+    }
+    assert!(!dir.path().join("out_GATCGATCGATCGATC_S1_L001_R1_001.fastq.gz").exists());
+    let counts = fs::read_to_string(dir.path().join("out_per_barcode_counts.tsv")).unwrap();
+    assert_eq!(counts.lines().next(), Some("barcode\tpairs"));
+    assert!(counts.contains("AAAACCCCGGGGTTTT\t10\n") && counts.contains("GATCGATCGATCGATC\t0\n"), "{}", counts);
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--whitelist", path_str(&whitelist), "--split-per-barcode", "--max-barcodes", "5"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("more than --max-barcodes 5"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--split-per-barcode"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_shard_by_bc_prefix_keeps_trios_together() {
+    let dir = tempfile::tempdir().unwrap();
+    let barcodes = ["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT", "ACCCAAAAGGGGTTTT", "TTTTGGGGCCCCAAAA", "NAAACCCCGGGGTTTT"];
+    let (r1, r2) = common::write_fixture(dir.path(), 50, &barcodes);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--shard-by-bc-prefix", "2"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Records per shard:"), "{}", stderr);
+    assert!(stderr.contains("  AC: 20") && stderr.contains("  N: 10"), "{}", stderr);
+
+    for (shard, expected) in [("AA", &barcodes[..1]), ("AC", &barcodes[1..3]), ("TT", &barcodes[3..4]),
+                              ("N", &barcodes[4..])] {
+        let read = |r: &str| fs::read_to_string(dir.path().join(format!("out.shard_{}_S1_L001_{}_001.fastq", shard, r)))
+            .unwrap();
+        let (r1, r2, r3) = (read("R1"), read("R2"), read("R3"));
+        let names = |text: &str| -> Vec<String> {
+            text.lines().step_by(4).map(|h| h.trim_end_matches(['1', '2', '3']).to_string()).collect()
+        };
+        assert_eq!(names(&r1).len(), expected.len() * 10, "{}", shard);
+        assert_eq!(names(&r1), names(&r2));
+        assert_eq!(names(&r1), names(&r3));
+        assert!(r2.lines().skip(1).step_by(4).all(|bc| expected.contains(&bc)), "{}", shard);
+    }
+    assert!(!dir.path().join("out.shard_GG_S1_L001_R1_001.fastq").exists());
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--shard-by-bc-prefix", "5"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_prescan_reports_r2_lengths_without_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 8, &["AAAACCCCGGGGTTTT"]);
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "--prescan"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    // 报告写到标准错误，标准输出留给机器可读的内容
+    assert!(out.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("R2 reads: 8\n  166 bp: 8 (100.00%)\n  Other lengths: 0 (0.00%)"), "{}", stderr);
+    assert!(!stderr.contains("WARNING"));
+
+    // 再加两条 150 bp 的 R2
+    let mut text = fs::read_to_string(&r2).unwrap();
+    for i in 0..2 {
+        text.push_str(&format!("@short{}/2\n{}\n+\n{}\n", i, "A".repeat(150), "I".repeat(150)));
+    }
+    fs::write(&r2, text).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--prescan"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("  166 bp: 8 (80.00%)") && stderr.contains("  150 bp: 2 (20.00%)"), "{}", stderr);
+    assert!(stderr.contains("WARNING: 2 R2 reads are not 166 bp"), "{}", stderr);
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+}
+
+#[test]
+fn test_head_and_max_records_read_the_first_pairs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"]);
+    let split = |name: &str, flag: &str| {
+        let prefix = dir.path().join(name);
+        let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--progress", "none",
+                        "--threads", "1", "--batch-size", "3", flag, "5"]);
+        assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+        ["R1", "R2", "R3"].map(|read| fs::read_to_string(dir.path().join(format!("{}_S1_L001_{}_001.fastq", name, read))).unwrap())
+    };
+    let head = split("head", "--head");
+    assert!(head.iter().all(|text| text.lines().count() == 5 * 4));
+    assert!((0..5).all(|i| head[2].contains(&format!("@read{}\n", i))) && !head[2].contains("@read5\n"), "{}", head[2]);
+    assert_eq!(split("max_records", "--max-records"), head);
+    assert_eq!(run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", "x", "--head", "0"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_check_duplicate_names_flags_doubled_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let base = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--check-duplicate-names"];
+    let out = run(&base);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Duplicate read names: ~0 of 20 pairs"), "{}", stderr);
+    assert!(!stderr.contains("repeat an earlier read name"), "{}", stderr);
+
+    // 同一批 read 拼接两次
+    for path in [&r1, &r2] {
+        let text = fs::read_to_string(path).unwrap();
+        fs::write(path, text.repeat(2)).unwrap();
+    }
+    let out = run(&[&base[..], &["--quiet"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("WARNING: 50.00% of input pairs (~20) repeat an earlier read name"), "{}", stderr);
+
+    let out = run(&[&base[..], &["--strict"]].concat());
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("repeat an earlier read name"));
+}
+
+#[test]
+fn test_summary_breaks_down_filtered_pairs_by_reason() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 7, &["AAAACCCCGGGGTTTT"]);
+    let (mut r1_text, mut r2_text) = (fs::read_to_string(&r1).unwrap(), fs::read_to_string(&r2).unwrap());
+    // 两条 R2 过短，一对 header 不一致
+    for i in 0..2 {
+        r1_text.push_str(&format!("@short{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@short{}/2\n{}\n+\n{}\n", i, "A".repeat(150), "I".repeat(150)));
+    }
+    r1_text.push_str(&format!("@mate/1\n{}\n+\n{}\n", "A".repeat(50), "I".repeat(50)));
+    r2_text.push_str(&format!("@other/2\n{}\n+\n{}\n", "A".repeat(166), "I".repeat(166)));
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Filtered out records: 3\n  length: 2 (20.00% of input pairs)\n  \
+                             header_mismatch: 1 (10.00% of input pairs)\n"), "{}", stderr);
+    assert!(!stderr.contains("barcode_no_match:"), "{}", stderr);
+}
+
+#[test]
+fn test_rename_reads_shares_new_name_across_outputs() {
+    use std::io::Read;
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 30, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT"]);
+    let prefix = dir.path().join("out");
+    let map = dir.path().join("names.tsv.gz");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--threads", "3",
+                    "--rename-reads", "cell", "--rename-map", path_str(&map)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let heads = |read: &str| -> Vec<String> {
+        fs::read_to_string(dir.path().join(format!("out_S1_L001_{}_001.fastq", read))).unwrap()
+            .lines().step_by(4).map(str::to_string).collect()
+    };
+    let r1_heads = heads("R1");
+    let expected: Vec<String> = (1..=30).map(|i| format!("@cell:{}", i)).collect();
+    assert_eq!(r1_heads, expected);
+    assert_eq!(heads("R2"), r1_heads);
+    assert_eq!(heads("R3"), r1_heads);
+
+    let mut text = String::new();
+    flate2::read::MultiGzDecoder::new(fs::File::open(&map).unwrap()).read_to_string(&mut text).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "original\trenamed");
+    assert_eq!(lines.len(), 31);
+    let mut originals: Vec<&str> = lines[1..].iter().enumerate().map(|(i, line)| {
+        let (original, renamed) = line.split_once('\t').unwrap();
+        assert_eq!(renamed, format!("cell:{}", i + 1));
+        original
+    }).collect();
+    originals.sort();
+    let mut names: Vec<String> = (0..30).map(|i| format!("read{}", i)).collect();
+    names.sort();
+    assert_eq!(originals, names);
+
+    // 不给前缀时为 read
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--rename-reads"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(heads("R3")[29], "@read:30");
+}
+
+#[test]
+fn test_scan_bc_offset_applies_best_window() {
+    let dir = tempfile::tempdir().unwrap();
+    // 条形码比默认位置提前 1 bp，末尾多出 1 个碱基
+    let (r1, r2) = (dir.path().join("R1.fastq"), dir.path().join("R2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    for i in 0..20 {
+        let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}{}C\n+\n{}\n", i, "G".repeat(149), bc_rc, "I".repeat(166)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let whitelist = dir.path().join("wl.txt");
+    fs::write(&whitelist, "AAAACCCCGGGGTTTT\n").unwrap();
+    let prefix = dir.path().join("out");
+    let base = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                "-w", path_str(&whitelist), "--scan-bc-offset", "148..151"];
+
+    // 只报告：不创建输出
+    let out = run(&base);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Best barcode window: offset 149, reverse complement (100.00%)"), "{}", stderr);
+    assert!(!dir.path().join("out_S1_L001_R2_001.fastq").exists());
+
+    let out = run(&[&base[..], &["--apply-best"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Processed records: 20"));
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "AAAACCCCGGGGTTTT"));
+    let r3_out = fs::read_to_string(dir.path().join("out_S1_L001_R3_001.fastq")).unwrap();
+    assert_eq!(r3_out.lines().nth(1).unwrap(), "G".repeat(149));
+}
+
+#[test]
+fn test_no_rc_barcode_keeps_forward_orientation() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 3, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--no-rc-barcode"]);
+    assert!(out.status.success());
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    // fixture 中存的是反向互补序列，原样输出
+    let bc_rc = scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT");
+    assert_eq!(r2_out.lines().nth(1).unwrap().as_bytes(), bc_rc);
+}
+
+#[test]
+fn test_skewed_barcode_composition_warns() {
+    let dir = tempfile::tempdir().unwrap();
+    // 只有一个条形码：每个位置都是单一碱基
+    let (r1, r2) = common::write_fixture(dir.path(), 1000, &["AAAACCCCGGGGTTTA"]);
+    let prefix = dir.path().join("out");
+    let stats_path = dir.path().join("stats.json");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("WARNING: Barcode position 16 is 100.0% A"), "{}", stderr);
+
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    let composition = stats["barcode_composition"].as_array().unwrap();
+    assert_eq!(composition.len(), 16);
+    assert_eq!(composition[4]["c"], 1.0);
+}
+
+#[test]
+fn test_swap_r2_r3_exchanges_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let (plain, swapped) = (dir.path().join("plain"), dir.path().join("swapped"));
+    for (prefix, extra) in [(&plain, None), (&swapped, Some("--swap-r2-r3"))] {
+        let mut args = vec!["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(prefix)];
+        args.extend(extra);
+        assert!(run(&args).status.success());
+    }
+    let read = |prefix: &std::path::Path, read: &str| {
+        fs::read(format!("{}_S1_L001_{}_001.fastq", path_str(prefix), read)).unwrap()
+    };
+    assert_eq!(read(&swapped, "R2"), read(&plain, "R3"));
+    assert_eq!(read(&swapped, "R3"), read(&plain, "R2"));
+    assert_eq!(read(&swapped, "R1"), read(&plain, "R1"));
+}
+
+#[test]
+fn test_two_pass_requires_regular_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let whitelist = dir.path().join("wl.txt");
+    fs::write(&whitelist, "AAAACCCCGGGGTTTT\n").unwrap();
+    let prefix = dir.path().join("out");
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", "-", "-o", path_str(&prefix),
+                    "-w", path_str(&whitelist), "--two-pass"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("is not a regular file"));
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "-w", path_str(&whitelist), "--two-pass"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("First pass: 5 reads with exact whitelist barcodes (1 barcodes)"));
+}
+
+#[test]
+fn test_min_reads_per_barcode_counts_corrected_barcodes() {
+    let dir = tempfile::tempdir().unwrap();
+    // 纠错后 AAAACCCCGGGGTTTT 有 11 条（其中 5 条需要纠错），ACGTACGTACGTACGT 有 10 条
+    let (r1, r2) = common::write_fixture(dir.path(), 21,
+        &["AAAACCCCGGGGTTTT", "AAAACCCCGGGGTTTA", "ACGTACGTACGTACGT", "ACGTACGTACGTACGT"]);
+    let whitelist = dir.path().join("wl.txt");
+    fs::write(&whitelist, "AAAACCCCGGGGTTTT\nACGTACGTACGTACGT\n").unwrap();
+    let prefix = dir.path().join("out");
+    let base = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                "-w", path_str(&whitelist), "--min-reads-per-barcode", "11"];
+
+    let out = run(&base);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Barcodes with fewer than 11 reads: 1 barcodes, 10 pairs (dropped)"), "{}", stderr);
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    assert_eq!(r2_out.lines().count(), 11 * 4);
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "AAAACCCCGGGGTTTT"));
+
+    let out = run(&[&base[..], &["--unselected", "unassigned"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let unassigned = fs::read_to_string(dir.path().join("out_unassigned_S1_L001_R2_001.fastq")).unwrap();
+    assert!(unassigned.lines().skip(1).step_by(4).all(|seq| seq == "ACGTACGTACGTACGT"));
+    assert_eq!(unassigned.lines().count(), 10 * 4);
+}
+
+#[test]
+fn test_head_limits_pre_passes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 21,
+        &["AAAACCCCGGGGTTTT", "AAAACCCCGGGGTTTA", "ACGTACGTACGTACGT", "ACGTACGTACGTACGT"]);
+    let whitelist = dir.path().join("wl.txt");
+    fs::write(&whitelist, "AAAACCCCGGGGTTTT\nACGTACGTACGTACGT\n").unwrap();
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "-w", path_str(&whitelist),
+                    "--two-pass", "--min-reads-per-barcode", "5", "--head", "8"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    // 两遍预先计数都只看前 8 个 pair：纠错后两个条形码各 4 条，都不到 5 条
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("First pass: 6 reads with exact whitelist barcodes (2 barcodes)"), "{}", stderr);
+    assert!(stderr.contains("Barcodes with fewer than 5 reads: 2 barcodes, 8 pairs (dropped)"), "{}", stderr);
+    assert_eq!(fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap(), "");
+}
+
+#[test]
+fn test_stats_report_output_size_and_compression_ratio() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 200, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGT"]);
+    let stats_of = |name: &str, extra: &[&str]| -> serde_json::Value {
+        let (prefix, stats_path) = (dir.path().join(name), dir.path().join(format!("{}.json", name)));
+        let args = [&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                      "--stats-json", path_str(&stats_path)], extra].concat();
+        assert!(run(&args).status.success());
+        serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap()
+    };
+
+    let plain = stats_of("plain", &[]);
+    let r3_size = fs::metadata(dir.path().join("plain_S1_L001_R3_001.fastq")).unwrap().len();
+    assert_eq!(plain["r3_output_mb"], r3_size as f64 / 1e6);
+    assert!(plain.get("output_compression").is_none());
+
+    let gz = stats_of("gz", &["--compress"]);
+    let r3_gz_size = fs::metadata(dir.path().join("gz_S1_L001_R3_001.fastq.gz")).unwrap().len();
+    assert_eq!(gz["r3_output_mb"], r3_gz_size as f64 / 1e6);
+    // 压缩前的数据量与不压缩时写出的相同
+    for read in ["r1", "r2", "r3"] {
+        assert_eq!(gz["output_compression"][format!("{}_raw_mb", read)], plain[format!("{}_output_mb", read)]);
+    }
+    assert!(gz["output_compression"]["ratio"].as_f64().unwrap() > 1.0);
+}
+
+#[test]
+fn test_quiet_prints_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "-q",
+                    "--progress", "log"]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty(), "{}", String::from_utf8_lossy(&out.stdout));
+    assert!(out.stderr.is_empty(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(dir.path().join("out_S1_L001_R2_001.fastq").exists());
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "-q", "-v"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_readthrough_drop_filters_adapter_barcodes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+    for i in 0..10 {
+        // 每 4 对中有一对在条形码窗口读到了接头
+        let window = if i % 4 == 0 { "CTGTCTCTTATACACA" } else { bc_rc.as_str() };
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}{}\n+\n{}\n", i, "G".repeat(150), window, "I".repeat(166)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--readthrough", "drop", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Adapter read-through in barcode window: 3 pairs (30.00%, dropped)"));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["adapter_readthrough"], 3);
+    assert_eq!(stats["pairs_written"], 7);
+}
+
+#[test]
+fn test_r3_region_trims_genomic_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--r3-start", "10", "--r3-end", "140"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let r2_lines: Vec<String> = fs::read_to_string(&r2).unwrap().lines().map(String::from).collect();
+    let r3_text = fs::read_to_string(dir.path().join("out_S1_L001_R3_001.fastq")).unwrap();
+    let r3_lines: Vec<&str> = r3_text.lines().collect();
+    assert_eq!(r3_lines[1], &r2_lines[1][10..140]);
+    assert_eq!(r3_lines[3].len(), 130);
+
+    // R3 不能与条形码重叠
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix), "--r3-end", "151"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("R3 region 0..151"));
+}
+
+#[test]
+fn test_exclude_tiles_and_tile_stats() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+    for i in 0..40 {
+        // tile 1101–1103 各 10 对，另有 10 对是非 Illumina header；tile 1103 的 R2 都太短
+        let name = match i % 4 {
+            3 => format!("read{}", i),
+            t => format!("M0:7:FC:1:110{}:{}:1", t + 1, i),
+        };
+        let genomic = if i % 4 == 2 { "G".repeat(149) } else { "G".repeat(150) };
+        r1_text.push_str(&format!("@{}/1\n{}\n+\n{}\n", name, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@{}/2\n{}{}\n+\n{}\n", name, genomic, bc_rc, "I".repeat(genomic.len() + 16)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--exclude-tiles", "1101,2201", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Highest tile filter rates (lane:tile): 1:1103 100.00% of 10"), "{}", stderr);
+    assert!(stderr.contains("Excluded tiles: 10 pairs dropped"), "{}", stderr);
+
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["excluded_tile"], 10);
+    assert_eq!(stats["filtered_by_reason"]["length"], 10);
+    assert_eq!(stats["pairs_written"], 20);
+    let tiles: Vec<(u64, f64)> = stats["worst_tiles"].as_array().unwrap().iter()
+        .map(|t| (t["tile"].as_u64().unwrap(), t["filter_rate"].as_f64().unwrap()))
+        .collect();
+    assert_eq!(tiles, [(1103, 1.0), (1102, 0.0)]);
+}
+
+#[test]
+fn test_mark_optical_dups_drop() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+    // 同一片段在 tile 1101 的相邻位置出现两次、在远处出现一次、在 tile 1102 出现一次
+    let reads = [(1101, 5000, 5000), (1101, 5010, 5005), (1101, 9000, 9000), (1102, 5000, 5000)];
+    for (tile, x, y) in reads {
+        let name = format!("M0:7:FC:1:{}:{}:{}", tile, x, y);
+        r1_text.push_str(&format!("@{}/1\n{}\n+\n{}\n", name, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@{}/2\n{}{}\n+\n{}\n", name, "G".repeat(150), bc_rc, "I".repeat(166)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--mark-optical-dups", "100", "--optical-dup-policy", "drop",
+                    "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr)
+        .contains("Optical duplicates (within 100 px): 1 of 4 pairs (25.00%, dropped)"));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["optical_duplicate"], 1);
+    assert_eq!(stats["pairs_written"], 3);
+    assert_eq!(stats["optical_duplicates"]["duplicates"], 1);
+}
+
+#[test]
+fn test_allow_truncated_bc_rescues_unique_prefix() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    // R2 少了最后 1 bp，即条形码的第一个碱基
+    for (i, bc) in ["AAAACCCCGGGGTTTT", "TTTTGGGGCCCCAAAA"].iter().enumerate() {
+        let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(bc.as_bytes())).unwrap();
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}{}\n+\n{}\n", i, "G".repeat(150), &bc_rc[..15], "I".repeat(165)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let whitelist = dir.path().join("wl.txt");
+    // 第二个条形码的截短序列同时匹配两个条目
+    fs::write(&whitelist, "AAAACCCCGGGGTTTT\nTTTTGGGGCCCCAAAA\nGTTTGGGGCCCCAAAA\n").unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let base = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                "-w", path_str(&whitelist), "--stats-json", path_str(&stats_path)];
+
+    let out = run(&base);
+    assert!(out.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["length"], 2);
+
+    let out = run(&[&base[..], &["--allow-truncated-bc", "1"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    let lines: Vec<_> = r2_out.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[1], "AAAACCCCGGGGTTTT");
+    assert_eq!(lines[3], format!("!{}", "I".repeat(15)));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["barcode_correction"]["truncated_rescued"], 1);
+    assert_eq!(stats["barcode_correction"]["truncated_ambiguous"], 1);
+    assert_eq!(stats["filtered_by_reason"]["barcode_no_match"], 1);
+}
+
+#[test]
+fn test_correct_indels_rescues_shifted_barcodes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    // 条形码 ACGTTGCAAGCTTCGA 分别带一个插入（T）和一个缺失（第 9 个碱基），R2 中为反向互补
+    for (i, observed) in ["CGTTGCAAGTCTTCGA", "GACGTTGCAGCTTCGA"].iter().enumerate() {
+        let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(observed.as_bytes())).unwrap();
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}{}\n+\n{}\n", i, "G".repeat(150), bc_rc, "I".repeat(166)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let whitelist = dir.path().join("wl.txt");
+    fs::write(&whitelist, "ACGTTGCAAGCTTCGA\nTTTTGGGGCCCCAAAA\n").unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let base = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                "-w", path_str(&whitelist), "--stats-json", path_str(&stats_path)];
+
+    let out = run(&base);
+    assert!(out.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["barcode_correction"]["no_match"], 2);
+
+    let out = run(&[&base[..], &["--correct-indels", "--verbose"]].concat());
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Corrected (1 indel): 2"), "{}", stderr);
+    assert!(stderr.contains("Indel correction: 2 barcodes tried"), "{}", stderr);
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "ACGTTGCAAGCTTCGA"));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["barcode_correction"]["indel_corrected"], 2);
+    assert_eq!(stats["barcode_correction"]["no_match"], 0);
+}
+
+#[test]
+fn test_warns_when_most_batches_are_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = write_cross_run_fixture(dir.path());
+    let prefix = dir.path().join("out");
+    // 唯一的 pair 因 header 不一致被过滤
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("WARNING: 1 of 1 batches had every pair filtered"));
+}
+
+#[test]
+fn test_library_log_follows_verbose_and_rust_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let args = ["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)];
+
+    let out = run(&args);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("Finished writing"));
+    let out = run(&[&args[..], &["--verbose"]].concat());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Finished writing 5 records"));
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_scatac-barcode-splitter"))
+        .args(args).env("RUST_LOG", "info").output().unwrap();
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Finished reading record pairs"));
+}
+
+#[test]
+fn test_anchor_splits_at_constant_sequence() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    let me = "AGATGTGTATAAGAGACAG";
+    let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+    // 基因组片段长度不一；第三对没有锚定序列，第四对有两处
+    let r2_seqs = [
+        format!("{}{}{}", "C".repeat(100), me, bc_rc),
+        format!("{}{}{}TT", "C".repeat(80), me, bc_rc),
+        format!("{}{}", "C".repeat(119), bc_rc),
+        format!("{}{}{}{}{}", "C".repeat(40), me, "C".repeat(20), me, bc_rc),
+    ];
+    for (i, seq) in r2_seqs.iter().enumerate() {
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}\n+\n{}\n", i, seq, "I".repeat(seq.len())));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--anchor", me, "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let r2_out = fs::read_to_string(dir.path().join("out_S1_L001_R2_001.fastq")).unwrap();
+    assert!(r2_out.lines().skip(1).step_by(4).all(|seq| seq == "AAAACCCCGGGGTTTT"));
+    let r3_out = fs::read_to_string(dir.path().join("out_S1_L001_R3_001.fastq")).unwrap();
+    let r3_lens: Vec<usize> = r3_out.lines().skip(1).step_by(4).map(str::len).collect();
+    assert_eq!(r3_lens, [100, 80, 40 + me.len() + 20]);
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["anchor_not_found"], 1);
+    assert_eq!(stats["anchor"]["not_found"], 1);
+    assert_eq!(stats["anchor"]["ambiguous"], 1);
+}
+
+#[test]
+fn test_filter_low_complexity_drops_repeats() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("r1.fastq"), dir.path().join("r2.fastq"));
+    let (mut r1_text, mut r2_text) = (String::new(), String::new());
+    let bc_rc = String::from_utf8(scatac_barcode_splitter::reverse_complement(b"AAAACCCCGGGGTTTT")).unwrap();
+    let genomic: String = (0..150).map(|j| b"ACGT"[(j * 7 + j / 3 + j * j / 11) % 4] as char).collect();
+    for (i, insert) in ["A".repeat(150), "AT".repeat(75), genomic].iter().enumerate() {
+        r1_text.push_str(&format!("@read{}/1\n{}\n+\n{}\n", i, "A".repeat(50), "I".repeat(50)));
+        r2_text.push_str(&format!("@read{}/2\n{}{}\n+\n{}\n", i, insert, bc_rc, "I".repeat(166)));
+    }
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--filter-low-complexity", "0.5", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Low-complexity R3 (entropy below 0.5): 2 pairs dropped"));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["filtered_by_reason"]["low_complexity"], 2);
+    assert_eq!(stats["pairs_written"], 1);
+
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--filter-low-complexity", "1.5"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn test_contaminant_screen_warns_above_threshold() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT"]);
+    // 第一条 read 的基因组序列作为污染序列：1/4 的 read 与之相同，另有 1/4 恰好落在其反向互补链上
+    let genomic: String = (0..150).map(|j| b"ACGT"[(j * 3 + j / 5) % 4] as char).collect();
+    let fasta = dir.path().join("contaminants.fa");
+    fs::write(&fasta, format!(">phiX\n{}\n", genomic)).unwrap();
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--contaminants", path_str(&fasta), "--contaminant-every", "1", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("phiX: 10 (50.00%)"), "{}", stderr);
+    assert!(stderr.contains("WARNING: 50.00% of screened R3 reads match contaminant 'phiX'"), "{}", stderr);
+
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["contaminants"]["sampled"], 20);
+    assert_eq!(stats["contaminants"]["contaminants"][0]["reads"], 10);
+}
+
+#[test]
+fn test_overrepresented_sequences_in_summary_and_stats() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 20, &["AAAACCCCGGGGTTTT"]);
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--overrepresented-every", "4", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Overrepresented R3 sequences (> 0.10% of 5 sampled reads): 1"), "{}", stderr);
+
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    let over = &stats["overrepresented"];
+    assert_eq!(over["sampled"], 5);
+    assert_eq!(over["sequences"][0]["count"], 5);
+    assert_eq!(over["sequences"][0]["sequence"].as_str().unwrap().len(), 50);
+}
+
+#[test]
+fn test_invalid_arguments_name_the_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 2, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let cases: [(&[&str], &str); 8] = [
+        (&["--batch-size", "0"], "--batch-size"),
+        (&["--threads", "0"], "--threads"),
+        (&["--record-pool", "0"], "--record-pool"),
+        (&["-n", "abc"], "--number-suffix"),
+        (&["--r3-start", "20", "--r3-end", "10"], "--r3-start 20 must be less than --r3-end 10"),
+        (&["--r3-end", "160"], "--r3-end 160"),
+        (&["--r3-start", "150"], "--r3-start 150"),
+        (&["--anchor", "ACGT", "--anchor-max-mismatch", "4"], "--anchor-max-mismatch"),
+    ];
+    for (extra, flag) in cases {
+        let args = [&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix)][..], extra].concat();
+        let out = run(&args);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(2), "{:?}: {}", extra, stderr);
+        assert!(stderr.contains(flag), "{:?}: {}", extra, stderr);
+    }
+    assert!(!dir.path().join("out_S1_L001_R1_001.fastq").exists());
+
+    let out = run(&["passthrough", "-1", path_str(&r1), "--barcode", path_str(&r2), "-3", path_str(&r2),
+                    "-o", path_str(&prefix), "-n", "1"]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--number-suffix"));
+}
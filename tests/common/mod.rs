@@ -0,0 +1,99 @@
+// 集成测试共用的工具函数
+#![allow(dead_code)]
+
+use fastq::OwnedRecord;
+use scatac_barcode_splitter::fastq_io::write_fastq_record;
+use scatac_barcode_splitter::stats::RunStats;
+use scatac_barcode_splitter::{reverse_complement, run_pipeline, PipelineConfig, PipelineWriters, SharedBuffer};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// 条形码测试默认使用的条形码
+pub const BARCODE: &str = "AAAACCCCGGGGTTTT";
+
+/// 内存中的合成 read 对：第 i 对名为 `read{i}`，R2 为 150bp 基因组 + 16bp 条形码（反向互补后为 `barcodes[i % len]`）
+pub struct Fixture {
+    n: usize,
+    barcodes: Vec<String>,
+    r1_len: fn(usize) -> usize,
+}
+
+impl Fixture {
+    pub fn new(n: usize, barcodes: &[&str]) -> Self {
+        Fixture { n, barcodes: barcodes.iter().map(|bc| bc.to_string()).collect(), r1_len: |_| 50 }
+    }
+
+    /// 第 i 对 R1 的长度（默认 50，不超过 150）
+    pub fn r1_len(mut self, r1_len: fn(usize) -> usize) -> Self {
+        self.r1_len = r1_len;
+        self
+    }
+
+    /// 质量值全为 'I' 的记录
+    pub fn records(&self) -> Vec<(OwnedRecord, OwnedRecord)> {
+        (0..self.n).map(|i| {
+            let genomic: Vec<u8> = (0..150).map(|j| b"ACGT"[(i * 7 + j * 3 + j / 5) % 4]).collect();
+            let bc_rc = reverse_complement(self.barcodes[i % self.barcodes.len()].as_bytes());
+            (record(format!("read{}/1", i), genomic[..(self.r1_len)(i)].to_vec()),
+             record(format!("read{}/2", i), [genomic, bc_rc].concat()))
+        }).collect()
+    }
+
+    /// R1、R2 的 FASTQ 文本
+    pub fn text(&self) -> (String, String) {
+        fastq_text(&self.records())
+    }
+}
+
+/// 质量值全为 'I' 的记录
+pub fn record(head: impl Into<Vec<u8>>, seq: Vec<u8>) -> OwnedRecord {
+    OwnedRecord { head: head.into(), qual: vec![b'I'; seq.len()], seq, sep: None }
+}
+
+/// read 对的 R1、R2 FASTQ 文本
+pub fn fastq_text(pairs: &[(OwnedRecord, OwnedRecord)]) -> (String, String) {
+    let (mut r1, mut r2) = (Vec::new(), Vec::new());
+    for (record1, record2) in pairs {
+        write_fastq_record(&mut r1, record1).unwrap();
+        write_fastq_record(&mut r2, record2).unwrap();
+    }
+    (String::from_utf8(r1).unwrap(), String::from_utf8(r2).unwrap())
+}
+
+/// 在内存中拆分 R1/R2 文本，返回统计与 R1、R2、R3 输出
+pub fn split_in_memory(config: PipelineConfig, r1: &str, r2: &str) -> (RunStats, [Vec<u8>; 3]) {
+    let outputs = [SharedBuffer::new(), SharedBuffer::new(), SharedBuffer::new()];
+    let [o1, o2, o3] = outputs.clone();
+    let stats = run_pipeline(config, Cursor::new(r1.as_bytes().to_vec()), Cursor::new(r2.as_bytes().to_vec()),
+                             PipelineWriters::new(o1, o2, o3)).unwrap();
+    (stats, outputs.map(|buf| buf.contents()))
+}
+
+/// 与 [`split_in_memory`] 相同，只返回 R1、R2、R3 输出
+pub fn run_in_memory(config: PipelineConfig, r1: &str, r2: &str) -> [Vec<u8>; 3] {
+    split_in_memory(config, r1, r2).1
+}
+
+/// 把 [`Fixture`] 写成文件 `input_R1.fastq`、`input_R2.fastq`
+pub fn write_fixture(dir: &Path, n: usize, barcodes: &[&str]) -> (PathBuf, PathBuf) {
+    let r1 = dir.join("input_R1.fastq");
+    let r2 = dir.join("input_R2.fastq");
+    let (r1_text, r2_text) = Fixture::new(n, barcodes).text();
+    fs::write(&r1, r1_text).unwrap();
+    fs::write(&r2, r2_text).unwrap();
+    (r1, r2)
+}
+
+/// 运行程序本身
+pub fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_scatac-barcode-splitter"))
+        .args(args)
+        .output()
+        .expect("failed to run scatac-barcode-splitter")
+}
+
+pub fn path_str(p: &Path) -> &str {
+    p.to_str().unwrap()
+}
@@ -0,0 +1,21 @@
+use scatac_barcode_splitter::complexity::{estimate_library_size, projected_unique, saturation};
+
+#[test]
+fn test_saturation() {
+    assert_eq!(saturation(1000, 250), 0.75);
+    assert_eq!(saturation(1000, 1000), 0.0);
+    assert_eq!(saturation(0, 0), 0.0);
+}
+
+#[test]
+fn test_lander_waterman_projection() {
+    // C = 1000、N = 1000 时 U = 1000 × (1 − e^−1) ≈ 632.12；2 倍深度时 1000 × (1 − e^−2) ≈ 864.66
+    let size = estimate_library_size(1000, 632).unwrap();
+    assert!((size - 1000.0).abs() < 1.0, "{}", size);
+    let projected = projected_unique(1000, 632, 2.0);
+    assert!((projected - 864.66).abs() < 0.5, "{}", projected);
+
+    // 没有重复时无法估计文库大小，按深度同比增长
+    assert_eq!(estimate_library_size(500, 500), None);
+    assert_eq!(projected_unique(500, 500, 2.0), 1000.0);
+}
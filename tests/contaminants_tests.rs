@@ -0,0 +1,60 @@
+use scatac_barcode_splitter::contaminants::{ContaminantIndex, ContaminantScreen, KMER_LEN};
+use scatac_barcode_splitter::reverse_complement;
+
+/// 固定种子的伪随机序列
+fn random_seq(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len).map(|_| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        b"ACGT"[(seed >> 62) as usize]
+    }).collect()
+}
+
+fn fasta(records: &[(&str, &[u8])]) -> String {
+    records.iter().map(|(name, seq)| {
+        // 每行 60 bp
+        let lines: Vec<&str> = seq.chunks(60).map(|c| std::str::from_utf8(c).unwrap()).collect();
+        format!(">{} description\n{}\n", name, lines.join("\n"))
+    }).collect()
+}
+
+#[test]
+fn test_shared_kmers_on_both_strands() {
+    let (phix, dimer) = (random_seq(500, 1), random_seq(60, 2));
+    let index = ContaminantIndex::from_reader(fasta(&[("phiX", &phix), ("dimer", &dimer)]).as_bytes()).unwrap();
+    assert_eq!(index.names(), ["phiX", "dimer"]);
+
+    let read = &phix[100..150];
+    assert_eq!(index.shared_kmers(read), [50 - KMER_LEN + 1, 0]);
+    assert_eq!(index.shared_kmers(&reverse_complement(read)), [50 - KMER_LEN + 1, 0]);
+    assert_eq!(index.shared_kmers(&random_seq(150, 3)), [0, 0]);
+    // N 所在的 k-mer 不计
+    let mut masked = read.to_vec();
+    masked[25] = b'N';
+    assert_eq!(index.shared_kmers(&masked), [50 - KMER_LEN + 1 - KMER_LEN, 0]);
+}
+
+#[test]
+fn test_invalid_fasta_is_rejected() {
+    assert!(ContaminantIndex::from_reader("ACGT\n".as_bytes()).is_err());
+    assert!(ContaminantIndex::from_reader(">short\nACGTACGT\n".as_bytes()).is_err());
+    assert!(ContaminantIndex::from_reader("".as_bytes()).is_err());
+}
+
+#[test]
+fn test_screen_samples_and_counts_hits() {
+    let phix = random_seq(1000, 4);
+    let index = ContaminantIndex::from_reader(fasta(&[("phiX", &phix)]).as_bytes()).unwrap();
+    let mut screen = ContaminantScreen::new(index, 2, 3);
+    for i in 0..100u64 {
+        // 每 4 条中有 1 条来自 phiX，只有第 1、3 条……被抽中
+        if i % 4 == 1 {
+            screen.add(&phix[i as usize..i as usize + 150]);
+        } else {
+            screen.add(&random_seq(150, 100 + i));
+        }
+    }
+    let stats = screen.to_stats();
+    assert_eq!((stats.sample_every, stats.sampled, stats.min_kmers), (2, 50, 3));
+    assert_eq!(stats.contaminants[0].reads, 25);
+    assert_eq!(stats.contaminants[0].fraction, 0.5);
+}
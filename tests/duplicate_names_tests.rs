@@ -0,0 +1,56 @@
+use scatac_barcode_splitter::duplicate_names::DuplicateNames;
+
+fn name(i: u64) -> Vec<u8> {
+    format!("A00123:8:HXXXXXXXX:1:1101:{}:{}", 1000 + i % 5000, 1000 + i / 5000).into_bytes()
+}
+
+#[test]
+fn test_exact_counts_below_the_sample_limit() {
+    let mut names = DuplicateNames::new();
+    for i in 0..1000 {
+        names.add(&name(i));
+    }
+    for i in 0..10 {
+        names.add(&name(i));
+    }
+    let stats = names.to_stats();
+    assert_eq!((stats.names, stats.sampled, stats.sampled_duplicates), (1010, 1010, 10));
+    assert_eq!(stats.sample_rate, 1.0);
+    assert_eq!(stats.estimated_duplicates, 10);
+}
+
+#[test]
+fn test_doubled_input_is_estimated_from_a_bounded_sample() {
+    let mut names = DuplicateNames::with_max_sampled(1000);
+    // 同一批 read 拼接了两次：一半的名字是重复的
+    for _ in 0..2 {
+        for i in 0..100_000 {
+            names.add(&name(i));
+        }
+    }
+    let stats = names.to_stats();
+    assert_eq!(stats.names, 200_000);
+    assert!(stats.sample_rate < 0.02 && stats.sampled <= 2000, "{:?}", stats);
+    // 同名的 read 同时被抽中，抽样中恰好一半是重复
+    assert_eq!(stats.fraction, 0.5);
+    assert_eq!(stats.estimated_duplicates, 100_000);
+}
+
+#[test]
+fn test_merge_matches_single_tracker() {
+    let (mut a, mut b, mut all) = (DuplicateNames::with_max_sampled(500), DuplicateNames::with_max_sampled(500),
+                                   DuplicateNames::with_max_sampled(500));
+    for i in 0..20_000 {
+        // 每 10 个名字中有一个在另一个线程中重复出现
+        let target = if i % 2 == 0 { &mut a } else { &mut b };
+        target.add(&name(i));
+        all.add(&name(i));
+        if i % 10 == 0 {
+            b.add(&name(i));
+            all.add(&name(i));
+        }
+    }
+    a.merge(&b);
+    assert_eq!(a.to_stats(), all.to_stats());
+    assert!((all.to_stats().fraction - 1.0 / 11.0).abs() < 0.03, "{:?}", all.to_stats());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use anyhow::Context;
+use common::{path_str, run};
+use scatac_barcode_splitter::failure::{Classify, Failure};
+use std::fs;
+use std::io;
+
+#[test]
+fn test_failure_of_error_chain() {
+    let err = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+        .context("Failed to open x.fastq")
+        .classify(Failure::InputUnreadable)
+        .context("Failed to read lane 1")
+        .unwrap_err();
+    assert_eq!(Failure::of(&err), Some(Failure::InputUnreadable));
+    // 标注不改变错误的显示
+    assert_eq!(format!("{:#}", err), "Failed to read lane 1: Failed to open x.fastq: entity not found");
+
+    let malformed = anyhow::Error::new(io::Error::new(io::ErrorKind::InvalidData, "truncated")).context("record 2");
+    assert_eq!(Failure::exit_code_of(&malformed), Failure::MalformedInput.exit_code());
+    assert_eq!(Failure::exit_code_of(&anyhow::anyhow!("other")), Failure::OTHER_EXIT_CODE);
+}
+
+#[test]
+fn test_exit_code_per_failure_class() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 4, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let split = |r1: &str, r2: &str, extra: &[&str]| {
+        let args = [&["split", "-1", r1, "-2", r2, "-o", path_str(&prefix), "--progress", "none"][..], extra].concat();
+        run(&args).status.code()
+    };
+
+    assert_eq!(split(path_str(&r1), path_str(&r2), &["--no-such-option"]), Some(2));
+    assert_eq!(run(&["split", "-1", path_str(&r1), "-o", path_str(&prefix)]).status.code(), Some(2));
+    let missing = dir.path().join("missing_R2.fastq");
+    assert_eq!(split(path_str(&r1), path_str(&missing), &[]), Some(3));
+
+    // 第 3 条 R2 缺少 '+' 行
+    let text = fs::read_to_string(&r2).unwrap();
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.remove(10);
+    let malformed = dir.path().join("malformed_R2.fastq");
+    fs::write(&malformed, lines.join("\n") + "\n").unwrap();
+    assert_eq!(split(path_str(&r1), path_str(&malformed), &[]), Some(4));
+
+    // R2 少一条：--strict 下按不成对退出
+    let short = dir.path().join("short_R2.fastq");
+    fs::write(&short, text.lines().take(12).collect::<Vec<_>>().join("\n") + "\n").unwrap();
+    assert_eq!(split(path_str(&r1), path_str(&short), &["--strict"]), Some(5));
+    assert_eq!(split(path_str(&r1), path_str(&short), &[]), Some(0));
+}
@@ -0,0 +1,75 @@
+use fastq::OwnedRecord;
+use scatac_barcode_splitter::fastq_io::{empty_record, read_fastq_record, write_fastq_record};
+use scatac_barcode_splitter::FastqWriter;
+
+fn written(record: &OwnedRecord) -> String {
+    let mut out = Vec::new();
+    write_fastq_record(&mut out, record).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_plus_line_round_trip() {
+    let input = "@read1 comment\nACGT\n+read1 lane=3\nIIII\n@read2\nGG\n+\nFF\n";
+    let mut reader = input.as_bytes();
+    let mut record = empty_record();
+    let mut output = String::new();
+    while read_fastq_record(&mut reader, &mut record).unwrap() {
+        output.push_str(&written(&record));
+    }
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_bare_plus_without_sep() {
+    let record = OwnedRecord { head: b"read1".to_vec(), seq: b"ACGT".to_vec(), sep: None, qual: b"IIII".to_vec() };
+    assert_eq!(written(&record), "@read1\nACGT\n+\nIIII\n");
+    let record = OwnedRecord { sep: Some(b"meta".to_vec()), ..record };
+    assert_eq!(written(&record), "@read1\nACGT\n+meta\nIIII\n");
+}
+
+#[test]
+fn test_fastq_writer_matches_write_fastq_record() {
+    let records = [
+        OwnedRecord { head: b"read1".to_vec(), seq: b"ACGT".to_vec(), sep: Some(b"read1".to_vec()), qual: b"IIII".to_vec() },
+        OwnedRecord { head: b"read2".to_vec(), seq: b"A".repeat(300), sep: None, qual: b"F".repeat(300) },
+    ];
+    let expected: String = records.iter().map(written).collect();
+    // 缓冲区比记录小时照常扩容
+    let mut writer = FastqWriter::with_buffer_capacity(Vec::new(), 16);
+    writer.write_batch(&records).unwrap();
+    for record in &records {
+        writer.write_record(record).unwrap();
+    }
+    writer.flush().unwrap();
+    assert_eq!(String::from_utf8(writer.into_inner().unwrap()).unwrap(), expected.repeat(2));
+}
+
+/// 写入成功、flush 失败的输出
+#[derive(Debug)]
+struct FailingFlush(Vec<u8>);
+
+impl std::io::Write for FailingFlush {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Err(std::io::Error::other("flush failed"))
+    }
+}
+
+#[test]
+fn test_fastq_writer_buffers_and_reports_flush_error() {
+    let record = OwnedRecord { head: b"read1".to_vec(), seq: b"ACGT".to_vec(), sep: None, qual: b"IIII".to_vec() };
+    let mut writer = FastqWriter::new(FailingFlush(Vec::new()));
+    writer.write_record(&record).unwrap();
+    // 记录先留在 BufWriter 中，取回时写出并报告 flush 的错误
+    assert!(writer.get_ref().0.is_empty());
+    assert_eq!(writer.into_inner().unwrap_err().to_string(), "flush failed");
+
+    // BufWriter 容量为 0 时直接写入已有缓冲的输出
+    let mut writer = FastqWriter::with_capacities(FailingFlush(Vec::new()), 0, 16);
+    writer.write_record(&record).unwrap();
+    assert_eq!(writer.get_ref().0, written(&record).as_bytes());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{path_str, run};
+use std::fs;
+use std::process::{Command, Output};
+
+fn generate(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gen_test_data"))
+        .args(args)
+        .output()
+        .expect("failed to run gen_test_data")
+}
+
+#[test]
+fn test_same_seed_gives_same_reads() {
+    let first = generate(&["-n", "20", "--seed", "7"]);
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    let text = String::from_utf8(first.stdout.clone()).unwrap();
+    // R1 与 R2 交错输出
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 20 * 2 * 4);
+    assert!(lines[0].ends_with("/1") && lines[4].ends_with("/2"));
+    assert_eq!((lines[1].len(), lines[5].len()), (50, 166));
+
+    assert_eq!(generate(&["-n", "20", "--seed", "7"]).stdout, first.stdout);
+    assert_ne!(generate(&["-n", "20", "--seed", "8"]).stdout, first.stdout);
+}
+
+#[test]
+fn test_generated_reads_split_against_their_whitelist() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2, whitelist) = (dir.path().join("R1.fastq.gz"), dir.path().join("R2.fastq.gz"), dir.path().join("wl.txt"));
+    let out = generate(&["-n", "500", "--barcodes", "10", "--barcode-error-rate", "0.01",
+                         "--write-barcodes", path_str(&whitelist), "-1", path_str(&r1), "-2", path_str(&r2)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(fs::read_to_string(&whitelist).unwrap().lines().count(), 10);
+
+    let (prefix, stats_path) = (dir.path().join("out"), dir.path().join("stats.json"));
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--whitelist", path_str(&whitelist), "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stats: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!(stats["pairs_read"], 500);
+    // 1% 的替换率下绝大多数条形码都能精确命中或纠正
+    assert!(stats["pairs_written"].as_u64().unwrap() >= 475, "{}", stats);
+    assert!(stats["barcode_correction"]["corrected"].as_u64().unwrap() > 0, "{}", stats);
+}
@@ -0,0 +1,33 @@
+use scatac_barcode_splitter::header::parse_illumina_header;
+
+#[test]
+fn test_parse_illumina_header() {
+    let h = parse_illumina_header(b"A00123:45:HXXXXDSXY:2:1101:10000:1000 1:N:0:ACGTACGT").unwrap();
+    assert_eq!(h.instrument, b"A00123");
+    assert_eq!(h.run_number, b"45");
+    assert_eq!(h.flowcell, b"HXXXXDSXY");
+    assert_eq!((h.lane, h.tile, h.x, h.y), (2, 1101, 10000, 1000));
+}
+
+#[test]
+fn test_parse_illumina_header_with_read_suffix() {
+    let h = parse_illumina_header(b"A00123:45:HXXXXDSXY:1:2203:5:7/2").unwrap();
+    assert_eq!((h.lane, h.tile, h.x, h.y), (1, 2203, 5, 7));
+}
+
+#[test]
+fn test_non_illumina_headers() {
+    assert!(parse_illumina_header(b"SRR1234567.1 1 length=150").is_none());
+    assert!(parse_illumina_header(b"V350012345L1C001R00100000001/1").is_none());
+    assert!(parse_illumina_header(b"A00123:45:HXXXXDSXY:x:1101:1:1").is_none());
+}
+
+#[test]
+fn test_same_run() {
+    let a = parse_illumina_header(b"A00123:45:HAAAA:1:1101:1:1").unwrap();
+    let b = parse_illumina_header(b"A00123:45:HAAAA:2:1102:9:9").unwrap();
+    let c = parse_illumina_header(b"A00123:46:HBBBB:1:1101:1:1").unwrap();
+    assert!(a.same_run(&b));
+    assert!(!a.same_run(&c));
+    assert_eq!(c.run_id(), "A00123:46:HBBBB");
+}
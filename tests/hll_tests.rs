@@ -0,0 +1,32 @@
+use scatac_barcode_splitter::hll::HyperLogLog;
+
+fn barcode(i: u64) -> Vec<u8> {
+    (0..16).map(|j| b"ACGT"[((i >> (2 * j)) & 3) as usize]).collect()
+}
+
+#[test]
+fn test_estimate_within_expected_error() {
+    let mut hll = HyperLogLog::new();
+    for i in 0..200_000 {
+        // 每个条形码出现两次，重复不影响估计
+        hll.add(&barcode(i));
+        hll.add(&barcode(i));
+    }
+    let estimate = hll.estimate() as f64;
+    assert!((estimate / 200_000.0 - 1.0).abs() < 4.0 * HyperLogLog::RELATIVE_ERROR, "{}", estimate);
+}
+
+#[test]
+fn test_small_counts_and_merge() {
+    let mut a = HyperLogLog::new();
+    let mut b = HyperLogLog::new();
+    let mut all = HyperLogLog::new();
+    for i in 0..30 {
+        if i % 2 == 0 { a.add(&barcode(i)) } else { b.add(&barcode(i)) }
+        all.add(&barcode(i));
+    }
+    assert_eq!(HyperLogLog::new().estimate(), 0);
+    assert_eq!(all.estimate(), 30);
+    a.merge(&b);
+    assert_eq!(a.estimate(), all.estimate());
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{path_str, run};
+use scatac_barcode_splitter::anchor::Anchor;
+use scatac_barcode_splitter::inspect::{describe_read, Layout, Segments};
+use scatac_barcode_splitter::whitelist::Whitelist;
+use scatac_barcode_splitter::{reverse_complement, BarcodeWindow};
+use std::io::Cursor;
+
+fn fixed_layout(r3_range: std::ops::Range<usize>) -> Layout {
+    Layout { window: BarcodeWindow::default(), r3_range, anchor: None }
+}
+
+#[test]
+fn test_fixed_layout_annotation() {
+    let segments = fixed_layout(10..140).segments(&[b'A'; 166]).unwrap();
+    assert_eq!(segments, Segments { genomic: 10..140, spacer: 140..150, barcode: 150..166 });
+    let annotation = segments.annotation(166);
+    assert_eq!(annotation, format!("{}{}{}{}", ".".repeat(10), "G".repeat(130), "S".repeat(10), "B".repeat(16)));
+
+    // read 过短时截到末端
+    let segments = fixed_layout(0..150).segments(&[b'A'; 155]).unwrap();
+    assert_eq!(segments.barcode, 150..155);
+}
+
+#[test]
+fn test_anchor_layout() {
+    let layout = Layout { anchor: Some(Anchor::new("CTGTCTCTTATA", 0).unwrap()), ..fixed_layout(0..0) };
+    let seq = [&b"G".repeat(40)[..], b"CTGTCTCTTATA", &b"T".repeat(20)].concat();
+    let segments = layout.segments(&seq).unwrap();
+    assert_eq!(segments, Segments { genomic: 0..40, spacer: 40..52, barcode: 52..68 });
+    assert_eq!(&segments.annotation(seq.len())[68..], "....");
+    assert!(layout.segments(&[b'G'; 80]).is_none());
+    assert!(describe_read(b"r/2", &[b'G'; 80], &layout, None).contains("anchor not found"));
+}
+
+#[test]
+fn test_describe_read_whitelist() {
+    let bc = b"AAAACCCCGGGGTTTT";
+    let whitelist = Whitelist::from_reader(Cursor::new(&bc[..])).unwrap();
+    let mut seq = [&b"G".repeat(150)[..], &reverse_complement(bc)].concat();
+    let text = describe_read(b"read1/2", &seq, &fixed_layout(0..150), Some(&whitelist));
+    assert!(text.starts_with("read1/2 (166 bp)\n"), "{}", text);
+    assert!(text.contains("AAAACCCCGGGGTTTT after reverse complement"), "{}", text);
+    assert!(text.contains("whitelist exact match"), "{}", text);
+
+    seq[150] = b'G';
+    let text = describe_read(b"read1/2", &seq, &fixed_layout(0..150), Some(&whitelist));
+    assert!(text.contains("corrected to AAAACCCCGGGGTTTT (1 mismatch)"), "{}", text);
+
+    let text = describe_read(b"read1/2", &seq[..158], &fixed_layout(0..150), Some(&whitelist));
+    assert!(text.contains("read ends 8 bases into the 16 bp barcode") && !text.contains("whitelist"), "{}", text);
+}
+
+#[test]
+fn test_inspect_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    let (_, r2) = common::write_fixture(dir.path(), 5, &["AAAACCCCGGGGTTTT"]);
+    let out = run(&["inspect", "-2", path_str(&r2), "-n", "3"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("Layout: R3 = R2[0..150], barcode = R2[150..166] (reverse complement)"), "{}", stdout);
+    assert_eq!(stdout.matches("after reverse complement").count(), 3);
+    // 不写出任何文件
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    let out = run(&["inspect", "-2", path_str(&r2), "--bc-start", "140", "--r3-end", "145"]);
+    assert!(!out.status.success());
+}
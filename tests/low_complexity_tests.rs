@@ -0,0 +1,33 @@
+use scatac_barcode_splitter::low_complexity::{trinucleotide_entropy, SUGGESTED_MIN_COMPLEXITY};
+
+/// 固定种子的伪随机序列
+fn random_seq(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len).map(|_| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        b"ACGT"[(seed >> 62) as usize]
+    }).collect()
+}
+
+#[test]
+fn test_entropy_of_repeats_and_random_sequence() {
+    assert_eq!(trinucleotide_entropy(&[b'A'; 150]), 0.0);
+    let at = trinucleotide_entropy(&b"AT".repeat(75));
+    assert!((at - 1.0 / 6.0).abs() < 1e-9, "{}", at);
+    let acg = trinucleotide_entropy(&b"ACG".repeat(50));
+    assert!(acg < SUGGESTED_MIN_COMPLEXITY, "{}", acg);
+    for seed in 0..20 {
+        let score = trinucleotide_entropy(&random_seq(150, seed));
+        assert!(score > 0.85, "seed {}: {}", seed, score);
+    }
+}
+
+#[test]
+fn test_entropy_skips_n_and_short_sequences() {
+    assert_eq!(trinucleotide_entropy(b"ACG"), 0.0);
+    assert_eq!(trinucleotide_entropy(b""), 0.0);
+    // 两个不同的三核苷酸：归一化后为 1
+    assert_eq!(trinucleotide_entropy(b"ACGT"), 1.0);
+    // 跨过 N 的三核苷酸不计：ACG、CGT 各 2 次，熵 1 bit，按 log2(4) 归一化
+    assert_eq!(trinucleotide_entropy(b"ACGTNNNACGT"), 0.5);
+    assert_eq!(trinucleotide_entropy(b"acgt"), 1.0);
+}
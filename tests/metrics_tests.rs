@@ -0,0 +1,48 @@
+mod common;
+
+use common::{path_str, run};
+use scatac_barcode_splitter::metrics::{escape_label_value, render_prometheus};
+use scatac_barcode_splitter::progress::CounterSnapshot;
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_escape_label_value() {
+    assert_eq!(escape_label_value("R1"), "R1");
+    assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+}
+
+#[test]
+fn test_render_prometheus() {
+    let snapshot = CounterSnapshot {
+        pairs_read: 10,
+        pairs_written: 7,
+        pairs_filtered: 3,
+        filtered_by_reason: [1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0],
+        unpaired: 0,
+        bytes_written: [100, 200, 300, 0],
+        input_bytes_read: 0,
+    };
+    let text = render_prometheus(&snapshot, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    assert!(text.contains("scatac_pairs_read_total 10\n"));
+    assert!(text.contains("scatac_pairs_written_total 7\n"));
+    assert!(text.contains("scatac_pairs_filtered_total{reason=\"length\"} 1\n"));
+    assert!(text.contains("scatac_pairs_filtered_total{reason=\"barcode_no_match\"} 2\n"));
+    assert!(text.contains("scatac_bytes_written_total{output=\"R2\"} 200\n"));
+    assert!(text.contains("scatac_heartbeat_timestamp_seconds 1700000000.000\n"));
+}
+
+#[test]
+fn test_metrics_file_matches_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 12, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let metrics = dir.path().join("scatac.prom");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--metrics-file", path_str(&metrics)]);
+    assert!(out.status.success());
+    let text = fs::read_to_string(&metrics).unwrap();
+    assert!(text.contains("scatac_pairs_written_total 12\n"), "{}", text);
+    let r1_bytes = fs::metadata(dir.path().join("out_S1_L001_R1_001.fastq")).unwrap().len();
+    assert!(text.contains(&format!("scatac_bytes_written_total{{output=\"R1\"}} {}\n", r1_bytes)), "{}", text);
+}
@@ -0,0 +1,20 @@
+use scatac_barcode_splitter::offset_scan::{best_window, scan_barcode_offsets};
+use scatac_barcode_splitter::whitelist::Whitelist;
+use scatac_barcode_splitter::{reverse_complement, BarcodeWindow};
+
+#[test]
+fn test_scan_finds_shifted_window() {
+    let wl = Whitelist::from_reader("AAAACCCCGGGGTTTT\nACGTACGTACGTACGA\n".as_bytes()).unwrap();
+    // 条形码（反向互补）从 148 开始，最后两个碱基不属于条形码
+    let r2: Vec<Vec<u8>> = ["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"].iter()
+        .map(|bc| [vec![b'G'; 148], reverse_complement(bc.as_bytes()), b"TT".to_vec()].concat())
+        .collect();
+    let rows = scan_barcode_offsets(&r2, &wl, 146..=150);
+    assert_eq!(rows.len(), 10);
+    let best = best_window(&rows).unwrap();
+    assert_eq!(best.window, BarcodeWindow { offset: 148, reverse_complement: true });
+    assert_eq!((best.reads, best.hits), (2, 2));
+    // 150 之后放不下 16 bp 的窗口
+    assert_eq!(rows.iter().filter(|r| r.window.offset == 150).map(|r| r.reads).sum::<u64>(), 4);
+    assert!(best_window(&rows[..2]).is_none());
+}
@@ -0,0 +1,26 @@
+use scatac_barcode_splitter::optical::{OpticalDupDetector, MAX_OPEN_TILES};
+
+#[test]
+fn test_optical_duplicates_need_same_tile_key_and_nearby_coordinates() {
+    let mut detector = OpticalDupDetector::new(100);
+    assert!(!detector.check(1, 1101, 1000, 1000, b"A|x"));
+    // 距离 100 以内
+    assert!(detector.check(1, 1101, 1060, 1080, b"A|x"));
+    // 太远、片段不同、tile 或 lane 不同
+    assert!(!detector.check(1, 1101, 1000, 1101, b"A|x"));
+    assert!(!detector.check(1, 1101, 1000, 1000, b"A|y"));
+    assert!(!detector.check(1, 1102, 1000, 1000, b"A|x"));
+    assert!(!detector.check(2, 1101, 1000, 1000, b"A|x"));
+    assert_eq!((detector.examined(), detector.duplicates()), (6, 1));
+}
+
+#[test]
+fn test_old_tiles_are_evicted() {
+    let mut detector = OpticalDupDetector::new(10);
+    for tile in 0..=MAX_OPEN_TILES as u32 {
+        detector.check(1, tile, 0, 0, b"A");
+    }
+    // 最近的 tile 仍在，最早的 tile 已被淘汰
+    assert!(detector.check(1, MAX_OPEN_TILES as u32, 0, 0, b"A"));
+    assert!(!detector.check(1, 0, 0, 0, b"A"));
+}
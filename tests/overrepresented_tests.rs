@@ -0,0 +1,52 @@
+use scatac_barcode_splitter::overrepresented::{OverrepresentedConfig, OverrepresentedCounter, PREFIX_LEN};
+
+/// 固定种子的伪随机序列
+fn random_seq(len: usize, mut seed: u64) -> Vec<u8> {
+    (0..len).map(|_| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        b"ACGT"[(seed >> 62) as usize]
+    }).collect()
+}
+
+#[test]
+fn test_reports_sequences_above_fraction_of_sample() {
+    let config = OverrepresentedConfig { every: 2, min_fraction: 0.1 };
+    let mut counter = OverrepresentedCounter::new(config);
+    let dimer = b"CTGTCTCTTATACACATCT".repeat(8);
+    for i in 0..1000u64 {
+        // 每 4 条中有 1 条是接头二聚体；只在第 50 bp 之后不同的 read 按同一序列计数
+        if i % 4 == 1 {
+            let mut seq = dimer.clone();
+            seq[PREFIX_LEN + (i as usize % 10)] = b'N';
+            counter.add(&seq);
+        } else {
+            counter.add(&random_seq(150, i));
+        }
+    }
+    let stats = counter.to_stats();
+    assert_eq!((stats.sample_every, stats.sampled, stats.evicted), (2, 500, 0));
+    assert_eq!(stats.sequences.len(), 1);
+    let top = &stats.sequences[0];
+    assert_eq!(top.sequence.as_bytes(), &dimer[..PREFIX_LEN]);
+    assert_eq!(top.count, 250);
+    assert_eq!(top.fraction, 0.5);
+}
+
+#[test]
+fn test_tracked_sequences_stay_bounded() {
+    let config = OverrepresentedConfig { every: 1, min_fraction: 0.05 };
+    let mut counter = OverrepresentedCounter::with_max_tracked(config, 100);
+    let hot = random_seq(60, 12345);
+    for i in 0..10_000u64 {
+        if i % 10 == 0 {
+            counter.add(&hot);
+        } else {
+            counter.add(&random_seq(60, i));
+        }
+    }
+    let stats = counter.to_stats();
+    assert!(stats.evicted > 8000, "{}", stats.evicted);
+    // 计数多的序列不会被淘汰
+    assert_eq!(stats.sequences.len(), 1);
+    assert_eq!(stats.sequences[0].count, 1000);
+}
@@ -0,0 +1,31 @@
+use flate2::read::MultiGzDecoder;
+use flate2::Compression;
+use scatac_barcode_splitter::parallel_gz::ParallelGzWriter;
+use std::io::{Read, Write};
+
+fn roundtrip(data: &[u8], threads: usize) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    {
+        let mut w = ParallelGzWriter::new(&mut compressed, threads, Compression::new(1));
+        // 以不规则的小块写入，跨越多个压缩块边界
+        for piece in data.chunks(7919) {
+            w.write_all(piece).unwrap();
+        }
+        w.flush().unwrap();
+    }
+    let mut out = Vec::new();
+    MultiGzDecoder::new(&compressed[..]).read_to_end(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn test_parallel_gz_roundtrip() {
+    let data: Vec<u8> = (0..5_000_000u32).map(|i| b"ACGT"[(i * 7 % 13 % 4) as usize]).collect();
+    assert_eq!(roundtrip(&data, 4), data);
+    assert_eq!(roundtrip(&data, 1), data);
+}
+
+#[test]
+fn test_parallel_gz_empty_is_valid_gzip() {
+    assert_eq!(roundtrip(b"", 2), b"");
+}
@@ -0,0 +1,104 @@
+// 任意输入的 read 对都不能让处理线程 panic（越界切片等），只能按某个原因过滤
+use fastq::OwnedRecord;
+use proptest::prelude::*;
+use scatac_barcode_splitter::anchor::Anchor;
+use scatac_barcode_splitter::optical::OpticalDupPolicy;
+use scatac_barcode_splitter::pipeline::RecordBatch;
+use scatac_barcode_splitter::readthrough::ReadthroughPolicy;
+use scatac_barcode_splitter::whitelist::Whitelist;
+use scatac_barcode_splitter::{run_pipeline_from_channel, PipelineConfig, PipelineWriters};
+
+/// 多数是碱基（条形码才有机会命中白名单），也有任意字节
+fn sequence(len: impl Strategy<Value = usize>) -> impl Strategy<Value = (Vec<u8>, Vec<u8>)> {
+    let base = prop_oneof![4 => prop::sample::select(b"ACGTN".to_vec()), 1 => any::<u8>()];
+    len.prop_flat_map(move |len| {
+        (prop::collection::vec(base.clone(), len), prop::collection::vec(any::<u8>(), len))
+    })
+}
+
+/// 一对 read；名字多数相同（带 /1、/2），有时是任意字节
+fn read_pair() -> impl Strategy<Value = (OwnedRecord, OwnedRecord)> {
+    // R2 长度集中在 166 附近
+    let r2_len = prop_oneof![0..200usize, 140..172usize];
+    (prop::collection::vec(any::<u8>(), 0..40), any::<bool>(), sequence(0..80usize), sequence(r2_len))
+        .prop_map(|(name, same_name, (r1_seq, r1_qual), (r2_seq, r2_qual))| {
+            let head = |mate: &[u8]| if same_name { [name.as_slice(), mate].concat() } else { name.clone() };
+            (OwnedRecord { head: head(b"/1"), seq: r1_seq, qual: r1_qual, sep: None },
+             OwnedRecord { head: head(b"/2"), seq: r2_seq, qual: r2_qual, sep: None })
+        })
+}
+
+fn run(pairs: Vec<(OwnedRecord, OwnedRecord)>, config: PipelineConfig) -> u64 {
+    let n = pairs.len() as u64;
+    let soft_filter = config.soft_filter;
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let batch: RecordBatch = pairs.into_iter().unzip();
+    tx.send(batch).unwrap();
+    drop(tx);
+    let writers = PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink());
+    let stats = run_pipeline_from_channel(rx, config, writers).unwrap();
+    assert_eq!(stats.pairs_read, n);
+    if soft_filter {
+        // 被过滤的 pair 加上标记照样写出
+        assert_eq!(stats.pairs_written, n);
+    } else {
+        assert_eq!(stats.pairs_written + stats.pairs_filtered, n);
+    }
+    stats.pairs_written
+}
+
+fn whitelist() -> Whitelist {
+    Whitelist::from_reader(&b"AAAACCCCGGGGTTTT\nACGTACGTACGTACGT\nTTTTGGGGCCCCAAAA\n"[..]).unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn default_config_never_panics(pairs in prop::collection::vec(read_pair(), 1..16)) {
+        run(pairs, PipelineConfig { threads: 1, ..PipelineConfig::default() });
+    }
+
+    #[test]
+    fn correcting_config_never_panics(pairs in prop::collection::vec(read_pair(), 1..16)) {
+        let config = PipelineConfig {
+            whitelist: Some(whitelist()),
+            bc_mask_qual: Some(20),
+            allow_truncated_bc: 2,
+            correct_indels: true,
+            readthrough: ReadthroughPolicy::Drop,
+            min_complexity: Some(0.5),
+            mark_optical_dups: Some(100),
+            optical_dup_policy: OpticalDupPolicy::Drop,
+            reverse_r3: true,
+            swap_r2_r3: true,
+            threads: 1,
+            ..PipelineConfig::default()
+        };
+        run(pairs, config);
+    }
+
+    #[test]
+    fn anchored_config_never_panics(pairs in prop::collection::vec(read_pair(), 1..16)) {
+        let config = PipelineConfig {
+            anchor: Some(Anchor::new("AGATGTGTATAAGAGACAG", 2).unwrap()),
+            whitelist: Some(whitelist()),
+            threads: 1,
+            ..PipelineConfig::default()
+        };
+        run(pairs, config);
+    }
+
+    #[test]
+    fn soft_filter_never_panics(pairs in prop::collection::vec(read_pair(), 1..16)) {
+        let config = PipelineConfig {
+            whitelist: Some(whitelist()),
+            min_complexity: Some(0.5),
+            reverse_r3: true,
+            soft_filter: true,
+            threads: 1,
+            ..PipelineConfig::default()
+        };
+        run(pairs, config);
+    }
+}
@@ -0,0 +1,411 @@
+mod common;
+
+use common::{fastq_text, record, run_in_memory, split_in_memory, Fixture, BARCODE};
+use scatac_barcode_splitter::adaptive_batch::BatchTarget;
+use scatac_barcode_splitter::whitelist::Whitelist;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use scatac_barcode_splitter::failure::Failure;
+use scatac_barcode_splitter::{
+    reverse_complement, run_pipeline, run_pipeline_from_channel, FinishWrite, PipelineConfig, PipelineWriters,
+    SharedBuffer,
+};
+use std::io::{Cursor, Read};
+use std::time::Duration;
+
+#[test]
+fn test_run_pipeline_into_memory() {
+    let barcodes = ["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA", "TTTTGGGGCCCCAAAA"];
+    let mut pairs = Fixture::new(1000, &barcodes).records();
+    let (mut r1_expected, mut r2_expected, mut r3_expected) = (String::new(), String::new(), String::new());
+    for (i, (r1, r2)) in pairs.iter_mut().enumerate() {
+        r2.qual[150..].copy_from_slice(b"ABCDEFGHIJKLMNOP");
+        let text = |seq: &[u8]| String::from_utf8(seq.to_vec()).unwrap();
+        if i % 100 == 99 {
+            // 每 100 对中有一对 R2 短了 1bp，应被过滤
+            r2.seq.remove(150);
+            r2.qual.pop();
+        } else {
+            r1_expected.push_str(&format!("@read{}\n{}\n+\n{}\n", i, text(&r1.seq), text(&r1.qual)));
+            r2_expected.push_str(&format!("@read{}\n{}\n+\nPONMLKJIHGFEDCBA\n", i, barcodes[i % barcodes.len()]));
+            r3_expected.push_str(&format!("@read{}\n{}\n+\n{}\n", i, text(&r2.seq[..150]), "I".repeat(150)));
+        }
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+
+    // 记录的字节数：header（不含 '@'）、序列、质量值
+    let record_bytes = |text: &str| -> usize {
+        text.lines().enumerate().filter(|(i, _)| i % 4 != 2).map(|(i, l)| if i % 4 == 0 { l.len() - 1 } else { l.len() }).sum()
+    };
+    let (r1_bytes, r2_bytes) = (record_bytes(&r1_in), record_bytes(&r2_in));
+
+    // 单个处理线程保证输出顺序与输入一致；batch 较小以覆盖多个 batch
+    let config = PipelineConfig { threads: 1, batch_size: 64, ..PipelineConfig::default() };
+    let (stats, [r1, r2, r3]) = split_in_memory(config, &r1_in, &r2_in);
+
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.pairs_filtered), (1000, 990, 10));
+    assert_eq!(stats.filtered_by_reason["length"], 10);
+    assert_eq!(stats.lane_pairs, vec![1000]);
+    assert_eq!((stats.r1_read_mb, stats.r2_read_mb), (r1_bytes as f64 / 1e6, r2_bytes as f64 / 1e6));
+    assert!(stats.total_throughput_mb_s > 0.0);
+    assert_eq!(String::from_utf8(r1).unwrap(), r1_expected);
+    assert_eq!(String::from_utf8(r2).unwrap(), r2_expected);
+    assert_eq!(String::from_utf8(r3).unwrap(), r3_expected);
+}
+
+#[test]
+fn test_reverse_r3_twice_restores_r3() {
+    let mut pairs = Fixture::new(50, &[BARCODE]).records();
+    for (i, (_, r2)) in pairs.iter_mut().enumerate() {
+        r2.seq[i * 3 % 150] = b'N';
+        r2.qual[..150].iter_mut().enumerate().for_each(|(j, q)| *q = b'!' + (j % 40) as u8);
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let run_r3 = |reverse_r3: bool| -> Vec<String> {
+        let config = PipelineConfig { threads: 1, reverse_r3, ..PipelineConfig::default() };
+        let [_, _, r3] = run_in_memory(config, &r1_in, &r2_in);
+        String::from_utf8(r3).unwrap().lines().map(String::from).collect()
+    };
+
+    let forward = run_r3(false);
+    let reversed = run_r3(true);
+    assert_ne!(forward, reversed);
+    // 对 --reverse-r3 的输出再做一次反向互补，应得到原始的 R3
+    for (i, line) in reversed.iter().enumerate() {
+        let restored = match i % 4 {
+            1 => String::from_utf8(reverse_complement(line.as_bytes())).unwrap(),
+            3 => line.chars().rev().collect(),
+            _ => line.clone(),
+        };
+        assert_eq!(restored, forward[i]);
+    }
+}
+
+#[test]
+fn test_preallocated_writer_buffer_same_output() {
+    let (r1_in, r2_in) = Fixture::new(300, &[BARCODE]).r1_len(|i| 50 + i % 7).text();
+    let run = |preallocate_writer_buffer: bool, swap_r2_r3: bool| {
+        let config = PipelineConfig { threads: 1, batch_size: 64, preallocate_writer_buffer, swap_r2_r3,
+                                      ..PipelineConfig::default() };
+        run_in_memory(config, &r1_in, &r2_in)
+    };
+    for swap in [false, true] {
+        let expected = run(false, swap);
+        assert!(expected.iter().all(|out| !out.is_empty()));
+        assert_eq!(run(true, swap), expected);
+    }
+}
+
+#[test]
+fn test_lane_stats_from_headers() {
+    // lane 1、lane 2 与非 Illumina header 各 10 对；lane 2 的条形码不在白名单中
+    let mut pairs = Fixture::new(30, &[BARCODE, "GTGTGTGTGTGTGTGT", BARCODE]).records();
+    for (i, (r1, r2)) in pairs.iter_mut().enumerate() {
+        let name = match i % 3 {
+            0 => format!("M0:7:FC:1:1101:{}:1", i),
+            1 => format!("M0:7:FC:2:1101:{}:1", i),
+            _ => continue,
+        };
+        (r1.head, r2.head) = (format!("{}/1", name).into_bytes(), format!("{}/2", name).into_bytes());
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let whitelist = Whitelist::from_reader(Cursor::new("AAAACCCCGGGGTTTT\n")).unwrap();
+    let config = PipelineConfig { threads: 2, batch_size: 4, whitelist: Some(whitelist), ..PipelineConfig::default() };
+    let (stats, _) = split_in_memory(config, &r1_in, &r2_in);
+
+    let lanes: Vec<_> = stats.lanes.iter().map(|l| (l.lane.as_str(), l.pairs_read, l.pairs_filtered)).collect();
+    assert_eq!(lanes, [("1", 10, 0), ("2", 10, 10), ("unknown", 10, 0)]);
+    assert_eq!(stats.lanes[1].filtered_by_reason["barcode_no_match"], 10);
+    assert_eq!(stats.lanes[0].whitelist_match_rate, Some(1.0));
+    assert_eq!(stats.lanes[1].whitelist_match_rate, Some(0.0));
+}
+
+#[test]
+fn test_run_pipeline_from_channel() {
+    let mut pairs = Fixture::new(30, &[BARCODE]).records().into_iter();
+    let (tx, rx) = crossbeam_channel::bounded(2);
+    let sender = std::thread::spawn(move || {
+        for _ in 0..3 {
+            let (r1, mut r2): (Vec<_>, Vec<_>) = pairs.by_ref().take(10).unzip();
+            // 每个 batch 的最后一对 R2 过短
+            r2[9].seq.remove(0);
+            r2[9].qual.pop();
+            tx.send((r1, r2)).unwrap();
+        }
+    });
+
+    let r2 = SharedBuffer::new();
+    let config = PipelineConfig { threads: 2, ..PipelineConfig::default() };
+    let stats = run_pipeline_from_channel(rx, config, PipelineWriters::new(std::io::sink(), r2.clone(), std::io::sink()))
+        .unwrap();
+    sender.join().unwrap();
+
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.pairs_filtered), (30, 27, 3));
+    assert_eq!(stats.lane_pairs, vec![30]);
+    assert!(stats.r1_read_mb > 0.0 && stats.r2_read_mb > stats.r1_read_mb);
+    assert_eq!(String::from_utf8(r2.contents()).unwrap().matches(BARCODE).count(), 27);
+}
+
+#[test]
+fn test_batch_timeout() {
+    let batch: (Vec<_>, Vec<_>) = Fixture::new(100_000, &[BARCODE]).records().into_iter().unzip();
+    let run_with_timeout = |ms: u64| {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        tx.send(batch.clone()).unwrap();
+        drop(tx);
+        let config = PipelineConfig {
+            threads: 1,
+            batch_timeout: Some(std::time::Duration::from_millis(ms)),
+            ..PipelineConfig::default()
+        };
+        run_pipeline_from_channel(rx, config, PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink()))
+    };
+
+    assert_eq!(run_with_timeout(60_000).unwrap().pairs_written, 100_000);
+    // 10 万对无法在 1 ms 内处理完
+    let err = run_with_timeout(1).unwrap_err();
+    assert!(err.to_string().contains("took longer than 1 ms"), "{}", err);
+}
+
+#[test]
+fn test_empty_output_batches_counted() {
+    let mut pairs = Fixture::new(40, &[BARCODE]).records();
+    // 前 30 对（3 个 batch）R2 过短，全部被过滤
+    for (_, r2) in &mut pairs[..30] {
+        r2.seq.truncate(100);
+        r2.qual.truncate(100);
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let config = PipelineConfig { threads: 1, batch_size: 10, ..PipelineConfig::default() };
+    let (stats, _) = split_in_memory(config, &r1_in, &r2_in);
+    assert_eq!(stats.pairs_written, 10);
+    assert_eq!((stats.batches_processed, stats.empty_output_batches), (4, 3));
+}
+
+#[test]
+fn test_adaptive_batch_grows_fast_batches() {
+    let (r1_in, r2_in) = Fixture::new(2000, &[BARCODE]).text();
+    // 目标耗时远大于实际耗时，batch 只会变大，直到上限
+    let target = BatchTarget { min: Duration::from_secs(60), max: Duration::from_secs(120), max_batch_size: Some(40) };
+    let config = PipelineConfig { threads: 1, batch_size: 10, adaptive_batch: Some(target), ..PipelineConfig::default() };
+    let (stats, _) = split_in_memory(config, &r1_in, &r2_in);
+    assert_eq!(stats.pairs_written, 2000);
+    let adaptive = stats.adaptive_batch.unwrap();
+    assert_eq!((adaptive.initial, adaptive.largest), (10, 40));
+    assert!(adaptive.adjustments > 0);
+    // 批次变大后 batch 数少于固定大小时的 200 个
+    assert!(stats.batches_processed < 200);
+}
+
+#[test]
+fn test_r2_off_by_one_lengths_are_filtered() {
+    let (r1, r2) = Fixture::new(1, &[BARCODE]).records().remove(0);
+    for len in [150, 165, 166, 167] {
+        // 167 bp 时多出一个碱基；其余为截短，150 bp 时整个条形码窗口都缺失
+        let seq = if len > 166 { [r2.seq.clone(), b"A".to_vec()].concat() } else { r2.seq[..len].to_vec() };
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        tx.send((vec![r1.clone()], vec![record(r2.head.clone(), seq)])).unwrap();
+        drop(tx);
+        let config = PipelineConfig { threads: 1, ..PipelineConfig::default() };
+        let writers = PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink());
+        let stats = run_pipeline_from_channel(rx, config, writers).unwrap();
+        let expected = if len == 166 { (1, 0) } else { (0, 1) };
+        assert_eq!((stats.pairs_written, stats.filtered_by_reason["length"]), expected, "R2 of {} bp", len);
+    }
+}
+
+#[test]
+fn test_unequal_batches_count_unpaired() {
+    let mut pairs = Fixture::new(8, &[BARCODE]).records().into_iter();
+    let (tx, rx) = crossbeam_channel::bounded(2);
+    // 第一个 batch R1 多两条，第二个 batch R2 多一条
+    for (n1, n2) in [(5, 3), (2, 3)] {
+        let (mut r1, mut r2): (Vec<_>, Vec<_>) = pairs.by_ref().take(n1.max(n2)).unzip();
+        r1.truncate(n1);
+        r2.truncate(n2);
+        tx.send((r1, r2)).unwrap();
+    }
+    drop(tx);
+    let config = PipelineConfig { threads: 1, ..PipelineConfig::default() };
+    let writers = PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink());
+    let stats = run_pipeline_from_channel(rx, config, writers).unwrap();
+
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.unpaired), (8, 5, 3));
+    assert_eq!(stats.pairs_written + stats.pairs_filtered + stats.unpaired, stats.pairs_read);
+    assert_eq!(stats.first_unpaired.as_deref(), Some("read3"));
+}
+
+#[test]
+fn test_soft_filter_writes_flagged_pairs() {
+    let mut pairs = Fixture::new(4, &[BARCODE]).records();
+    let genomic = pairs[2].1.seq[..150].to_vec();
+    // 条形码不在白名单中
+    pairs[1].1.seq[150..].fill(b'C');
+    // R2 过短，取不到条形码
+    pairs[2].1.seq.truncate(140);
+    pairs[2].1.qual.truncate(140);
+    pairs[3].1.head = b"other3/2".to_vec();
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let whitelist = Whitelist::from_reader(&b"AAAACCCCGGGGTTTT\n"[..]).unwrap();
+    let config = PipelineConfig { whitelist: Some(whitelist), soft_filter: true, threads: 1, ..PipelineConfig::default() };
+    let (stats, outputs) = split_in_memory(config, &r1_in, &r2_in);
+
+    // 被过滤的 pair 照样写出，仍按原因计数
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.pairs_filtered), (4, 4, 3));
+    assert_eq!(stats.filtered_by_reason["barcode_no_match"], 1);
+    let [r1, r2, r3] = outputs.map(|out| String::from_utf8(out).unwrap().lines().map(String::from).collect::<Vec<_>>());
+    let heads: Vec<&str> = r1.iter().step_by(4).map(String::as_str).collect();
+    assert_eq!(heads, ["@read0", "@read1\tFT:Z:barcode_no_match", "@read2\tFT:Z:length",
+                       "@read3\tFT:Z:header_mismatch"]);
+    for out in [&r2, &r3] {
+        assert_eq!(out.iter().step_by(4).map(String::as_str).collect::<Vec<_>>(), heads);
+    }
+    assert_eq!(r2[5], "G".repeat(16));
+    assert_eq!((r2[9].as_str(), r2[11].as_str()), ("NNNNNNNNNNNNNNNN", "!!!!!!!!!!!!!!!!"));
+    assert_eq!(r3[9].as_bytes(), &genomic[..140]);
+}
+
+#[test]
+fn test_preserve_plus_line() {
+    let mut pairs = Fixture::new(3, &[BARCODE]).records();
+    for (i, (r1, r2)) in pairs.iter_mut().enumerate() {
+        (r1.sep, r2.sep) = (Some(format!("r1meta{}", i).into_bytes()), Some(format!("r2meta{}", i).into_bytes()));
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let run = |preserve_plus_line: bool| -> [Vec<String>; 3] {
+        let config = PipelineConfig { preserve_plus_line, threads: 1, ..PipelineConfig::default() };
+        run_in_memory(config, &r1_in, &r2_in).map(|out| {
+            String::from_utf8(out).unwrap().lines().skip(2).step_by(4).map(String::from).collect()
+        })
+    };
+
+    // 默认仍为单独的 '+'
+    for plus_lines in run(false) {
+        assert_eq!(plus_lines, ["+", "+", "+"]);
+    }
+    let [r1, r2, r3] = run(true);
+    assert_eq!(r1, ["+r1meta0", "+r1meta1", "+r1meta2"]);
+    assert_eq!(r2, ["+r2meta0", "+r2meta1", "+r2meta2"]);
+    assert_eq!(r3, r2);
+}
+
+#[test]
+fn test_keep_header_comments() {
+    // 空格分隔的 Casava 注释、tab 分隔的注释、没有注释
+    let r1_heads = ["a 1:N:0:ACGT", "b\tBC:Z:x", "c/1"];
+    let r2_heads = ["a 2:N:0:ACGT", "b\tRX:Z:y", "c/2"];
+    let mut pairs = Fixture::new(3, &[BARCODE]).records();
+    for ((r1, r2), (h1, h2)) in pairs.iter_mut().zip(r1_heads.iter().zip(r2_heads)) {
+        (r1.head, r2.head) = (h1.as_bytes().to_vec(), h2.as_bytes().to_vec());
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let run = |keep_header_comments: bool| -> (u64, [Vec<u8>; 3]) {
+        let config = PipelineConfig { keep_header_comments, threads: 1, ..PipelineConfig::default() };
+        let (stats, outputs) = split_in_memory(config, &r1_in, &r2_in);
+        (stats.pairs_written, outputs.map(|out| {
+            out.split(|&b| b == b'\n').step_by(4).filter(|h| !h.is_empty())
+                .flat_map(|h| [h, b"\n"].concat()).collect()
+        }))
+    };
+
+    // 默认比较完整 header：注释不同的 pair 被过滤
+    let (written, [r1, r2, r3]) = run(false);
+    assert_eq!(written, 1);
+    assert_eq!(r1, b"@c\n");
+    assert_eq!(r2, b"@c\n");
+    assert_eq!(r3, r2);
+
+    // 只比较 read 名；注释连同原来的分隔符逐字节保留
+    let (written, [r1, r2, r3]) = run(true);
+    assert_eq!(written, 3);
+    assert_eq!(r1, b"@a 1:N:0:ACGT\n@b\tBC:Z:x\n@c\n");
+    assert_eq!(r2, b"@a 2:N:0:ACGT\n@b\tRX:Z:y\n@c\n");
+    assert_eq!(r3, r2);
+}
+
+/// 第一次写入就失败的输出
+struct FailingWriter;
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FinishWrite for FailingWriter {}
+
+/// 写满 `capacity` 字节后报错的输出，模拟磁盘已满
+struct DiskFull {
+    written: usize,
+    capacity: usize,
+}
+
+impl std::io::Write for DiskFull {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.capacity - self.written);
+        if n == 0 && !buf.is_empty() {
+            return Err(std::io::Error::from(std::io::ErrorKind::StorageFull));
+        }
+        self.written += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_gzip_trailer_failure_is_reported() {
+    let (r1_in, r2_in) = Fixture::new(100, &[BARCODE]).text();
+    let run = |r3: Box<dyn FinishWrite>| {
+        let config = PipelineConfig { threads: 1, batch_size: 32, ..PipelineConfig::default() };
+        run_pipeline(config, Cursor::new(r1_in.clone().into_bytes()), Cursor::new(r2_in.clone().into_bytes()),
+                     PipelineWriters::new(std::io::sink(), std::io::sink(), r3))
+    };
+    let full = SharedBuffer::new();
+    run(Box::new(GzEncoder::new(full.clone(), Compression::new(1)))).unwrap();
+    let gz = full.contents();
+    let mut text = String::new();
+    MultiGzDecoder::new(&gz[..]).read_to_string(&mut text).unwrap();
+    assert_eq!(text.lines().count(), 400);
+
+    // 最后 8 字节（CRC 与长度）写不下：报告输出错误，而不是留下截断的 gzip
+    let err = run(Box::new(GzEncoder::new(DiskFull { written: 0, capacity: gz.len() - 4 }, Compression::new(1))))
+        .unwrap_err();
+    assert_eq!(Failure::of(&err), Some(Failure::OutputIo), "{:#}", err);
+}
+
+#[test]
+fn test_writer_failure_stops_pipeline() {
+    let n = 20000;
+    let (r1_in, r2_in) = Fixture::new(n as usize, &[BARCODE]).text();
+    let config = PipelineConfig { batch_size: 100, threads: 2, ..PipelineConfig::default() };
+    let counters = std::sync::Arc::clone(&config.counters);
+    let writers = PipelineWriters::new(std::io::sink(), std::io::sink(), FailingWriter);
+    let err = run_pipeline(config, Cursor::new(r1_in.into_bytes()), Cursor::new(r2_in.into_bytes()), writers)
+        .unwrap_err();
+    assert!(err.to_string().contains("disk full"), "{:#}", err);
+    // 读取端随之停止，不会读完整个输入
+    assert!(counters.pairs_read() < n, "read {} pairs", counters.pairs_read());
+}
+
+#[test]
+fn test_reader_error_is_reported_first() {
+    let pairs = Fixture::new(1000, &[BARCODE]).records();
+    let config = PipelineConfig { batch_size: 10, threads: 1, ..PipelineConfig::default() };
+    let writers = PipelineWriters::new(std::io::sink(), std::io::sink(), std::io::sink());
+    let err = scatac_barcode_splitter::pipeline::run_pipeline_with_reader(config, move |sender| {
+        for (r1, r2) in pairs {
+            sender.push(r1, r2)?;
+        }
+        anyhow::bail!("corrupt input at pair 1001")
+    }, writers).unwrap_err();
+    // 报告输入的错误，而不是下游随之停止的错误
+    assert_eq!(err.to_string(), "corrupt input at pair 1001");
+}
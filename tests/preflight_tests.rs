@@ -0,0 +1,41 @@
+use scatac_barcode_splitter::preflight::{available_space, estimate_output_bytes, path_collisions, GZIP_RATIO};
+use std::fs;
+use std::slice::from_ref;
+
+#[test]
+fn test_path_collisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let r1 = dir.path().join("R1.fastq");
+    let r2 = dir.path().join("R2.fastq");
+    fs::write(&r1, "@a\nA\n+\nI\n").unwrap();
+    fs::write(&r2, "@a\nC\n+\nI\n").unwrap();
+    let output = dir.path().join("out_R1.fastq");
+    assert!(path_collisions(from_ref(&r1), from_ref(&r2), from_ref(&output)).is_empty());
+
+    // 硬链接也是同一个文件；所有问题一起报告
+    let link = dir.path().join("link.fastq");
+    fs::hard_link(&r1, &link).unwrap();
+    fs::write(&output, "").unwrap();
+    let problems = path_collisions(from_ref(&r1), from_ref(&link), &[output, r1.clone()]);
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+    assert!(problems[0].contains("are the same file"));
+    assert!(problems[1].starts_with(&format!("Output {} would overwrite R1 input", r1.display())));
+    assert!(problems[2].ends_with(&format!("would overwrite R2 input {}", link.display())));
+    // 标准输入不检查
+    assert!(path_collisions(&["-".into()], &["-".into()], &[]).is_empty());
+}
+
+#[test]
+fn test_estimate_output_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("R1.fastq");
+    let gz = dir.path().join("R2.fastq.gz");
+    fs::write(&plain, vec![b'A'; 1000]).unwrap();
+    fs::write(&gz, [&[0x1f, 0x8b][..], &[0; 998]].concat()).unwrap();
+    let plain_only = estimate_output_bytes(std::slice::from_ref(&plain), false);
+    assert!(plain_only > 1000);
+    let both = estimate_output_bytes(&[plain.clone(), gz], false);
+    assert_eq!(both, (plain_only as f64 * (1.0 + GZIP_RATIO)).round() as u64);
+    assert!(estimate_output_bytes(&[plain], true) < 1000);
+    assert!(available_space(dir.path()).is_some_and(|free| free > 0));
+}
@@ -0,0 +1,38 @@
+use scatac_barcode_splitter::qc::FastqQc;
+
+#[test]
+fn test_qc_metrics() {
+    let mut qc = FastqQc::new(true);
+    // '+' = Q10, 'I' = Q40
+    qc.add(b"GGCC", b"II++");
+    qc.add(b"AATT", b"++II");
+    qc.add(b"GGCC", b"IIII");
+    qc.add(b"ACG", b"III");
+
+    assert_eq!(qc.records, 4);
+    assert_eq!(qc.bases, 15);
+    assert_eq!(qc.length_distribution.get(&4), Some(&3));
+    assert_eq!(qc.length_distribution.get(&3), Some(&1));
+    assert_eq!(qc.mean_quality_by_position(), vec![32.5, 32.5, 32.5, 30.0]);
+    assert!((qc.gc_fraction() - 10.0 / 15.0).abs() < 1e-9);
+    assert_eq!(qc.gc_histogram.get(&100), Some(&2));
+    assert_eq!(qc.gc_histogram.get(&0), Some(&1));
+    assert_eq!(qc.gc_histogram.get(&66), Some(&1));
+
+    let counts = qc.sorted_sequence_counts();
+    assert_eq!(counts[0], (&b"GGCC"[..], 2));
+    assert_eq!(counts[1], (&b"AATT"[..], 1));
+}
+
+#[test]
+fn test_qc_write_tsv() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut qc = FastqQc::new(true);
+    qc.add(b"ACGT", b"IIII");
+    qc.write_tsv(dir.path(), "R2").unwrap();
+    for name in ["quality_by_position", "gc_content", "length_distribution", "barcode_counts"] {
+        assert!(dir.path().join(format!("R2_{}.tsv", name)).exists(), "{}", name);
+    }
+    let counts = std::fs::read_to_string(dir.path().join("R2_barcode_counts.tsv")).unwrap();
+    assert_eq!(counts, "barcode\treads\nACGT\t1\n");
+}
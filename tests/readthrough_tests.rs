@@ -0,0 +1,26 @@
+use scatac_barcode_splitter::readthrough::{has_me_readthrough, ME_READTHROUGH};
+
+#[test]
+fn test_readthrough_detected_at_any_start() {
+    // 接头从窗口第 4 位开始，之后是接头的其余部分
+    let mut window = b"ACGT".to_vec();
+    window.extend_from_slice(&ME_READTHROUGH[..12]);
+    assert!(has_me_readthrough(&window));
+    // 整个窗口都在 ME 内部
+    assert!(has_me_readthrough(&ME_READTHROUGH[3..19]));
+    // 一个错配仍然命中，两个不命中
+    let mut one = ME_READTHROUGH[..16].to_vec();
+    one[5] = b'N';
+    assert!(has_me_readthrough(&one));
+    one[9] = b'G';
+    assert!(!has_me_readthrough(&one));
+}
+
+#[test]
+fn test_short_overlap_and_barcodes_not_flagged() {
+    // 只有末尾 8bp 是接头：重叠太短
+    let mut window = b"ACGTACGT".to_vec();
+    window.extend_from_slice(&ME_READTHROUGH[..8]);
+    assert!(!has_me_readthrough(&window));
+    assert!(!has_me_readthrough(b"AAAACCCCGGGGTTTT"));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{fastq_text, run_in_memory, Fixture, BARCODE};
+use scatac_barcode_splitter::record_pool::RecordPool;
+use scatac_barcode_splitter::PipelineConfig;
+use std::sync::Arc;
+
+#[test]
+fn test_checkout_returns_on_drop() {
+    let pool = RecordPool::new(2, 166);
+    {
+        let mut record = pool.checkout();
+        assert!(record.seq.is_empty() && record.seq.capacity() >= 166);
+        record.seq.extend_from_slice(b"ACGT");
+        record.sep = Some(b"extra".to_vec());
+        assert_eq!(pool.available(), 1);
+    }
+    assert_eq!(pool.available(), 2);
+    // 放回时清空，保留容量
+    let record = pool.checkout();
+    assert!(record.seq.is_empty() && record.sep.is_none());
+
+    // 取走的记录不自动放回；池满时多余的记录直接释放
+    let detached = pool.checkout().into_inner();
+    let extra = pool.checkout();
+    assert_eq!(pool.available(), 0);
+    drop(extra);
+    pool.recycle(detached);
+    drop(record);
+    assert_eq!(pool.available(), 2);
+    pool.recycle(fastq::OwnedRecord { head: vec![], seq: vec![], sep: None, qual: vec![] });
+    assert_eq!(pool.available(), 2);
+}
+
+#[test]
+fn test_pipeline_with_record_pool_same_output() {
+    let mut pairs = Fixture::new(300, &[BARCODE]).records();
+    // 奇数号 R2 短了 1bp，被过滤的记录也要放回池中
+    for (_, r2) in pairs.iter_mut().skip(1).step_by(2) {
+        r2.seq.remove(0);
+        r2.qual.pop();
+    }
+    let (r1_in, r2_in) = fastq_text(&pairs);
+    let run = |record_pool: Option<Arc<RecordPool>>| {
+        let config = PipelineConfig { threads: 1, batch_size: 32, record_pool, ..PipelineConfig::default() };
+        run_in_memory(config, &r1_in, &r2_in)
+    };
+    let expected = run(None);
+    assert!(expected.iter().all(|out| !out.is_empty()));
+    let pool = Arc::new(RecordPool::new(64, 166));
+    assert_eq!(run(Some(Arc::clone(&pool))), expected);
+    // 写入线程写完后把记录放回池中
+    assert_eq!(pool.available(), 64);
+}
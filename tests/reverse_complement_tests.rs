@@ -1,4 +1,4 @@
-use scatac_barcode_splitter::reverse_complement;
+use scatac_barcode_splitter::{canonicalize_iupac, reverse_complement, reverse_complement_in_place, reverse_complement_iupac};
 
 #[test]
 fn test_reverse_complement_basic() {
@@ -70,4 +70,41 @@ fn test_reverse_complement_palindrome() {
     let expected = b"GAATTC";
     let result = reverse_complement(input);
     assert_eq!(result, expected);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_reverse_complement_iupac_table() {
+    // (碱基, 互补碱基)：4 个标准碱基、6 个二碱基简并码、4 个三碱基简并码、N 及未知字符
+    let table: &[(u8, u8)] = &[
+        (b'A', b'T'), (b'T', b'A'), (b'G', b'C'), (b'C', b'G'),
+        (b'R', b'Y'), (b'Y', b'R'), (b'S', b'S'), (b'W', b'W'), (b'K', b'M'), (b'M', b'K'),
+        (b'B', b'V'), (b'V', b'B'), (b'D', b'H'), (b'H', b'D'),
+        (b'N', b'N'), (b'X', b'N'),
+    ];
+    for &(base, comp) in table {
+        assert_eq!(reverse_complement_iupac(&[base]), vec![comp], "base {}", base as char);
+        assert_eq!(reverse_complement_iupac(&[base.to_ascii_lowercase()]), vec![comp],
+                   "base {}", base.to_ascii_lowercase() as char);
+        // 两次反向互补回到规范化后的原序列
+        let seq = [base, b'A', base.to_ascii_lowercase()];
+        assert_eq!(reverse_complement_iupac(&reverse_complement_iupac(&seq)), canonicalize_iupac(&seq),
+                   "base {}", base as char);
+    }
+}
+
+#[test]
+fn test_reverse_complement_iupac_sequence() {
+    let input = b"ACGTRYSWKMBDHVN";
+    let expected = b"NBDHVKMWSRYACGT";
+    assert_eq!(reverse_complement_iupac(input), expected);
+    assert_eq!(reverse_complement_iupac(&reverse_complement_iupac(input)), canonicalize_iupac(input));
+}
+
+#[test]
+fn test_reverse_complement_in_place() {
+    for seq in [&b""[..], b"A", b"AtGcN", b"ACGTRYACGTT"] {
+        let mut buf = seq.to_vec();
+        reverse_complement_in_place(&mut buf);
+        assert_eq!(buf, reverse_complement(seq));
+    }
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use scatac_barcode_splitter::run_info::{format_rfc3339, write_atomic, RunInfo};
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_format_rfc3339() {
+    assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    // 2000-02-29 闰日
+    assert_eq!(format_rfc3339(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661)), "2000-02-29T01:01:01Z");
+}
+
+#[test]
+fn test_write_atomic_leaves_no_temp_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.json");
+    write_atomic(&path, b"{}").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"{}");
+    assert!(!dir.path().join("out.json.tmp").exists());
+}
+
+#[test]
+fn test_split_writes_run_info() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = common::run(&["split", "-1", common::path_str(&r1), "-2", common::path_str(&r2),
+                            "-o", common::path_str(&prefix), "--run-info-checksums"]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+
+    let json = std::fs::read(dir.path().join("out_run_info.json")).unwrap();
+    let info: RunInfo = serde_json::from_slice(&json).unwrap();
+    assert_eq!(info.tool_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.inputs.len(), 2);
+    assert!(info.inputs.iter().all(|i| i.size_bytes > 0 && i.sha256.as_ref().is_some_and(|h| h.len() == 64)));
+    assert_eq!(info.outputs.len(), 3);
+    assert_eq!(info.options["batch_size"], 200000);
+    assert!(info.options["threads"].as_u64().unwrap() >= 1);
+}
+
+#[test]
+fn test_no_run_info() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 3, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let out = common::run(&["split", "-1", common::path_str(&r1), "-2", common::path_str(&r2),
+                            "-o", common::path_str(&prefix), "--no-run-info"]);
+    assert!(out.status.success());
+    assert!(!dir.path().join("out_run_info.json").exists());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use scatac_barcode_splitter::groups::BarcodeGroups;
+use scatac_barcode_splitter::run::{run, RunConfig, ThreadTopology};
+use scatac_barcode_splitter::PipelineConfig;
+use std::fs;
+use std::io::Cursor;
+
+fn topology() -> ThreadTopology {
+    ThreadTopology { read: 1, process: 1, write: 1 }
+}
+
+#[test]
+fn test_run_writes_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 25, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"]);
+    // 第一个条形码的 R2 都短了 1bp，应按长度过滤
+    let text = fs::read_to_string(&r2).unwrap();
+    fs::write(&r2, text.replace("AAAACCCCGGGGTTTT\n+", "AAAACCCCGGGGTTT\n+")).unwrap();
+    let config = RunConfig {
+        r1_inputs: vec![r1],
+        r2_inputs: vec![r2],
+        output_prefix: dir.path().join("out").to_string_lossy().into_owned(),
+        topology: topology(),
+        ..RunConfig::default()
+    };
+    let [r1_out, r2_out, r3_out] = config.main_outputs();
+    let stats = run(config).unwrap();
+
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.pairs_filtered), (25, 12, 13));
+    assert_eq!(stats.filtered_by_reason["length"], 13);
+    assert!(stats.group_outputs.is_empty());
+    let r2_text = fs::read_to_string(&r2_out).unwrap();
+    assert_eq!(r2_text.lines().count(), 12 * 4);
+    assert!(r2_text.lines().skip(1).step_by(4).all(|seq| seq == "ACGTACGTACGTACGA"), "{}", r2_text);
+    assert!(fs::read_to_string(&r3_out).unwrap().lines().skip(1).step_by(4).all(|seq| seq.len() == 150));
+    assert_eq!(fs::read_to_string(&r1_out).unwrap().lines().next(), Some("@read1"));
+}
+
+#[test]
+fn test_run_grouped_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 6, &["AAAACCCCGGGGTTTT", "ACGTACGTACGTACGA"]);
+    let groups = BarcodeGroups::from_reader(Cursor::new("AAAACCCCGGGGTTTT\tA\nACGTACGTACGTACGA\tB\n")).unwrap();
+    let config = RunConfig {
+        r1_inputs: vec![r1],
+        r2_inputs: vec![r2],
+        output_prefix: dir.path().join("out").to_string_lossy().into_owned(),
+        topology: topology(),
+        pipeline: PipelineConfig { groups: Some(groups), ..PipelineConfig::default() },
+        ..RunConfig::default()
+    };
+    let main_outputs = config.main_outputs();
+    let stats = run(config).unwrap();
+
+    assert_eq!(stats.pairs_written, 6);
+    assert_eq!(stats.group_outputs.len(), 6);
+    assert!(main_outputs.iter().all(|path| !path.exists()));
+    let group_r1 = dir.path().join("out_A_S1_L001_R1_001.fastq");
+    assert_eq!(stats.group_outputs[0], group_r1);
+    assert_eq!(fs::read_to_string(group_r1).unwrap().lines().count(), 3 * 4);
+}
+
+#[test]
+fn test_run_marks_partial_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, _) = common::write_fixture(dir.path(), 3, &["AAAACCCCGGGGTTTT"]);
+    let config = RunConfig {
+        r1_inputs: vec![r1],
+        r2_inputs: vec![dir.path().join("missing_R2.fastq")],
+        output_prefix: dir.path().join("out").to_string_lossy().into_owned(),
+        topology: topology(),
+        ..RunConfig::default()
+    };
+    let [r1_out, ..] = config.main_outputs();
+    assert!(run(config).is_err());
+    assert!(!r1_out.exists());
+    assert!(dir.path().join("out_S1_L001_R1_001.fastq.incomplete").exists());
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{path_str, run};
+use scatac_barcode_splitter::simulate::{self, Defect, SimulateConfig, Simulator};
+use std::fs;
+
+#[test]
+fn test_same_seed_same_reads() {
+    let config = SimulateConfig { pairs: 50, defect_rate: 0.2, ..Default::default() };
+    let a: Vec<_> = Simulator::new(config.clone()).unwrap().map(|p| (p.r1.seq, p.r2.seq, p.defect)).collect();
+    let b: Vec<_> = Simulator::new(config.clone()).unwrap().map(|p| (p.r1.seq, p.r2.seq, p.defect)).collect();
+    assert_eq!(a, b);
+    let c: Vec<_> = Simulator::new(SimulateConfig { seed: 7, ..config }).unwrap().map(|p| (p.r1.seq, p.r2.seq, p.defect)).collect();
+    assert_ne!(a, c);
+}
+
+#[test]
+fn test_barcode_placement() {
+    let config = SimulateConfig { pairs: 20, r2_len: 100, barcode_offset: Some(10), ..Default::default() };
+    for pair in Simulator::new(config).unwrap() {
+        assert_eq!(pair.r2.seq.len(), 100);
+        assert_eq!(pair.r2.seq[10..26], scatac_barcode_splitter::reverse_complement(&pair.barcode)[..]);
+    }
+    assert!(Simulator::new(SimulateConfig { r2_len: 20, barcode_offset: Some(10), ..Default::default() }).is_err());
+}
+
+#[test]
+fn test_split_filters_match_defects() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("sim_R1.fastq.gz"), dir.path().join("sim_R2.fastq.gz"));
+    let (truth, whitelist) = (dir.path().join("truth.tsv"), dir.path().join("whitelist.txt"));
+    let simulator = Simulator::new(SimulateConfig { pairs: 400, defect_rate: 0.3, ..Default::default() }).unwrap();
+    simulate::write_barcodes(&whitelist, simulator.barcodes()).unwrap();
+    let summary = simulate::simulate_to_files(simulator, &r1, &r2, Some(&truth)).unwrap();
+    assert_eq!(summary.pairs, 400);
+    assert!(summary.defects.iter().all(|&n| n > 0), "{:?}", summary);
+
+    let truth = fs::read_to_string(&truth).unwrap();
+    assert_eq!(truth.lines().next(), Some("name\tbarcode\tdefect"));
+    assert_eq!(truth.lines().filter(|l| l.ends_with("\tname_mismatch")).count() as u64, summary.defects[1]);
+
+    let stats_path = dir.path().join("stats.json");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&dir.path().join("out")),
+                    "--whitelist", path_str(&whitelist), "--progress", "none", "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let stats: serde_json::Value = serde_json::from_str(&fs::read_to_string(&stats_path).unwrap()).unwrap();
+    let by_reason = &stats["filtered_by_reason"];
+    let expected = |defect: Defect| summary.defects[Defect::ALL.iter().position(|&d| d == defect).unwrap()];
+    assert_eq!(by_reason["length"].as_u64(), Some(expected(Defect::TruncatedR2)));
+    assert_eq!(by_reason["header_mismatch"].as_u64(), Some(expected(Defect::NameMismatch)));
+    assert_eq!(by_reason["barcode_no_match"].as_u64(), Some(expected(Defect::UnknownBarcode)));
+    assert_eq!(stats["pairs_written"].as_u64(), Some(400 - summary.defects.iter().sum::<u64>()));
+}
+
+#[test]
+fn test_simulate_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = (dir.path().join("R1.fastq"), dir.path().join("R2.fastq"));
+    let out = run(&["simulate", "-n", "30", "--barcodes", "5", "--defect-rate", "0.1", "--seed", "3",
+                    "-1", path_str(&r1), "-2", path_str(&r2)]);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Simulated 30 pairs"));
+    assert_eq!(fs::read_to_string(&r1).unwrap().lines().count(), 30 * 4);
+
+    let out = run(&["simulate", "--defect-rate", "1.5", "-1", path_str(&r1), "-2", path_str(&r2)]);
+    assert!(!out.status.success());
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{path_str, run};
+use scatac_barcode_splitter::stats::{RunStats, WorkerWallMs};
+use scatac_barcode_splitter::{find_lagging_workers, WorkerStats};
+use std::fs;
+
+#[test]
+fn test_worker_wall_ms_median_and_max() {
+    assert_eq!(WorkerWallMs::from_samples(&[]), WorkerWallMs { median: 0, max: 0 });
+    assert_eq!(WorkerWallMs::from_samples(&[30, 10, 20]), WorkerWallMs { median: 20, max: 30 });
+    assert_eq!(WorkerWallMs::from_samples(&[40, 10, 20, 30]), WorkerWallMs { median: 25, max: 40 });
+}
+
+#[test]
+fn test_stats_json_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let (r1, r2) = common::write_fixture(dir.path(), 10, &["AAAACCCCGGGGTTTT"]);
+    let prefix = dir.path().join("out");
+    let stats_path = dir.path().join("stats.json");
+    let out = run(&["split", "-1", path_str(&r1), "-2", path_str(&r2), "-o", path_str(&prefix),
+                    "--stats-json", path_str(&stats_path)]);
+    assert!(out.status.success());
+
+    let stats: RunStats = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    assert_eq!((stats.pairs_read, stats.pairs_written, stats.pairs_filtered), (10, 10, 0));
+    assert_eq!(stats.lane_pairs, vec![10]);
+    assert!(stats.barcode_correction.is_none());
+    assert!(!stats.worker_stats.is_empty());
+    assert_eq!(stats.worker_stats.iter().map(|w| w.records).sum::<u64>(), 10);
+    let json: serde_json::Value = serde_json::from_slice(&fs::read(&stats_path).unwrap()).unwrap();
+    for key in ["reader_wall_ms", "worker_wall_ms", "distributor_wall_ms",
+                "writer_r1_wall_ms", "writer_r2_wall_ms", "writer_r3_wall_ms"] {
+        assert!(json["timings"].get(key).is_some(), "missing {}", key);
+    }
+}
+
+#[test]
+fn test_find_lagging_workers() {
+    let worker = |worker_id, records| WorkerStats { worker_id, records, wall_ms: 100 };
+    assert!(find_lagging_workers(&[worker(0, 10)]).is_empty());
+    assert!(find_lagging_workers(&[worker(0, 100), worker(1, 90), worker(2, 110)]).is_empty());
+    assert_eq!(find_lagging_workers(&[worker(0, 100), worker(1, 20), worker(2, 110)]), vec![1]);
+}
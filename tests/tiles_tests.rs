@@ -0,0 +1,32 @@
+use scatac_barcode_splitter::tiles::{TileExclusion, TileTally};
+
+#[test]
+fn test_tile_exclusion_list_and_file() {
+    let tiles = TileExclusion::parse("1101,1102, 2203").unwrap();
+    assert_eq!(tiles.len(), 3);
+    assert!(tiles.contains(2203) && !tiles.contains(1103));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tiles.txt");
+    std::fs::write(&path, "1101\n1102,1103\n\n").unwrap();
+    let tiles = TileExclusion::parse(path.to_str().unwrap()).unwrap();
+    assert_eq!(tiles.len(), 3);
+
+    assert!(TileExclusion::parse("1101,x").is_err());
+    assert!(TileExclusion::parse(",").is_err());
+}
+
+#[test]
+fn test_worst_tiles_ranked_by_filter_rate() {
+    let mut a = TileTally::default();
+    let mut b = TileTally::default();
+    for i in 0..10 {
+        a.record(1, 1101, i == 0);
+        b.record(1, 1102, i < 5);
+        b.record(2, 1101, false);
+    }
+    a.merge(&b);
+    assert_eq!(a.len(), 3);
+    let worst: Vec<_> = a.worst(2).iter().map(|t| (t.lane, t.tile, t.pairs_filtered, t.filter_rate)).collect();
+    assert_eq!(worst, [(1, 1102, 5, 0.5), (1, 1101, 1, 0.1)]);
+}
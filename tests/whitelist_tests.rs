@@ -0,0 +1,116 @@
+use scatac_barcode_splitter::whitelist::{
+    correct_barcode, correct_barcode_indel, mask_low_quality, BarcodeMatch, CorrectionStats, PartialMatch, Whitelist,
+};
+
+fn whitelist(barcodes: &[&str]) -> Whitelist {
+    Whitelist::from_reader(barcodes.join("\n").as_bytes()).unwrap()
+}
+
+#[test]
+fn test_whitelist_exact_and_corrected() {
+    let wl = whitelist(&["AAAACCCC", "GGGGTTTT"]);
+    assert_eq!(wl.correct(b"AAAACCCC"), BarcodeMatch::Exact);
+    assert_eq!(wl.correct(b"AAAACCCA"), BarcodeMatch::Corrected(b"AAAACCCC".to_vec()));
+    assert_eq!(wl.correct(b"ACGTACGT"), BarcodeMatch::NoMatch);
+}
+
+#[test]
+fn test_whitelist_single_n_rescue() {
+    let wl = whitelist(&["AAAACCCC", "GGGGTTTT"]);
+    assert_eq!(wl.correct(b"AAANCCCC"), BarcodeMatch::NRescued(b"AAAACCCC".to_vec()));
+    // 两个 N 不做救回
+    assert_eq!(wl.correct(b"AANNCCCC"), BarcodeMatch::NoMatch);
+}
+
+#[test]
+fn test_whitelist_ambiguous_correction() {
+    // AAAACCCA 与两个条目都只差一个碱基
+    let wl = whitelist(&["AAAACCCC", "AAAACCCG"]);
+    assert_eq!(wl.correct(b"AAAACCCA"), BarcodeMatch::NoMatch);
+}
+
+#[test]
+fn test_priors_resolve_ambiguous_correction() {
+    let mut wl = whitelist(&["AAAACCCC", "AAAACCCG"]);
+    wl.set_priors([(b"AAAACCCC".to_vec(), 100), (b"AAAACCCG".to_vec(), 2)].into_iter().collect());
+    assert_eq!(wl.correct(b"AAAACCCA"), BarcodeMatch::Corrected(b"AAAACCCC".to_vec()));
+    // 丰度接近时仍然放弃
+    wl.set_priors([(b"AAAACCCC".to_vec(), 100), (b"AAAACCCG".to_vec(), 90)].into_iter().collect());
+    assert_eq!(wl.correct(b"AAAACCCA"), BarcodeMatch::NoMatch);
+}
+
+#[test]
+fn test_mask_low_quality() {
+    let mut seq = b"ACGT".to_vec();
+    // '#' = Q2, 'I' = Q40
+    let masked = mask_low_quality(&mut seq, b"I#II", 10);
+    assert_eq!(masked, 1);
+    assert_eq!(seq, b"ANGT");
+}
+
+#[test]
+fn test_masked_base_rescued_by_whitelist() {
+    let wl = whitelist(&["AAAACCCC"]);
+    let mut stats = CorrectionStats::default();
+    // 低质量位置上的错误碱基被屏蔽为 N，然后由单 N 救回
+    let bc = correct_barcode(&wl, b"AAAGCCCC", b"III#IIII", Some(10), &mut stats);
+    assert_eq!(bc, Some(b"AAAACCCC".to_vec()));
+    assert_eq!(stats.n_rescued, 1);
+    assert_eq!(stats.masked_reads, 1);
+    assert_eq!(stats.masked_rescued, 1);
+}
+
+#[test]
+fn test_multiple_masked_bases_not_rescued() {
+    let wl = whitelist(&["AAAACCCC"]);
+    let mut stats = CorrectionStats::default();
+    let bc = correct_barcode(&wl, b"AAAGCCCA", b"III#III#", Some(10), &mut stats);
+    assert_eq!(bc, None);
+    assert_eq!(stats.no_match, 1);
+    assert_eq!(stats.masked_reads, 1);
+    assert_eq!(stats.masked_rescued, 0);
+}
+
+#[test]
+fn test_partial_match_prefix_and_suffix() {
+    let mut wl = whitelist(&["AAAACCCC", "AAAACCCG", "GGGGTTTT"]);
+    wl.build_partial_index();
+    assert_eq!(wl.match_prefix(b"GGGGTTT"), PartialMatch::Unique(b"GGGGTTTT".to_vec()));
+    assert_eq!(wl.match_prefix(b"AAAACCC"), PartialMatch::Ambiguous);
+    assert_eq!(wl.match_prefix(b"CCCC"), PartialMatch::NoMatch);
+    assert_eq!(wl.match_suffix(b"AAACCCG"), PartialMatch::Unique(b"AAAACCCG".to_vec()));
+    assert_eq!(wl.match_suffix(b"TTTTT"), PartialMatch::NoMatch);
+}
+
+#[test]
+fn test_indel_correction_forward_anchor() {
+    let wl = whitelist(&["ACGTTGCAAGCTTCGA", "TTTTGGGGCCCCAAAA"]);
+    // 第 6 个碱基后插入 G：最后一个碱基被挤到窗口外，即相邻的 flank
+    assert_eq!(wl.correct(b"ACGTTGGCAAGCTTCG"), BarcodeMatch::NoMatch);
+    assert_eq!(wl.correct_indel(b"ACGTTGGCAAGCTTCG", true, Some(b'A')), Some(b"ACGTTGCAAGCTTCGA".to_vec()));
+    // 缺失第 7 个碱基 C：窗口末尾多读进一个碱基
+    assert_eq!(wl.correct_indel(b"ACGTTGAAGCTTCGAT", true, None), Some(b"ACGTTGCAAGCTTCGA".to_vec()));
+    // 两处差异不做纠正
+    assert_eq!(wl.correct_indel(b"ACGTTGGCAAGCTACG", true, Some(b'A')), None);
+}
+
+#[test]
+fn test_indel_correction_end_anchor() {
+    let wl = whitelist(&["ACGTTGCAAGCTTCGA", "TTTTGGGGCCCCAAAA"]);
+    // 固定端在条形码末尾（R2 中反向互补）时，错开的是开头
+    // 插入 T，开头的 A 被挤出窗口
+    assert_eq!(wl.correct_indel(b"CGTTGCAAGTCTTCGA", false, None), Some(b"ACGTTGCAAGCTTCGA".to_vec()));
+    // 缺失第 9 个碱基 A，开头多读进一个 G
+    assert_eq!(wl.correct_indel(b"GACGTTGCAGCTTCGA", false, None), Some(b"ACGTTGCAAGCTTCGA".to_vec()));
+}
+
+#[test]
+fn test_correct_barcode_indel_stats() {
+    let wl = whitelist(&["ACGTTGCAAGCTTCGA", "TTTTGGGGCCCCAAAA"]);
+    let mut stats = CorrectionStats::default();
+    let seq = b"ACGTTGGCAAGCTTCG";
+    assert_eq!(correct_barcode(&wl, seq, &[b'I'; 16], None, &mut stats), None);
+    let hit = correct_barcode_indel(&wl, seq, true, Some(b'A'), &mut stats);
+    assert_eq!(hit, Some(b"ACGTTGCAAGCTTCGA".to_vec()));
+    assert_eq!((stats.no_match, stats.indel_attempted, stats.indel_corrected, stats.rewritten), (0, 1, 1, 1));
+}